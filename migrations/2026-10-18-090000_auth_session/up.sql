@@ -0,0 +1,14 @@
+create table auth_session (
+    id bigserial primary key,
+    user_id bigint not null references "user" on delete cascade,
+    access_token_hash bytea not null,
+    access_token_expires timestamptz not null,
+    refresh_token_hash bytea not null,
+    refresh_token_expires timestamptz not null
+);
+
+create unique index auth_session__access_token_hash__key on auth_session (access_token_hash);
+
+create unique index auth_session__refresh_token_hash__key on auth_session (refresh_token_hash);
+
+create index auth_session__user_id__idx on auth_session (user_id);
@@ -0,0 +1,11 @@
+-- encrypted passwords can not be decrypted without the key from the server config
+do $$
+begin
+    if exists (select from platform_credential where password_encrypted) then
+        raise exception 'platform credentials with encrypted passwords can not be migrated back';
+    end if;
+end
+$$;
+
+alter table platform_credential drop column password_encrypted;
+alter table platform_credential alter column password type varchar(80);
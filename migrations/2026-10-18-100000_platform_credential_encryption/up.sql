@@ -0,0 +1,8 @@
+-- encrypted passwords are longer than the plaintext ones
+alter table platform_credential alter column password type text;
+
+-- the passwords of existing credentials are encrypted by the server at startup
+-- because the key is part of the server config
+-- all passwords stored by the server from now on are encrypted
+alter table platform_credential add column password_encrypted boolean not null default false;
+alter table platform_credential alter column password_encrypted set default true;
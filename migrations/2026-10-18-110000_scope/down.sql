@@ -0,0 +1,6 @@
+drop table scope_grant_archive;
+drop table scope_grant;
+drop table action_provider_scope_archive;
+drop table action_provider_scope;
+drop type scope_access;
+drop type scope_entity;
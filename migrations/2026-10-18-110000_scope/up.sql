@@ -0,0 +1,69 @@
+create type scope_entity as enum(
+    'diary',
+    'wod',
+    'movement',
+    'strength_session',
+    'strength_set',
+    'metcon',
+    'metcon_session',
+    'metcon_movement',
+    'cardio_session',
+    'route'
+);
+
+create type scope_access as enum('read', 'write');
+
+create table action_provider_scope (
+    id bigint primary key,
+    action_provider_id bigint not null references action_provider on delete cascade,
+    entity scope_entity not null,
+    access scope_access not null,
+    epoch bigint not null,
+    deleted boolean not null default false
+);
+
+create unique index action_provider_scope__action_provider_id__entity__access__key
+    on action_provider_scope (action_provider_id, entity, access) where deleted = false;
+
+create trigger set_epoch before insert or update on action_provider_scope
+    for each row execute function set_epoch();
+
+create table action_provider_scope_archive (
+    primary key (id),
+    check (deleted = true)
+) inherits (action_provider_scope);
+
+create trigger archive_action_provider_scope
+    after insert or update of deleted or delete
+    on action_provider_scope
+    for each row execute procedure archive_record();
+
+create table scope_grant (
+    id bigint primary key,
+    user_id bigint not null references "user" on delete cascade,
+    action_provider_scope_id bigint not null references action_provider_scope on delete cascade,
+    epoch bigint not null,
+    deleted boolean not null default false
+);
+
+create unique index scope_grant__user_id__action_provider_scope_id__key
+    on scope_grant (user_id, action_provider_scope_id)
+    where deleted = false;
+
+create index scope_grant__user_id__epoch__idx
+    on scope_grant (user_id, epoch)
+    where deleted = false;
+
+create trigger set_epoch before insert or update on scope_grant
+    for each row execute function set_epoch_for_user();
+
+create table scope_grant_archive (
+    primary key (id),
+    foreign key (user_id) references "user" on delete cascade,
+    check (deleted = true)
+) inherits (scope_grant);
+
+create trigger archive_scope_grant
+    after insert or update of deleted or delete
+    on scope_grant
+    for each row execute procedure archive_record();
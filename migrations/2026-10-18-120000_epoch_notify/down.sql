@@ -0,0 +1,45 @@
+create or replace function set_epoch() 
+    returns trigger as $$
+    declare
+        max_epoch bigint;
+    begin
+        execute format('select max(epoch) + 1 from %I.%I', tg_table_schema, tg_table_name)
+        into max_epoch;
+
+        new.epoch := coalesce(max_epoch, 1);
+        return new;
+    end;
+    $$ language plpgsql;
+
+create or replace function set_epoch_for_user() 
+    returns trigger as $$
+    declare
+        max_epoch bigint;
+    begin
+        if new.user_id is null then
+            execute format('select max(epoch) + 1 from %I.%I where user_id is null', tg_table_schema, tg_table_name)
+            into max_epoch;
+        else
+            execute format('select max(epoch) + 1 from %I.%I where user_id = $1', tg_table_schema, tg_table_name)
+            using new.user_id
+            into max_epoch;
+        end if;
+
+        new.epoch := coalesce(max_epoch, 1);
+        return new;
+    end;
+    $$ language plpgsql;
+
+create or replace function set_epoch_for_user_in_user_table() 
+    returns trigger as $$
+    declare
+        max_epoch bigint;
+    begin
+        execute format('select max(epoch) + 1 from %I.%I where id = $1', tg_table_schema, tg_table_name)
+        using new.id
+        into max_epoch;
+
+        new.epoch := coalesce(max_epoch, 1);
+        return new;
+    end;
+    $$ language plpgsql;
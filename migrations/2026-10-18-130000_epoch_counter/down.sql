@@ -0,0 +1,53 @@
+create or replace function set_epoch() 
+    returns trigger as $$
+    declare
+        max_epoch bigint;
+    begin
+        execute format('select max(epoch) + 1 from %I.%I', tg_table_schema, tg_table_name)
+        into max_epoch;
+
+        new.epoch := coalesce(max_epoch, 1);
+        perform pg_notify('epoch', concat(tg_table_name, ',,', new.epoch));
+        return new;
+    end;
+    $$ language plpgsql;
+
+create or replace function set_epoch_for_user() 
+    returns trigger as $$
+    declare
+        max_epoch bigint;
+    begin
+        if new.user_id is null then
+            execute format('select max(epoch) + 1 from %I.%I where user_id is null', tg_table_schema, tg_table_name)
+            into max_epoch;
+        else
+            execute format('select max(epoch) + 1 from %I.%I where user_id = $1', tg_table_schema, tg_table_name)
+            using new.user_id
+            into max_epoch;
+        end if;
+
+        new.epoch := coalesce(max_epoch, 1);
+        perform pg_notify('epoch', concat(tg_table_name, ',', new.user_id, ',', new.epoch));
+        return new;
+    end;
+    $$ language plpgsql;
+
+create or replace function set_epoch_for_user_in_user_table() 
+    returns trigger as $$
+    declare
+        max_epoch bigint;
+    begin
+        execute format('select max(epoch) + 1 from %I.%I where id = $1', tg_table_schema, tg_table_name)
+        using new.id
+        into max_epoch;
+
+        new.epoch := coalesce(max_epoch, 1);
+        perform pg_notify('epoch', concat(tg_table_name, ',', new.id, ',', new.epoch));
+        return new;
+    end;
+    $$ language plpgsql;
+
+drop trigger delete_epoch_counters on "user";
+drop function delete_epoch_counters;
+drop function next_epoch;
+drop table epoch_counter;
@@ -0,0 +1,110 @@
+-- The last epoch of every table, separately for every user.
+-- Entries without user (`user_id is null`) have their own counter per table.
+-- Updating the counter row locks it until the end of the transaction, so concurrent transactions
+-- writing the same table for the same user get distinct epochs and commit in the order of their
+-- epochs.
+create table epoch_counter (
+    table_name text not null,
+    user_id bigint,
+    epoch bigint not null
+);
+
+create unique index epoch_counter__table_name__user_id__key
+    on epoch_counter (table_name, user_id)
+    where user_id is not null;
+
+create unique index epoch_counter__table_name__key
+    on epoch_counter (table_name)
+    where user_id is null;
+
+create function next_epoch(counter_table text, counter_user_id bigint)
+    returns bigint as $$
+    declare
+        new_epoch bigint;
+    begin
+        if counter_user_id is null then
+            insert into epoch_counter as counter (table_name, user_id, epoch)
+                values (counter_table, null, 1)
+                on conflict (table_name) where user_id is null
+                do update set epoch = counter.epoch + 1
+                returning counter.epoch into new_epoch;
+        else
+            insert into epoch_counter as counter (table_name, user_id, epoch)
+                values (counter_table, counter_user_id, 1)
+                on conflict (table_name, user_id) where user_id is not null
+                do update set epoch = counter.epoch + 1
+                returning counter.epoch into new_epoch;
+        end if;
+        return new_epoch;
+    end;
+    $$ language plpgsql;
+
+create function delete_epoch_counters()
+    returns trigger as $$
+    begin
+        delete from epoch_counter where user_id = old.id;
+        return null;
+    end;
+    $$ language plpgsql;
+
+create trigger delete_epoch_counters after delete on "user"
+    for each row execute function delete_epoch_counters();
+
+-- seed the counters with the current epochs (including archived entries)
+do $$
+    declare
+        epoch_trigger record;
+    begin
+        for epoch_trigger in
+            select c.relname as table_name, p.proname as function_name
+            from pg_trigger t
+                join pg_class c on c.oid = t.tgrelid
+                join pg_proc p on p.oid = t.tgfoid
+            where t.tgname = 'set_epoch'
+        loop
+            if epoch_trigger.function_name = 'set_epoch' then
+                execute format(
+                    'insert into epoch_counter select %L, null, max(epoch) from %I having count(*) > 0',
+                    epoch_trigger.table_name, epoch_trigger.table_name
+                );
+            elsif epoch_trigger.function_name = 'set_epoch_for_user' then
+                execute format(
+                    'insert into epoch_counter select %L, user_id, max(epoch) from %I group by user_id',
+                    epoch_trigger.table_name, epoch_trigger.table_name
+                );
+            else
+                execute format(
+                    'insert into epoch_counter select %L, id, max(epoch) from %I group by id',
+                    epoch_trigger.table_name, epoch_trigger.table_name
+                );
+            end if;
+        end loop;
+    end;
+    $$;
+
+create or replace function set_epoch() 
+    returns trigger as $$
+    begin
+        new.epoch := next_epoch(tg_table_name, null);
+        perform pg_notify('epoch', concat(tg_table_name, ',,', new.epoch));
+        return new;
+    end;
+    $$ language plpgsql;
+
+create or replace function set_epoch_for_user() 
+    returns trigger as $$
+    begin
+        new.epoch := next_epoch(tg_table_name, new.user_id);
+        perform pg_notify('epoch', concat(tg_table_name, ',', new.user_id, ',', new.epoch));
+        return new;
+    end;
+    $$ language plpgsql;
+
+create or replace function set_epoch_for_user_in_user_table() 
+    returns trigger as $$
+    begin
+        new.epoch := next_epoch(tg_table_name, new.id);
+        perform pg_notify('epoch', concat(tg_table_name, ',', new.id, ',', new.epoch));
+        return new;
+    end;
+    $$ language plpgsql;
@@ -0,0 +1,48 @@
+drop function restore_record;
+
+create or replace function archive_record()
+    returns trigger as $$
+    begin
+        if (tg_op = 'INSERT' and new.deleted = true) then
+            execute format('delete from %I.%I where id = $1', tg_table_schema, tg_table_name) using new.id;
+            return old;
+        end if;
+        -- when a soft-delete happens...
+        if (tg_op = 'UPDATE' and new.deleted = true) then
+            execute format('delete from %I.%I where id = $1', tg_table_schema, tg_table_name) using old.id;
+            return old;
+        end if;
+        -- when a hard-delete or a cascaded delete happens
+        if (tg_op = 'DELETE') then
+            if (old.deleted = false) then 
+                old.deleted := true;
+            end if;
+            execute format('insert into %I.%I select $1.*', tg_table_schema, tg_table_name || '_archive')
+            using old;
+            raise notice 'soft deleting % %', tg_table_name, old.id;
+        end if;
+        return null;
+    exception when foreign_key_violation then 
+        raise notice 'hard deleting % %', tg_table_name, old.id;
+        return null;
+    end;
+    $$ language plpgsql;
+
+alter table platform drop column archived_at, drop column archived_by;
+alter table platform_credential drop column archived_at, drop column archived_by;
+alter table action_provider drop column archived_at, drop column archived_by;
+alter table action drop column archived_at, drop column archived_by;
+alter table action_rule drop column archived_at, drop column archived_by;
+alter table action_event drop column archived_at, drop column archived_by;
+alter table action_provider_scope drop column archived_at, drop column archived_by;
+alter table scope_grant drop column archived_at, drop column archived_by;
+alter table diary drop column archived_at, drop column archived_by;
+alter table wod drop column archived_at, drop column archived_by;
+alter table movement drop column archived_at, drop column archived_by;
+alter table strength_session drop column archived_at, drop column archived_by;
+alter table strength_set drop column archived_at, drop column archived_by;
+alter table metcon drop column archived_at, drop column archived_by;
+alter table metcon_movement drop column archived_at, drop column archived_by;
+alter table metcon_session drop column archived_at, drop column archived_by;
+alter table route drop column archived_at, drop column archived_by;
+alter table cardio_session drop column archived_at, drop column archived_by;
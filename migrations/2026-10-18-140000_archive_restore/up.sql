@@ -0,0 +1,194 @@
+-- The time an entry has been moved to the archive. It is null for entries which are not archived.
+-- Entries that have been archived before this migration get the time of the migration.
+-- `archived_by` is the id of the entry whose delete has been cascaded to the entry. It is null for
+-- entries which have been deleted directly.
+alter table platform add column archived_at timestamptz, add column archived_by bigint;
+update platform_archive set archived_at = now();
+alter table platform_archive alter column archived_at set not null;
+
+alter table platform_credential add column archived_at timestamptz, add column archived_by bigint;
+update platform_credential_archive set archived_at = now();
+alter table platform_credential_archive alter column archived_at set not null;
+
+alter table action_provider add column archived_at timestamptz, add column archived_by bigint;
+update action_provider_archive set archived_at = now();
+alter table action_provider_archive alter column archived_at set not null;
+
+alter table action add column archived_at timestamptz, add column archived_by bigint;
+update action_archive set archived_at = now();
+alter table action_archive alter column archived_at set not null;
+
+alter table action_rule add column archived_at timestamptz, add column archived_by bigint;
+update action_rule_archive set archived_at = now();
+alter table action_rule_archive alter column archived_at set not null;
+
+alter table action_event add column archived_at timestamptz, add column archived_by bigint;
+update action_event_archive set archived_at = now();
+alter table action_event_archive alter column archived_at set not null;
+
+alter table action_provider_scope add column archived_at timestamptz, add column archived_by bigint;
+update action_provider_scope_archive set archived_at = now();
+alter table action_provider_scope_archive alter column archived_at set not null;
+
+alter table scope_grant add column archived_at timestamptz, add column archived_by bigint;
+update scope_grant_archive set archived_at = now();
+alter table scope_grant_archive alter column archived_at set not null;
+
+alter table diary add column archived_at timestamptz, add column archived_by bigint;
+update diary_archive set archived_at = now();
+alter table diary_archive alter column archived_at set not null;
+
+alter table wod add column archived_at timestamptz, add column archived_by bigint;
+update wod_archive set archived_at = now();
+alter table wod_archive alter column archived_at set not null;
+
+alter table movement add column archived_at timestamptz, add column archived_by bigint;
+update movement_archive set archived_at = now();
+alter table movement_archive alter column archived_at set not null;
+
+alter table strength_session add column archived_at timestamptz, add column archived_by bigint;
+update strength_session_archive set archived_at = now();
+alter table strength_session_archive alter column archived_at set not null;
+
+alter table strength_set add column archived_at timestamptz, add column archived_by bigint;
+update strength_set_archive set archived_at = now();
+alter table strength_set_archive alter column archived_at set not null;
+
+alter table metcon add column archived_at timestamptz, add column archived_by bigint;
+update metcon_archive set archived_at = now();
+alter table metcon_archive alter column archived_at set not null;
+
+alter table metcon_movement add column archived_at timestamptz, add column archived_by bigint;
+update metcon_movement_archive set archived_at = now();
+alter table metcon_movement_archive alter column archived_at set not null;
+
+alter table metcon_session add column archived_at timestamptz, add column archived_by bigint;
+update metcon_session_archive set archived_at = now();
+alter table metcon_session_archive alter column archived_at set not null;
+
+alter table route add column archived_at timestamptz, add column archived_by bigint;
+update route_archive set archived_at = now();
+alter table route_archive alter column archived_at set not null;
+
+alter table cardio_session add column archived_at timestamptz, add column archived_by bigint;
+update cardio_session_archive set archived_at = now();
+alter table cardio_session_archive alter column archived_at set not null;
+
+create or replace function archive_record()
+    returns trigger as $$
+    declare
+        parent record;
+        parent_id bigint;
+        parent_exists boolean;
+    begin
+        if (tg_op = 'INSERT' and new.deleted = true) then
+            execute format('delete from %I.%I where id = $1', tg_table_schema, tg_table_name) using new.id;
+            return old;
+        end if;
+        -- when a soft-delete happens...
+        if (tg_op = 'UPDATE' and new.deleted = true) then
+            execute format('delete from %I.%I where id = $1', tg_table_schema, tg_table_name) using old.id;
+            return old;
+        end if;
+        -- when a hard-delete or a cascaded delete happens
+        if (tg_op = 'DELETE') then
+            if (old.deleted = false) then 
+                old.deleted := true;
+            end if;
+            old.archived_at := now();
+            -- a cascaded delete happens after the referenced entry has been deleted
+            for parent in
+                select relname as parent_table, attname as child_column
+                from pg_constraint
+                    join pg_attribute on attrelid = conrelid and attnum = conkey[1]
+                    join pg_class on pg_class.oid = confrelid
+                where contype = 'f'
+                    and conrelid = tg_relid
+                    and confdeltype = 'c'
+                    and to_regclass(quote_ident(relname || '_archive')) is not null
+            loop
+                execute format('select ($1).%I', parent.child_column) using old into parent_id;
+                continue when parent_id is null;
+                execute format('select exists (select from only %I where id = $1)', parent.parent_table)
+                using parent_id
+                into parent_exists;
+                if not parent_exists then
+                    old.archived_by := parent_id;
+                    exit;
+                end if;
+            end loop;
+            execute format('insert into %I.%I select $1.*', tg_table_schema, tg_table_name || '_archive')
+            using old;
+            raise notice 'soft deleting % %', tg_table_name, old.id;
+        end if;
+        return null;
+    exception when foreign_key_violation then 
+        raise notice 'hard deleting % %', tg_table_name, old.id;
+        return null;
+    end;
+    $$ language plpgsql;
+
+-- Move the archived entry `record_id` of `record_table` back into `record_table` together with all
+-- entries that have been archived by the cascaded delete of it.
+-- The restored entries get a new epoch, so that they are picked up by the next synchronization.
+-- Returns the number of restored entries.
+create function restore_record(record_table text, record_id bigint)
+    returns bigint as $$
+    declare
+        columns text;
+        restored_id bigint;
+        restored bigint := 1;
+        child record;
+        child_id bigint;
+    begin
+        select string_agg(
+            case attname
+                when 'deleted' then 'false'
+                when 'archived_at' then 'null'
+                when 'archived_by' then 'null'
+                else quote_ident(attname)
+            end,
+            ', ' order by attnum
+        )
+        into columns
+        from pg_attribute
+        where attrelid = record_table::regclass and attnum > 0 and not attisdropped;
+
+        execute format(
+            'with archived as (delete from only %I where id = $1 returning *)
+                insert into %I select %s from archived returning id',
+            record_table || '_archive',
+            record_table,
+            columns
+        )
+        using record_id
+        into restored_id;
+        if restored_id is null then
+            return 0;
+        end if;
+
+        -- children that have been deleted by "on delete cascade" of the restored entry
+        for child in
+            select conrelid::regclass::text as child_table, attname as child_column
+            from pg_constraint
+                join pg_attribute on attrelid = conrelid and attnum = conkey[1]
+            where contype = 'f'
+                and confrelid = record_table::regclass
+                and confdeltype = 'c'
+                and to_regclass(conrelid::regclass::text || '_archive') is not null
+        loop
+            for child_id in
+                execute format(
+                    'select id from only %I where %I = $1 and archived_by = $1',
+                    child.child_table || '_archive',
+                    child.child_column
+                )
+                using record_id
+            loop
+                restored := restored + restore_record(child.child_table, child_id);
+            end loop;
+        end loop;
+
+        return restored;
+    end;
+    $$ language plpgsql;
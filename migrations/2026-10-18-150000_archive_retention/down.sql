@@ -0,0 +1,3 @@
+drop function purge_archive;
+
+alter table epoch_counter drop column purged_epoch;
@@ -0,0 +1,52 @@
+-- The highest epoch of the archived entries of the table and user that have been deleted
+-- permanently. Clients that have synchronized the table up to an earlier epoch can not learn about
+-- these deletions anymore and have to perform an init sync.
+alter table epoch_counter add column purged_epoch bigint not null default 0;
+
+-- Permanently delete the entries of `record_table` that have been archived more than
+-- `retention_days` days ago and remember their highest epoch in `epoch_counter`.
+-- Returns the number of deleted entries.
+create function purge_archive(record_table text, retention_days integer)
+    returns bigint as $$
+    declare
+        user_column text := 'null::bigint';
+        purged record;
+        purged_count bigint := 0;
+    begin
+        if exists (
+            select from pg_attribute
+            where attrelid = record_table::regclass and attname = 'user_id' and not attisdropped
+        ) then
+            user_column := 'user_id';
+        end if;
+
+        for purged in
+            execute format(
+                'with purged as (
+                    delete from only %I where archived_at < now() - make_interval(days => $1)
+                    returning %s as user_id, epoch
+                )
+                select user_id, max(epoch) as epoch, count(*) as count from purged group by user_id',
+                record_table || '_archive',
+                user_column
+            )
+            using retention_days
+        loop
+            purged_count := purged_count + purged.count;
+            if purged.user_id is null then
+                insert into epoch_counter as counter (table_name, user_id, epoch, purged_epoch)
+                    values (record_table, null, purged.epoch, purged.epoch)
+                    on conflict (table_name) where user_id is null
+                    do update set purged_epoch = greatest(counter.purged_epoch, excluded.purged_epoch);
+            -- the archived entries of deleted users are not relevant for any client
+            elsif exists (select from "user" where id = purged.user_id) then
+                insert into epoch_counter as counter (table_name, user_id, epoch, purged_epoch)
+                    values (record_table, purged.user_id, purged.epoch, purged.epoch)
+                    on conflict (table_name, user_id) where user_id is not null
+                    do update set purged_epoch = greatest(counter.purged_epoch, excluded.purged_epoch);
+            end if;
+        end loop;
+
+        return purged_count;
+    end;
+    $$ language plpgsql;
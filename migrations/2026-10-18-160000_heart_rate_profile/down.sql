@@ -0,0 +1,3 @@
+alter table "user"
+    drop column max_heart_rate,
+    drop column resting_heart_rate;
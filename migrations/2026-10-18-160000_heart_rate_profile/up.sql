@@ -0,0 +1,4 @@
+alter table "user"
+    add column max_heart_rate integer check (max_heart_rate between 1 and 300),
+    add column resting_heart_rate integer check (resting_heart_rate >= 1),
+    add check (resting_heart_rate < max_heart_rate);
@@ -0,0 +1,32 @@
+drop table training_zone_archive;
+drop table training_zone;
+
+drop function training_zone_thresholds_sorted;
+
+drop type training_zone_type;
+
+delete from epoch_counter where table_name = 'training_zone';
+
+-- enum values can not be dropped, so the type is recreated without 'training_zone'
+delete from only action_provider_scope where entity = 'training_zone';
+delete from action_provider_scope_archive where entity = 'training_zone';
+
+alter type scope_entity rename to scope_entity_old;
+
+create type scope_entity as enum(
+    'diary',
+    'wod',
+    'movement',
+    'strength_session',
+    'strength_set',
+    'metcon',
+    'metcon_session',
+    'metcon_movement',
+    'cardio_session',
+    'route'
+);
+
+alter table action_provider_scope
+    alter column entity type scope_entity using entity::text::scope_entity;
+
+drop type scope_entity_old;
@@ -0,0 +1,56 @@
+alter type scope_entity add value 'training_zone';
+
+create type training_zone_type as enum('heart_rate', 'pace');
+
+-- The thresholds are the boundaries between the zones from low to high intensity,
+-- so heart rates (beats per minute) are ascending and paces (seconds per kilometer) descending.
+create function training_zone_thresholds_sorted(
+    zone_type training_zone_type,
+    thresholds integer[]
+)
+    returns boolean as $$
+        select coalesce(
+            bool_and(case when zone_type = 'heart_rate' then low < high else low > high end),
+            true
+        )
+        from unnest(thresholds[1:cardinality(thresholds) - 1], thresholds[2:]) as t(low, high);
+    $$ language sql immutable;
+
+create table training_zone (
+    id bigint primary key,
+    user_id bigint not null references "user" on delete cascade,
+    zone_type training_zone_type not null,
+    movement_id bigint references movement on delete cascade,
+    thresholds integer[] not null check (
+        cardinality(thresholds) between 1 and 10
+        and array_position(thresholds, null) is null
+        and 0 < all(thresholds)
+        and training_zone_thresholds_sorted(zone_type, thresholds)
+    ),
+    epoch bigint not null,
+    deleted boolean not null default false,
+    archived_at timestamptz,
+    archived_by bigint
+);
+
+create unique index training_zone__user_id__zone_type__movement_id__key
+    on training_zone (user_id, zone_type, coalesce(movement_id, 0)) where deleted = false;
+
+create index training_zone__user_id__epoch__idx
+    on training_zone (user_id, epoch) where deleted = false;
+
+create trigger set_epoch before insert or update on training_zone
+    for each row execute function set_epoch_for_user();
+
+create table training_zone_archive (
+    primary key (id),
+    foreign key (user_id) references "user" on delete cascade,
+    check (deleted = true)
+) inherits (training_zone);
+
+alter table training_zone_archive alter column archived_at set not null;
+
+create trigger archive_training_zone
+    after insert or update of deleted or delete
+    on training_zone
+    for each row execute procedure archive_record();
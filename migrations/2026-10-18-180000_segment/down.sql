@@ -0,0 +1,30 @@
+drop table segment_effort;
+drop table segment_archive;
+drop table segment;
+
+delete from epoch_counter where table_name = 'segment';
+
+-- enum values can not be dropped, so the type is recreated without 'segment'
+delete from only action_provider_scope where entity = 'segment';
+delete from action_provider_scope_archive where entity = 'segment';
+
+alter type scope_entity rename to scope_entity_old;
+
+create type scope_entity as enum(
+    'diary',
+    'wod',
+    'movement',
+    'strength_session',
+    'strength_set',
+    'metcon',
+    'metcon_session',
+    'metcon_movement',
+    'cardio_session',
+    'route',
+    'training_zone'
+);
+
+alter table action_provider_scope
+    alter column entity type scope_entity using entity::text::scope_entity;
+
+drop type scope_entity_old;
@@ -0,0 +1,53 @@
+alter type scope_entity add value 'segment';
+
+-- The first and the last position of the track are the start and the end of the segment.
+create table segment (
+    id bigint primary key,
+    user_id bigint not null references "user" on delete cascade,
+    name varchar(80) not null check (length(name) >= 2),
+    track "position"[] not null check (cardinality(track) >= 2),
+    epoch bigint not null,
+    deleted boolean not null default false,
+    archived_at timestamptz,
+    archived_by bigint
+);
+
+create unique index segment__user_id__name__key
+    on segment (user_id, name) where deleted = false;
+
+create index segment__user_id__epoch__idx
+    on segment (user_id, epoch) where deleted = false;
+
+create trigger set_epoch before insert or update on segment
+    for each row execute function set_epoch_for_user();
+
+create table segment_archive (
+    primary key (id),
+    foreign key (user_id) references "user" on delete cascade,
+    check (deleted = true)
+) inherits (segment);
+
+alter table segment_archive alter column archived_at set not null;
+
+create trigger archive_segment
+    after insert or update of deleted or delete
+    on segment
+    for each row execute procedure archive_record();
+
+-- The traversals of segments detected in the tracks of cardio sessions.
+-- There are no foreign keys on the segment and the cardio session, so that the efforts are kept
+-- when either of them is archived and are available again when it is restored.
+-- The efforts of segments and cardio sessions that have been deleted permanently are deleted when
+-- the archive is purged.
+create table segment_effort (
+    user_id bigint not null references "user" on delete cascade,
+    segment_id bigint not null,
+    cardio_session_id bigint not null,
+    start integer not null check (start >= 0), -- milliseconds since start of the session
+    time integer not null check (time > 0), -- milliseconds
+    avg_heart_rate integer check (avg_heart_rate > 0),
+    datetime timestamptz not null,
+    primary key (segment_id, cardio_session_id, start)
+);
+
+create index segment_effort__cardio_session_id__idx on segment_effort (cardio_session_id);
@@ -0,0 +1,11 @@
+alter table cardio_session drop column if exists track_geometry;
+alter table route drop column if exists track_geometry;
+drop function if exists track_geometry;
+
+drop trigger set_track_bbox on cardio_session;
+drop trigger set_track_bbox on route;
+drop function set_track_bbox;
+
+alter table cardio_session drop column track_bbox;
+alter table route drop column track_bbox;
+drop function track_bbox;
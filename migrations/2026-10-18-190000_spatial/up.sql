@@ -0,0 +1,100 @@
+-- The bounding box of a track with the longitude as x and the latitude as y coordinate.
+create function track_bbox(track "position"[])
+    returns box as $$
+        select box(point(min(longitude), min(latitude)), point(max(longitude), max(latitude)))
+        from unnest(track);
+    $$ language sql immutable;
+
+create function set_track_bbox()
+    returns trigger as $$
+    begin
+        new.track_bbox := track_bbox(new.track);
+        return new;
+    end;
+    $$ language plpgsql;
+
+alter table cardio_session add column track_bbox box;
+alter table route add column track_bbox box;
+
+create trigger set_track_bbox before insert or update of track on cardio_session
+    for each row execute function set_track_bbox();
+
+create trigger set_track_bbox before insert or update of track on route
+    for each row execute function set_track_bbox();
+
+-- the epochs must not change, otherwise all clients would download all tracks again
+alter table cardio_session disable trigger set_epoch;
+update cardio_session set track_bbox = track_bbox(track) where track is not null;
+alter table cardio_session enable trigger set_epoch;
+
+alter table route disable trigger set_epoch;
+update route set track_bbox = track_bbox(track) where track is not null;
+alter table route enable trigger set_epoch;
+
+create index cardio_session__track_bbox__idx
+    on cardio_session using gist (track_bbox) where deleted = false;
+
+create index route__track_bbox__idx
+    on route using gist (track_bbox) where deleted = false;
+
+-- If PostGIS is available, the tracks are additionally stored as geometries, so that they can be
+-- queried exactly. Otherwise the server falls back to the bounding boxes.
+-- Creating the extension requires superuser privileges unless it has been created before.
+do $do$
+    begin
+        if not exists (select from pg_available_extensions where name = 'postgis') then
+            raise notice 'postgis is not available, spatial queries use bounding boxes only';
+            return;
+        end if;
+
+        begin
+            create extension if not exists postgis;
+        exception when insufficient_privilege then
+            raise notice 'postgis can not be created by this user, spatial queries use bounding boxes only';
+            return;
+        end;
+
+        create function track_geometry(track "position"[])
+            returns geometry as $$
+                select ST_SetSRID(
+                    case
+                        when count(*) > 1 then ST_MakeLine(array_agg(point order by n))
+                        else (array_agg(point))[1]
+                    end,
+                    4326
+                )
+                from unnest(track) with ordinality as t(longitude, latitude, elevation, distance, time, n),
+                    lateral (select ST_MakePoint(t.longitude, t.latitude) as point) as p;
+            $$ language sql immutable;
+
+        create or replace function set_track_bbox()
+            returns trigger as $$
+            begin
+                new.track_bbox := track_bbox(new.track);
+                new.track_geometry := track_geometry(new.track);
+                return new;
+            end;
+            $$ language plpgsql;
+
+        alter table cardio_session add column track_geometry geometry(Geometry, 4326);
+        alter table route add column track_geometry geometry(Geometry, 4326);
+
+        alter table cardio_session disable trigger set_epoch;
+        update cardio_session set track_geometry = track_geometry(track) where track is not null;
+        alter table cardio_session enable trigger set_epoch;
+
+        alter table route disable trigger set_epoch;
+        update route set track_geometry = track_geometry(track) where track is not null;
+        alter table route enable trigger set_epoch;
+
+        create index cardio_session__track_geometry__idx
+            on cardio_session using gist (track_geometry) where deleted = false;
+        create index cardio_session__track_geography__idx
+            on cardio_session using gist ((track_geometry::geography)) where deleted = false;
+
+        create index route__track_geometry__idx
+            on route using gist (track_geometry) where deleted = false;
+        create index route__track_geography__idx
+            on route using gist ((track_geometry::geography)) where deleted = false;
+    end;
+$do$;
@@ -0,0 +1,2 @@
+drop table route_overview;
+drop table cardio_session_overview;
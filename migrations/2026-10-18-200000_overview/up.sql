@@ -0,0 +1,11 @@
+create table cardio_session_overview (
+    cardio_session_id bigint primary key references cardio_session on delete cascade,
+    epoch bigint not null, -- epoch of the cardio session the overview was computed for
+    track "position"[] not null
+);
+
+create table route_overview (
+    route_id bigint primary key references route on delete cascade,
+    epoch bigint not null, -- epoch of the route the overview was computed for
+    track "position"[] not null
+);
@@ -173,6 +173,7 @@ async fn setup(config: &Config) -> Result<()> {
                 "Reserve a spot in a Open Gym class in gym 2.",
             ),
         ],
+        &[],
         Duration::try_days(14).unwrap(),
         Duration::zero(),
     )
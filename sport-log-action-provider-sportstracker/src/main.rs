@@ -8,7 +8,7 @@ use serde::Deserialize;
 use sport_log_ap_utils::{disable_events, get_events, setup as setup_db};
 use sport_log_types::{
     ActionEventId, CardioSession, CardioSessionId, CardioType, ExecutableActionEvent, ID_HEADER,
-    Movement, Position,
+    Movement, Position, ScopeAccess, ScopeEntity,
     uri::{CARDIO_SESSION, MOVEMENT, route_max_version},
 };
 use thiserror::Error;
@@ -193,6 +193,11 @@ async fn setup(config: &Config) -> Result<()> {
         PLATFORM_NAME,
         true,
         &[("fetch", "Fetch and save new workouts.")],
+        &[
+            (ScopeEntity::Movement, ScopeAccess::Read),
+            (ScopeEntity::CardioSession, ScopeAccess::Read),
+            (ScopeEntity::CardioSession, ScopeAccess::Write),
+        ],
         Duration::try_hours(168).unwrap(),
         Duration::zero(),
     )
@@ -4,15 +4,19 @@ use chrono::{Duration, SecondsFormat, Utc};
 use rand::RngExt;
 use reqwest::{Client, Error, StatusCode};
 use sport_log_types::{
-    Action, ActionEventId, ActionId, ActionProvider, ActionProviderId, ExecutableActionEvent,
-    Platform, PlatformId,
+    Action, ActionEventId, ActionId, ActionProvider, ActionProviderId, ActionProviderScope,
+    ActionProviderScopeId, ExecutableActionEvent, Platform, PlatformId, ScopeAccess, ScopeEntity,
     uri::{
-        AP_ACTION, AP_ACTION_EVENT, AP_ACTION_PROVIDER, AP_EXECUTABLE_ACTION_EVENT, AP_PLATFORM,
-        route_max_version,
+        AP_ACTION, AP_ACTION_EVENT, AP_ACTION_PROVIDER, AP_ACTION_PROVIDER_SCOPE,
+        AP_EXECUTABLE_ACTION_EVENT, AP_PLATFORM, route_max_version,
     },
 };
 use tracing::{debug, error, info};
 
+/// Register the platform, the action provider, its actions and the scopes it requires.
+///
+/// The `scopes` are the entity types the action provider needs to access on behalf of a user.
+/// They can only be used after the user has granted them.
 #[allow(clippy::too_many_arguments)]
 pub async fn setup(
     server_url: &str,
@@ -22,6 +26,7 @@ pub async fn setup(
     platform_name: &str,
     credential: bool,
     actions: &[(&str, &str)],
+    scopes: &[(ScopeEntity, ScopeAccess)],
     create_before: Duration,
     delete_after: Duration,
 ) -> Result<(), Error> {
@@ -138,8 +143,36 @@ pub async fn setup(
         .await?
         .status()
     {
-        StatusCode::OK => info!("action created\nsetup successful"),
-        StatusCode::CONFLICT => info!("action already exists\nsetup successful"),
+        StatusCode::OK => info!("action created"),
+        StatusCode::CONFLICT => info!("action already exists"),
+        status => error!("an error occurred (status {status})"),
+    }
+
+    let scopes: Vec<ActionProviderScope> = scopes
+        .iter()
+        .map(|&(entity, access)| ActionProviderScope {
+            id: ActionProviderScopeId(rng.random()),
+            action_provider_id,
+            entity,
+            access,
+            deleted: false,
+        })
+        .collect();
+
+    match client
+        .post(route_max_version(
+            server_url,
+            AP_ACTION_PROVIDER_SCOPE,
+            None,
+        ))
+        .basic_auth(name, Some(&password))
+        .json(&scopes)
+        .send()
+        .await?
+        .status()
+    {
+        StatusCode::OK => info!("scopes created\nsetup successful"),
+        StatusCode::CONFLICT => info!("scopes already exist\nsetup successful"),
         status => error!("an error occurred (status {status})"),
     }
 
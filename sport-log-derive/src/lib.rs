@@ -52,6 +52,13 @@ pub fn db_with_ap_id(input: TokenStream) -> TokenStream {
     impl_db_with_ap_id(Identifiers::from_ast(&ast))
 }
 
+/// Derives `DbWithScope`.
+#[proc_macro_derive(DbWithScope)]
+pub fn db_with_scope(input: TokenStream) -> TokenStream {
+    let ast = syn::parse(input).unwrap();
+    impl_db_with_scope(Identifiers::from_ast(&ast))
+}
+
 /// Derives `DbWithDateTime`.
 #[proc_macro_derive(DbWithDateTime)]
 pub fn db_with_datetime(input: TokenStream) -> TokenStream {
@@ -65,3 +72,10 @@ pub fn modifiable_db(input: TokenStream) -> TokenStream {
     let ast = syn::parse(input).unwrap();
     impl_modifiable_db(Identifiers::from_ast(&ast))
 }
+
+/// Derives `ArchivableDb`.
+#[proc_macro_derive(ArchivableDb)]
+pub fn archivable_db(input: TokenStream) -> TokenStream {
+    let ast = syn::parse(input).unwrap();
+    impl_archivable_db(Identifiers::from_ast(&ast))
+}
@@ -68,6 +68,22 @@ pub(crate) fn impl_db_with_ap_id(
     .into()
 }
 
+pub(crate) fn impl_db_with_scope(
+    Identifiers {
+        db_type,
+        value_type,
+        ..
+    }: Identifiers,
+) -> TokenStream {
+    quote! {
+        impl crate::db::DbWithScope for #db_type {
+            const SCOPE_ENTITY: sport_log_types::ScopeEntity =
+                sport_log_types::ScopeEntity::#value_type;
+        }
+    }
+    .into()
+}
+
 pub(crate) fn impl_db_with_datetime(
     Identifiers {
         db_type,
@@ -105,3 +121,25 @@ pub(crate) fn impl_modifiable_db(
     }
     .into()
 }
+
+pub(crate) fn impl_archivable_db(
+    Identifiers {
+        db_type,
+        value_name,
+        ..
+    }: Identifiers,
+) -> TokenStream {
+    let table_name = value_name.to_string();
+    quote! {
+        impl crate::db::ArchivableDb for #db_type {
+            const TABLE_NAME: &'static str = #table_name;
+
+            type ArchivedAtColumn = sport_log_types::schema::#value_name::columns::archived_at;
+
+            fn archived_at_column() -> Self::ArchivedAtColumn {
+                sport_log_types::schema::#value_name::columns::archived_at
+            }
+        }
+    }
+    .into()
+}
@@ -16,6 +16,9 @@
 //! However most [`ActionProvider`](sport_log_types::ActionProvider) will delete a
 //! [`ActionEvents`](sport_log_types::ActionEvent) directly after execution.
 //!
+//! Furthermore **Sport Log Scheduler** lets the server permanently delete archived entries that
+//! are older than the archive retention configured on the server.
+//!
 //! # Usage
 //!
 //! The **Sport Log Scheduler** has do be executed periodically, preferably as a cron job every
@@ -34,8 +37,10 @@ use reqwest::{Error as ReqwestError, blocking::Client};
 use serde::Deserialize;
 use sport_log_types::{
     ADMIN_USERNAME, ActionEvent, ActionEventId, CreatableActionRule, DeletableActionEvent,
+    PurgedArchive,
     uri::{
-        ADM_ACTION_EVENT, ADM_CREATABLE_ACTION_RULE, ADM_DELETABLE_ACTION_EVENT, route_max_version,
+        ADM_ACTION_EVENT, ADM_CREATABLE_ACTION_RULE, ADM_DELETABLE_ACTION_EVENT, ADM_PURGE_ARCHIVE,
+        route_max_version,
     },
 };
 use tracing::{debug, error, info};
@@ -92,6 +97,10 @@ fn main() -> ExitCode {
         error!("failed to delete old action events: {error}");
         return ExitCode::FAILURE;
     }
+    if let Err(error) = purge_archive(&client, &config) {
+        error!("failed to purge archive: {error}");
+        return ExitCode::FAILURE;
+    }
 
     ExitCode::SUCCESS
 }
@@ -226,6 +235,28 @@ fn delete_action_events(client: &Client, config: &Config) -> Result<(), ReqwestE
     Ok(())
 }
 
+fn purge_archive(client: &Client, config: &Config) -> Result<(), ReqwestError> {
+    let purged_archives: Vec<PurgedArchive> = client
+        .post(route_max_version(
+            &config.server_url,
+            ADM_PURGE_ARCHIVE,
+            None,
+        ))
+        .basic_auth(ADMIN_USERNAME, Some(&config.admin_password))
+        .send()?
+        .error_for_status()?
+        .json()?;
+
+    for purged_archive in &purged_archives {
+        info!(
+            "purged {} archived entries of {}",
+            purged_archive.count, purged_archive.table
+        );
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use std::str::FromStr;
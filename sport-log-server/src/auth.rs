@@ -6,20 +6,23 @@ use axum::{
 };
 use axum_extra::{
     TypedHeader,
-    headers::{Authorization, authorization::Basic},
+    headers::{
+        Authorization,
+        authorization::{Basic, Bearer},
+    },
 };
-use sport_log_types::{ActionProviderId, ID_HEADER, UserId};
+use sport_log_types::{ActionProviderId, ID_HEADER, ScopeAccess, ScopeEntity, UserId};
 
 use crate::{
     AppState, Config,
-    db::{ActionProviderDb, AdminDb, GetById, UserDb},
-    error::HandlerError,
+    db::{ActionProviderDb, AdminDb, AuthSessionDb, DbWithScope, GetById, ScopeGrantDb, UserDb},
+    error::{ErrorMessage, HandlerError},
 };
 
 /// [`AuthUser`] is used as a request guard to authenticate a user.
 ///
-/// For the creation of an [`AuthUser`] the username and password have to be transmitted via HTTP
-/// basic auth.
+/// For the creation of an [`AuthUser`] either an access token has to be transmitted via HTTP
+/// bearer auth or the username and password have to be transmitted via HTTP basic auth.
 ///
 /// The admin can also use endpoints with an [`AuthUser`] as request guard.
 ///
@@ -45,13 +48,18 @@ where
     type Rejection = HandlerError;
 
     async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        if let Some(session) = AuthSession::from_bearer(parts, state).await? {
+            return Ok(Self(session.user_id));
+        }
+
         let TypedHeader(auth) =
             TypedHeader::<Authorization<Basic>>::from_request_parts(parts, state).await?;
         let username = auth.username();
         let password = auth.password();
 
-        let State(AppState { config, db_pool }) =
-            State::<AppState>::from_request_parts(parts, state).await?;
+        let State(AppState {
+            config, db_pool, ..
+        }) = State::<AppState>::from_request_parts(parts, state).await?;
 
         let mut db = db_pool.get().await?;
 
@@ -74,8 +82,8 @@ where
 
 /// [`AuthUserOrAP`] is used as a request guard to authenticate a user.
 ///
-/// For the creation of an [`AuthUserOrAP`] the username and password have to be transmitted via
-/// HTTP basic auth.
+/// For the creation of an [`AuthUserOrAP`] either an access token has to be transmitted via HTTP
+/// bearer auth or the username and password have to be transmitted via HTTP basic auth.
 ///
 /// [`ActionProvider`](sport_log_types::ActionProvider) can also use endpoints with an
 /// [`AuthUserOrAP`] as request guard if the user has an enabled
@@ -85,6 +93,9 @@ where
 /// In order to do so, the username and password must the ones from the
 /// [`ActionProvider`](sport_log_types::ActionProvider). and a `id` header must be preset that is
 /// set to the id of the user the action provider wants to authenticate as.
+/// An [`ActionProvider`](sport_log_types::ActionProvider) can only access the entities the user
+/// has granted it a [`ScopeGrant`](sport_log_types::ScopeGrant) for. This has to be checked using
+/// [`AuthUserOrAP::check_scope`].
 ///
 /// The admin can also use endpoints with an [`AuthUserOrAP`] as request guard.
 ///
@@ -92,13 +103,48 @@ where
 /// configured in `sport-log-server.toml` and a `id` header must be preset that is set to the id of
 /// the user the admin wants to authenticate as.
 #[derive(Debug, Clone, Copy)]
-pub struct AuthUserOrAP(UserId);
+pub struct AuthUserOrAP {
+    user_id: UserId,
+    /// `None` if authenticated as user or admin.
+    scopes: Option<Scopes>,
+}
+
+impl AuthUserOrAP {
+    fn user(user_id: UserId) -> Self {
+        Self {
+            user_id,
+            scopes: None,
+        }
+    }
+
+    /// Check if the entity type of `D` may be accessed with `access`.
+    ///
+    /// Users and the admin have access to everything, action providers only to the granted scopes.
+    pub fn check_scope<D: DbWithScope>(self, access: ScopeAccess) -> Result<(), HandlerError> {
+        match self.scopes {
+            Some(scopes) if !scopes.contains(D::SCOPE_ENTITY, access) => Err(HandlerError::from((
+                StatusCode::FORBIDDEN,
+                ErrorMessage::MissingScope {
+                    entity: D::SCOPE_ENTITY,
+                    access,
+                },
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl From<AuthUser> for AuthUserOrAP {
+    fn from(auth: AuthUser) -> Self {
+        Self::user(*auth)
+    }
+}
 
 impl Deref for AuthUserOrAP {
     type Target = UserId;
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.user_id
     }
 }
 
@@ -110,25 +156,39 @@ where
     type Rejection = HandlerError;
 
     async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        if let Some(session) = AuthSession::from_bearer(parts, state).await? {
+            return Ok(Self::user(session.user_id));
+        }
+
         let TypedHeader(auth) =
             TypedHeader::<Authorization<Basic>>::from_request_parts(parts, state).await?;
         let username = auth.username();
         let password = auth.password();
 
-        let State(AppState { config, db_pool }) =
-            State::<AppState>::from_request_parts(parts, state).await?;
+        let State(AppState {
+            config, db_pool, ..
+        }) = State::<AppState>::from_request_parts(parts, state).await?;
 
         let mut db = db_pool.get().await?;
 
         if let Ok(id) = UserDb::auth(username, password, &mut db).await {
-            return Ok(Self(id));
+            return Ok(Self::user(id));
         }
 
         let user_id = parse_id_header(parts, UserId)?;
         if let Ok(auth) = ActionProviderDb::auth_as_user(username, password, user_id, &mut db).await
         {
             match auth {
-                AuthApForUser::Allowed(_) => return Ok(Self(user_id)),
+                AuthApForUser::Allowed(ap_id) => {
+                    let scopes = ScopeGrantDb::get_scopes(ap_id, user_id, &mut db)
+                        .await?
+                        .into_iter()
+                        .collect();
+                    return Ok(Self {
+                        user_id,
+                        scopes: Some(scopes),
+                    });
+                }
                 AuthApForUser::Forbidden => return Err(StatusCode::FORBIDDEN.into()),
             }
         }
@@ -139,18 +199,104 @@ where
             UserDb::get_by_id(user_id, &mut db)
                 .await
                 .map_err(|_| HandlerError::from(StatusCode::NOT_FOUND))?;
-            return Ok(Self(user_id));
+            return Ok(Self::user(user_id));
         }
         Err(StatusCode::UNAUTHORIZED.into())
     }
 }
 
+/// [`AuthSession`] is used as a request guard to authenticate a user by an access token.
+///
+/// For the creation of an [`AuthSession`] an access token obtained by logging in has to be
+/// transmitted via HTTP bearer auth.
+#[derive(Debug, Clone, Copy)]
+pub struct AuthSession {
+    session_id: i64,
+    user_id: UserId,
+}
+
+impl AuthSession {
+    pub fn session_id(self) -> i64 {
+        self.session_id
+    }
+
+    /// Returns `None` if no bearer auth is present.
+    async fn from_bearer<S>(parts: &mut Parts, state: &S) -> Result<Option<Self>, HandlerError>
+    where
+        S: Send + Sync,
+        AppState: FromRef<S>,
+    {
+        let Ok(TypedHeader(auth)) =
+            TypedHeader::<Authorization<Bearer>>::from_request_parts(parts, state).await
+        else {
+            return Ok(None);
+        };
+
+        let State(AppState { db_pool, .. }) =
+            State::<AppState>::from_request_parts(parts, state).await?;
+
+        let mut db = db_pool.get().await?;
+
+        match AuthSessionDb::auth(auth.token(), &mut db).await {
+            Ok((session_id, user_id)) => Ok(Some(Self {
+                session_id,
+                user_id,
+            })),
+            Err(_) => Err(StatusCode::UNAUTHORIZED.into()),
+        }
+    }
+}
+
+impl Deref for AuthSession {
+    type Target = UserId;
+
+    fn deref(&self) -> &Self::Target {
+        &self.user_id
+    }
+}
+
+impl<S> FromRequestParts<S> for AuthSession
+where
+    S: Send + Sync,
+    AppState: FromRef<S>,
+{
+    type Rejection = HandlerError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        Self::from_bearer(parts, state)
+            .await?
+            .ok_or_else(|| StatusCode::UNAUTHORIZED.into())
+    }
+}
+
 pub enum AuthApForUser {
-    #[allow(dead_code)]
     Allowed(ActionProviderId),
     Forbidden,
 }
 
+/// The set of scopes a user has granted to an action provider.
+#[derive(Debug, Clone, Copy, Default)]
+struct Scopes(u32);
+
+impl Scopes {
+    fn bit(entity: ScopeEntity, access: ScopeAccess) -> u32 {
+        1 << (entity as u32 * 2 + access as u32)
+    }
+
+    fn contains(self, entity: ScopeEntity, access: ScopeAccess) -> bool {
+        self.0 & Self::bit(entity, access) != 0
+    }
+}
+
+impl FromIterator<(ScopeEntity, ScopeAccess)> for Scopes {
+    fn from_iter<T: IntoIterator<Item = (ScopeEntity, ScopeAccess)>>(iter: T) -> Self {
+        Self(
+            iter.into_iter()
+                .fold(0, |bits, (entity, access)| bits | Self::bit(entity, access)),
+        )
+    }
+}
+
 /// [`AuthAP`] is used as a request guard to authenticate an action provider.
 ///
 /// For the creation of an [`AuthAP`] the username and password have to be transmitted via HTTP
@@ -185,8 +331,9 @@ where
         let username = auth.username();
         let password = auth.password();
 
-        let State(AppState { config, db_pool }) =
-            State::<AppState>::from_request_parts(parts, state).await?;
+        let State(AppState {
+            config, db_pool, ..
+        }) = State::<AppState>::from_request_parts(parts, state).await?;
 
         let mut db = db_pool.get().await?;
 
@@ -0,0 +1,212 @@
+//! Validation and normalization of the summary fields of cardio sessions.
+//!
+//! The `distance`, `ascent`, `descent` and `time` of a cardio session are sent by the client and
+//! can disagree with its track.
+//! They can be recomputed from the track after it has been checked for physically impossible
+//! positions.
+
+use std::fmt;
+
+use axum::http::StatusCode;
+use diesel::prelude::*;
+use sport_log_types::{
+    CardioSession, CardioSessionId, CardioSessionValidation, CardioSummaryDiscrepancy,
+    CardioSummaryField, Position, schema::cardio_session,
+};
+
+use crate::{
+    error::{ErrorMessage, HandlerError},
+    track_file,
+};
+
+/// The maximal speed in meter per second between two positions.
+const MAX_SPEED: f64 = 100.;
+
+/// The distance in meter by which two positions may be apart regardless of the time between them.
+///
+/// Smaller jumps are mostly GPS noise.
+const GPS_TOLERANCE: f64 = 50.;
+
+/// The number of positions on either side over which the elevation is averaged.
+const SMOOTHING_RADIUS: usize = 2;
+
+/// The relative difference up to which a stored value is considered equal to the computed one.
+const DISCREPANCY_TOLERANCE: f64 = 0.02;
+
+/// The summary fields and the track of a cardio session.
+#[derive(Queryable, Selectable, Debug)]
+#[diesel(table_name = cardio_session)]
+pub struct CardioSessionSummaryTrack {
+    pub id: CardioSessionId,
+    pub distance: Option<i32>,
+    pub ascent: Option<i32>,
+    pub descent: Option<i32>,
+    pub time: Option<i32>,
+    pub track: Option<Vec<Position>>,
+}
+
+/// The summary fields computed from a track.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TrackSummary {
+    pub distance: i32,
+    pub ascent: i32,
+    pub descent: i32,
+    pub time: i32,
+}
+
+#[derive(Debug)]
+pub struct InvalidTrack(String);
+
+impl fmt::Display for InvalidTrack {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<InvalidTrack> for HandlerError {
+    fn from(error: InvalidTrack) -> Self {
+        (
+            StatusCode::BAD_REQUEST,
+            ErrorMessage::Other {
+                error: error.to_string(),
+            },
+        )
+            .into()
+    }
+}
+
+/// Check that the track is physically possible.
+///
+/// Time and distance must not decrease and consecutive positions must not be further apart than
+/// possible at [`MAX_SPEED`].
+fn check_track(track: &[Position]) -> Result<(), InvalidTrack> {
+    for (i, positions) in track.windows(2).enumerate() {
+        let (from, to) = (&positions[0], &positions[1]);
+        if to.time < from.time {
+            return Err(InvalidTrack(format!(
+                "negative time difference at position {}",
+                i + 1
+            )));
+        }
+        if to.distance < from.distance {
+            return Err(InvalidTrack(format!(
+                "negative distance difference at position {}",
+                i + 1
+            )));
+        }
+        let jump = track_file::distance(from.latitude, from.longitude, to.latitude, to.longitude);
+        let seconds = f64::from(to.time - from.time) / 1000.;
+        if jump > MAX_SPEED * seconds + GPS_TOLERANCE {
+            return Err(InvalidTrack(format!(
+                "jump of {jump:.0} m in {seconds:.0} s at position {}",
+                i + 1
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// The elevations of the track averaged over [`SMOOTHING_RADIUS`] positions on either side.
+fn smoothed_elevations(track: &[Position]) -> impl Iterator<Item = f64> + '_ {
+    (0..track.len()).map(|i| {
+        let window =
+            &track[i.saturating_sub(SMOOTHING_RADIUS)..track.len().min(i + SMOOTHING_RADIUS + 1)];
+        window
+            .iter()
+            .map(|position| position.elevation)
+            .sum::<f64>()
+            / window.len() as f64
+    })
+}
+
+/// Compute the summary fields from a track.
+///
+/// Returns `None` if the track is empty.
+pub fn summarize(track: &[Position]) -> Result<Option<TrackSummary>, InvalidTrack> {
+    check_track(track)?;
+    let Some(last) = track.last() else {
+        return Ok(None);
+    };
+    let (ascent, descent) = track_file::ascent_descent(smoothed_elevations(track));
+    Ok(Some(TrackSummary {
+        distance: last.distance.round() as i32,
+        ascent: ascent.unwrap_or_default(),
+        descent: descent.unwrap_or_default(),
+        time: last.time,
+    }))
+}
+
+/// Replace the summary fields of a cardio session by the ones computed from its track.
+///
+/// Sessions without a track are left unchanged.
+pub fn normalize(cardio_session: &mut CardioSession) -> Result<(), InvalidTrack> {
+    let Some(track) = &cardio_session.track else {
+        return Ok(());
+    };
+    if let Some(summary) = summarize(track)? {
+        cardio_session.distance = Some(summary.distance);
+        cardio_session.ascent = Some(summary.ascent);
+        cardio_session.descent = Some(summary.descent);
+        cardio_session.time = Some(summary.time);
+    }
+    Ok(())
+}
+
+/// Compare the summary fields of a cardio session with the ones computed from its track.
+///
+/// Returns `None` if the session has no track and the computed summary if the track is valid.
+pub fn validate(
+    session: &CardioSessionSummaryTrack,
+) -> Option<(CardioSessionValidation, Option<TrackSummary>)> {
+    let track = session.track.as_deref()?;
+    let summary = match summarize(track) {
+        Ok(summary) => summary?,
+        Err(InvalidTrack(error)) => {
+            return Some((
+                CardioSessionValidation {
+                    cardio_session_id: session.id,
+                    invalid_track: Some(error),
+                    discrepancies: vec![],
+                },
+                None,
+            ));
+        }
+    };
+
+    let discrepancies = [
+        (
+            CardioSummaryField::Distance,
+            session.distance,
+            summary.distance,
+        ),
+        (CardioSummaryField::Ascent, session.ascent, summary.ascent),
+        (
+            CardioSummaryField::Descent,
+            session.descent,
+            summary.descent,
+        ),
+        (CardioSummaryField::Time, session.time, summary.time),
+    ]
+    .into_iter()
+    .filter(|(_, stored, computed)| {
+        stored.is_none_or(|stored| {
+            f64::from(stored.abs_diff(*computed))
+                > (f64::from(*computed) * DISCREPANCY_TOLERANCE).max(1.)
+        })
+    })
+    .map(|(field, stored, computed)| CardioSummaryDiscrepancy {
+        field,
+        stored,
+        computed,
+    })
+    .collect();
+
+    Some((
+        CardioSessionValidation {
+            cardio_session_id: session.id,
+            invalid_track: None,
+            discrepancies,
+        },
+        Some(summary),
+    ))
+}
@@ -1,4 +1,4 @@
-use std::{net::SocketAddr, path::PathBuf};
+use std::{collections::BTreeMap, net::SocketAddr, path::PathBuf};
 
 use serde::{Deserialize, Serialize};
 
@@ -11,13 +11,42 @@ use serde::{Deserialize, Serialize};
 ///
 /// `ap_self_registration` determines if action providers can register themselves or if only the
 /// admin can create new action provider.
+///
+/// `credential_key` is the base64 encoded 32 byte key used to encrypt the passwords of
+/// [`PlatformCredential`](sport_log_types::PlatformCredential). When the key is replaced the
+/// previous keys have to be kept in `old_credential_keys` until all passwords have been
+/// re-encrypted.
+///
+/// `archive_retention` determines after how many days archived entries are deleted permanently.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Config {
     pub admin_password: String,
     pub user_self_registration: bool,
     pub ap_self_registration: bool,
+    pub credential_key: String,
+    #[serde(default)]
+    pub old_credential_keys: Vec<String>,
     pub database_url: String,
     pub release_address: SocketAddr,
     pub debug_address: SocketAddr,
     pub app_dir: Option<PathBuf>,
+    #[serde(default)]
+    pub archive_retention: ArchiveRetention,
+}
+
+/// Retention of archived entries in days.
+///
+/// `default` applies to all tables that have no retention of their own in `tables`.
+/// Archived entries of tables without retention are kept forever.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ArchiveRetention {
+    pub default: Option<u32>,
+    #[serde(flatten)]
+    pub tables: BTreeMap<String, u32>,
+}
+
+impl ArchiveRetention {
+    pub fn days(&self, table: &str) -> Option<u32> {
+        self.tables.get(table).copied().or(self.default)
+    }
 }
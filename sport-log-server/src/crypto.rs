@@ -0,0 +1,169 @@
+//! Envelope encryption of [`PlatformCredential`](sport_log_types::PlatformCredential) passwords.
+//!
+//! Every password is encrypted with its own random data key. The data key itself is encrypted
+//! with the server key (`credential_key` in `sport-log-server.toml`). Therefore rotating the server
+//! key only requires re-encrypting the data keys.
+//!
+//! An encrypted password has the format `$enc$v1$<key id>$<encrypted data key>$<ciphertext>` where
+//! the key id identifies the server key and the last two parts are base64 encoded and prefixed
+//! with their nonce. The id of the user the credential belongs to is used as associated data so
+//! that an encrypted password can not be used for another user.
+//!
+//! Whether a stored password is encrypted is recorded in the column `password_encrypted` and never
+//! derived from the password itself.
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use chacha20poly1305::{
+    KeyInit, XChaCha20Poly1305, XNonce,
+    aead::{Aead, Payload},
+};
+use rand::RngExt;
+use sha2::{Digest, Sha256};
+use sport_log_types::UserId;
+
+use crate::config::Config;
+
+const PREFIX: &str = "$enc$v1$";
+const NONCE_LEN: usize = 24;
+
+struct Key {
+    id: String,
+    cipher: XChaCha20Poly1305,
+}
+
+impl Key {
+    fn new(key: &str) -> Result<Self, String> {
+        let key = STANDARD
+            .decode(key)
+            .map_err(|err| format!("credential key is not valid base64: {err}"))?;
+        let cipher = XChaCha20Poly1305::new_from_slice(&key)
+            .map_err(|_| "credential key must be 32 bytes long".to_owned())?;
+        let id = Sha256::digest(&key)[..4]
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+        Ok(Self { id, cipher })
+    }
+}
+
+/// The current and all previous server keys used for credential encryption.
+pub struct CredentialKeys {
+    current: Key,
+    old: Vec<Key>,
+}
+
+impl CredentialKeys {
+    pub fn new(config: &Config) -> Result<Self, String> {
+        Ok(Self {
+            current: Key::new(&config.credential_key)?,
+            old: config
+                .old_credential_keys
+                .iter()
+                .map(|key| Key::new(key))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    fn key(&self, id: &str) -> Option<&Key> {
+        std::iter::once(&self.current)
+            .chain(&self.old)
+            .find(|key| key.id == id)
+    }
+
+    /// Encrypt the password with a new data key.
+    pub fn encrypt(&self, password: &str, user_id: UserId) -> String {
+        let data_key: [u8; 32] = rand::rng().random();
+        let data_cipher = XChaCha20Poly1305::new(&data_key.into());
+        let ciphertext = seal(&data_cipher, password.as_bytes(), user_id);
+        let encrypted_data_key = seal(&self.current.cipher, &data_key, user_id);
+
+        format!(
+            "{PREFIX}{}${}${}",
+            self.current.id,
+            STANDARD.encode(encrypted_data_key),
+            STANDARD.encode(ciphertext)
+        )
+    }
+
+    /// Decrypt the password.
+    ///
+    /// Returns `None` if the server key is unknown or the decryption fails.
+    pub fn decrypt(&self, password: &str, user_id: UserId) -> Option<String> {
+        let (key, encrypted_data_key, ciphertext) = self.split(password)?;
+        let data_key = open(&key.cipher, &encrypted_data_key, user_id)?;
+        let data_cipher = XChaCha20Poly1305::new_from_slice(&data_key).ok()?;
+        let plaintext = open(&data_cipher, &ciphertext, user_id)?;
+        String::from_utf8(plaintext).ok()
+    }
+
+    /// Make sure the password is encrypted with the current server key.
+    ///
+    /// `encrypted` states whether the password is already encrypted or still stored in plaintext.
+    /// Returns `None` if the password is already encrypted with the current server key or if it
+    /// is encrypted with an unknown server key.
+    pub fn reencrypt(&self, password: &str, encrypted: bool, user_id: UserId) -> Option<String> {
+        if !encrypted {
+            return Some(self.encrypt(password, user_id));
+        }
+
+        let (key, encrypted_data_key, ciphertext) = self.split(password)?;
+        if key.id == self.current.id {
+            return None;
+        }
+        let data_key = open(&key.cipher, &encrypted_data_key, user_id)?;
+        let encrypted_data_key = seal(&self.current.cipher, &data_key, user_id);
+
+        Some(format!(
+            "{PREFIX}{}${}${}",
+            self.current.id,
+            STANDARD.encode(encrypted_data_key),
+            STANDARD.encode(ciphertext)
+        ))
+    }
+
+    fn split(&self, password: &str) -> Option<(&Key, Vec<u8>, Vec<u8>)> {
+        let mut parts = password.strip_prefix(PREFIX)?.split('$');
+        let key = self.key(parts.next()?)?;
+        let encrypted_data_key = STANDARD.decode(parts.next()?).ok()?;
+        let ciphertext = STANDARD.decode(parts.next()?).ok()?;
+        parts
+            .next()
+            .is_none()
+            .then_some((key, encrypted_data_key, ciphertext))
+    }
+}
+
+fn seal(cipher: &XChaCha20Poly1305, plaintext: &[u8], user_id: UserId) -> Vec<u8> {
+    let nonce: [u8; NONCE_LEN] = rand::rng().random();
+    let aad = user_id.0.to_be_bytes();
+    let mut sealed = nonce.to_vec();
+    sealed.extend(
+        cipher
+            .encrypt(
+                XNonce::from_slice(&nonce),
+                Payload {
+                    msg: plaintext,
+                    aad: &aad,
+                },
+            )
+            .expect("encryption of in-memory buffer can not fail"),
+    );
+    sealed
+}
+
+fn open(cipher: &XChaCha20Poly1305, sealed: &[u8], user_id: UserId) -> Option<Vec<u8>> {
+    if sealed.len() < NONCE_LEN {
+        return None;
+    }
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+    let aad = user_id.0.to_be_bytes();
+    cipher
+        .decrypt(
+            XNonce::from_slice(nonce),
+            Payload {
+                msg: ciphertext,
+                aad: &aad,
+            },
+        )
+        .ok()
+}
@@ -1,16 +1,42 @@
-use diesel::QueryResult;
-use diesel_async::AsyncPgConnection;
-use sport_log_types::{AccountData, EpochMap, UserId};
+use diesel::{QueryResult, result::Error as DieselError};
+use diesel_async::{AsyncConnection, AsyncPgConnection};
+use futures_util::{FutureExt, future::BoxFuture};
+use sport_log_types::{
+    AccountData, AccountDataChanges, AccountDataCursor, AccountDataPage, AccountDataSync, Epoch,
+    EpochMap, TableConflict, UserId,
+};
 
-use crate::db::*;
+use crate::{
+    crypto::CredentialKeys,
+    db::*,
+    error::{HandlerError, table_conflict},
+};
 
 pub struct AccountDataDb;
 
 impl AccountDataDb {
+    /// Get all entries belonging to the user.
+    ///
+    /// If `omit_tracks` is set, the summaries of routes and cardio sessions are returned instead.
     pub async fn get_by_user(
         user_id: UserId,
+        omit_tracks: bool,
         db: &mut AsyncPgConnection,
     ) -> QueryResult<AccountData> {
+        let (cardio_sessions, cardio_session_summaries) = if omit_tracks {
+            (
+                vec![],
+                CardioSessionDb::get_summaries_by_user(user_id, db).await?,
+            )
+        } else {
+            (CardioSessionDb::get_by_user(user_id, db).await?, vec![])
+        };
+        let (routes, route_summaries) = if omit_tracks {
+            (vec![], RouteDb::get_summaries_by_user(user_id, db).await?)
+        } else {
+            (RouteDb::get_by_user(user_id, db).await?, vec![])
+        };
+
         Ok(AccountData {
             user: Some(UserDb::get_by_id(user_id, db).await?),
             diaries: DiaryDb::get_by_user(user_id, db).await?,
@@ -21,23 +47,58 @@ impl AccountDataDb {
             metcons: MetconDb::get_by_user(user_id, db).await?,
             metcon_sessions: MetconSessionDb::get_by_user(user_id, db).await?,
             metcon_movements: MetconMovementDb::get_by_user(user_id, db).await?,
-            cardio_sessions: CardioSessionDb::get_by_user(user_id, db).await?,
-            routes: RouteDb::get_by_user(user_id, db).await?,
+            cardio_sessions,
+            cardio_session_summaries,
+            routes,
+            route_summaries,
+            training_zones: TrainingZoneDb::get_by_user(user_id, db).await?,
+            segments: SegmentDb::get_by_user(user_id, db).await?,
             platforms: PlatformDb::get_all(db).await?,
             platform_credentials: PlatformCredentialDb::get_by_user(user_id, db).await?,
             action_providers: ActionProviderDb::get_all(db).await?,
             actions: ActionDb::get_all(db).await?,
             action_rules: ActionRuleDb::get_by_user(user_id, db).await?,
             action_events: ActionEventDb::get_by_user(user_id, db).await?,
+            action_provider_scopes: ActionProviderScopeDb::get_all(db).await?,
+            scope_grants: ScopeGrantDb::get_by_user(user_id, db).await?,
             epoch_map: Self::get_epoch_map_by_user(user_id, db).await?,
         })
     }
 
+    /// Get all entries belonging to the user that have been changed since `epoch_map`.
+    ///
+    /// If `omit_tracks` is set, the summaries of routes and cardio sessions are returned instead.
     pub async fn get_by_user_and_epoch(
         user_id: UserId,
         epoch_map: EpochMap,
+        omit_tracks: bool,
         db: &mut AsyncPgConnection,
     ) -> QueryResult<AccountData> {
+        let (cardio_sessions, cardio_session_summaries) = if omit_tracks {
+            let summaries = CardioSessionDb::get_summaries_by_user_and_epoch(
+                user_id,
+                epoch_map.cardio_session,
+                db,
+            )
+            .await?;
+            (vec![], summaries)
+        } else {
+            let cardio_sessions =
+                CardioSessionDb::get_by_user_and_epoch(user_id, epoch_map.cardio_session, db)
+                    .await?;
+            (cardio_sessions, vec![])
+        };
+        let (routes, route_summaries) = if omit_tracks {
+            let summaries =
+                RouteDb::get_summaries_by_user_and_epoch(user_id, epoch_map.route, db).await?;
+            (vec![], summaries)
+        } else {
+            (
+                RouteDb::get_by_user_and_epoch(user_id, epoch_map.route, db).await?,
+                vec![],
+            )
+        };
+
         Ok(AccountData {
             user: UserDb::get_by_id_and_epoch(user_id, epoch_map.user, db).await?,
             diaries: DiaryDb::get_by_user_and_epoch(user_id, epoch_map.diary, db).await?,
@@ -68,13 +129,17 @@ impl AccountDataDb {
                 db,
             )
             .await?,
-            cardio_sessions: CardioSessionDb::get_by_user_and_epoch(
+            cardio_sessions,
+            cardio_session_summaries,
+            routes,
+            route_summaries,
+            training_zones: TrainingZoneDb::get_by_user_and_epoch(
                 user_id,
-                epoch_map.cardio_session,
+                epoch_map.training_zone,
                 db,
             )
             .await?,
-            routes: RouteDb::get_by_user_and_epoch(user_id, epoch_map.route, db).await?,
+            segments: SegmentDb::get_by_user_and_epoch(user_id, epoch_map.segment, db).await?,
             platforms: PlatformDb::get_by_epoch(epoch_map.platform, db).await?,
             platform_credentials: PlatformCredentialDb::get_by_user_and_epoch(
                 user_id,
@@ -92,11 +157,132 @@ impl AccountDataDb {
                 db,
             )
             .await?,
+            action_provider_scopes: ActionProviderScopeDb::get_by_epoch(
+                epoch_map.action_provider_scope,
+                db,
+            )
+            .await?,
+            scope_grants: ScopeGrantDb::get_by_user_and_epoch(user_id, epoch_map.scope_grant, db)
+                .await?,
             epoch_map: Self::get_epoch_map_by_user(user_id, db).await?,
         })
     }
 
-    async fn get_epoch_map_by_user(
+    /// Get a page of at most `page_size` entries that have been changed since `epoch_map`.
+    ///
+    /// The entries of all tables are paged in the order of their dependencies and within each
+    /// table ordered by epoch and id.
+    pub async fn get_page_by_user_and_epoch(
+        user_id: UserId,
+        epoch_map: EpochMap,
+        cursor: Option<AccountDataCursor>,
+        page_size: i64,
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<AccountDataPage> {
+        let mut account_data = AccountData {
+            user: match cursor {
+                Some(_) => None,
+                None => UserDb::get_by_id_and_epoch(user_id, epoch_map.user, db).await?,
+            },
+            epoch_map: Self::get_epoch_map_by_user(user_id, db).await?,
+            ..AccountData::default()
+        };
+        let a = &mut account_data;
+        let e = &epoch_map;
+        let mut p = Pager {
+            user_id,
+            cursor,
+            remaining: page_size,
+            next: None,
+        };
+
+        #[rustfmt::skip]
+        {
+            p.page::<MovementDb>(0, e.movement, &mut a.movements, db).await?;
+            p.page::<MetconDb>(1, e.metcon, &mut a.metcons, db).await?;
+            p.page::<MetconMovementDb>(2, e.metcon_movement, &mut a.metcon_movements, db).await?;
+            p.page::<MetconSessionDb>(3, e.metcon_session, &mut a.metcon_sessions, db).await?;
+            p.page::<StrengthSessionDb>(4, e.strength_session, &mut a.strength_sessions, db).await?;
+            p.page::<StrengthSetDb>(5, e.strength_set, &mut a.strength_sets, db).await?;
+            p.page::<RouteDb>(6, e.route, &mut a.routes, db).await?;
+            p.page::<CardioSessionDb>(7, e.cardio_session, &mut a.cardio_sessions, db).await?;
+            p.page::<DiaryDb>(8, e.diary, &mut a.diaries, db).await?;
+            p.page::<WodDb>(9, e.wod, &mut a.wods, db).await?;
+            p.page::<PlatformDb>(10, e.platform, &mut a.platforms, db).await?;
+            p.page::<PlatformCredentialDb>(11, e.platform_credential, &mut a.platform_credentials, db).await?;
+            p.page::<ActionProviderDb>(12, e.action_provider, &mut a.action_providers, db).await?;
+            p.page::<ActionDb>(13, e.action, &mut a.actions, db).await?;
+            p.page::<ActionRuleDb>(14, e.action_rule, &mut a.action_rules, db).await?;
+            p.page::<ActionEventDb>(15, e.action_event, &mut a.action_events, db).await?;
+            p.page::<ActionProviderScopeDb>(16, e.action_provider_scope, &mut a.action_provider_scopes, db).await?;
+            p.page::<ScopeGrantDb>(17, e.scope_grant, &mut a.scope_grants, db).await?;
+            p.page::<TrainingZoneDb>(18, e.training_zone, &mut a.training_zones, db).await?;
+            p.page::<SegmentDb>(19, e.segment, &mut a.segments, db).await?;
+        };
+
+        Ok(AccountDataPage {
+            account_data,
+            next: p.next,
+        })
+    }
+
+    /// Create all entries in a single transaction.
+    ///
+    /// The tables are created in the order of their dependencies.
+    /// If the entries of any table conflict with the existing data, nothing is created and the
+    /// conflicts of all tables are returned.
+    pub async fn create(
+        changes: &mut AccountDataChanges,
+        keys: &'static CredentialKeys,
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<Vec<TableConflict>> {
+        Self::sync(changes, &mut AccountDataChanges::default(), keys, db).await
+    }
+
+    /// Update all entries in a single transaction.
+    ///
+    /// The tables are updated in the order of their dependencies.
+    /// If the entries of any table conflict with the existing data, nothing is updated and the
+    /// conflicts of all tables are returned.
+    pub async fn update(
+        changes: &mut AccountDataChanges,
+        keys: &'static CredentialKeys,
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<Vec<TableConflict>> {
+        Self::sync(&mut AccountDataChanges::default(), changes, keys, db).await
+    }
+
+    /// Create and update all entries in a single transaction.
+    ///
+    /// All tables are created before they are updated, each in the order of their dependencies.
+    /// If the entries of any table conflict with the existing data, nothing is changed and the
+    /// conflicts of all tables are returned.
+    pub async fn sync(
+        created: &mut AccountDataChanges,
+        updated: &mut AccountDataChanges,
+        keys: &'static CredentialKeys,
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<Vec<TableConflict>> {
+        let mut conflicts = Vec::new();
+        let result = db
+            .transaction(async |db| {
+                create_all(created, keys, &mut conflicts, db).await?;
+                update_all(updated, keys, &mut conflicts, db).await?;
+                if conflicts.is_empty() {
+                    Ok(())
+                } else {
+                    Err(DieselError::RollbackTransaction)
+                }
+            })
+            .await;
+        match result {
+            Ok(()) => Ok(conflicts),
+            Err(DieselError::RollbackTransaction) if !conflicts.is_empty() => Ok(conflicts),
+            Err(error) => Err(error),
+        }
+    }
+
+    pub async fn get_epoch_map_by_user(
         user_id: UserId,
         db: &mut AsyncPgConnection,
     ) -> QueryResult<EpochMap> {
@@ -112,12 +298,254 @@ impl AccountDataDb {
             metcon_movement: MetconMovementDb::get_epoch_by_user_optional(user_id, db).await?,
             cardio_session: CardioSessionDb::get_epoch_by_user(user_id, db).await?,
             route: RouteDb::get_epoch_by_user(user_id, db).await?,
+            training_zone: TrainingZoneDb::get_epoch_by_user(user_id, db).await?,
+            segment: SegmentDb::get_epoch_by_user(user_id, db).await?,
             platform: PlatformDb::get_epoch(db).await?,
             platform_credential: PlatformCredentialDb::get_epoch_by_user(user_id, db).await?,
             action_provider: ActionProviderDb::get_epoch(db).await?,
             action: ActionDb::get_epoch(db).await?,
             action_rule: ActionRuleDb::get_epoch_by_user(user_id, db).await?,
             action_event: ActionEventDb::get_epoch_by_user(user_id, db).await?,
+            action_provider_scope: ActionProviderScopeDb::get_epoch(db).await?,
+            scope_grant: ScopeGrantDb::get_epoch_by_user(user_id, db).await?,
+        })
+    }
+}
+
+/// Collects the entries of all tables until a page is full.
+struct Pager {
+    user_id: UserId,
+    cursor: Option<AccountDataCursor>,
+    remaining: i64,
+    next: Option<AccountDataCursor>,
+}
+
+impl Pager {
+    async fn page<D: GetPageByUserAndEpoch>(
+        &mut self,
+        table: u8,
+        epoch: Epoch,
+        values: &mut Vec<D::Type>,
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<()> {
+        let (after_epoch, after_id) = match self.cursor {
+            Some(cursor) if cursor.table > table => return Ok(()),
+            Some(cursor) if cursor.table == table => (cursor.epoch, cursor.id),
+            _ => (epoch, i64::MAX),
+        };
+        if self.remaining == 0 {
+            return Ok(());
+        }
+
+        let entries =
+            D::get_page_by_user_and_epoch(self.user_id, after_epoch, after_id, self.remaining, db)
+                .await?;
+        self.remaining -= entries.len() as i64;
+        if self.remaining == 0
+            && let Some((epoch, id, _)) = entries.last()
+        {
+            self.next = Some(AccountDataCursor {
+                table,
+                epoch: *epoch,
+                id: *id,
+            });
+        }
+        values.extend(entries.into_iter().map(|(_, _, value)| value));
+
+        Ok(())
+    }
+}
+
+async fn create_all(
+    c: &mut AccountDataChanges,
+    keys: &'static CredentialKeys,
+    conflicts: &mut Vec<TableConflict>,
+    db: &mut AsyncPgConnection,
+) -> QueryResult<()> {
+    #[rustfmt::skip]
+    {
+        apply("movement", &mut c.movements, conflicts, db, |v, db| MovementDb::create_multiple(v, db).boxed()).await?;
+        apply("metcon", &mut c.metcons, conflicts, db, |v, db| MetconDb::create_multiple(v, db).boxed()).await?;
+        apply("metcon_movement", &mut c.metcon_movements, conflicts, db, |v, db| MetconMovementDb::create_multiple(v, db).boxed()).await?;
+        apply("metcon_session", &mut c.metcon_sessions, conflicts, db, |v, db| MetconSessionDb::create_multiple(v, db).boxed()).await?;
+        apply("strength_session", &mut c.strength_sessions, conflicts, db, |v, db| StrengthSessionDb::create_multiple(v, db).boxed()).await?;
+        apply("strength_set", &mut c.strength_sets, conflicts, db, |v, db| StrengthSetDb::create_multiple(v, db).boxed()).await?;
+        apply("route", &mut c.routes, conflicts, db, |v, db| RouteDb::create_multiple(v, db).boxed()).await?;
+        apply("cardio_session", &mut c.cardio_sessions, conflicts, db, |v, db| CardioSessionDb::create_multiple(v, db).boxed()).await?;
+        apply("diary", &mut c.diaries, conflicts, db, |v, db| DiaryDb::create_multiple(v, db).boxed()).await?;
+        apply("wod", &mut c.wods, conflicts, db, |v, db| WodDb::create_multiple(v, db).boxed()).await?;
+        apply("training_zone", &mut c.training_zones, conflicts, db, |v, db| TrainingZoneDb::create_multiple(v, db).boxed()).await?;
+        apply("segment", &mut c.segments, conflicts, db, |v, db| SegmentDb::create_multiple(v, db).boxed()).await?;
+        apply("platform_credential", &mut c.platform_credentials, conflicts, db, |v, db| PlatformCredentialDb::create_multiple(v, keys, db).boxed()).await?;
+        apply("action_rule", &mut c.action_rules, conflicts, db, |v, db| ActionRuleDb::create_multiple(v, db).boxed()).await?;
+        apply("action_event", &mut c.action_events, conflicts, db, |v, db| ActionEventDb::create_multiple(v, db).boxed()).await?;
+        apply("scope_grant", &mut c.scope_grants, conflicts, db, |v, db| ScopeGrantDb::create_multiple(v, db).boxed()).await?;
+    };
+    Ok(())
+}
+
+async fn update_all(
+    c: &mut AccountDataChanges,
+    keys: &'static CredentialKeys,
+    conflicts: &mut Vec<TableConflict>,
+    db: &mut AsyncPgConnection,
+) -> QueryResult<()> {
+    #[rustfmt::skip]
+    {
+        apply("movement", &mut c.movements, conflicts, db, |v, db| MovementDb::update_multiple(v, db).boxed()).await?;
+        apply("metcon", &mut c.metcons, conflicts, db, |v, db| MetconDb::update_multiple(v, db).boxed()).await?;
+        apply("metcon_movement", &mut c.metcon_movements, conflicts, db, |v, db| MetconMovementDb::update_multiple(v, db).boxed()).await?;
+        apply("metcon_session", &mut c.metcon_sessions, conflicts, db, |v, db| MetconSessionDb::update_multiple(v, db).boxed()).await?;
+        apply("strength_session", &mut c.strength_sessions, conflicts, db, |v, db| StrengthSessionDb::update_multiple(v, db).boxed()).await?;
+        apply("strength_set", &mut c.strength_sets, conflicts, db, |v, db| StrengthSetDb::update_multiple(v, db).boxed()).await?;
+        apply("route", &mut c.routes, conflicts, db, |v, db| RouteDb::update_multiple(v, db).boxed()).await?;
+        apply("cardio_session", &mut c.cardio_sessions, conflicts, db, |v, db| CardioSessionDb::update_multiple(v, db).boxed()).await?;
+        apply("diary", &mut c.diaries, conflicts, db, |v, db| DiaryDb::update_multiple(v, db).boxed()).await?;
+        apply("wod", &mut c.wods, conflicts, db, |v, db| WodDb::update_multiple(v, db).boxed()).await?;
+        apply("training_zone", &mut c.training_zones, conflicts, db, |v, db| TrainingZoneDb::update_multiple(v, db).boxed()).await?;
+        apply("segment", &mut c.segments, conflicts, db, |v, db| SegmentDb::update_multiple(v, db).boxed()).await?;
+        apply("platform_credential", &mut c.platform_credentials, conflicts, db, |v, db| PlatformCredentialDb::update_multiple(v, keys, db).boxed()).await?;
+        apply("action_rule", &mut c.action_rules, conflicts, db, |v, db| ActionRuleDb::update_multiple(v, db).boxed()).await?;
+        apply("action_event", &mut c.action_events, conflicts, db, |v, db| ActionEventDb::update_multiple(v, db).boxed()).await?;
+        apply("scope_grant", &mut c.scope_grants, conflicts, db, |v, db| ScopeGrantDb::update_multiple(v, db).boxed()).await?;
+    };
+    Ok(())
+}
+
+/// Apply the changes of a single table in a nested transaction.
+///
+/// If the changes conflict with the existing data, only the nested transaction is rolled back so
+/// that the remaining tables can still be checked for conflicts.
+async fn apply<T: Send>(
+    table: &str,
+    values: &mut [T],
+    conflicts: &mut Vec<TableConflict>,
+    db: &mut AsyncPgConnection,
+    query: impl for<'a> FnOnce(
+        &'a mut [T],
+        &'a mut AsyncPgConnection,
+    ) -> BoxFuture<'a, QueryResult<usize>>
+    + Send,
+) -> QueryResult<()> {
+    if values.is_empty() {
+        return Ok(());
+    }
+
+    match db
+        .transaction(async move |db| query(values, db).await)
+        .await
+    {
+        Ok(_) => Ok(()),
+        Err(error) => {
+            conflicts.push(table_conflict(table, error)?);
+            Ok(())
+        }
+    }
+}
+
+impl Unverified<AccountDataChanges> {
+    pub fn verify_user_create(self, auth: AuthUser) -> Result<AccountDataChanges, HandlerError> {
+        let changes = self.0;
+        let auth_user_or_ap = AuthUserOrAP::from(auth);
+        Ok(AccountDataChanges {
+            diaries: Unverified(changes.diaries).verify_user_ap_create(auth_user_or_ap)?,
+            wods: Unverified(changes.wods).verify_user_ap_create(auth_user_or_ap)?,
+            movements: Unverified(changes.movements).verify_user_ap_create(auth_user_or_ap)?,
+            strength_sessions: Unverified(changes.strength_sessions)
+                .verify_user_ap_create(auth_user_or_ap)?,
+            strength_sets: Unverified(changes.strength_sets)
+                .verify_user_ap_create(auth_user_or_ap)?,
+            metcons: Unverified(changes.metcons).verify_user_ap_create(auth_user_or_ap)?,
+            metcon_sessions: Unverified(changes.metcon_sessions)
+                .verify_user_ap_create(auth_user_or_ap)?,
+            metcon_movements: Unverified(changes.metcon_movements)
+                .verify_user_ap_create(auth_user_or_ap)?,
+            cardio_sessions: Unverified(changes.cardio_sessions)
+                .verify_user_ap_create(auth_user_or_ap)?,
+            routes: Unverified(changes.routes).verify_user_ap_create(auth_user_or_ap)?,
+            training_zones: Unverified(changes.training_zones)
+                .verify_user_ap_create(auth_user_or_ap)?,
+            segments: Unverified(changes.segments).verify_user_ap_create(auth_user_or_ap)?,
+            platform_credentials: Unverified(changes.platform_credentials)
+                .verify_user_create(auth)?,
+            action_rules: Unverified(changes.action_rules).verify_user_create(auth)?,
+            action_events: Unverified(changes.action_events).verify_user_create(auth)?,
+            scope_grants: Unverified(changes.scope_grants).verify_user_create(auth)?,
+        })
+    }
+
+    pub async fn verify_user_update(
+        self,
+        auth: AuthUser,
+        db: &mut AsyncPgConnection,
+    ) -> Result<AccountDataChanges, HandlerError> {
+        let changes = self.0;
+        let auth_user_or_ap = AuthUserOrAP::from(auth);
+        Ok(AccountDataChanges {
+            diaries: Unverified(changes.diaries)
+                .verify_user_ap_update(auth_user_or_ap, db)
+                .await?,
+            wods: Unverified(changes.wods)
+                .verify_user_ap_update(auth_user_or_ap, db)
+                .await?,
+            movements: Unverified(changes.movements)
+                .verify_user_ap_update(auth_user_or_ap, db)
+                .await?,
+            strength_sessions: Unverified(changes.strength_sessions)
+                .verify_user_ap_update(auth_user_or_ap, db)
+                .await?,
+            strength_sets: Unverified(changes.strength_sets)
+                .verify_user_ap_update(auth_user_or_ap, db)
+                .await?,
+            metcons: Unverified(changes.metcons)
+                .verify_user_ap_update(auth_user_or_ap, db)
+                .await?,
+            metcon_sessions: Unverified(changes.metcon_sessions)
+                .verify_user_ap_update(auth_user_or_ap, db)
+                .await?,
+            metcon_movements: Unverified(changes.metcon_movements)
+                .verify_user_ap_update(auth_user_or_ap, db)
+                .await?,
+            cardio_sessions: Unverified(changes.cardio_sessions)
+                .verify_user_ap_update(auth_user_or_ap, db)
+                .await?,
+            routes: Unverified(changes.routes)
+                .verify_user_ap_update(auth_user_or_ap, db)
+                .await?,
+            training_zones: Unverified(changes.training_zones)
+                .verify_user_ap_update(auth_user_or_ap, db)
+                .await?,
+            segments: Unverified(changes.segments)
+                .verify_user_ap_update(auth_user_or_ap, db)
+                .await?,
+            platform_credentials: Unverified(changes.platform_credentials)
+                .verify_user_update(auth, db)
+                .await?,
+            action_rules: Unverified(changes.action_rules)
+                .verify_user_update(auth, db)
+                .await?,
+            action_events: Unverified(changes.action_events)
+                .verify_user_update(auth, db)
+                .await?,
+            scope_grants: Unverified(changes.scope_grants)
+                .verify_user_update(auth, db)
+                .await?,
+        })
+    }
+}
+
+impl Unverified<AccountDataSync> {
+    pub async fn verify_user_sync(
+        self,
+        auth: AuthUser,
+        db: &mut AsyncPgConnection,
+    ) -> Result<AccountDataSync, HandlerError> {
+        let sync = self.0;
+        Ok(AccountDataSync {
+            created: Unverified(sync.created).verify_user_create(auth)?,
+            updated: Unverified(sync.updated)
+                .verify_user_update(auth, db)
+                .await?,
         })
     }
 }
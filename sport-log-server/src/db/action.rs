@@ -9,8 +9,9 @@ use sport_log_types::{
     DeletableActionEvent, ExecutableActionEvent, UserId,
     schema::{action, action_event, action_provider, action_rule, platform_credential},
 };
+use tracing::warn;
 
-use crate::{auth::*, db::*};
+use crate::{auth::*, crypto::CredentialKeys, db::*};
 
 #[derive(Db, ModifiableDb, Deftly)]
 #[derive_deftly(
@@ -19,6 +20,7 @@ use crate::{auth::*, db::*};
     GetById,
     GetAll,
     GetByEpoch,
+    GetPageByEpoch,
     GetEpoch,
     VerifyForAdmin,
     VerifyUncheckedCreate
@@ -140,6 +142,7 @@ impl ActionProviderDb {
     GetById,
     GetAll,
     GetByEpoch,
+    GetPageByEpoch,
     Update,
     GetEpoch,
     CheckAPId,
@@ -168,6 +171,7 @@ impl ActionDb {
     GetById,
     GetByUser,
     GetByUserAndEpoch,
+    GetPageByUserAndEpoch,
     Update,
     GetEpochByUser,
     CheckUserId,
@@ -187,6 +191,7 @@ pub struct ActionRuleDb;
     GetById,
     GetByUser,
     GetByUserAndEpoch,
+    GetPageByUserAndEpoch,
     Update,
     GetEpochByUser,
     CheckUserId,
@@ -319,6 +324,7 @@ impl Db for ExecutableActionEventDb {
 impl ExecutableActionEventDb {
     pub async fn get_by_action_provider(
         action_provider_id: ActionProviderId,
+        keys: &CredentialKeys,
         db: &mut AsyncPgConnection,
     ) -> QueryResult<Vec<ExecutableActionEvent>> {
         action_event::table
@@ -332,22 +338,27 @@ impl ExecutableActionEventDb {
             .filter(action_event::columns::enabled.eq(true))
             .filter(action_event::columns::deleted.eq(false))
             .select((
-                action_event::columns::id,
-                action::columns::name,
-                action_event::columns::datetime,
-                action_event::columns::arguments,
-                action_event::columns::user_id,
-                platform_credential::columns::username.nullable(),
-                platform_credential::columns::password.nullable(),
+                (
+                    action_event::columns::id,
+                    action::columns::name,
+                    action_event::columns::datetime,
+                    action_event::columns::arguments,
+                    action_event::columns::user_id,
+                    platform_credential::columns::username.nullable(),
+                    platform_credential::columns::password.nullable(),
+                ),
+                platform_credential::columns::password_encrypted.nullable(),
             ))
             .get_results(db)
             .await
+            .map(|events| decrypt(events, keys))
     }
 
     pub async fn get_ordered_by_action_provider_and_timespan(
         action_provider_id: ActionProviderId,
         start_datetime: DateTime<Utc>,
         end_datetime: DateTime<Utc>,
+        keys: &CredentialKeys,
         db: &mut AsyncPgConnection,
     ) -> QueryResult<Vec<ExecutableActionEvent>> {
         action_event::table
@@ -362,18 +373,50 @@ impl ExecutableActionEventDb {
             .filter(action_event::columns::deleted.eq(false))
             .filter(action_event::columns::datetime.between(start_datetime, end_datetime))
             .select((
-                action_event::columns::id,
-                action::columns::name,
-                action_event::columns::datetime,
-                action_event::columns::arguments,
-                action_event::columns::user_id,
-                platform_credential::columns::username.nullable(),
-                platform_credential::columns::password.nullable(),
+                (
+                    action_event::columns::id,
+                    action::columns::name,
+                    action_event::columns::datetime,
+                    action_event::columns::arguments,
+                    action_event::columns::user_id,
+                    platform_credential::columns::username.nullable(),
+                    platform_credential::columns::password.nullable(),
+                ),
+                platform_credential::columns::password_encrypted.nullable(),
             ))
             .order_by(action_event::columns::datetime)
             .get_results(db)
             .await
+            .map(|events| decrypt(events, keys))
+    }
+}
+
+/// Decrypt the passwords of the credentials that are stored encrypted.
+///
+/// This is the only place where passwords are decrypted, so that they are only handed out to
+/// action providers.
+fn decrypt(
+    events: Vec<(ExecutableActionEvent, Option<bool>)>,
+    keys: &CredentialKeys,
+) -> Vec<ExecutableActionEvent> {
+    let mut decrypted_events = Vec::with_capacity(events.len());
+    for (mut event, encrypted) in events {
+        event.password = event.password.take().and_then(|password| {
+            if encrypted != Some(true) {
+                return Some(password);
+            }
+            let decrypted = keys.decrypt(&password, event.user_id);
+            if decrypted.is_none() {
+                warn!(
+                    "failed to decrypt credential of user {} for action event {}",
+                    event.user_id.0, event.action_event_id.0
+                );
+            }
+            decrypted
+        });
+        decrypted_events.push(event);
     }
+    decrypted_events
 }
 
 pub struct DeletableActionEventDb();
@@ -0,0 +1,92 @@
+use diesel::{
+    QueryResult, QueryableByName, define_sql_function,
+    sql_types::{BigInt, Integer, Text},
+};
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use sport_log_types::{Epoch, EpochMap, UserId};
+
+/// The tables whose deleted entries are moved to an archive table.
+pub const ARCHIVED_TABLES: [&str; 20] = [
+    "platform",
+    "platform_credential",
+    "action_provider",
+    "action",
+    "action_rule",
+    "action_event",
+    "action_provider_scope",
+    "scope_grant",
+    "diary",
+    "wod",
+    "movement",
+    "strength_session",
+    "strength_set",
+    "metcon",
+    "metcon_movement",
+    "metcon_session",
+    "route",
+    "cardio_session",
+    "training_zone",
+    "segment",
+];
+
+define_sql_function! {
+    /// Permanently delete the entries that have been archived more than `retention_days` days ago
+    /// and return the number of deleted entries.
+    fn purge_archive(record_table: Text, retention_days: Integer) -> BigInt;
+}
+
+#[derive(QueryableByName)]
+struct PurgedEpoch {
+    #[diesel(sql_type = Text)]
+    table_name: String,
+    #[diesel(sql_type = BigInt)]
+    purged_epoch: Epoch,
+}
+
+pub struct ArchiveDb;
+
+impl ArchiveDb {
+    /// Permanently delete the entries of `table` that have been archived more than
+    /// `retention_days` days ago.
+    ///
+    /// Returns the number of deleted entries.
+    pub async fn purge(
+        table: &str,
+        retention_days: u32,
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<i64> {
+        let retention_days = i32::try_from(retention_days).unwrap_or(i32::MAX);
+        diesel::select(purge_archive(table, retention_days))
+            .get_result(db)
+            .await
+    }
+
+    /// Get a table of which deleted entries have been purged that the client with `epoch_map` has
+    /// not seen yet.
+    ///
+    /// Tables the client has never synchronized (epoch 0) are ignored since the client can not
+    /// have any entries of them.
+    pub async fn get_purged_table(
+        user_id: UserId,
+        epoch_map: &EpochMap,
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<Option<String>> {
+        let purged_epochs: Vec<PurgedEpoch> = diesel::sql_query(
+            "select table_name, max(purged_epoch) as purged_epoch from epoch_counter \
+            where (user_id = $1 or user_id is null) and purged_epoch > 0 \
+            group by table_name",
+        )
+        .bind::<BigInt, _>(user_id)
+        .load(db)
+        .await?;
+
+        Ok(purged_epochs
+            .into_iter()
+            .find(|purged| {
+                epoch_map
+                    .get(&purged.table_name)
+                    .is_some_and(|epoch| epoch > Epoch(0) && epoch < purged.purged_epoch)
+            })
+            .map(|purged| purged.table_name))
+    }
+}
@@ -0,0 +1,131 @@
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use chrono::{TimeDelta, Utc};
+use diesel::{prelude::*, result::Error};
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use rand::RngExt;
+use sha2::{Digest, Sha256};
+use sport_log_types::{AuthTokens, UserId, schema::auth_session};
+
+/// Lifetime of an access token.
+///
+/// Access tokens are short lived because they are sent with every request.
+const ACCESS_TOKEN_LIFETIME: TimeDelta = TimeDelta::hours(1);
+
+/// Lifetime of a refresh token.
+///
+/// Every refresh issues a new refresh token so a session stays alive as long as it is used at
+/// least once within this time.
+const REFRESH_TOKEN_LIFETIME: TimeDelta = TimeDelta::days(90);
+
+/// A login session of a user.
+///
+/// Only the SHA-256 hashes of the tokens are stored in the database.
+pub struct AuthSessionDb;
+
+impl AuthSessionDb {
+    /// Create a new session for the user and return the tokens for it.
+    ///
+    /// Expired sessions of the user are removed.
+    pub async fn create(user_id: UserId, db: &mut AsyncPgConnection) -> QueryResult<AuthTokens> {
+        diesel::delete(
+            auth_session::table
+                .filter(auth_session::columns::user_id.eq(user_id))
+                .filter(auth_session::columns::refresh_token_expires.lt(Utc::now())),
+        )
+        .execute(db)
+        .await?;
+
+        let (tokens, access_token_hash, refresh_token_hash) = new_tokens();
+
+        diesel::insert_into(auth_session::table)
+            .values((
+                auth_session::columns::user_id.eq(user_id),
+                auth_session::columns::access_token_hash.eq(access_token_hash),
+                auth_session::columns::access_token_expires.eq(tokens.access_token_expires),
+                auth_session::columns::refresh_token_hash.eq(refresh_token_hash),
+                auth_session::columns::refresh_token_expires.eq(tokens.refresh_token_expires),
+            ))
+            .execute(db)
+            .await?;
+
+        Ok(tokens)
+    }
+
+    /// Replace both tokens of the session the refresh token belongs to.
+    ///
+    /// The old access and refresh token are invalid afterwards.
+    pub async fn refresh(
+        refresh_token: &str,
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<AuthTokens> {
+        let (tokens, access_token_hash, refresh_token_hash) = new_tokens();
+
+        let updated = diesel::update(
+            auth_session::table
+                .filter(auth_session::columns::refresh_token_hash.eq(hash_token(refresh_token)))
+                .filter(auth_session::columns::refresh_token_expires.gt(Utc::now())),
+        )
+        .set((
+            auth_session::columns::access_token_hash.eq(access_token_hash),
+            auth_session::columns::access_token_expires.eq(tokens.access_token_expires),
+            auth_session::columns::refresh_token_hash.eq(refresh_token_hash),
+            auth_session::columns::refresh_token_expires.eq(tokens.refresh_token_expires),
+        ))
+        .execute(db)
+        .await?;
+
+        if updated == 1 {
+            Ok(tokens)
+        } else {
+            Err(Error::NotFound)
+        }
+    }
+
+    /// Returns the session id and the user id for a valid access token.
+    pub async fn auth(
+        access_token: &str,
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<(i64, UserId)> {
+        auth_session::table
+            .filter(auth_session::columns::access_token_hash.eq(hash_token(access_token)))
+            .filter(auth_session::columns::access_token_expires.gt(Utc::now()))
+            .select((auth_session::columns::id, auth_session::columns::user_id))
+            .get_result(db)
+            .await
+    }
+
+    pub async fn delete(session_id: i64, db: &mut AsyncPgConnection) -> QueryResult<usize> {
+        diesel::delete(auth_session::table.find(session_id))
+            .execute(db)
+            .await
+    }
+
+    pub async fn delete_by_user(user_id: UserId, db: &mut AsyncPgConnection) -> QueryResult<usize> {
+        diesel::delete(auth_session::table.filter(auth_session::columns::user_id.eq(user_id)))
+            .execute(db)
+            .await
+    }
+}
+
+fn new_token() -> String {
+    let bytes: [u8; 32] = rand::rng().random();
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+fn hash_token(token: &str) -> Vec<u8> {
+    Sha256::digest(token.as_bytes()).to_vec()
+}
+
+/// Generate a new pair of tokens and their hashes.
+fn new_tokens() -> (AuthTokens, Vec<u8>, Vec<u8>) {
+    let now = Utc::now();
+    let tokens = AuthTokens {
+        access_token: new_token(),
+        access_token_expires: now + ACCESS_TOKEN_LIFETIME,
+        refresh_token: new_token(),
+        refresh_token_expires: now + REFRESH_TOKEN_LIFETIME,
+    };
+    let access_token_hash = hash_token(&tokens.access_token);
+    let refresh_token_hash = hash_token(&tokens.refresh_token);
+    (tokens, access_token_hash, refresh_token_hash)
+}
@@ -1,14 +1,48 @@
 use derive_deftly::Deftly;
+use diesel::{
+    dsl::{exists, not, sql},
+    pg::Pg,
+    prelude::*,
+    sql_types::{BigInt, Bool, Double, Nullable, Text, Timestamptz},
+    upsert::excluded,
+};
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use futures_util::{TryStreamExt, future};
 use sport_log_derive::*;
+use sport_log_types::{
+    CardioBestEffort, CardioSessionId, CardioSessionSummary, CardioSessionTrack, CardioStatistic,
+    Epoch, MovementId, Position, Route, RouteId, RouteSummary, RouteTrack, Segment, SegmentEffort,
+    SegmentId, StatisticInterval, TrainingZone, UserId,
+    schema::{
+        cardio_session, cardio_session_overview, route, route_overview, segment, segment_effort,
+        training_zone,
+    },
+};
 
-#[derive(Db, DbWithUserId, ModifiableDb, Deftly)]
+use crate::{
+    cardio_summary::{CardioSessionSummaryTrack, TrackSummary},
+    db::*,
+    heatmap::HeatmapOption,
+    route_match::RouteMatchTrack,
+    segment::CardioSessionSegmentData,
+    simplify::StoredOverview,
+    spatial::{CardioSessionCandidate, RouteCandidate, SpatialFilter},
+    split::{BestEfforts, CardioSessionPositions},
+    training_load::CardioSessionHeartRate,
+    training_zone::CardioSessionZoneData,
+};
+
+#[derive(Db, DbWithUserId, DbWithScope, ModifiableDb, ArchivableDb, Deftly)]
 #[derive_deftly(
     VerifyForUserOrAPGet,
     Create,
     GetById,
     GetByUser,
     GetByUserAndEpoch,
+    GetPageByUserAndEpoch,
     Update,
+    Archive,
+    CheckedMultiple,
     GetEpochByUser,
     CheckUserId,
     VerifyForUserOrAPUpdate,
@@ -16,7 +50,7 @@ use sport_log_derive::*;
 )]
 pub struct RouteDb;
 
-#[derive(Db, DbWithUserId, DbWithDateTime, ModifiableDb, Deftly)]
+#[derive(Db, DbWithUserId, DbWithDateTime, DbWithScope, ModifiableDb, ArchivableDb, Deftly)]
 #[derive_deftly(
     VerifyForUserOrAPGet,
     Create,
@@ -24,10 +58,1001 @@ pub struct RouteDb;
     GetByUser,
     GetByUserTimespan,
     GetByUserAndEpoch,
+    GetPageByUserAndEpoch,
     Update,
+    Archive,
+    CheckedMultiple,
     GetEpochByUser,
     CheckUserId,
     VerifyForUserOrAPUpdate,
     VerifyForUserOrAPCreate
 )]
 pub struct CardioSessionDb;
+
+#[derive(Db, DbWithUserId, DbWithScope, ModifiableDb, ArchivableDb, Deftly)]
+#[derive_deftly(
+    VerifyForUserOrAPGet,
+    Create,
+    GetById,
+    GetByUser,
+    GetByUserAndEpoch,
+    GetPageByUserAndEpoch,
+    Update,
+    Archive,
+    CheckedMultiple,
+    GetEpochByUser,
+    CheckUserId,
+    VerifyForUserOrAPUpdate,
+    VerifyForUserOrAPCreate
+)]
+pub struct TrainingZoneDb;
+
+#[derive(Db, DbWithUserId, DbWithScope, ModifiableDb, ArchivableDb, Deftly)]
+#[derive_deftly(
+    VerifyForUserOrAPGet,
+    Create,
+    GetById,
+    GetByUser,
+    GetByUserAndEpoch,
+    GetPageByUserAndEpoch,
+    Update,
+    Archive,
+    CheckedMultiple,
+    GetEpochByUser,
+    CheckUserId,
+    VerifyForUserOrAPUpdate,
+    VerifyForUserOrAPCreate
+)]
+pub struct SegmentDb;
+
+pub struct SegmentEffortDb;
+
+pub struct CardioSessionOverviewDb;
+
+pub struct RouteOverviewDb;
+
+impl RouteDb {
+    pub async fn get_summary_by_id(
+        route_id: RouteId,
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<RouteSummary> {
+        route::table
+            .find(route_id)
+            .select(RouteSummary::as_select())
+            .get_result(db)
+            .await
+    }
+
+    pub async fn get_summaries_by_user(
+        user_id: UserId,
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<Vec<RouteSummary>> {
+        route::table
+            .filter(route::columns::user_id.eq(user_id))
+            .select(RouteSummary::as_select())
+            .get_results(db)
+            .await
+    }
+
+    pub async fn get_summaries_by_user_and_epoch(
+        user_id: UserId,
+        epoch: Epoch,
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<Vec<RouteSummary>> {
+        route::table
+            .filter(route::columns::user_id.eq(user_id))
+            .filter(route::columns::epoch.gt(epoch))
+            .select(RouteSummary::as_select())
+            .get_results(db)
+            .await
+    }
+
+    /// Get the routes of a user whose track passes through the area of `filter`.
+    pub async fn get_summaries_by_user_and_spatial_filter(
+        user_id: UserId,
+        filter: SpatialFilter,
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<Vec<RouteSummary>> {
+        let postgis = postgis_enabled(db).await?;
+        let mut ids: Vec<_> = get_spatial_ids("route", user_id, filter, postgis, db)
+            .await?
+            .into_iter()
+            .map(RouteId)
+            .collect();
+        if !postgis {
+            ids = route::table
+                .filter(route::columns::id.eq_any(ids))
+                .select(RouteCandidate::as_select())
+                .get_results(db)
+                .await?
+                .into_iter()
+                .filter(|route| route.track.as_deref().is_some_and(|t| filter.matches(t)))
+                .map(|route| route.id)
+                .collect();
+        }
+        route::table
+            .filter(route::columns::id.eq_any(ids))
+            .select(RouteSummary::as_select())
+            .order_by(route::columns::name)
+            .get_results(db)
+            .await
+    }
+
+    pub async fn get_track_by_id(
+        route_id: RouteId,
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<RouteTrack> {
+        route::table
+            .find(route_id)
+            .select(RouteTrack::as_select())
+            .get_result(db)
+            .await
+    }
+
+    /// Get the tracks of the routes of a user which have a track.
+    pub async fn get_tracks_by_user(
+        user_id: UserId,
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<Vec<RouteMatchTrack>> {
+        route::table
+            .filter(route::columns::user_id.eq(user_id))
+            .filter(route::columns::deleted.eq(false))
+            .filter(route::columns::track.is_not_null())
+            .select(RouteMatchTrack::as_select())
+            .get_results(db)
+            .await
+    }
+}
+
+#[derive(QueryableByName)]
+struct PostgisEnabled {
+    #[diesel(sql_type = Bool)]
+    enabled: bool,
+}
+
+#[derive(QueryableByName)]
+struct SpatialId {
+    #[diesel(sql_type = BigInt)]
+    id: i64,
+}
+
+/// Check whether the tracks are stored as PostGIS geometries.
+async fn postgis_enabled(db: &mut AsyncPgConnection) -> QueryResult<bool> {
+    diesel::sql_query(
+        "select exists (
+            select from pg_attribute
+            where attrelid = 'cardio_session'::regclass
+                and attname = 'track_geometry'
+                and not attisdropped
+        ) as enabled",
+    )
+    .get_result::<PostgisEnabled>(db)
+    .await
+    .map(|postgis| postgis.enabled)
+}
+
+/// Get the ids of the entries of `table` of a user whose track matches `filter`.
+///
+/// Without PostGIS only the bounding boxes of the tracks are compared, so the result contains
+/// false positives.
+async fn get_spatial_ids(
+    table: &str,
+    user_id: UserId,
+    filter: SpatialFilter,
+    postgis: bool,
+    db: &mut AsyncPgConnection,
+) -> QueryResult<Vec<i64>> {
+    if postgis
+        && let SpatialFilter::Distance {
+            latitude,
+            longitude,
+            radius,
+        } = filter
+    {
+        return diesel::sql_query(format!(
+            "select id from {table}
+            where user_id = $1
+                and not deleted
+                and ST_DWithin(
+                    track_geometry::geography,
+                    ST_SetSRID(ST_MakePoint($2, $3), 4326)::geography,
+                    $4
+                )"
+        ))
+        .bind::<BigInt, _>(user_id)
+        .bind::<Double, _>(longitude)
+        .bind::<Double, _>(latitude)
+        .bind::<Double, _>(radius)
+        .load::<SpatialId>(db)
+        .await
+        .map(|ids| ids.into_iter().map(|id| id.id).collect());
+    }
+
+    let condition = if postgis {
+        "ST_Intersects(track_geometry, ST_MakeEnvelope($2, $3, $4, $5, 4326))"
+    } else {
+        "track_bbox && box(point($2, $3), point($4, $5))"
+    };
+    let (min_longitude, min_latitude, max_longitude, max_latitude) = filter.bounds();
+    diesel::sql_query(format!(
+        "select id from {table} where user_id = $1 and not deleted and {condition}"
+    ))
+    .bind::<BigInt, _>(user_id)
+    .bind::<Double, _>(min_longitude)
+    .bind::<Double, _>(min_latitude)
+    .bind::<Double, _>(max_longitude)
+    .bind::<Double, _>(max_latitude)
+    .load::<SpatialId>(db)
+    .await
+    .map(|ids| ids.into_iter().map(|id| id.id).collect())
+}
+
+/// Whether the bounding box of the track of a cardio session intersects `bounds`.
+fn track_bbox_intersects(
+    (min_longitude, min_latitude, max_longitude, max_latitude): (f64, f64, f64, f64),
+) -> Box<dyn BoxableExpression<cardio_session::table, Pg, SqlType = Bool>> {
+    Box::new(
+        sql::<Bool>("track_bbox && box(point(")
+            .bind::<Double, _>(min_longitude)
+            .sql(", ")
+            .bind::<Double, _>(min_latitude)
+            .sql("), point(")
+            .bind::<Double, _>(max_longitude)
+            .sql(", ")
+            .bind::<Double, _>(max_latitude)
+            .sql("))"),
+    )
+}
+
+const CARDIO_STATISTIC_QUERY: &str = "
+    select
+        movement_id,
+        date_trunc($2, datetime, 'UTC') as bucket,
+        count(*) as session_count,
+        coalesce(sum(distance), 0) as distance,
+        coalesce(sum(ascent), 0) as ascent,
+        coalesce(sum(time), 0) as time
+    from cardio_session
+    where user_id = $1
+        and not deleted
+        and ($3::bigint is null or movement_id = $3)
+        and ($4::timestamptz is null or datetime >= $4)
+        and ($5::timestamptz is null or datetime <= $5)
+    group by movement_id, bucket
+    order by bucket, movement_id";
+
+impl CardioSessionDb {
+    pub async fn get_summary_by_id(
+        cardio_session_id: CardioSessionId,
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<CardioSessionSummary> {
+        cardio_session::table
+            .find(cardio_session_id)
+            .select(CardioSessionSummary::as_select())
+            .get_result(db)
+            .await
+    }
+
+    pub async fn get_summaries_by_user(
+        user_id: UserId,
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<Vec<CardioSessionSummary>> {
+        cardio_session::table
+            .filter(cardio_session::columns::user_id.eq(user_id))
+            .select(CardioSessionSummary::as_select())
+            .get_results(db)
+            .await
+    }
+
+    pub async fn get_summaries_by_user_and_timespan(
+        user_id: UserId,
+        timespan: Timespan,
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<Vec<CardioSessionSummary>> {
+        let query = cardio_session::table
+            .filter(cardio_session::columns::user_id.eq(user_id))
+            .select(CardioSessionSummary::as_select())
+            .into_boxed();
+        let query = match timespan {
+            Timespan::StartEnd(start, end) => {
+                query.filter(cardio_session::columns::datetime.between(start, end))
+            }
+            Timespan::Start(start) => query.filter(cardio_session::columns::datetime.ge(start)),
+            Timespan::End(end) => query.filter(cardio_session::columns::datetime.le(end)),
+            Timespan::All => query,
+        };
+        query.get_results(db).await
+    }
+
+    pub async fn get_summaries_by_user_and_epoch(
+        user_id: UserId,
+        epoch: Epoch,
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<Vec<CardioSessionSummary>> {
+        cardio_session::table
+            .filter(cardio_session::columns::user_id.eq(user_id))
+            .filter(cardio_session::columns::epoch.gt(epoch))
+            .select(CardioSessionSummary::as_select())
+            .get_results(db)
+            .await
+    }
+
+    pub async fn get_track_by_id(
+        cardio_session_id: CardioSessionId,
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<CardioSessionTrack> {
+        cardio_session::table
+            .find(cardio_session_id)
+            .select(CardioSessionTrack::as_select())
+            .get_result(db)
+            .await
+    }
+
+    pub async fn get_heart_rates_by_user_and_timespan(
+        user_id: UserId,
+        timespan: Timespan,
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<Vec<CardioSessionHeartRate>> {
+        let (start, end) = timespan.bounds();
+        let mut query = cardio_session::table
+            .filter(cardio_session::columns::user_id.eq(user_id))
+            .filter(cardio_session::columns::deleted.eq(false))
+            .select(CardioSessionHeartRate::as_select())
+            .order_by(cardio_session::columns::datetime)
+            .into_boxed();
+        if let Some(start) = start {
+            query = query.filter(cardio_session::columns::datetime.ge(start));
+        }
+        if let Some(end) = end {
+            query = query.filter(cardio_session::columns::datetime.le(end));
+        }
+        query.get_results(db).await
+    }
+
+    pub async fn get_zone_data_by_id(
+        cardio_session_id: CardioSessionId,
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<CardioSessionZoneData> {
+        cardio_session::table
+            .find(cardio_session_id)
+            .select(CardioSessionZoneData::as_select())
+            .get_result(db)
+            .await
+    }
+
+    pub async fn get_zone_data_by_user_and_timespan(
+        user_id: UserId,
+        movement_id: Option<MovementId>,
+        timespan: Timespan,
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<Vec<CardioSessionZoneData>> {
+        let (start, end) = timespan.bounds();
+        let mut query = cardio_session::table
+            .filter(cardio_session::columns::user_id.eq(user_id))
+            .filter(cardio_session::columns::deleted.eq(false))
+            .select(CardioSessionZoneData::as_select())
+            .order_by(cardio_session::columns::datetime)
+            .into_boxed();
+        if let Some(movement_id) = movement_id {
+            query = query.filter(cardio_session::columns::movement_id.eq(movement_id));
+        }
+        if let Some(start) = start {
+            query = query.filter(cardio_session::columns::datetime.ge(start));
+        }
+        if let Some(end) = end {
+            query = query.filter(cardio_session::columns::datetime.le(end));
+        }
+        query.get_results(db).await
+    }
+
+    /// Get the best efforts of the cardio sessions of a user which have a track.
+    ///
+    /// The tracks are streamed, so that only one of them is loaded at a time.
+    pub async fn get_best_efforts_by_user_and_timespan(
+        user_id: UserId,
+        movement_id: Option<MovementId>,
+        timespan: Timespan,
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<Vec<CardioBestEffort>> {
+        let (start, end) = timespan.bounds();
+        let mut query = cardio_session::table
+            .filter(cardio_session::columns::user_id.eq(user_id))
+            .filter(cardio_session::columns::deleted.eq(false))
+            .filter(cardio_session::columns::track.is_not_null())
+            .select(CardioSessionPositions::as_select())
+            .order_by(cardio_session::columns::datetime)
+            .into_boxed();
+        if let Some(movement_id) = movement_id {
+            query = query.filter(cardio_session::columns::movement_id.eq(movement_id));
+        }
+        if let Some(start) = start {
+            query = query.filter(cardio_session::columns::datetime.ge(start));
+        }
+        if let Some(end) = end {
+            query = query.filter(cardio_session::columns::datetime.le(end));
+        }
+        query
+            .load_stream::<CardioSessionPositions>(db)
+            .await?
+            .try_fold(BestEfforts::default(), |mut best_efforts, session| {
+                best_efforts.add(&session);
+                future::ready(Ok(best_efforts))
+            })
+            .await
+            .map(BestEfforts::into_vec)
+    }
+
+    /// Get the tracks of the cardio sessions of a user which have a track but no route.
+    pub async fn get_positions_without_route_by_user_and_timespan(
+        user_id: UserId,
+        timespan: Timespan,
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<Vec<CardioSessionPositions>> {
+        let (start, end) = timespan.bounds();
+        let mut query = cardio_session::table
+            .filter(cardio_session::columns::user_id.eq(user_id))
+            .filter(cardio_session::columns::deleted.eq(false))
+            .filter(cardio_session::columns::track.is_not_null())
+            .filter(cardio_session::columns::route_id.is_null())
+            .select(CardioSessionPositions::as_select())
+            .order_by(cardio_session::columns::datetime)
+            .into_boxed();
+        if let Some(start) = start {
+            query = query.filter(cardio_session::columns::datetime.ge(start));
+        }
+        if let Some(end) = end {
+            query = query.filter(cardio_session::columns::datetime.le(end));
+        }
+        query.get_results(db).await
+    }
+
+    /// Get the cardio sessions of a user whose track passes through the area of `filter` ordered
+    /// by datetime.
+    pub async fn get_summaries_by_user_and_spatial_filter(
+        user_id: UserId,
+        filter: SpatialFilter,
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<Vec<CardioSessionSummary>> {
+        let postgis = postgis_enabled(db).await?;
+        let mut ids: Vec<_> = get_spatial_ids("cardio_session", user_id, filter, postgis, db)
+            .await?
+            .into_iter()
+            .map(CardioSessionId)
+            .collect();
+        if !postgis {
+            ids = cardio_session::table
+                .filter(cardio_session::columns::id.eq_any(ids))
+                .select(CardioSessionCandidate::as_select())
+                .get_results(db)
+                .await?
+                .into_iter()
+                .filter(|session| session.track.as_deref().is_some_and(|t| filter.matches(t)))
+                .map(|session| session.id)
+                .collect();
+        }
+        cardio_session::table
+            .filter(cardio_session::columns::id.eq_any(ids))
+            .select(CardioSessionSummary::as_select())
+            .order_by(cardio_session::columns::datetime)
+            .get_results(db)
+            .await
+    }
+
+    /// Get the tracks of the cardio sessions of a user matching `option` whose bounding box
+    /// intersects `bounds`.
+    pub async fn get_tracks_by_user_and_heatmap_option(
+        user_id: UserId,
+        option: &HeatmapOption,
+        bounds: (f64, f64, f64, f64),
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<Vec<Vec<Position>>> {
+        let mut query = cardio_session::table
+            .filter(cardio_session::columns::user_id.eq(user_id))
+            .filter(cardio_session::columns::deleted.eq(false))
+            .select(cardio_session::columns::track.assume_not_null())
+            .into_boxed()
+            .filter(track_bbox_intersects(bounds));
+        if let Some(movement_id) = option.movement_id {
+            query = query.filter(cardio_session::columns::movement_id.eq(movement_id));
+        }
+        if let Some(cardio_type) = option.cardio_type {
+            query = query.filter(cardio_session::columns::cardio_type.eq(cardio_type));
+        }
+        let query = match option.timespan() {
+            Timespan::StartEnd(start, end) => {
+                query.filter(cardio_session::columns::datetime.between(start, end))
+            }
+            Timespan::Start(start) => query.filter(cardio_session::columns::datetime.ge(start)),
+            Timespan::End(end) => query.filter(cardio_session::columns::datetime.le(end)),
+            Timespan::All => query,
+        };
+        query.get_results(db).await
+    }
+
+    /// Get the cardio sessions on a route ordered by datetime.
+    pub async fn get_summaries_by_route(
+        route_id: RouteId,
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<Vec<CardioSessionSummary>> {
+        cardio_session::table
+            .filter(cardio_session::columns::route_id.eq(route_id))
+            .filter(cardio_session::columns::deleted.eq(false))
+            .select(CardioSessionSummary::as_select())
+            .order_by(cardio_session::columns::datetime)
+            .get_results(db)
+            .await
+    }
+
+    pub async fn set_route(
+        cardio_session_id: CardioSessionId,
+        route_id: RouteId,
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<usize> {
+        diesel::update(cardio_session::table.find(cardio_session_id))
+            .set(cardio_session::columns::route_id.eq(route_id))
+            .execute(db)
+            .await
+    }
+
+    /// Get the tracks and heart rates of the cardio sessions among `cardio_session_ids`.
+    pub async fn get_segment_data_by_ids(
+        user_id: UserId,
+        cardio_session_ids: &[CardioSessionId],
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<Vec<CardioSessionSegmentData>> {
+        cardio_session::table
+            .filter(cardio_session::columns::user_id.eq(user_id))
+            .filter(cardio_session::columns::deleted.eq(false))
+            .filter(cardio_session::columns::id.eq_any(cardio_session_ids))
+            .select(CardioSessionSegmentData::as_select())
+            .get_results(db)
+            .await
+    }
+
+    /// Get the tracks and heart rates of the cardio sessions of a user whose bounding box
+    /// intersects all of `bounds`.
+    pub async fn get_segment_data_by_user_and_bounds(
+        user_id: UserId,
+        bounds: &[(f64, f64, f64, f64)],
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<Vec<CardioSessionSegmentData>> {
+        let mut query = cardio_session::table
+            .filter(cardio_session::columns::user_id.eq(user_id))
+            .filter(cardio_session::columns::deleted.eq(false))
+            .select(CardioSessionSegmentData::as_select())
+            .into_boxed();
+        for &bounds in bounds {
+            query = query.filter(track_bbox_intersects(bounds));
+        }
+        query.get_results(db).await
+    }
+
+    pub async fn get_summary_track_by_id(
+        cardio_session_id: CardioSessionId,
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<CardioSessionSummaryTrack> {
+        cardio_session::table
+            .find(cardio_session_id)
+            .select(CardioSessionSummaryTrack::as_select())
+            .get_result(db)
+            .await
+    }
+
+    pub async fn get_summary_tracks_by_user_and_timespan(
+        user_id: UserId,
+        timespan: Timespan,
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<Vec<CardioSessionSummaryTrack>> {
+        let (start, end) = timespan.bounds();
+        let mut query = cardio_session::table
+            .filter(cardio_session::columns::user_id.eq(user_id))
+            .filter(cardio_session::columns::deleted.eq(false))
+            .filter(cardio_session::columns::track.is_not_null())
+            .select(CardioSessionSummaryTrack::as_select())
+            .order_by(cardio_session::columns::datetime)
+            .into_boxed();
+        if let Some(start) = start {
+            query = query.filter(cardio_session::columns::datetime.ge(start));
+        }
+        if let Some(end) = end {
+            query = query.filter(cardio_session::columns::datetime.le(end));
+        }
+        query.get_results(db).await
+    }
+
+    /// Get at most `limit` cardio sessions of all users with a track ordered by id, starting
+    /// after `after_id`.
+    pub async fn get_summary_tracks_page(
+        after_id: Option<CardioSessionId>,
+        limit: i64,
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<Vec<CardioSessionSummaryTrack>> {
+        let mut query = cardio_session::table
+            .filter(cardio_session::columns::deleted.eq(false))
+            .filter(cardio_session::columns::track.is_not_null())
+            .select(CardioSessionSummaryTrack::as_select())
+            .order_by(cardio_session::columns::id)
+            .limit(limit)
+            .into_boxed();
+        if let Some(after_id) = after_id {
+            query = query.filter(cardio_session::columns::id.gt(after_id));
+        }
+        query.get_results(db).await
+    }
+
+    /// Replace the summary fields of a cardio session.
+    pub async fn set_summary(
+        cardio_session_id: CardioSessionId,
+        summary: TrackSummary,
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<usize> {
+        diesel::update(cardio_session::table.find(cardio_session_id))
+            .set((
+                cardio_session::columns::distance.eq(summary.distance),
+                cardio_session::columns::ascent.eq(summary.ascent),
+                cardio_session::columns::descent.eq(summary.descent),
+                cardio_session::columns::time.eq(summary.time),
+            ))
+            .execute(db)
+            .await
+    }
+
+    /// Get the cardio sessions of a user aggregated per movement and time bucket.
+    pub async fn get_statistics_by_user(
+        user_id: UserId,
+        interval: StatisticInterval,
+        movement_id: Option<MovementId>,
+        timespan: Timespan,
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<Vec<CardioStatistic>> {
+        let (start, end) = timespan.bounds();
+        diesel::sql_query(CARDIO_STATISTIC_QUERY)
+            .bind::<BigInt, _>(user_id)
+            .bind::<Text, _>(interval.as_str())
+            .bind::<Nullable<BigInt>, _>(movement_id)
+            .bind::<Nullable<Timestamptz>, _>(start)
+            .bind::<Nullable<Timestamptz>, _>(end)
+            .load(db)
+            .await
+    }
+}
+
+impl TrainingZoneDb {
+    /// Get the zone definitions of a user which have not been deleted.
+    pub async fn get_active_by_user(
+        user_id: UserId,
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<Vec<TrainingZone>> {
+        training_zone::table
+            .filter(training_zone::columns::user_id.eq(user_id))
+            .filter(training_zone::columns::deleted.eq(false))
+            .select(TrainingZone::as_select())
+            .get_results(db)
+            .await
+    }
+}
+
+impl SegmentDb {
+    /// Get the segments among `segment_ids` or all segments of the user if `segment_ids` is
+    /// `None` which have not been deleted.
+    pub async fn get_active_by_user(
+        user_id: UserId,
+        segment_ids: Option<&[SegmentId]>,
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<Vec<Segment>> {
+        let mut query = segment::table
+            .filter(segment::columns::user_id.eq(user_id))
+            .filter(segment::columns::deleted.eq(false))
+            .select(Segment::as_select())
+            .into_boxed();
+        if let Some(segment_ids) = segment_ids {
+            query = query.filter(segment::columns::id.eq_any(segment_ids));
+        }
+        query.get_results(db).await
+    }
+}
+
+impl SegmentEffortDb {
+    /// Replace the efforts of the segments `segment_ids` in the cardio sessions
+    /// `cardio_session_ids` or in all cardio sessions which have not been deleted if
+    /// `cardio_session_ids` is `None` by `efforts`.
+    pub async fn replace(
+        segment_ids: &[SegmentId],
+        cardio_session_ids: Option<&[CardioSessionId]>,
+        efforts: &[SegmentEffort],
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<()> {
+        let query = diesel::delete(segment_effort::table)
+            .filter(segment_effort::columns::segment_id.eq_any(segment_ids));
+        match cardio_session_ids {
+            Some(cardio_session_ids) => {
+                query
+                    .filter(segment_effort::columns::cardio_session_id.eq_any(cardio_session_ids))
+                    .execute(db)
+                    .await?
+            }
+            None => {
+                query
+                    .filter(
+                        segment_effort::columns::cardio_session_id.eq_any(
+                            cardio_session::table
+                                .filter(cardio_session::columns::deleted.eq(false))
+                                .select(cardio_session::columns::id),
+                        ),
+                    )
+                    .execute(db)
+                    .await?
+            }
+        };
+        diesel::insert_into(segment_effort::table)
+            .values(efforts)
+            .execute(db)
+            .await?;
+        Ok(())
+    }
+
+    /// Delete the efforts of segments or cardio sessions which have been deleted permanently.
+    pub async fn delete_orphaned(db: &mut AsyncPgConnection) -> QueryResult<usize> {
+        diesel::delete(segment_effort::table)
+            .filter(
+                not(exists(segment::table.filter(
+                    segment::columns::id.eq(segment_effort::columns::segment_id),
+                )))
+                .or(not(exists(cardio_session::table.filter(
+                    cardio_session::columns::id.eq(segment_effort::columns::cardio_session_id),
+                )))),
+            )
+            .execute(db)
+            .await
+    }
+
+    /// Get the efforts of a user on segments and in cardio sessions which have not been deleted
+    /// ordered by datetime.
+    pub async fn get_by_user_and_timespan(
+        user_id: UserId,
+        segment_id: Option<SegmentId>,
+        timespan: Timespan,
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<Vec<SegmentEffort>> {
+        let (start, end) = timespan.bounds();
+        let mut query = segment_effort::table
+            .inner_join(
+                segment::table.on(segment::columns::id.eq(segment_effort::columns::segment_id)),
+            )
+            .inner_join(
+                cardio_session::table
+                    .on(cardio_session::columns::id.eq(segment_effort::columns::cardio_session_id)),
+            )
+            .filter(segment_effort::columns::user_id.eq(user_id))
+            .filter(segment::columns::deleted.eq(false))
+            .filter(cardio_session::columns::deleted.eq(false))
+            .select(SegmentEffort::as_select())
+            .order_by((
+                segment_effort::columns::datetime,
+                segment_effort::columns::segment_id,
+            ))
+            .into_boxed();
+        if let Some(segment_id) = segment_id {
+            query = query.filter(segment_effort::columns::segment_id.eq(segment_id));
+        }
+        if let Some(start) = start {
+            query = query.filter(segment_effort::columns::datetime.ge(start));
+        }
+        if let Some(end) = end {
+            query = query.filter(segment_effort::columns::datetime.le(end));
+        }
+        query.get_results(db).await
+    }
+}
+
+impl CardioSessionOverviewDb {
+    /// Get the ids and epochs of the cardio sessions of a user with a track ordered by datetime
+    /// together with the epochs and tracks of their stored overviews.
+    pub async fn get_by_user(
+        user_id: UserId,
+        cardio_session_id: Option<CardioSessionId>,
+        timespan: Timespan,
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<Vec<StoredOverview<CardioSessionId>>> {
+        let (start, end) = timespan.bounds();
+        let mut query = cardio_session::table
+            .left_join(cardio_session_overview::table)
+            .filter(cardio_session::columns::user_id.eq(user_id))
+            .filter(cardio_session::columns::deleted.eq(false))
+            .filter(cardio_session::columns::track.is_not_null())
+            .select((
+                cardio_session::columns::id,
+                cardio_session::columns::epoch,
+                cardio_session_overview::columns::epoch.nullable(),
+                cardio_session_overview::columns::track.nullable(),
+            ))
+            .order_by(cardio_session::columns::datetime)
+            .into_boxed();
+        if let Some(cardio_session_id) = cardio_session_id {
+            query = query.filter(cardio_session::columns::id.eq(cardio_session_id));
+        }
+        if let Some(start) = start {
+            query = query.filter(cardio_session::columns::datetime.ge(start));
+        }
+        if let Some(end) = end {
+            query = query.filter(cardio_session::columns::datetime.le(end));
+        }
+        query.get_results(db).await
+    }
+
+    pub async fn get_tracks(
+        cardio_session_ids: &[CardioSessionId],
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<Vec<(CardioSessionId, Vec<Position>)>> {
+        if cardio_session_ids.is_empty() {
+            return Ok(vec![]);
+        }
+        cardio_session::table
+            .filter(cardio_session::columns::id.eq_any(cardio_session_ids))
+            .select((
+                cardio_session::columns::id,
+                cardio_session::columns::track.assume_not_null(),
+            ))
+            .get_results(db)
+            .await
+    }
+
+    /// Store overviews computed for the given epochs of their cardio sessions.
+    pub async fn upsert(
+        overviews: &[(CardioSessionId, Epoch, Vec<Position>)],
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<()> {
+        if overviews.is_empty() {
+            return Ok(());
+        }
+        let values: Vec<_> = overviews
+            .iter()
+            .map(|(cardio_session_id, epoch, track)| {
+                (
+                    cardio_session_overview::columns::cardio_session_id.eq(*cardio_session_id),
+                    cardio_session_overview::columns::epoch.eq(*epoch),
+                    cardio_session_overview::columns::track.eq(track),
+                )
+            })
+            .collect();
+        diesel::insert_into(cardio_session_overview::table)
+            .values(&values)
+            .on_conflict(cardio_session_overview::columns::cardio_session_id)
+            .do_update()
+            .set((
+                cardio_session_overview::columns::epoch
+                    .eq(excluded(cardio_session_overview::columns::epoch)),
+                cardio_session_overview::columns::track
+                    .eq(excluded(cardio_session_overview::columns::track)),
+            ))
+            .execute(db)
+            .await?;
+        Ok(())
+    }
+}
+
+impl RouteOverviewDb {
+    /// Get the ids and epochs of the routes of a user with a track ordered by name together with
+    /// the epochs and tracks of their stored overviews.
+    pub async fn get_by_user(
+        user_id: UserId,
+        route_id: Option<RouteId>,
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<Vec<StoredOverview<RouteId>>> {
+        let mut query = route::table
+            .left_join(route_overview::table)
+            .filter(route::columns::user_id.eq(user_id))
+            .filter(route::columns::deleted.eq(false))
+            .filter(route::columns::track.is_not_null())
+            .select((
+                route::columns::id,
+                route::columns::epoch,
+                route_overview::columns::epoch.nullable(),
+                route_overview::columns::track.nullable(),
+            ))
+            .order_by(route::columns::name)
+            .into_boxed();
+        if let Some(route_id) = route_id {
+            query = query.filter(route::columns::id.eq(route_id));
+        }
+        query.get_results(db).await
+    }
+
+    pub async fn get_tracks(
+        route_ids: &[RouteId],
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<Vec<(RouteId, Vec<Position>)>> {
+        if route_ids.is_empty() {
+            return Ok(vec![]);
+        }
+        route::table
+            .filter(route::columns::id.eq_any(route_ids))
+            .select((route::columns::id, route::columns::track.assume_not_null()))
+            .get_results(db)
+            .await
+    }
+
+    /// Store overviews computed for the given epochs of their routes.
+    pub async fn upsert(
+        overviews: &[(RouteId, Epoch, Vec<Position>)],
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<()> {
+        if overviews.is_empty() {
+            return Ok(());
+        }
+        let values: Vec<_> = overviews
+            .iter()
+            .map(|(route_id, epoch, track)| {
+                (
+                    route_overview::columns::route_id.eq(*route_id),
+                    route_overview::columns::epoch.eq(*epoch),
+                    route_overview::columns::track.eq(track),
+                )
+            })
+            .collect();
+        diesel::insert_into(route_overview::table)
+            .values(&values)
+            .on_conflict(route_overview::columns::route_id)
+            .do_update()
+            .set((
+                route_overview::columns::epoch.eq(excluded(route_overview::columns::epoch)),
+                route_overview::columns::track.eq(excluded(route_overview::columns::track)),
+            ))
+            .execute(db)
+            .await?;
+        Ok(())
+    }
+}
+
+impl GetByUniqueKey for CardioSessionDb {}
+
+impl GetByUniqueKey for RouteDb {
+    async fn get_by_unique_key(
+        value: &Route,
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<Option<Route>> {
+        route::table
+            .filter(route::columns::user_id.eq(value.user_id))
+            .filter(route::columns::name.eq(&value.name))
+            .filter(route::columns::deleted.eq(false))
+            .select(Route::as_select())
+            .get_result(db)
+            .await
+            .optional()
+    }
+}
+
+impl GetByUniqueKey for TrainingZoneDb {
+    async fn get_by_unique_key(
+        value: &TrainingZone,
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<Option<TrainingZone>> {
+        // boxed because the enum types do not implement `QueryId`
+        training_zone::table
+            .filter(training_zone::columns::user_id.eq(value.user_id))
+            .filter(training_zone::columns::zone_type.eq(value.zone_type))
+            .filter(training_zone::columns::movement_id.is_not_distinct_from(value.movement_id))
+            .filter(training_zone::columns::deleted.eq(false))
+            .select(TrainingZone::as_select())
+            .into_boxed()
+            .get_result(db)
+            .await
+            .optional()
+    }
+}
+
+impl GetByUniqueKey for SegmentDb {
+    async fn get_by_unique_key(
+        value: &Segment,
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<Option<Segment>> {
+        segment::table
+            .filter(segment::columns::user_id.eq(value.user_id))
+            .filter(segment::columns::name.eq(&value.name))
+            .filter(segment::columns::deleted.eq(false))
+            .select(Segment::as_select())
+            .get_result(db)
+            .await
+            .optional()
+    }
+}
@@ -1,14 +1,25 @@
 use derive_deftly::Deftly;
+use diesel::prelude::*;
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
 use sport_log_derive::*;
+use sport_log_types::{
+    Diary, Wod,
+    schema::{diary, wod},
+};
 
-#[derive(Db, DbWithUserId, ModifiableDb, Deftly)]
+use crate::db::*;
+
+#[derive(Db, DbWithUserId, DbWithScope, ModifiableDb, ArchivableDb, Deftly)]
 #[derive_deftly(
     VerifyForUserOrAPGet,
     Create,
     GetById,
     GetByUser,
     GetByUserAndEpoch,
+    GetPageByUserAndEpoch,
     Update,
+    Archive,
+    CheckedMultiple,
     GetEpochByUser,
     CheckUserId,
     VerifyForUserOrAPUpdate,
@@ -16,17 +27,52 @@ use sport_log_derive::*;
 )]
 pub struct DiaryDb;
 
-#[derive(Db, DbWithUserId, ModifiableDb, Deftly)]
+#[derive(Db, DbWithUserId, DbWithScope, ModifiableDb, ArchivableDb, Deftly)]
 #[derive_deftly(
     VerifyForUserOrAPGet,
     Create,
     GetById,
     GetByUser,
     GetByUserAndEpoch,
+    GetPageByUserAndEpoch,
     Update,
+    Archive,
+    CheckedMultiple,
     GetEpochByUser,
     CheckUserId,
     VerifyForUserOrAPUpdate,
     VerifyForUserOrAPCreate
 )]
 pub struct WodDb;
+
+impl GetByUniqueKey for DiaryDb {
+    async fn get_by_unique_key(
+        value: &Diary,
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<Option<Diary>> {
+        diary::table
+            .filter(diary::columns::user_id.eq(value.user_id))
+            .filter(diary::columns::date.eq(value.date))
+            .filter(diary::columns::deleted.eq(false))
+            .select(Diary::as_select())
+            .get_result(db)
+            .await
+            .optional()
+    }
+}
+
+impl GetByUniqueKey for WodDb {
+    async fn get_by_unique_key(
+        value: &Wod,
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<Option<Wod>> {
+        wod::table
+            .filter(wod::columns::user_id.eq(value.user_id))
+            .filter(wod::columns::date.eq(value.date))
+            .filter(wod::columns::deleted.eq(false))
+            .select(Wod::as_select())
+            .get_result(db)
+            .await
+            .optional()
+    }
+}
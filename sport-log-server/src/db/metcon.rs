@@ -1,16 +1,28 @@
 use derive_deftly::Deftly;
+use diesel::{
+    prelude::*,
+    sql_types::{BigInt, Nullable, Text, Timestamptz},
+};
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
 use sport_log_derive::*;
+use sport_log_types::{
+    Metcon, MetconMovement, MetconStatistic, MovementId, StatisticInterval, UserId,
+    schema::{metcon, metcon_movement},
+};
 
 use crate::db::*;
 
-#[derive(Db, DbWithUserId, ModifiableDb, Deftly)]
+#[derive(Db, DbWithUserId, DbWithScope, ModifiableDb, ArchivableDb, Deftly)]
 #[derive_deftly(
     Create,
     GetById,
     GetByUserOptional,
     GetByUserAndEpochOptional,
+    GetPageByUserAndEpochOptional,
     GetEpochByUserOptional,
     Update,
+    Archive,
+    CheckedMultiple,
     CheckOptionalUserId,
     VerifyForUserOrAPGetOptional,
     VerifyForUserOrAPUpdateOptional,
@@ -18,14 +30,17 @@ use crate::db::*;
 )]
 pub struct MetconDb;
 
-#[derive(Db, DbWithUserId, ModifiableDb, Deftly)]
+#[derive(Db, DbWithUserId, DbWithScope, ModifiableDb, ArchivableDb, Deftly)]
 #[derive_deftly(
     Create,
     GetById,
     GetByUserOptional,
     GetByUserAndEpochOptional,
+    GetPageByUserAndEpochOptional,
     GetEpochByUserOptional,
     Update,
+    Archive,
+    CheckedMultiple,
     CheckOptionalUserId,
     VerifyForUserOrAPGetOptional,
     VerifyForUserOrAPUpdateOptional,
@@ -33,7 +48,7 @@ pub struct MetconDb;
 )]
 pub struct MetconMovementDb;
 
-#[derive(Db, DbWithUserId, DbWithDateTime, ModifiableDb, Deftly)]
+#[derive(Db, DbWithUserId, DbWithDateTime, DbWithScope, ModifiableDb, ArchivableDb, Deftly)]
 #[derive_deftly(
     VerifyForUserOrAPGet,
     Create,
@@ -41,10 +56,93 @@ pub struct MetconMovementDb;
     GetByUser,
     GetByUserTimespan,
     GetByUserAndEpoch,
+    GetPageByUserAndEpoch,
     Update,
+    Archive,
+    CheckedMultiple,
     GetEpochByUser,
     CheckUserId,
     VerifyForUserOrAPUpdate,
     VerifyForUserOrAPCreate
 )]
 pub struct MetconSessionDb;
+
+const METCON_STATISTIC_QUERY: &str = "
+    select
+        metcon_id,
+        date_trunc($2, datetime, 'UTC') as bucket,
+        count(*) as session_count
+    from metcon_session
+    where user_id = $1
+        and not deleted
+        and (
+            $3::bigint is null
+            or exists (
+                select 1 from metcon_movement
+                where metcon_movement.metcon_id = metcon_session.metcon_id
+                    and metcon_movement.movement_id = $3
+                    and not metcon_movement.deleted
+            )
+        )
+        and ($4::timestamptz is null or datetime >= $4)
+        and ($5::timestamptz is null or datetime <= $5)
+    group by metcon_id, bucket
+    order by bucket, metcon_id";
+
+impl MetconSessionDb {
+    /// Get the metcon sessions of a user aggregated per metcon and time bucket.
+    ///
+    /// If `movement_id` is set, only sessions of metcons containing the movement are considered.
+    pub async fn get_statistics_by_user(
+        user_id: UserId,
+        interval: StatisticInterval,
+        movement_id: Option<MovementId>,
+        timespan: Timespan,
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<Vec<MetconStatistic>> {
+        let (start, end) = timespan.bounds();
+        diesel::sql_query(METCON_STATISTIC_QUERY)
+            .bind::<BigInt, _>(user_id)
+            .bind::<Text, _>(interval.as_str())
+            .bind::<Nullable<BigInt>, _>(movement_id)
+            .bind::<Nullable<Timestamptz>, _>(start)
+            .bind::<Nullable<Timestamptz>, _>(end)
+            .load(db)
+            .await
+    }
+}
+
+impl GetByUniqueKey for MetconDb {
+    async fn get_by_unique_key(
+        value: &Metcon,
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<Option<Metcon>> {
+        metcon::table
+            .filter(metcon::columns::user_id.eq(value.user_id))
+            .filter(metcon::columns::name.eq(&value.name))
+            .filter(metcon::columns::deleted.eq(false))
+            .select(Metcon::as_select())
+            .get_result(db)
+            .await
+            .optional()
+    }
+}
+
+impl GetByUniqueKey for MetconMovementDb {
+    async fn get_by_unique_key(
+        value: &MetconMovement,
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<Option<MetconMovement>> {
+        metcon_movement::table
+            .filter(metcon_movement::columns::user_id.eq(value.user_id))
+            .filter(metcon_movement::columns::metcon_id.eq(value.metcon_id))
+            .filter(metcon_movement::columns::movement_number.eq(value.movement_number))
+            .filter(metcon_movement::columns::deleted.eq(false))
+            .select(MetconMovement::as_select())
+            .get_result(db)
+            .await
+            .optional()
+    }
+}
+
+impl GetByUniqueKey for MetconSessionDb {}
@@ -3,34 +3,48 @@ use argon2::Argon2;
 use argon2::{Algorithm, Params, Version};
 use axum::http::StatusCode;
 use chrono::{DateTime, Utc};
-use diesel::{Column, QueryResult, Table};
-use diesel_async::AsyncPgConnection;
+use diesel::{
+    Column, QueryResult, Table, define_sql_function,
+    result::{DatabaseErrorKind, Error as DieselError},
+    sql_types::{BigInt, Text},
+};
+use diesel_async::{AsyncConnection, AsyncPgConnection};
+use futures_util::{TryFutureExt, future::BoxFuture};
 use serde::Deserialize;
-use sport_log_types::{ActionProviderId, Epoch, UserId};
+use sport_log_types::{ActionProviderId, Archived, Epoch, ScopeEntity, UserId};
 
 mod account;
 mod action;
 mod admin;
+mod archive;
+mod auth_session;
 mod cardio;
 mod diary_wod;
 mod metcon;
 mod movement;
 mod platform;
+mod scope;
 mod strength;
 mod user;
 
 pub use account::*;
 pub use action::*;
 pub use admin::*;
+pub use archive::*;
+pub use auth_session::*;
 pub use cardio::*;
 pub use diary_wod::*;
 pub use metcon::*;
 pub use movement::*;
 pub use platform::*;
+pub use scope::*;
 pub use strength::*;
 pub use user::*;
 
-use crate::auth::*;
+use crate::{
+    auth::*,
+    error::{HandlerError, RowResult},
+};
 
 pub fn build_hasher() -> Argon2<'static> {
     #[cfg(not(test))]
@@ -84,9 +98,21 @@ pub enum Timespan {
     All,
 }
 
+impl Timespan {
+    /// The inclusive start and end of the timespan.
+    pub fn bounds(&self) -> (Option<DateTime<Utc>>, Option<DateTime<Utc>>) {
+        match *self {
+            Timespan::StartEnd(start, end) => (Some(start), Some(end)),
+            Timespan::Start(start) => (Some(start), None),
+            Timespan::End(end) => (None, Some(end)),
+            Timespan::All => (None, None),
+        }
+    }
+}
+
 pub trait Db {
-    type Id;
-    type Type;
+    type Id: Send;
+    type Type: Send + Sync;
     type Table: Table;
 
     fn table() -> Self::Table;
@@ -105,6 +131,11 @@ pub trait DbWithApId: Db {
     fn ap_id_column() -> Self::ApIdColumn;
 }
 
+/// A type an [`ActionProvider`](sport_log_types::ActionProvider) needs a granted scope for.
+pub trait DbWithScope: Db {
+    const SCOPE_ENTITY: ScopeEntity;
+}
+
 pub trait DbWithDateTime: Db {
     type DateTimeColumn: Column;
 
@@ -117,9 +148,27 @@ pub trait ModifiableDb: Db {
     fn epoch_column() -> Self::EpochColumn;
 }
 
+/// A type whose deleted entries are moved to the archive table `{TABLE_NAME}_archive`.
+pub trait ArchivableDb: Db {
+    const TABLE_NAME: &'static str;
+
+    type ArchivedAtColumn: Column;
+
+    fn archived_at_column() -> Self::ArchivedAtColumn;
+}
+
+define_sql_function! {
+    /// Restore an archived entry together with all entries that have been archived by the cascaded
+    /// delete of it and return the number of restored entries.
+    fn restore_record(record_table: Text, record_id: BigInt) -> BigInt;
+}
+
 /// A type for which a new database entry can be created.
 pub trait Create: Db {
-    async fn create(value: &Self::Type, db: &mut AsyncPgConnection) -> QueryResult<usize>;
+    fn create(
+        value: &Self::Type,
+        db: &mut AsyncPgConnection,
+    ) -> impl Future<Output = QueryResult<usize>> + Send;
 
     async fn create_multiple(
         values: &[Self::Type],
@@ -129,7 +178,10 @@ pub trait Create: Db {
 
 /// A type for which an entry can be retrieved by id from the database.
 pub trait GetById: Db {
-    async fn get_by_id(id: Self::Id, db: &mut AsyncPgConnection) -> QueryResult<Self::Type>;
+    fn get_by_id(
+        id: Self::Id,
+        db: &mut AsyncPgConnection,
+    ) -> impl Future<Output = QueryResult<Self::Type>> + Send;
 }
 
 /// A type for which entries can be retrieved by user from the database.
@@ -167,6 +219,20 @@ pub trait GetByEpoch: Db {
     -> QueryResult<Vec<Self::Type>>;
 }
 
+/// A type for which entries changed since the epoch of the last synchronization can be retrieved
+/// page-wise from the database.
+pub trait GetPageByUserAndEpoch: Db {
+    /// Get at most `limit` entries ordered by epoch and id that come after the entry with
+    /// `after_epoch` and `after_id`, together with their epoch and id.
+    async fn get_page_by_user_and_epoch(
+        user_id: UserId,
+        after_epoch: Epoch,
+        after_id: i64,
+        limit: i64,
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<Vec<(Epoch, i64, Self::Type)>>;
+}
+
 /// A type for which all entries can be retrieved from the database.
 pub trait GetAll: Db {
     async fn get_all(db: &mut AsyncPgConnection) -> QueryResult<Vec<Self::Type>>;
@@ -174,7 +240,10 @@ pub trait GetAll: Db {
 
 /// A type which can be used to update an entry in the database.
 pub trait Update: Db {
-    async fn update(value: &Self::Type, db: &mut AsyncPgConnection) -> QueryResult<usize>;
+    fn update(
+        value: &Self::Type,
+        db: &mut AsyncPgConnection,
+    ) -> impl Future<Output = QueryResult<usize>> + Send;
 
     async fn update_multiple(
         values: &[Self::Type],
@@ -182,6 +251,184 @@ pub trait Update: Db {
     ) -> QueryResult<usize>;
 }
 
+/// A type for which multiple entries can be created or updated while reporting the result of
+/// every single entry.
+pub trait CheckedMultiple:
+    Create + UpdateIfUnchanged + GetById + GetByUniqueKey + Sized + 'static
+{
+    fn id(value: &Self::Type) -> Self::Id;
+
+    /// Get the existing entry `value` conflicts with if `error` is a unique violation.
+    ///
+    /// Entries of other users are never returned.
+    fn get_conflicting(
+        value: &Self::Type,
+        error: &DieselError,
+        db: &mut AsyncPgConnection,
+    ) -> impl Future<Output = QueryResult<Option<Self::Type>>> + Send;
+
+    /// Create all entries if none of them conflicts with the existing data.
+    async fn create_multiple_checked(
+        values: &[Self::Type],
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<Vec<RowResult<Self::Id, Self::Type>>> {
+        apply_checked::<Self>(values, db, |value, db| {
+            Box::pin(Self::create(value, db).map_ok(|_| None))
+        })
+        .await
+    }
+
+    /// Update all entries if none of them conflicts with the existing data.
+    ///
+    /// If `last_epoch` is set, entries that have been changed since `last_epoch` are treated as
+    /// conflicts.
+    async fn update_multiple_checked(
+        values: &[Self::Type],
+        last_epoch: Option<Epoch>,
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<Vec<RowResult<Self::Id, Self::Type>>> {
+        apply_checked::<Self>(values, db, |value, db| match last_epoch {
+            Some(epoch) => Box::pin(Self::update_if_unchanged(value, epoch, db)),
+            None => Box::pin(Self::update(value, db).map_ok(|_| None)),
+        })
+        .await
+    }
+}
+
+/// Apply `query` to every value in its own savepoint so that the conflicts of all values are
+/// found.
+///
+/// `query` returns the current epoch of the entry if it has not been applied because the entry
+/// has been changed in the meantime.
+/// If any value conflicts with the existing data, the whole transaction is rolled back.
+async fn apply_checked<D: CheckedMultiple>(
+    values: &[D::Type],
+    db: &mut AsyncPgConnection,
+    query: impl for<'a> Fn(
+        &'a D::Type,
+        &'a mut AsyncPgConnection,
+    ) -> BoxFuture<'a, QueryResult<Option<Epoch>>>
+    + Sync,
+) -> QueryResult<Vec<RowResult<D::Id, D::Type>>> {
+    let mut results = Vec::with_capacity(values.len());
+    let result = db
+        .transaction(async |db| {
+            for value in values {
+                results.push(apply_checked_single::<D>(value, db, &query).await?);
+            }
+            if results.iter().all(RowResult::is_ok) {
+                Ok(())
+            } else {
+                Err(DieselError::RollbackTransaction)
+            }
+        })
+        .await;
+    match result {
+        Ok(()) => Ok(results),
+        Err(DieselError::RollbackTransaction) if !results.iter().all(RowResult::is_ok) => {
+            Ok(results)
+        }
+        Err(error) => Err(error),
+    }
+}
+
+async fn apply_checked_single<D: CheckedMultiple>(
+    value: &D::Type,
+    db: &mut AsyncPgConnection,
+    query: &(
+         impl for<'a> Fn(
+        &'a D::Type,
+        &'a mut AsyncPgConnection,
+    ) -> BoxFuture<'a, QueryResult<Option<Epoch>>>
+         + Sync
+     ),
+) -> QueryResult<RowResult<D::Id, D::Type>> {
+    match db.transaction(async |db| query(value, db).await).await {
+        Ok(None) => Ok(RowResult::ok(D::id(value))),
+        Ok(Some(epoch)) => {
+            let existing = D::get_by_id(D::id(value), db).await?;
+            Ok(RowResult::changed(D::id(value), epoch, existing))
+        }
+        Err(error) => {
+            let existing = D::get_conflicting(value, &error, db).await?;
+            RowResult::from_error(D::id(value), error, existing)
+        }
+    }
+}
+
+/// The key of the existing entry that caused a unique violation.
+pub enum UniqueKey {
+    PrimaryKey,
+    /// The columns of a unique index, see [`GetByUniqueKey`].
+    Index,
+}
+
+impl UniqueKey {
+    pub fn from_error(error: &DieselError) -> Option<Self> {
+        let DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, info) = error else {
+            return None;
+        };
+        if info
+            .constraint_name()
+            .is_some_and(|constraint| constraint.ends_with("_pkey"))
+        {
+            Some(Self::PrimaryKey)
+        } else {
+            Some(Self::Index)
+        }
+    }
+}
+
+/// A type whose entries can be retrieved by the columns of their unique index.
+///
+/// Types without a unique index besides their primary key use the default implementation.
+pub trait GetByUniqueKey: Db {
+    /// Get the entry of the same user that has the same values in the columns of the unique index
+    /// as `value`.
+    fn get_by_unique_key(
+        _value: &Self::Type,
+        _db: &mut AsyncPgConnection,
+    ) -> impl Future<Output = QueryResult<Option<Self::Type>>> + Send {
+        async { Ok(None) }
+    }
+}
+
+/// A type which can be used to update an entry in the database unless it has been changed since
+/// the client has last seen it.
+pub trait UpdateIfUnchanged: Update {
+    /// Update the entry if its epoch is not greater than `epoch`.
+    ///
+    /// Returns the current epoch of the entry if it has been changed since `epoch` and has
+    /// therefore not been updated.
+    fn update_if_unchanged(
+        value: &Self::Type,
+        epoch: Epoch,
+        db: &mut AsyncPgConnection,
+    ) -> impl Future<Output = QueryResult<Option<Epoch>>> + Send;
+}
+
+/// A type for which the archived entries of a user can be retrieved and restored.
+pub trait Archive: ArchivableDb {
+    /// Get the archived entries of the user, most recently deleted first.
+    async fn get_archived_by_user(
+        user_id: UserId,
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<Vec<Archived<Self::Type>>>;
+
+    /// Restore the archived entry with id `id` of the [`User`](sport_log_types::User) with
+    /// `user_id`.
+    ///
+    /// Entries that have been deleted together with it by a cascaded delete (e.g. the strength sets
+    /// of a strength session) are restored as well.
+    /// All restored entries get a new epoch, so that clients receive them during the next
+    /// synchronization.
+    async fn restore(
+        id: Self::Id,
+        user_id: UserId,
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<Self::Type>;
+}
+
 /// A type for which the maximum epoch of a user can be retrieved.
 pub trait GetEpochByUser: ModifiableDb {
     async fn get_epoch_by_user(user_id: UserId, db: &mut AsyncPgConnection) -> QueryResult<Epoch>;
@@ -267,7 +514,7 @@ pub trait VerifyForUserOrAPGet {
         self,
         auth: AuthUserOrAP,
         db: &mut AsyncPgConnection,
-    ) -> Result<Self::Id, StatusCode>;
+    ) -> Result<Self::Id, HandlerError>;
 }
 
 pub trait VerifyForActionProviderGet {
@@ -347,7 +594,7 @@ pub trait VerifyForUserOrAPUpdate {
         self,
         auth: AuthUserOrAP,
         db: &mut AsyncPgConnection,
-    ) -> Result<Self::Type, StatusCode>;
+    ) -> Result<Self::Type, HandlerError>;
 }
 
 pub trait VerifyMultipleForUserOrAPUpdate {
@@ -357,19 +604,19 @@ pub trait VerifyMultipleForUserOrAPUpdate {
         self,
         auth: AuthUserOrAP,
         db: &mut AsyncPgConnection,
-    ) -> Result<Vec<Self::Type>, StatusCode>;
+    ) -> Result<Vec<Self::Type>, HandlerError>;
 }
 
 pub trait VerifyForUserOrAPCreate {
     type Type;
 
-    fn verify_user_ap_create(self, auth: AuthUserOrAP) -> Result<Self::Type, StatusCode>;
+    fn verify_user_ap_create(self, auth: AuthUserOrAP) -> Result<Self::Type, HandlerError>;
 }
 
 pub trait VerifyMultipleForUserOrAPCreate {
     type Type;
 
-    fn verify_user_ap_create(self, auth: AuthUserOrAP) -> Result<Vec<Self::Type>, StatusCode>;
+    fn verify_user_ap_create(self, auth: AuthUserOrAP) -> Result<Vec<Self::Type>, HandlerError>;
 }
 
 pub trait VerifyForActionProviderUpdate {
@@ -1,16 +1,22 @@
 use derive_deftly::Deftly;
+use diesel::prelude::*;
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
 use sport_log_derive::*;
+use sport_log_types::{Movement, schema::movement};
 
 use crate::db::*;
 
-#[derive(Db, DbWithUserId, ModifiableDb, Deftly)]
+#[derive(Db, DbWithUserId, DbWithScope, ModifiableDb, ArchivableDb, Deftly)]
 #[derive_deftly(
     VerifyForAdminGet,
     Create,
     GetById,
     GetByUserOptional,
     GetByUserAndEpochOptional,
+    GetPageByUserAndEpochOptional,
     Update,
+    Archive,
+    CheckedMultiple,
     GetEpochByUserOptional,
     CheckOptionalUserId,
     VerifyForUserOrAPGetOptional,
@@ -19,3 +25,22 @@ use crate::db::*;
     VerifyForAdmin
 )]
 pub struct MovementDb;
+
+impl GetByUniqueKey for MovementDb {
+    async fn get_by_unique_key(
+        value: &Movement,
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<Option<Movement>> {
+        // boxed because the enum types do not implement `QueryId`
+        movement::table
+            .filter(movement::columns::user_id.eq(value.user_id))
+            .filter(movement::columns::name.eq(&value.name))
+            .filter(movement::columns::movement_dimension.eq(value.movement_dimension))
+            .filter(movement::columns::deleted.eq(false))
+            .select(Movement::as_select())
+            .into_boxed()
+            .get_result(db)
+            .await
+            .optional()
+    }
+}
@@ -1,5 +1,12 @@
 use derive_deftly::Deftly;
+use diesel::prelude::*;
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
 use sport_log_derive::*;
+use sport_log_types::{
+    PlatformCredential, PlatformCredentialId, UserId, schema::platform_credential,
+};
+
+use crate::{crypto::CredentialKeys, db::*};
 
 #[derive(Db, ModifiableDb, Deftly)]
 #[derive_deftly(
@@ -9,6 +16,7 @@ use sport_log_derive::*;
     GetAll,
     GetById,
     GetByEpoch,
+    GetPageByEpoch,
     Update,
     GetEpoch,
     VerifyForAdmin,
@@ -23,6 +31,7 @@ pub struct PlatformDb;
     GetById,
     GetByUser,
     GetByUserAndEpoch,
+    GetPageByUserAndEpoch,
     Update,
     GetEpochByUser,
     CheckUserId,
@@ -30,3 +39,155 @@ pub struct PlatformDb;
     VerifyForUserCreate
 )]
 pub struct PlatformCredentialDb;
+
+/// Same as traits [`Create`] and [`Update`] but the passwords are encrypted before they are stored.
+///
+/// Encrypted passwords that are sent back unchanged are kept as they are.
+impl PlatformCredentialDb {
+    pub async fn create(
+        platform_credential: &mut <Self as Db>::Type,
+        keys: &CredentialKeys,
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<usize> {
+        encrypt(platform_credential, keys);
+        <Self as Create>::create(platform_credential, db).await
+    }
+
+    pub async fn create_multiple(
+        platform_credentials: &mut [<Self as Db>::Type],
+        keys: &CredentialKeys,
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<usize> {
+        for platform_credential in &mut *platform_credentials {
+            encrypt(platform_credential, keys);
+        }
+        <Self as Create>::create_multiple(platform_credentials, db).await
+    }
+
+    pub async fn update(
+        platform_credential: &mut <Self as Db>::Type,
+        keys: &CredentialKeys,
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<usize> {
+        db.transaction(async |db| update_encrypted(platform_credential, keys, db).await)
+            .await
+    }
+
+    pub async fn update_multiple(
+        platform_credentials: &mut [<Self as Db>::Type],
+        keys: &CredentialKeys,
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<usize> {
+        let len = platform_credentials.len();
+        db.transaction(async |db| {
+            for platform_credential in &mut *platform_credentials {
+                update_encrypted(platform_credential, keys, db).await?;
+            }
+            Ok(len)
+        })
+        .await
+    }
+}
+
+#[allow(clippy::multiple_inherent_impl)]
+impl PlatformCredentialDb {
+    /// Make sure all passwords (including the ones of deleted credentials) are encrypted with the
+    /// current key.
+    ///
+    /// This encrypts passwords that are still stored in plaintext and re-encrypts passwords that
+    /// are encrypted with an old key.
+    /// Returns the number of updated credentials.
+    pub async fn reencrypt_all(
+        keys: &CredentialKeys,
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<usize> {
+        reencrypt(keys, false, db).await
+    }
+
+    /// Encrypt all passwords (including the ones of deleted credentials) that are still stored in
+    /// plaintext.
+    ///
+    /// Returns the number of updated credentials.
+    pub async fn encrypt_plaintext(
+        keys: &CredentialKeys,
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<usize> {
+        reencrypt(keys, true, db).await
+    }
+}
+
+/// Re-encrypt the passwords of all credentials or only of the ones stored in plaintext.
+async fn reencrypt(
+    keys: &CredentialKeys,
+    plaintext_only: bool,
+    db: &mut AsyncPgConnection,
+) -> QueryResult<usize> {
+    db.transaction(async |db| {
+        let encrypted: &[bool] = if plaintext_only {
+            &[false]
+        } else {
+            &[false, true]
+        };
+        let credentials: Vec<(PlatformCredentialId, UserId, String, bool)> =
+            platform_credential::table
+                .filter(platform_credential::columns::password_encrypted.eq_any(encrypted))
+                .select((
+                    platform_credential::columns::id,
+                    platform_credential::columns::user_id,
+                    platform_credential::columns::password,
+                    platform_credential::columns::password_encrypted,
+                ))
+                .for_update()
+                .get_results(db)
+                .await?;
+
+        let mut updated = 0;
+        for (id, user_id, password, encrypted) in credentials {
+            if let Some(password) = keys.reencrypt(&password, encrypted, user_id) {
+                updated += diesel::update(platform_credential::table.find(id))
+                    .set((
+                        platform_credential::columns::password.eq(password),
+                        platform_credential::columns::password_encrypted.eq(true),
+                    ))
+                    .execute(db)
+                    .await?;
+            }
+        }
+
+        Ok(updated)
+    })
+    .await
+}
+
+fn encrypt(platform_credential: &mut PlatformCredential, keys: &CredentialKeys) {
+    platform_credential.password =
+        keys.encrypt(&platform_credential.password, platform_credential.user_id);
+}
+
+/// Update the credential and encrypt its password unless it is the stored encrypted password.
+async fn update_encrypted(
+    platform_credential: &mut PlatformCredential,
+    keys: &CredentialKeys,
+    db: &mut AsyncPgConnection,
+) -> QueryResult<usize> {
+    let (password, encrypted): (String, bool) = platform_credential::table
+        .find(platform_credential.id)
+        .select((
+            platform_credential::columns::password,
+            platform_credential::columns::password_encrypted,
+        ))
+        .for_update()
+        .get_result(db)
+        .await?;
+    if !encrypted || password != platform_credential.password {
+        encrypt(platform_credential, keys);
+    }
+
+    diesel::update(platform_credential::table.find(platform_credential.id))
+        .set((
+            &*platform_credential,
+            platform_credential::columns::password_encrypted.eq(true),
+        ))
+        .execute(db)
+        .await
+}
@@ -0,0 +1,163 @@
+use axum::http::StatusCode;
+use derive_deftly::Deftly;
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use sport_log_derive::*;
+use sport_log_types::{
+    ActionProviderId, ActionProviderScope, ActionProviderScopeId, ScopeAccess, ScopeEntity, UserId,
+    schema::{action_provider_scope, scope_grant},
+};
+
+use crate::{auth::AuthAP, db::*};
+
+#[derive(Db, DbWithApId, ModifiableDb, Deftly)]
+#[derive_deftly(
+    VerifyUncheckedGet,
+    VerifyForActionProviderGet,
+    Create,
+    GetById,
+    GetAll,
+    GetByEpoch,
+    GetPageByEpoch,
+    Update,
+    GetEpoch,
+    CheckAPId,
+    VerifyForActionProviderCreate
+)]
+pub struct ActionProviderScopeDb;
+
+impl ActionProviderScopeDb {
+    pub async fn get_by_action_provider(
+        action_provider_id: ActionProviderId,
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<Vec<<Self as Db>::Type>> {
+        action_provider_scope::table
+            .filter(action_provider_scope::columns::action_provider_id.eq(action_provider_id))
+            .select(ActionProviderScope::as_select())
+            .get_results(db)
+            .await
+    }
+
+    /// Check that the scopes belong to the action provider and that their entity and access are
+    /// unchanged.
+    ///
+    /// Users grant scopes for the entity and access, so changing them would extend the access of
+    /// the action provider without the consent of the users.
+    async fn check_unchanged(
+        action_provider_scopes: &[ActionProviderScope],
+        action_provider_id: ActionProviderId,
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<bool> {
+        let stored: Vec<(
+            ActionProviderScopeId,
+            ActionProviderId,
+            ScopeEntity,
+            ScopeAccess,
+        )> = action_provider_scope::table
+            .filter(
+                action_provider_scope::columns::id.eq_any(
+                    action_provider_scopes
+                        .iter()
+                        .map(|scope| scope.id)
+                        .collect::<Vec<_>>(),
+                ),
+            )
+            .select((
+                action_provider_scope::columns::id,
+                action_provider_scope::columns::action_provider_id,
+                action_provider_scope::columns::entity,
+                action_provider_scope::columns::access,
+            ))
+            .get_results(db)
+            .await?;
+
+        Ok(action_provider_scopes.iter().all(|scope| {
+            scope.action_provider_id == action_provider_id
+                && stored.contains(&(scope.id, action_provider_id, scope.entity, scope.access))
+        }))
+    }
+}
+
+/// Action providers can only mark their scopes as deleted.
+impl VerifyForActionProviderUpdate for Unverified<ActionProviderScope> {
+    type Type = ActionProviderScope;
+
+    async fn verify_ap_update(
+        self,
+        auth: AuthAP,
+        db: &mut AsyncPgConnection,
+    ) -> Result<Self::Type, StatusCode> {
+        let action_provider_scope = self.0;
+        if ActionProviderScopeDb::check_unchanged(
+            std::slice::from_ref(&action_provider_scope),
+            *auth,
+            db,
+        )
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        {
+            Ok(action_provider_scope)
+        } else {
+            Err(StatusCode::FORBIDDEN)
+        }
+    }
+}
+
+/// Action providers can only mark their scopes as deleted.
+impl VerifyMultipleForActionProviderUpdate for Unverified<Vec<ActionProviderScope>> {
+    type Type = ActionProviderScope;
+
+    async fn verify_ap_update(
+        self,
+        auth: AuthAP,
+        db: &mut AsyncPgConnection,
+    ) -> Result<Vec<Self::Type>, StatusCode> {
+        let action_provider_scopes = self.0;
+        if ActionProviderScopeDb::check_unchanged(&action_provider_scopes, *auth, db)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        {
+            Ok(action_provider_scopes)
+        } else {
+            Err(StatusCode::FORBIDDEN)
+        }
+    }
+}
+
+#[derive(Db, DbWithUserId, ModifiableDb, Deftly)]
+#[derive_deftly(
+    VerifyForUserGet,
+    Create,
+    GetById,
+    GetByUser,
+    GetByUserAndEpoch,
+    GetPageByUserAndEpoch,
+    Update,
+    GetEpochByUser,
+    CheckUserId,
+    VerifyForUserUpdate,
+    VerifyForUserCreate
+)]
+pub struct ScopeGrantDb;
+
+impl ScopeGrantDb {
+    /// Returns all scopes the user granted to the action provider.
+    pub async fn get_scopes(
+        action_provider_id: ActionProviderId,
+        user_id: UserId,
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<Vec<(ScopeEntity, ScopeAccess)>> {
+        scope_grant::table
+            .inner_join(action_provider_scope::table)
+            .filter(action_provider_scope::columns::action_provider_id.eq(action_provider_id))
+            .filter(action_provider_scope::columns::deleted.eq(false))
+            .filter(scope_grant::columns::user_id.eq(user_id))
+            .filter(scope_grant::columns::deleted.eq(false))
+            .select((
+                action_provider_scope::columns::entity,
+                action_provider_scope::columns::access,
+            ))
+            .get_results(db)
+            .await
+    }
+}
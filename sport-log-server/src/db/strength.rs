@@ -1,7 +1,21 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
 use derive_deftly::Deftly;
+use diesel::{
+    prelude::*,
+    sql_types::{BigInt, Float4, Integer, Nullable, Text, Timestamptz},
+};
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
 use sport_log_derive::*;
+use sport_log_types::{
+    MovementId, StatisticInterval, StrengthRecord, StrengthRecords, StrengthSessionId, StrengthSet,
+    StrengthSetId, StrengthStatistic, UserId, schema::strength_set,
+};
+
+use crate::db::*;
 
-#[derive(Db, DbWithUserId, DbWithDateTime, ModifiableDb, Deftly)]
+#[derive(Db, DbWithUserId, DbWithDateTime, DbWithScope, ModifiableDb, ArchivableDb, Deftly)]
 #[derive_deftly(
     VerifyForUserOrAPGet,
     Create,
@@ -9,7 +23,10 @@ use sport_log_derive::*;
     GetByUser,
     GetByUserTimespan,
     GetByUserAndEpoch,
+    GetPageByUserAndEpoch,
     Update,
+    Archive,
+    CheckedMultiple,
     GetEpochByUser,
     CheckUserId,
     VerifyForUserOrAPUpdate,
@@ -17,15 +34,18 @@ use sport_log_derive::*;
 )]
 pub struct StrengthSessionDb;
 
-#[derive(Db, DbWithUserId, ModifiableDb, Deftly)]
+#[derive(Db, DbWithUserId, DbWithScope, ModifiableDb, ArchivableDb, Deftly)]
 #[derive_deftly(
     VerifyForUserOrAPGet,
     Create,
     GetById,
     GetByUser,
     GetByUserAndEpoch,
+    GetPageByUserAndEpoch,
     GetEpochByUser,
     Update,
+    Archive,
+    CheckedMultiple,
     CheckUserId,
     VerifyForUserOrAPUpdate,
     VerifyForUserOrAPCreate
@@ -35,3 +55,189 @@ pub struct StrengthSetDb;
 #[derive(Db, Deftly)]
 #[derive_deftly(VerifyForAdminGet, GetById, GetAll)]
 pub struct EormDb;
+
+const STRENGTH_STATISTIC_QUERY: &str = "
+    select
+        strength_session.movement_id,
+        date_trunc($2, strength_session.datetime, 'UTC') as bucket,
+        count(distinct strength_session.id) as session_count,
+        coalesce(sum(strength_set.count), 0) as count,
+        coalesce(sum(strength_set.count * strength_set.weight), 0) as volume
+    from strength_session
+        left join strength_set on strength_set.strength_session_id = strength_session.id
+            and not strength_set.deleted
+    where strength_session.user_id = $1
+        and not strength_session.deleted
+        and ($3::bigint is null or strength_session.movement_id = $3)
+        and ($4::timestamptz is null or strength_session.datetime >= $4)
+        and ($5::timestamptz is null or strength_session.datetime <= $5)
+    group by strength_session.movement_id, bucket
+    order by bucket, strength_session.movement_id";
+
+impl StrengthSessionDb {
+    /// Get the strength sessions of a user aggregated per movement and time bucket.
+    pub async fn get_statistics_by_user(
+        user_id: UserId,
+        interval: StatisticInterval,
+        movement_id: Option<MovementId>,
+        timespan: Timespan,
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<Vec<StrengthStatistic>> {
+        let (start, end) = timespan.bounds();
+        diesel::sql_query(STRENGTH_STATISTIC_QUERY)
+            .bind::<BigInt, _>(user_id)
+            .bind::<Text, _>(interval.as_str())
+            .bind::<Nullable<BigInt>, _>(movement_id)
+            .bind::<Nullable<Timestamptz>, _>(start)
+            .bind::<Nullable<Timestamptz>, _>(end)
+            .load(db)
+            .await
+    }
+}
+
+/// Selects the records of all kinds, one row per record.
+///
+/// Ties are resolved in favor of the earliest session.
+const STRENGTH_RECORD_QUERY: &str = "
+    with sets as (
+        select
+            strength_session.movement_id,
+            strength_session.id as strength_session_id,
+            strength_set.id as strength_set_id,
+            strength_session.datetime,
+            strength_set.count,
+            strength_set.weight
+        from strength_set
+            join strength_session on strength_session.id = strength_set.strength_session_id
+        where strength_set.user_id = $1
+            and not strength_set.deleted
+            and not strength_session.deleted
+            and ($2::bigint is null or strength_session.movement_id = $2)
+    )
+    (
+        select distinct on (movement_id, count)
+            'max_weight' as record, movement_id, count, weight,
+            strength_session_id, strength_set_id, datetime
+        from sets
+        where weight is not null
+        order by movement_id, count, weight desc, datetime
+    )
+    union all
+    (
+        select distinct on (sets.movement_id)
+            'max_eorm', sets.movement_id, sets.count, (sets.weight / eorm.percentage)::real,
+            sets.strength_session_id, sets.strength_set_id, sets.datetime
+        from sets
+            join eorm on eorm.reps = sets.count
+            join movement on movement.id = sets.movement_id
+        where sets.weight is not null and movement.movement_dimension = 'reps'
+        order by sets.movement_id, sets.weight / eorm.percentage desc, sets.datetime
+    )
+    union all
+    (
+        select distinct on (movement_id)
+            'max_volume', movement_id, sum(count)::integer, sum(count * weight)::real,
+            strength_session_id, null, datetime
+        from sets
+        where weight is not null
+        group by movement_id, strength_session_id, datetime
+        order by movement_id, sum(count * weight) desc, datetime
+    )
+    union all
+    (
+        select distinct on (movement_id)
+            'max_count', movement_id, count, weight,
+            strength_session_id, strength_set_id, datetime
+        from sets
+        order by movement_id, count desc, weight desc nulls last, datetime
+    )";
+
+#[derive(QueryableByName)]
+struct StrengthRecordRow {
+    #[diesel(sql_type = Text)]
+    record: String,
+    #[diesel(sql_type = BigInt)]
+    movement_id: MovementId,
+    #[diesel(sql_type = Integer)]
+    count: i32,
+    #[diesel(sql_type = Nullable<Float4>)]
+    weight: Option<f32>,
+    #[diesel(sql_type = BigInt)]
+    strength_session_id: StrengthSessionId,
+    #[diesel(sql_type = Nullable<BigInt>)]
+    strength_set_id: Option<StrengthSetId>,
+    #[diesel(sql_type = Timestamptz)]
+    datetime: DateTime<Utc>,
+}
+
+impl StrengthSetDb {
+    /// Get the personal records of a user for every movement the user has trained or only for
+    /// `movement_id`.
+    pub async fn get_records_by_user(
+        user_id: UserId,
+        movement_id: Option<MovementId>,
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<Vec<StrengthRecords>> {
+        let rows: Vec<StrengthRecordRow> = diesel::sql_query(STRENGTH_RECORD_QUERY)
+            .bind::<BigInt, _>(user_id)
+            .bind::<Nullable<BigInt>, _>(movement_id)
+            .load(db)
+            .await?;
+
+        let mut records = BTreeMap::new();
+        for row in rows {
+            let movement_records =
+                records
+                    .entry(row.movement_id)
+                    .or_insert_with(|| StrengthRecords {
+                        movement_id: row.movement_id,
+                        max_weight: vec![],
+                        max_eorm: None,
+                        max_volume: None,
+                        max_count: None,
+                    });
+            let record = StrengthRecord {
+                count: row.count,
+                weight: row.weight,
+                strength_session_id: row.strength_session_id,
+                strength_set_id: row.strength_set_id,
+                datetime: row.datetime,
+            };
+            match row.record.as_str() {
+                "max_weight" => movement_records.max_weight.push(record),
+                "max_eorm" => movement_records.max_eorm = Some(record),
+                "max_volume" => movement_records.max_volume = Some(record),
+                _ => movement_records.max_count = Some(record),
+            }
+        }
+
+        Ok(records
+            .into_values()
+            .map(|mut movement_records| {
+                movement_records
+                    .max_weight
+                    .sort_by_key(|record| record.count);
+                movement_records
+            })
+            .collect())
+    }
+}
+
+impl GetByUniqueKey for StrengthSessionDb {}
+
+impl GetByUniqueKey for StrengthSetDb {
+    async fn get_by_unique_key(
+        value: &StrengthSet,
+        db: &mut AsyncPgConnection,
+    ) -> QueryResult<Option<StrengthSet>> {
+        strength_set::table
+            .filter(strength_set::columns::user_id.eq(value.user_id))
+            .filter(strength_set::columns::strength_session_id.eq(value.strength_session_id))
+            .filter(strength_set::columns::set_number.eq(value.set_number))
+            .filter(strength_set::columns::deleted.eq(false))
+            .select(StrengthSet::as_select())
+            .get_result(db)
+            .await
+            .optional()
+    }
+}
@@ -13,22 +13,16 @@ use hyper::{
     header::{AUTHORIZATION, WWW_AUTHENTICATE},
 };
 use serde::{Deserialize, Serialize, ser::SerializeStruct};
+pub use sport_log_types::ErrorMessage;
+use sport_log_types::{Epoch, TableConflict};
 use tracing::{info, warn};
 
-#[derive(Serialize, Deserialize, Debug)]
-#[serde(rename_all = "snake_case")]
-pub enum ErrorMessage {
-    PrimaryKeyViolation { table: String },
-    ForeignKeyViolation { table: String, column: String },
-    UniqueViolation { table: String, columns: Vec<String> },
-    Other { error: String },
-}
-
 #[derive(Debug)]
 pub struct HandlerError {
     status: StatusCode,
     message: Option<ErrorMessage>,
-    headers: Option<HeaderMap>,
+    // boxed to keep results with a `HandlerError` small
+    headers: Option<Box<HeaderMap>>,
 }
 
 impl Serialize for HandlerError {
@@ -45,6 +39,82 @@ impl Serialize for HandlerError {
 
 pub type HandlerResult<T> = Result<T, HandlerError>;
 
+/// Create the conflict of a single table of a request that affects multiple tables.
+///
+/// Returns the error unchanged if it is not caused by a conflict with the existing data.
+pub fn table_conflict(table: &str, error: DieselError) -> Result<TableConflict, DieselError> {
+    let (status, message) = conflict_from_error(error)?;
+    Ok(TableConflict {
+        table: table.to_owned(),
+        status,
+        message,
+    })
+}
+
+/// The result of a single entry of a request that creates or updates multiple entries.
+///
+/// If the entry conflicts with an existing entry, `existing` contains the existing entry.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RowResult<I, T> {
+    pub id: I,
+    pub status: u16,
+    pub message: Option<ErrorMessage>,
+    pub existing: Option<T>,
+}
+
+impl<I, T> RowResult<I, T> {
+    pub fn ok(id: I) -> Self {
+        Self {
+            id,
+            status: StatusCode::OK.as_u16(),
+            message: None,
+            existing: None,
+        }
+    }
+
+    /// Returns the error unchanged if it is not caused by a conflict with the existing data.
+    pub fn from_error(id: I, error: DieselError, existing: Option<T>) -> Result<Self, DieselError> {
+        let (status, message) = conflict_from_error(error)?;
+        Ok(Self {
+            id,
+            status,
+            message,
+            existing,
+        })
+    }
+
+    /// The entry has not been applied because it has been changed since the client has last seen
+    /// it.
+    pub fn changed(id: I, epoch: Epoch, existing: T) -> Self {
+        Self {
+            id,
+            status: StatusCode::CONFLICT.as_u16(),
+            message: Some(ErrorMessage::EpochConflict { epoch }),
+            existing: Some(existing),
+        }
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.status == StatusCode::OK.as_u16()
+    }
+}
+
+fn conflict_from_error(error: DieselError) -> Result<(u16, Option<ErrorMessage>), DieselError> {
+    match error {
+        DieselError::NotFound
+        | DieselError::DatabaseError(
+            DatabaseErrorKind::UniqueViolation | DatabaseErrorKind::ForeignKeyViolation,
+            _,
+        ) => {
+            let HandlerError {
+                status, message, ..
+            } = error.into();
+            Ok((status.as_u16(), message))
+        }
+        error => Err(error),
+    }
+}
+
 impl From<StatusCode> for HandlerError {
     fn from(status: StatusCode) -> Self {
         HandlerError {
@@ -74,9 +144,11 @@ impl From<TypedHeaderRejection> for HandlerError {
                     error: format!("header {} missing", rejection.name()),
                 }),
                 headers: (rejection.name() == AUTHORIZATION).then(|| {
-                    [(WWW_AUTHENTICATE, HeaderValue::from_static("Basic"))]
-                        .into_iter()
-                        .collect()
+                    Box::new(
+                        [(WWW_AUTHENTICATE, HeaderValue::from_static("Basic"))]
+                            .into_iter()
+                            .collect(),
+                    )
                 }),
             },
             TypedHeaderRejectionReason::Error(error) => HandlerError {
@@ -190,7 +262,7 @@ impl IntoResponse for HandlerError {
             info!("{message:?}");
         }
         match &self.headers {
-            Some(header) => (self.status, header.to_owned(), Json(self)).into_response(),
+            Some(header) => (self.status, *header.to_owned(), Json(self)).into_response(),
             _ => (self.status, Json(self)).into_response(),
         }
     }
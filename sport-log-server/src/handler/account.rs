@@ -1,17 +1,193 @@
-use axum::Json;
-use sport_log_types::{AccountData, EpochMap};
+use axum::{
+    Json,
+    extract::{Query, State},
+    http::StatusCode,
+    response::sse::{Event, KeepAlive, Sse},
+};
+use futures_util::{Stream, stream};
+use serde::Deserialize;
+use sport_log_types::{
+    AccountData, AccountDataChanges, AccountDataChangesResponse, AccountDataCursor,
+    AccountDataPage, AccountDataSync, EpochMap, TableConflict,
+};
+use tokio::sync::broadcast::error::RecvError;
 
-use crate::{auth::AuthUser, db::AccountDataDb, error::HandlerResult, state::DbConn};
+use crate::{
+    auth::AuthUser,
+    crypto::CredentialKeys,
+    db::{AccountDataDb, ArchiveDb, Unverified},
+    error::{ErrorMessage, HandlerError, HandlerResult},
+    notify::EpochChanges,
+    segment,
+    state::DbConn,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct OmitTracksOption {
+    /// If set, the summaries of routes and cardio sessions are returned instead of the full
+    /// entries.
+    ///
+    /// The series can be retrieved separately for every route and cardio session.
+    #[serde(default)]
+    pub omit_tracks: bool,
+}
 
 pub async fn get_account_data(
     auth: AuthUser,
+    Query(OmitTracksOption { omit_tracks }): Query<OmitTracksOption>,
     mut db: DbConn,
     Json(epoch_map): Json<Option<EpochMap>>,
 ) -> HandlerResult<Json<AccountData>> {
     match epoch_map {
-        Some(epoch) => AccountDataDb::get_by_user_and_epoch(*auth, epoch, &mut db).await,
-        None => AccountDataDb::get_by_user(*auth, &mut db).await,
+        Some(epoch) => {
+            check_purged(auth, &epoch, &mut db).await?;
+            AccountDataDb::get_by_user_and_epoch(*auth, epoch, omit_tracks, &mut db).await
+        }
+        None => AccountDataDb::get_by_user(*auth, omit_tracks, &mut db).await,
     }
     .map(Json)
     .map_err(Into::into)
 }
+
+/// The number of entries of an [`AccountDataPage`] if no page size is requested.
+const DEFAULT_PAGE_SIZE: i64 = 1000;
+const MAX_PAGE_SIZE: i64 = 10000;
+
+#[derive(Debug, Deserialize)]
+pub struct AccountDataPageOption {
+    #[serde(default)]
+    pub cursor: Option<AccountDataCursor>,
+    #[serde(default)]
+    pub page_size: Option<i64>,
+}
+
+pub async fn get_account_data_page(
+    auth: AuthUser,
+    Query(AccountDataPageOption { cursor, page_size }): Query<AccountDataPageOption>,
+    mut db: DbConn,
+    Json(epoch_map): Json<Option<EpochMap>>,
+) -> HandlerResult<Json<AccountDataPage>> {
+    let page_size = page_size
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+    let epoch_map = epoch_map.unwrap_or_default();
+    check_purged(auth, &epoch_map, &mut db).await?;
+    AccountDataDb::get_page_by_user_and_epoch(*auth, epoch_map, cursor, page_size, &mut db)
+        .await
+        .map(Json)
+        .map_err(Into::into)
+}
+
+/// Reject the synchronization if deleted entries the client has not seen yet have been purged from
+/// the archive since the client would keep these entries forever.
+async fn check_purged(auth: AuthUser, epoch_map: &EpochMap, db: &mut DbConn) -> HandlerResult<()> {
+    match ArchiveDb::get_purged_table(*auth, epoch_map, db).await? {
+        Some(table) => Err(HandlerError::from((
+            StatusCode::GONE,
+            ErrorMessage::InitSyncRequired { table },
+        ))),
+        None => Ok(()),
+    }
+}
+
+pub async fn create_account_data(
+    auth: AuthUser,
+    State(keys): State<&'static CredentialKeys>,
+    mut db: DbConn,
+    Json(changes): Json<Unverified<AccountDataChanges>>,
+) -> HandlerResult<(StatusCode, Json<AccountDataChangesResponse>)> {
+    let mut changes = changes.verify_user_create(auth)?;
+    let conflicts = AccountDataDb::create(&mut changes, keys, &mut db).await?;
+    if conflicts.is_empty() {
+        detect_segment_efforts(auth, &changes, &mut db).await?;
+    }
+    changes_response(auth, conflicts, &mut db).await
+}
+
+pub async fn update_account_data(
+    auth: AuthUser,
+    State(keys): State<&'static CredentialKeys>,
+    mut db: DbConn,
+    Json(changes): Json<Unverified<AccountDataChanges>>,
+) -> HandlerResult<(StatusCode, Json<AccountDataChangesResponse>)> {
+    let mut changes = changes.verify_user_update(auth, &mut db).await?;
+    let conflicts = AccountDataDb::update(&mut changes, keys, &mut db).await?;
+    if conflicts.is_empty() {
+        detect_segment_efforts(auth, &changes, &mut db).await?;
+    }
+    changes_response(auth, conflicts, &mut db).await
+}
+
+/// Create and update the entries of all tables in a single transaction.
+pub async fn sync_account_data(
+    auth: AuthUser,
+    State(keys): State<&'static CredentialKeys>,
+    mut db: DbConn,
+    Json(sync): Json<Unverified<AccountDataSync>>,
+) -> HandlerResult<(StatusCode, Json<AccountDataChangesResponse>)> {
+    let AccountDataSync {
+        mut created,
+        mut updated,
+    } = sync.verify_user_sync(auth, &mut db).await?;
+    let conflicts = AccountDataDb::sync(&mut created, &mut updated, keys, &mut db).await?;
+    if conflicts.is_empty() {
+        detect_segment_efforts(auth, &created, &mut db).await?;
+        detect_segment_efforts(auth, &updated, &mut db).await?;
+    }
+    changes_response(auth, conflicts, &mut db).await
+}
+
+/// Detect the segment efforts of the changed cardio sessions and segments.
+async fn detect_segment_efforts(
+    auth: AuthUser,
+    changes: &AccountDataChanges,
+    db: &mut DbConn,
+) -> HandlerResult<()> {
+    let cardio_session_ids: Vec<_> = changes.cardio_sessions.iter().map(|c| c.id).collect();
+    segment::detect_for_cardio_sessions(*auth, &cardio_session_ids, db).await?;
+    let segment_ids: Vec<_> = changes.segments.iter().map(|s| s.id).collect();
+    segment::detect_for_segments(*auth, &segment_ids, db).await?;
+    Ok(())
+}
+
+async fn changes_response(
+    auth: AuthUser,
+    conflicts: Vec<TableConflict>,
+    db: &mut DbConn,
+) -> HandlerResult<(StatusCode, Json<AccountDataChangesResponse>)> {
+    let status = if conflicts.is_empty() {
+        StatusCode::OK
+    } else {
+        StatusCode::CONFLICT
+    };
+    let epoch_map = AccountDataDb::get_epoch_map_by_user(*auth, db).await?;
+    Ok((
+        status,
+        Json(AccountDataChangesResponse {
+            epoch_map,
+            conflicts,
+        }),
+    ))
+}
+
+/// Stream the new epochs of all changed tables as server-sent events.
+///
+/// Every `epoch` event contains an [`EpochMapDelta`](sport_log_types::EpochMapDelta).
+/// If changes have been lost because the client did not keep up, a `sync` event without data is
+/// sent instead and the client has to sync all tables.
+pub async fn get_account_data_events(
+    auth: AuthUser,
+    State(epoch_changes): State<EpochChanges>,
+) -> Sse<impl Stream<Item = Result<Event, axum::Error>>> {
+    let subscription = epoch_changes.subscribe(*auth);
+    let events = stream::unfold(subscription, async |mut subscription| {
+        let event = match subscription.next().await {
+            Ok(delta) => Event::default().event("epoch").json_data(delta),
+            Err(RecvError::Lagged(_)) => Ok(Event::default().event("sync")),
+            Err(RecvError::Closed) => return None,
+        };
+        Some((event, subscription))
+    });
+
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
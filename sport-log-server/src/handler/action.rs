@@ -11,6 +11,7 @@ use sport_log_types::{
 use crate::{
     auth::*,
     config::Config,
+    crypto::CredentialKeys,
     db::*,
     handler::{
         ErrorMessage, HandlerError, HandlerResult, IdOption, TimeSpanOption, UnverifiedSingleOrVec,
@@ -367,16 +368,17 @@ pub async fn adm_get_creatable_action_rules(
 pub async fn ap_get_executable_action_events(
     auth: AuthAP,
     Query(TimeSpanOption { start, end }): Query<TimeSpanOption>,
+    State(keys): State<&CredentialKeys>,
     mut db: DbConn,
 ) -> HandlerResult<Json<Vec<ExecutableActionEvent>>> {
     match (start, end) {
         (Some(start), Some(end)) => {
             ExecutableActionEventDb::get_ordered_by_action_provider_and_timespan(
-                *auth, start, end, &mut db,
+                *auth, start, end, keys, &mut db,
             )
             .await
         }
-        (None, None) => ExecutableActionEventDb::get_by_action_provider(*auth, &mut db).await,
+        (None, None) => ExecutableActionEventDb::get_by_action_provider(*auth, keys, &mut db).await,
         _ => {
             return Err(HandlerError::from((
                 StatusCode::BAD_REQUEST,
@@ -0,0 +1,68 @@
+use axum::{
+    Json,
+    extract::{Path, State},
+};
+use sport_log_types::{Archived, PurgedArchive};
+use tracing::warn;
+
+use crate::{
+    Config,
+    auth::{AuthAdmin, AuthUser},
+    db::*,
+    handler::HandlerResult,
+    state::DbConn,
+};
+
+pub async fn get_archived<D: Archive>(
+    auth: AuthUser,
+    mut db: DbConn,
+) -> HandlerResult<Json<Vec<Archived<D::Type>>>> {
+    D::get_archived_by_user(*auth, &mut db)
+        .await
+        .map(Json)
+        .map_err(Into::into)
+}
+
+/// Restore an archived entry together with the entries that have been deleted with it.
+///
+/// Returns the restored entry.
+pub async fn restore_archived<D: Archive>(
+    auth: AuthUser,
+    Path(id): Path<D::Id>,
+    mut db: DbConn,
+) -> HandlerResult<Json<D::Type>> {
+    D::restore(id, *auth, &mut db)
+        .await
+        .map(Json)
+        .map_err(Into::into)
+}
+
+/// Permanently delete the archived entries that are older than the configured retention.
+///
+/// Returns the number of deleted entries of every table with a retention.
+/// The segment efforts of segments and cardio sessions that have been deleted are deleted as well.
+pub async fn adm_purge_archive(
+    _auth: AuthAdmin,
+    State(config): State<&Config>,
+    mut db: DbConn,
+) -> HandlerResult<Json<Vec<PurgedArchive>>> {
+    let retention = &config.archive_retention;
+    for table in retention.tables.keys() {
+        if !ARCHIVED_TABLES.contains(&table.as_str()) {
+            warn!("archive retention configured for unknown table {table}");
+        }
+    }
+
+    let mut purged = vec![];
+    for table in ARCHIVED_TABLES {
+        if let Some(days) = retention.days(table) {
+            let count = ArchiveDb::purge(table, days, &mut db).await?;
+            purged.push(PurgedArchive {
+                table: table.to_owned(),
+                count,
+            });
+        }
+    }
+    SegmentEffortDb::delete_orphaned(&mut db).await?;
+    Ok(Json(purged))
+}
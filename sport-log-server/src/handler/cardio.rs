@@ -1,18 +1,143 @@
-use axum::{Json, extract::Query};
-use sport_log_types::{CardioSession, CardioSessionId, EpochResponse, Route, RouteId};
+use std::collections::BTreeMap;
+
+use axum::{
+    Json,
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{HeaderMap, HeaderName, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use chrono::{Days, NaiveTime, Utc};
+use diesel::QueryResult;
+use diesel_async::{AsyncConnection, AsyncPgConnection};
+use hyper::header::{CACHE_CONTROL, CONTENT_DISPOSITION, CONTENT_TYPE, ETAG};
+use serde::Deserialize;
+use sport_log_types::{
+    CardioBestEffort, CardioSession, CardioSessionId, CardioSessionOverview, CardioSessionSummary,
+    CardioSessionTrack, CardioSessionValidation, CardioSessionZones, CardioSplit, CardioStatistic,
+    CardioType, DistanceUnit, MovementId, Route, RouteHistory, RouteId, RouteMatch, RouteOverview,
+    RouteSummary, RouteTrack, ScopeAccess, Segment, SegmentEffort, SegmentId, TrainingImpulse,
+    TrainingLoad, TrainingZone, TrainingZoneId, UserId, ZoneStatistic,
+};
 
 use crate::{
-    auth::AuthUserOrAP,
+    auth::{AuthAdmin, AuthUserOrAP},
+    cardio_summary,
     db::*,
-    handler::{HandlerResult, IdOption, TimeSpanOption, UnverifiedSingleOrVec},
+    error::{ErrorMessage, HandlerError, RowResult},
+    handler::{
+        BulkResponse, HandlerResult, IdOption, LastEpochOption, MovementIdOption, RowResultsOption,
+        StatisticIntervalOption, TimeSpanOption, UnverifiedSingleOrVec,
+    },
+    heatmap::{self, HeatmapCache, HeatmapKey, HeatmapOption, Tile},
+    route_match::{self, Routes},
+    segment,
+    simplify::{self, SimplifyOption},
+    spatial::{SpatialFilter, SpatialOption},
+    split,
     state::DbConn,
+    track_file::{self, TrackFileFormat},
+    training_load::{self, CHRONIC_DAYS, HeartRateProfile},
+    training_zone::TrainingZones,
 };
 
+#[derive(Debug, Deserialize)]
+pub struct CardioSessionImportOption {
+    pub movement_id: UnverifiedId<MovementId>,
+    pub cardio_type: CardioType,
+    /// The format of the uploaded file.
+    ///
+    /// If not set, the format is detected from the content of the file.
+    #[serde(default)]
+    pub format: Option<TrackFileFormat>,
+}
+
+/// The number of cardio sessions that are normalized at once.
+const NORMALIZE_BATCH_SIZE: i64 = 100;
+
+#[derive(Debug, Deserialize)]
+pub struct NormalizeOption {
+    /// If set, the `distance`, `ascent`, `descent` and `time` of cardio sessions with a track are
+    /// recomputed from the track and tracks which are physically impossible are rejected.
+    #[serde(default)]
+    pub normalize: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MatchRouteOption {
+    /// If set, cardio sessions with a track but without a route are assigned the route of the
+    /// user which matches their track best.
+    #[serde(default)]
+    pub match_route: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SegmentIdOption {
+    #[serde(default)]
+    pub segment_id: Option<SegmentId>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DryRunOption {
+    /// If set, the changes are only reported but not applied.
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// The minimal distance of a split in meters.
+const MIN_SPLIT_DISTANCE: f64 = 10.;
+
+#[derive(Debug, Deserialize)]
+pub struct SplitOption {
+    /// The distance of a split in `unit`. Defaults to 1.
+    #[serde(default)]
+    pub distance: Option<f64>,
+    /// Defaults to kilometers.
+    #[serde(default)]
+    pub unit: Option<DistanceUnit>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExportOption {
+    /// The format of the exported file. Defaults to GPX.
+    #[serde(default)]
+    pub format: Option<TrackFileFormat>,
+}
+
+fn export_headers(name: &str, format: TrackFileFormat) -> [(HeaderName, HeaderValue); 2] {
+    let name: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    [
+        (
+            CONTENT_TYPE,
+            HeaderValue::from_static(format.content_type()),
+        ),
+        (
+            CONTENT_DISPOSITION,
+            HeaderValue::from_str(&format!(
+                r#"attachment; filename="{name}.{}""#,
+                format.extension()
+            ))
+            .unwrap(),
+        ),
+    ]
+}
+
 pub async fn create_routes(
     auth: AuthUserOrAP,
+    Query(RowResultsOption { row_results }): Query<RowResultsOption>,
     mut db: DbConn,
     Json(routes): Json<UnverifiedSingleOrVec<Route>>,
-) -> HandlerResult<Json<EpochResponse>> {
+) -> HandlerResult<BulkResponse<RouteId, Route>> {
+    let mut rows = None;
     match routes {
         UnverifiedSingleOrVec::Single(route) => {
             let route = route.verify_user_ap_create(auth)?;
@@ -20,11 +145,15 @@ pub async fn create_routes(
         }
         UnverifiedSingleOrVec::Vec(routes) => {
             let routes = routes.verify_user_ap_create(auth)?;
-            RouteDb::create_multiple(&routes, &mut db).await?;
+            if row_results {
+                rows = Some(RouteDb::create_multiple_checked(&routes, &mut db).await?);
+            } else {
+                RouteDb::create_multiple(&routes, &mut db).await?;
+            }
         }
     }
     let epoch = RouteDb::get_epoch_by_user(*auth, &mut db).await?;
-    Ok(Json(EpochResponse { epoch }))
+    Ok(BulkResponse { epoch, rows })
 }
 
 pub async fn get_routes(
@@ -32,6 +161,8 @@ pub async fn get_routes(
     Query(IdOption { id }): Query<IdOption<UnverifiedId<RouteId>>>,
     mut db: DbConn,
 ) -> HandlerResult<Json<Vec<Route>>> {
+    auth.check_scope::<RouteDb>(ScopeAccess::Read)?;
+
     match id {
         Some(id) => {
             let route_id = id.verify_user_ap_get(auth, &mut db).await?;
@@ -43,42 +174,175 @@ pub async fn get_routes(
     .map_err(Into::into)
 }
 
+pub async fn get_route_summaries(
+    auth: AuthUserOrAP,
+    Query(IdOption { id }): Query<IdOption<UnverifiedId<RouteId>>>,
+    mut db: DbConn,
+) -> HandlerResult<Json<Vec<RouteSummary>>> {
+    auth.check_scope::<RouteDb>(ScopeAccess::Read)?;
+
+    match id {
+        Some(id) => {
+            let route_id = id.verify_user_ap_get(auth, &mut db).await?;
+            RouteDb::get_summary_by_id(route_id, &mut db)
+                .await
+                .map(|r| vec![r])
+        }
+        None => RouteDb::get_summaries_by_user(*auth, &mut db).await,
+    }
+    .map(Json)
+    .map_err(Into::into)
+}
+
+/// Get the track and marked positions of a route.
+pub async fn get_route_track(
+    auth: AuthUserOrAP,
+    Path(id): Path<UnverifiedId<RouteId>>,
+    mut db: DbConn,
+) -> HandlerResult<Json<RouteTrack>> {
+    auth.check_scope::<RouteDb>(ScopeAccess::Read)?;
+
+    let route_id = id.verify_user_ap_get(auth, &mut db).await?;
+    RouteDb::get_track_by_id(route_id, &mut db)
+        .await
+        .map(Json)
+        .map_err(Into::into)
+}
+
+/// Get the simplified tracks of routes ordered by name.
+///
+/// Routes without a track are omitted.
+pub async fn get_route_overviews(
+    auth: AuthUserOrAP,
+    Query(IdOption { id }): Query<IdOption<UnverifiedId<RouteId>>>,
+    mut db: DbConn,
+) -> HandlerResult<Json<Vec<RouteOverview>>> {
+    auth.check_scope::<RouteDb>(ScopeAccess::Read)?;
+
+    let route_id = match id {
+        Some(id) => Some(id.verify_user_ap_get(auth, &mut db).await?),
+        None => None,
+    };
+    simplify::route_overviews(*auth, route_id, &mut db)
+        .await
+        .map(Json)
+        .map_err(Into::into)
+}
+
+/// Get the routes whose track passes through an area.
+///
+/// The area is either a bounding box or a circle around a position.
+pub async fn get_spatial_route_summaries(
+    auth: AuthUserOrAP,
+    Query(spatial_option): Query<SpatialOption>,
+    mut db: DbConn,
+) -> HandlerResult<Json<Vec<RouteSummary>>> {
+    auth.check_scope::<RouteDb>(ScopeAccess::Read)?;
+
+    let filter = SpatialFilter::try_from(spatial_option)?;
+    RouteDb::get_summaries_by_user_and_spatial_filter(*auth, filter, &mut db)
+        .await
+        .map(Json)
+        .map_err(Into::into)
+}
+
+pub async fn export_route(
+    auth: AuthUserOrAP,
+    Path(id): Path<UnverifiedId<RouteId>>,
+    Query(ExportOption { format }): Query<ExportOption>,
+    mut db: DbConn,
+) -> HandlerResult<impl IntoResponse + use<>> {
+    auth.check_scope::<RouteDb>(ScopeAccess::Read)?;
+
+    let route_id = id.verify_user_ap_get(auth, &mut db).await?;
+    let route = RouteDb::get_by_id(route_id, &mut db).await?;
+    let format = format.unwrap_or(TrackFileFormat::Gpx);
+    let file = track_file::export_route(&route, format)?;
+
+    Ok((export_headers(&route.name, format), file))
+}
+
 pub async fn update_routes(
     auth: AuthUserOrAP,
+    Query(RowResultsOption { row_results }): Query<RowResultsOption>,
+    Query(LastEpochOption { last_epoch }): Query<LastEpochOption>,
     mut db: DbConn,
     Json(routes): Json<UnverifiedSingleOrVec<Route>>,
-) -> HandlerResult<Json<EpochResponse>> {
+) -> HandlerResult<BulkResponse<RouteId, Route>> {
+    let mut rows = None;
     match routes {
         UnverifiedSingleOrVec::Single(route) => {
             let route = route.verify_user_ap_update(auth, &mut db).await?;
-            RouteDb::update(&route, &mut db).await?;
+            if last_epoch.is_some() {
+                rows = Some(RouteDb::update_multiple_checked(&[route], last_epoch, &mut db).await?);
+            } else {
+                RouteDb::update(&route, &mut db).await?;
+            }
         }
         UnverifiedSingleOrVec::Vec(routes) => {
             let routes = routes.verify_user_ap_update(auth, &mut db).await?;
-            RouteDb::update_multiple(&routes, &mut db).await?;
+            if row_results || last_epoch.is_some() {
+                rows = Some(RouteDb::update_multiple_checked(&routes, last_epoch, &mut db).await?);
+            } else {
+                RouteDb::update_multiple(&routes, &mut db).await?;
+            }
         }
     }
     let epoch = RouteDb::get_epoch_by_user(*auth, &mut db).await?;
-    Ok(Json(EpochResponse { epoch }))
+    Ok(BulkResponse { epoch, rows })
 }
 
 pub async fn create_cardio_sessions(
     auth: AuthUserOrAP,
+    Query(RowResultsOption { row_results }): Query<RowResultsOption>,
+    Query(NormalizeOption { normalize }): Query<NormalizeOption>,
+    Query(MatchRouteOption { match_route }): Query<MatchRouteOption>,
     mut db: DbConn,
     Json(cardio_sessions): Json<UnverifiedSingleOrVec<CardioSession>>,
-) -> HandlerResult<Json<EpochResponse>> {
+) -> HandlerResult<BulkResponse<CardioSessionId, CardioSession>> {
+    let mut rows = None;
     match cardio_sessions {
         UnverifiedSingleOrVec::Single(cardio_session) => {
-            let cardio_session = cardio_session.verify_user_ap_create(auth)?;
-            CardioSessionDb::create(&cardio_session, &mut db).await?;
+            let mut cardio_session = cardio_session.verify_user_ap_create(auth)?;
+            if normalize {
+                cardio_summary::normalize(&mut cardio_session)?;
+            }
+            if match_route {
+                assign_routes(std::slice::from_mut(&mut cardio_session), *auth, &mut db).await?;
+            }
+            db.transaction(async |db| {
+                CardioSessionDb::create(&cardio_session, db).await?;
+                segment::detect_for_cardio_sessions(*auth, &[cardio_session.id], db).await
+            })
+            .await?;
         }
         UnverifiedSingleOrVec::Vec(cardio_sessions) => {
-            let cardio_sessions = cardio_sessions.verify_user_ap_create(auth)?;
-            CardioSessionDb::create_multiple(&cardio_sessions, &mut db).await?;
+            let mut cardio_sessions = cardio_sessions.verify_user_ap_create(auth)?;
+            if normalize {
+                cardio_sessions
+                    .iter_mut()
+                    .try_for_each(cardio_summary::normalize)?;
+            }
+            if match_route {
+                assign_routes(&mut cardio_sessions, *auth, &mut db).await?;
+            }
+            rows = db
+                .transaction(async |db| {
+                    let rows = if row_results {
+                        Some(CardioSessionDb::create_multiple_checked(&cardio_sessions, db).await?)
+                    } else {
+                        CardioSessionDb::create_multiple(&cardio_sessions, db).await?;
+                        None
+                    };
+                    detect_segment_efforts(*auth, &cardio_sessions, rows.as_deref(), db)
+                        .await
+                        .map(|()| rows)
+                })
+                .await?;
         }
     }
     let epoch = CardioSessionDb::get_epoch_by_user(*auth, &mut db).await?;
-    Ok(Json(EpochResponse { epoch }))
+    Ok(BulkResponse { epoch, rows })
 }
 
 pub async fn get_cardio_sessions(
@@ -87,6 +351,8 @@ pub async fn get_cardio_sessions(
     Query(time_span_option): Query<TimeSpanOption>,
     mut db: DbConn,
 ) -> HandlerResult<Json<Vec<CardioSession>>> {
+    auth.check_scope::<CardioSessionDb>(ScopeAccess::Read)?;
+
     match id {
         Some(id) => {
             let cardio_session_id = id.verify_user_ap_get(auth, &mut db).await?;
@@ -102,21 +368,837 @@ pub async fn get_cardio_sessions(
     .map_err(Into::into)
 }
 
+pub async fn get_cardio_session_summaries(
+    auth: AuthUserOrAP,
+    Query(IdOption { id }): Query<IdOption<UnverifiedId<CardioSessionId>>>,
+    Query(time_span_option): Query<TimeSpanOption>,
+    mut db: DbConn,
+) -> HandlerResult<Json<Vec<CardioSessionSummary>>> {
+    auth.check_scope::<CardioSessionDb>(ScopeAccess::Read)?;
+
+    match id {
+        Some(id) => {
+            let cardio_session_id = id.verify_user_ap_get(auth, &mut db).await?;
+            CardioSessionDb::get_summary_by_id(cardio_session_id, &mut db)
+                .await
+                .map(|c| vec![c])
+        }
+        None => {
+            CardioSessionDb::get_summaries_by_user_and_timespan(
+                *auth,
+                time_span_option.into(),
+                &mut db,
+            )
+            .await
+        }
+    }
+    .map(Json)
+    .map_err(Into::into)
+}
+
+/// Get the simplified tracks of cardio sessions ordered by datetime.
+///
+/// Cardio sessions without a track are omitted.
+pub async fn get_cardio_session_overviews(
+    auth: AuthUserOrAP,
+    Query(IdOption { id }): Query<IdOption<UnverifiedId<CardioSessionId>>>,
+    Query(time_span_option): Query<TimeSpanOption>,
+    mut db: DbConn,
+) -> HandlerResult<Json<Vec<CardioSessionOverview>>> {
+    auth.check_scope::<CardioSessionDb>(ScopeAccess::Read)?;
+
+    let cardio_session_id = match id {
+        Some(id) => Some(id.verify_user_ap_get(auth, &mut db).await?),
+        None => None,
+    };
+    simplify::cardio_session_overviews(*auth, cardio_session_id, time_span_option.into(), &mut db)
+        .await
+        .map(Json)
+        .map_err(Into::into)
+}
+
+/// Get the cardio sessions whose track passes through an area.
+///
+/// The area is either a bounding box or a circle around a position.
+pub async fn get_spatial_cardio_session_summaries(
+    auth: AuthUserOrAP,
+    Query(spatial_option): Query<SpatialOption>,
+    mut db: DbConn,
+) -> HandlerResult<Json<Vec<CardioSessionSummary>>> {
+    auth.check_scope::<CardioSessionDb>(ScopeAccess::Read)?;
+
+    let filter = SpatialFilter::try_from(spatial_option)?;
+    CardioSessionDb::get_summaries_by_user_and_spatial_filter(*auth, filter, &mut db)
+        .await
+        .map(Json)
+        .map_err(Into::into)
+}
+
+/// Get a heatmap tile of the tracks of the cardio sessions of the user as PNG image.
+///
+/// The tile is identified by its zoom level `z`, column `x` and row `y`.
+/// The `ETag` of the tile changes with the cardio sessions of the user.
+pub async fn get_heatmap_tile(
+    auth: AuthUserOrAP,
+    Path((z, x, y)): Path<(u32, u32, u32)>,
+    Query(heatmap_option): Query<HeatmapOption>,
+    State(heatmap_cache): State<HeatmapCache>,
+    headers: HeaderMap,
+    mut db: DbConn,
+) -> HandlerResult<Response> {
+    auth.check_scope::<CardioSessionDb>(ScopeAccess::Read)?;
+
+    let tile = Tile::new(z, x, y)?;
+    let epoch = CardioSessionDb::get_epoch_by_user(*auth, &mut db).await?;
+    let key = HeatmapKey {
+        user_id: *auth,
+        tile,
+        option: heatmap_option,
+    };
+    let etag = key.etag(epoch);
+    if heatmap::if_none_match(&headers, &etag) {
+        return Ok((StatusCode::NOT_MODIFIED, [(ETAG, etag)]).into_response());
+    }
+
+    let image = if let Some(image) = heatmap_cache.get(&key, epoch) {
+        image
+    } else {
+        let tracks = CardioSessionDb::get_tracks_by_user_and_heatmap_option(
+            *auth,
+            &key.option,
+            tile.bounds(),
+            &mut db,
+        )
+        .await?;
+        let image = tokio::task::spawn_blocking(move || heatmap::render(tile, &tracks))
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        heatmap_cache.insert(key, epoch, image.clone());
+        image
+    };
+
+    Ok((
+        [
+            (CONTENT_TYPE, "image/png".to_owned()),
+            (CACHE_CONTROL, "private, no-cache".to_owned()),
+            (ETAG, etag),
+        ],
+        image,
+    )
+        .into_response())
+}
+
+/// Get the cardio sessions of the user aggregated in time buckets.
+pub async fn get_cardio_statistics(
+    auth: AuthUserOrAP,
+    Query(StatisticIntervalOption { interval }): Query<StatisticIntervalOption>,
+    Query(MovementIdOption { movement_id }): Query<MovementIdOption>,
+    Query(time_span_option): Query<TimeSpanOption>,
+    mut db: DbConn,
+) -> HandlerResult<Json<Vec<CardioStatistic>>> {
+    auth.check_scope::<CardioSessionDb>(ScopeAccess::Read)?;
+
+    CardioSessionDb::get_statistics_by_user(
+        *auth,
+        interval,
+        movement_id,
+        time_span_option.into(),
+        &mut db,
+    )
+    .await
+    .map(Json)
+    .map_err(Into::into)
+}
+
+async fn get_heart_rate_profile(
+    user_id: UserId,
+    db: &mut DbConn,
+) -> HandlerResult<HeartRateProfile> {
+    let user = UserDb::get_by_id(user_id, db).await?;
+    HeartRateProfile::of(&user).ok_or_else(|| {
+        HandlerError::from((
+            StatusCode::BAD_REQUEST,
+            ErrorMessage::Other {
+                error: "The max and resting heart rate of the user must be set.".to_owned(),
+            },
+        ))
+    })
+}
+
+/// Get the training impulse of every cardio session with heart rate data.
+pub async fn get_training_impulses(
+    auth: AuthUserOrAP,
+    Query(time_span_option): Query<TimeSpanOption>,
+    mut db: DbConn,
+) -> HandlerResult<Json<Vec<TrainingImpulse>>> {
+    auth.check_scope::<CardioSessionDb>(ScopeAccess::Read)?;
+
+    let heart_rate_profile = get_heart_rate_profile(*auth, &mut db).await?;
+    let sessions = CardioSessionDb::get_heart_rates_by_user_and_timespan(
+        *auth,
+        time_span_option.into(),
+        &mut db,
+    )
+    .await?;
+    Ok(Json(heart_rate_profile.training_impulses(&sessions)))
+}
+
+/// Get the daily training load from `start` to `end`.
+///
+/// `end` defaults to now and `start` to the start of the chronic window before `end`.
+/// The timespan must not exceed [`training_load::MAX_DAYS`] days.
+pub async fn get_training_load(
+    auth: AuthUserOrAP,
+    Query(TimeSpanOption { start, end }): Query<TimeSpanOption>,
+    mut db: DbConn,
+) -> HandlerResult<Json<Vec<TrainingLoad>>> {
+    auth.check_scope::<CardioSessionDb>(ScopeAccess::Read)?;
+
+    let end = end.unwrap_or_else(Utc::now);
+    let start = start.unwrap_or(end - Days::new(CHRONIC_DAYS - 1));
+    let first = start.date_naive();
+    let last = end.date_naive();
+    if last.signed_duration_since(first).num_days() >= training_load::MAX_DAYS as i64 {
+        return Err(HandlerError::from((
+            StatusCode::BAD_REQUEST,
+            ErrorMessage::Other {
+                error: format!(
+                    "The training load can be requested for at most {} days.",
+                    training_load::MAX_DAYS
+                ),
+            },
+        )));
+    }
+
+    let heart_rate_profile = get_heart_rate_profile(*auth, &mut db).await?;
+    // the loads of the first days depend on the sessions of the preceding chronic window
+    let sessions_start = (first - Days::new(CHRONIC_DAYS - 1))
+        .and_time(NaiveTime::MIN)
+        .and_utc();
+    let sessions = CardioSessionDb::get_heart_rates_by_user_and_timespan(
+        *auth,
+        Timespan::StartEnd(sessions_start, end),
+        &mut db,
+    )
+    .await?;
+    let impulses = heart_rate_profile.training_impulses(&sessions);
+    Ok(Json(training_load::training_loads(&impulses, first, last)))
+}
+
+/// Check the `distance`, `ascent`, `descent` and `time` of cardio sessions against their track.
+///
+/// Sessions without a track are omitted.
+pub async fn get_cardio_session_validations(
+    auth: AuthUserOrAP,
+    Query(IdOption { id }): Query<IdOption<UnverifiedId<CardioSessionId>>>,
+    Query(time_span_option): Query<TimeSpanOption>,
+    mut db: DbConn,
+) -> HandlerResult<Json<Vec<CardioSessionValidation>>> {
+    auth.check_scope::<CardioSessionDb>(ScopeAccess::Read)?;
+
+    let sessions = match id {
+        Some(id) => {
+            let cardio_session_id = id.verify_user_ap_get(auth, &mut db).await?;
+            vec![CardioSessionDb::get_summary_track_by_id(cardio_session_id, &mut db).await?]
+        }
+        None => {
+            CardioSessionDb::get_summary_tracks_by_user_and_timespan(
+                *auth,
+                time_span_option.into(),
+                &mut db,
+            )
+            .await?
+        }
+    };
+    Ok(Json(
+        sessions
+            .iter()
+            .filter_map(cardio_summary::validate)
+            .map(|(validation, _)| validation)
+            .collect(),
+    ))
+}
+
+/// Recompute the `distance`, `ascent`, `descent` and `time` of the cardio sessions of all users
+/// from their track.
+///
+/// Returns the sessions with discrepancies or an invalid track.
+/// Sessions with an invalid track are not changed.
+pub async fn adm_normalize_cardio_sessions(
+    _auth: AuthAdmin,
+    Query(DryRunOption { dry_run }): Query<DryRunOption>,
+    mut db: DbConn,
+) -> HandlerResult<Json<Vec<CardioSessionValidation>>> {
+    let mut reported = vec![];
+    let mut after_id = None;
+    loop {
+        let sessions =
+            CardioSessionDb::get_summary_tracks_page(after_id, NORMALIZE_BATCH_SIZE, &mut db)
+                .await?;
+        let Some(last) = sessions.last() else {
+            break;
+        };
+        after_id = Some(last.id);
+
+        for (validation, summary) in sessions.iter().filter_map(cardio_summary::validate) {
+            if validation.invalid_track.is_none() && validation.discrepancies.is_empty() {
+                continue;
+            }
+            if let Some(summary) = summary
+                && !dry_run
+            {
+                CardioSessionDb::set_summary(validation.cardio_session_id, summary, &mut db)
+                    .await?;
+            }
+            reported.push(validation);
+        }
+    }
+    Ok(Json(reported))
+}
+
+/// Assign the best matching route to the cardio sessions with a track but without a route.
+async fn assign_routes(
+    cardio_sessions: &mut [CardioSession],
+    user_id: UserId,
+    db: &mut DbConn,
+) -> HandlerResult<()> {
+    if cardio_sessions
+        .iter()
+        .all(|session| session.route_id.is_some() || session.track.is_none())
+    {
+        return Ok(());
+    }
+    let routes = Routes::new(RouteDb::get_tracks_by_user(user_id, db).await?);
+    for cardio_session in cardio_sessions {
+        if cardio_session.route_id.is_some() {
+            continue;
+        }
+        if let Some(track) = &cardio_session.track
+            && let Some(route_match) = routes.best_match(cardio_session.id, track)
+        {
+            cardio_session.route_id = Some(route_match.route_id);
+        }
+    }
+    Ok(())
+}
+
+/// Get the routes matching the track of a cardio session ordered by their distance to it.
+pub async fn get_cardio_session_route_matches(
+    auth: AuthUserOrAP,
+    Path(id): Path<UnverifiedId<CardioSessionId>>,
+    mut db: DbConn,
+) -> HandlerResult<Json<Vec<RouteMatch>>> {
+    auth.check_scope::<CardioSessionDb>(ScopeAccess::Read)?;
+    auth.check_scope::<RouteDb>(ScopeAccess::Read)?;
+
+    let cardio_session_id = id.verify_user_ap_get(auth, &mut db).await?;
+    let track = CardioSessionDb::get_track_by_id(cardio_session_id, &mut db).await?;
+    let Some(track) = track.track else {
+        return Ok(Json(vec![]));
+    };
+    let routes = Routes::new(RouteDb::get_tracks_by_user(*auth, &mut db).await?);
+    Ok(Json(routes.matches(cardio_session_id, &track)))
+}
+
+/// Assign the best matching route to all cardio sessions with a track but without a route.
+///
+/// Returns the assigned routes.
+pub async fn match_routes(
+    auth: AuthUserOrAP,
+    Query(time_span_option): Query<TimeSpanOption>,
+    Query(DryRunOption { dry_run }): Query<DryRunOption>,
+    mut db: DbConn,
+) -> HandlerResult<Json<Vec<RouteMatch>>> {
+    auth.check_scope::<CardioSessionDb>(ScopeAccess::Write)?;
+    auth.check_scope::<RouteDb>(ScopeAccess::Read)?;
+
+    let routes = Routes::new(RouteDb::get_tracks_by_user(*auth, &mut db).await?);
+    let sessions = CardioSessionDb::get_positions_without_route_by_user_and_timespan(
+        *auth,
+        time_span_option.into(),
+        &mut db,
+    )
+    .await?;
+
+    let mut matches = vec![];
+    for session in sessions {
+        let Some(route_match) = session
+            .track
+            .as_deref()
+            .and_then(|track| routes.best_match(session.id, track))
+        else {
+            continue;
+        };
+        if !dry_run {
+            CardioSessionDb::set_route(session.id, route_match.route_id, &mut db).await?;
+        }
+        matches.push(route_match);
+    }
+    Ok(Json(matches))
+}
+
+/// Get the cardio sessions on a route with the best time and the trend of the times.
+pub async fn get_route_history(
+    auth: AuthUserOrAP,
+    Path(id): Path<UnverifiedId<RouteId>>,
+    mut db: DbConn,
+) -> HandlerResult<Json<RouteHistory>> {
+    auth.check_scope::<CardioSessionDb>(ScopeAccess::Read)?;
+    auth.check_scope::<RouteDb>(ScopeAccess::Read)?;
+
+    let route_id = id.verify_user_ap_get(auth, &mut db).await?;
+    let sessions = CardioSessionDb::get_summaries_by_route(route_id, &mut db).await?;
+    Ok(Json(route_match::history(route_id, sessions)))
+}
+
+/// Get the splits of a cardio session.
+pub async fn get_cardio_session_splits(
+    auth: AuthUserOrAP,
+    Path(id): Path<UnverifiedId<CardioSessionId>>,
+    Query(SplitOption { distance, unit }): Query<SplitOption>,
+    mut db: DbConn,
+) -> HandlerResult<Json<Vec<CardioSplit>>> {
+    auth.check_scope::<CardioSessionDb>(ScopeAccess::Read)?;
+
+    let split_distance = distance.unwrap_or(1.) * unit.unwrap_or(DistanceUnit::Km).meters();
+    if !(split_distance >= MIN_SPLIT_DISTANCE && split_distance.is_finite()) {
+        return Err(HandlerError::from((
+            StatusCode::BAD_REQUEST,
+            ErrorMessage::Other {
+                error: format!("The split distance must be at least {MIN_SPLIT_DISTANCE} m."),
+            },
+        )));
+    }
+
+    let cardio_session_id = id.verify_user_ap_get(auth, &mut db).await?;
+    let track = CardioSessionDb::get_track_by_id(cardio_session_id, &mut db).await?;
+    Ok(Json(split::splits(&track, split_distance)))
+}
+
+/// Get the fastest efforts over common distances of all cardio sessions with a track.
+pub async fn get_cardio_best_efforts(
+    auth: AuthUserOrAP,
+    Query(MovementIdOption { movement_id }): Query<MovementIdOption>,
+    Query(time_span_option): Query<TimeSpanOption>,
+    mut db: DbConn,
+) -> HandlerResult<Json<Vec<CardioBestEffort>>> {
+    auth.check_scope::<CardioSessionDb>(ScopeAccess::Read)?;
+
+    CardioSessionDb::get_best_efforts_by_user_and_timespan(
+        *auth,
+        movement_id,
+        time_span_option.into(),
+        &mut db,
+    )
+    .await
+    .map(Json)
+    .map_err(Into::into)
+}
+
+pub async fn create_training_zones(
+    auth: AuthUserOrAP,
+    Query(RowResultsOption { row_results }): Query<RowResultsOption>,
+    mut db: DbConn,
+    Json(training_zones): Json<UnverifiedSingleOrVec<TrainingZone>>,
+) -> HandlerResult<BulkResponse<TrainingZoneId, TrainingZone>> {
+    let mut rows = None;
+    match training_zones {
+        UnverifiedSingleOrVec::Single(training_zone) => {
+            let training_zone = training_zone.verify_user_ap_create(auth)?;
+            TrainingZoneDb::create(&training_zone, &mut db).await?;
+        }
+        UnverifiedSingleOrVec::Vec(training_zones) => {
+            let training_zones = training_zones.verify_user_ap_create(auth)?;
+            if row_results {
+                rows =
+                    Some(TrainingZoneDb::create_multiple_checked(&training_zones, &mut db).await?);
+            } else {
+                TrainingZoneDb::create_multiple(&training_zones, &mut db).await?;
+            }
+        }
+    }
+    let epoch = TrainingZoneDb::get_epoch_by_user(*auth, &mut db).await?;
+    Ok(BulkResponse { epoch, rows })
+}
+
+pub async fn get_training_zones(
+    auth: AuthUserOrAP,
+    Query(IdOption { id }): Query<IdOption<UnverifiedId<TrainingZoneId>>>,
+    mut db: DbConn,
+) -> HandlerResult<Json<Vec<TrainingZone>>> {
+    auth.check_scope::<TrainingZoneDb>(ScopeAccess::Read)?;
+
+    match id {
+        Some(id) => {
+            let training_zone_id = id.verify_user_ap_get(auth, &mut db).await?;
+            TrainingZoneDb::get_by_id(training_zone_id, &mut db)
+                .await
+                .map(|t| vec![t])
+        }
+        None => TrainingZoneDb::get_by_user(*auth, &mut db).await,
+    }
+    .map(Json)
+    .map_err(Into::into)
+}
+
+pub async fn update_training_zones(
+    auth: AuthUserOrAP,
+    Query(RowResultsOption { row_results }): Query<RowResultsOption>,
+    Query(LastEpochOption { last_epoch }): Query<LastEpochOption>,
+    mut db: DbConn,
+    Json(training_zones): Json<UnverifiedSingleOrVec<TrainingZone>>,
+) -> HandlerResult<BulkResponse<TrainingZoneId, TrainingZone>> {
+    let mut rows = None;
+    match training_zones {
+        UnverifiedSingleOrVec::Single(training_zone) => {
+            let training_zone = training_zone.verify_user_ap_update(auth, &mut db).await?;
+            if last_epoch.is_some() {
+                rows = Some(
+                    TrainingZoneDb::update_multiple_checked(&[training_zone], last_epoch, &mut db)
+                        .await?,
+                );
+            } else {
+                TrainingZoneDb::update(&training_zone, &mut db).await?;
+            }
+        }
+        UnverifiedSingleOrVec::Vec(training_zones) => {
+            let training_zones = training_zones.verify_user_ap_update(auth, &mut db).await?;
+            if row_results || last_epoch.is_some() {
+                rows = Some(
+                    TrainingZoneDb::update_multiple_checked(&training_zones, last_epoch, &mut db)
+                        .await?,
+                );
+            } else {
+                TrainingZoneDb::update_multiple(&training_zones, &mut db).await?;
+            }
+        }
+    }
+    let epoch = TrainingZoneDb::get_epoch_by_user(*auth, &mut db).await?;
+    Ok(BulkResponse { epoch, rows })
+}
+
+pub async fn create_segments(
+    auth: AuthUserOrAP,
+    Query(RowResultsOption { row_results }): Query<RowResultsOption>,
+    mut db: DbConn,
+    Json(segments): Json<UnverifiedSingleOrVec<Segment>>,
+) -> HandlerResult<BulkResponse<SegmentId, Segment>> {
+    let mut rows = None;
+    match segments {
+        UnverifiedSingleOrVec::Single(segment) => {
+            let segment = segment.verify_user_ap_create(auth)?;
+            SegmentDb::create(&segment, &mut db).await?;
+            segment::detect_for_segments(*auth, &[segment.id], &mut db).await?;
+        }
+        UnverifiedSingleOrVec::Vec(segments) => {
+            let segments = segments.verify_user_ap_create(auth)?;
+            if row_results {
+                rows = Some(SegmentDb::create_multiple_checked(&segments, &mut db).await?);
+            } else {
+                SegmentDb::create_multiple(&segments, &mut db).await?;
+            }
+            let ids: Vec<_> = segments.iter().map(|s| s.id).collect();
+            segment::detect_for_segments(*auth, &ids, &mut db).await?;
+        }
+    }
+    let epoch = SegmentDb::get_epoch_by_user(*auth, &mut db).await?;
+    Ok(BulkResponse { epoch, rows })
+}
+
+pub async fn get_segments(
+    auth: AuthUserOrAP,
+    Query(IdOption { id }): Query<IdOption<UnverifiedId<SegmentId>>>,
+    mut db: DbConn,
+) -> HandlerResult<Json<Vec<Segment>>> {
+    auth.check_scope::<SegmentDb>(ScopeAccess::Read)?;
+
+    match id {
+        Some(id) => {
+            let segment_id = id.verify_user_ap_get(auth, &mut db).await?;
+            SegmentDb::get_by_id(segment_id, &mut db)
+                .await
+                .map(|s| vec![s])
+        }
+        None => SegmentDb::get_by_user(*auth, &mut db).await,
+    }
+    .map(Json)
+    .map_err(Into::into)
+}
+
+pub async fn update_segments(
+    auth: AuthUserOrAP,
+    Query(RowResultsOption { row_results }): Query<RowResultsOption>,
+    Query(LastEpochOption { last_epoch }): Query<LastEpochOption>,
+    mut db: DbConn,
+    Json(segments): Json<UnverifiedSingleOrVec<Segment>>,
+) -> HandlerResult<BulkResponse<SegmentId, Segment>> {
+    let mut rows = None;
+    match segments {
+        UnverifiedSingleOrVec::Single(segment) => {
+            let segment = segment.verify_user_ap_update(auth, &mut db).await?;
+            let segment_id = segment.id;
+            if last_epoch.is_some() {
+                rows = Some(
+                    SegmentDb::update_multiple_checked(&[segment], last_epoch, &mut db).await?,
+                );
+            } else {
+                SegmentDb::update(&segment, &mut db).await?;
+            }
+            segment::detect_for_segments(*auth, &[segment_id], &mut db).await?;
+        }
+        UnverifiedSingleOrVec::Vec(segments) => {
+            let segments = segments.verify_user_ap_update(auth, &mut db).await?;
+            if row_results || last_epoch.is_some() {
+                rows =
+                    Some(SegmentDb::update_multiple_checked(&segments, last_epoch, &mut db).await?);
+            } else {
+                SegmentDb::update_multiple(&segments, &mut db).await?;
+            }
+            let ids: Vec<_> = segments.iter().map(|s| s.id).collect();
+            segment::detect_for_segments(*auth, &ids, &mut db).await?;
+        }
+    }
+    let epoch = SegmentDb::get_epoch_by_user(*auth, &mut db).await?;
+    Ok(BulkResponse { epoch, rows })
+}
+
+/// Get the efforts on segments ordered by datetime.
+pub async fn get_segment_efforts(
+    auth: AuthUserOrAP,
+    Query(SegmentIdOption { segment_id }): Query<SegmentIdOption>,
+    Query(time_span_option): Query<TimeSpanOption>,
+    mut db: DbConn,
+) -> HandlerResult<Json<Vec<SegmentEffort>>> {
+    auth.check_scope::<SegmentDb>(ScopeAccess::Read)?;
+    auth.check_scope::<CardioSessionDb>(ScopeAccess::Read)?;
+
+    SegmentEffortDb::get_by_user_and_timespan(*auth, segment_id, time_span_option.into(), &mut db)
+        .await
+        .map(Json)
+        .map_err(Into::into)
+}
+
+/// Get the fastest effort on every segment.
+pub async fn get_segment_best_efforts(
+    auth: AuthUserOrAP,
+    Query(SegmentIdOption { segment_id }): Query<SegmentIdOption>,
+    Query(time_span_option): Query<TimeSpanOption>,
+    mut db: DbConn,
+) -> HandlerResult<Json<Vec<SegmentEffort>>> {
+    auth.check_scope::<SegmentDb>(ScopeAccess::Read)?;
+    auth.check_scope::<CardioSessionDb>(ScopeAccess::Read)?;
+
+    let efforts = SegmentEffortDb::get_by_user_and_timespan(
+        *auth,
+        segment_id,
+        time_span_option.into(),
+        &mut db,
+    )
+    .await?;
+    let mut best_efforts: BTreeMap<i64, SegmentEffort> = BTreeMap::new();
+    for effort in efforts {
+        best_efforts
+            .entry(effort.segment_id.0)
+            .and_modify(|best| {
+                if effort.time < best.time {
+                    *best = effort.clone();
+                }
+            })
+            .or_insert(effort);
+    }
+    Ok(Json(best_efforts.into_values().collect()))
+}
+
+/// Get the time in heart rate and pace zones of cardio sessions.
+pub async fn get_cardio_session_zones(
+    auth: AuthUserOrAP,
+    Query(IdOption { id }): Query<IdOption<UnverifiedId<CardioSessionId>>>,
+    Query(time_span_option): Query<TimeSpanOption>,
+    mut db: DbConn,
+) -> HandlerResult<Json<Vec<CardioSessionZones>>> {
+    auth.check_scope::<CardioSessionDb>(ScopeAccess::Read)?;
+    auth.check_scope::<TrainingZoneDb>(ScopeAccess::Read)?;
+
+    let training_zones = TrainingZones(TrainingZoneDb::get_active_by_user(*auth, &mut db).await?);
+    let sessions = match id {
+        Some(id) => {
+            let cardio_session_id = id.verify_user_ap_get(auth, &mut db).await?;
+            vec![CardioSessionDb::get_zone_data_by_id(cardio_session_id, &mut db).await?]
+        }
+        None => {
+            CardioSessionDb::get_zone_data_by_user_and_timespan(
+                *auth,
+                None,
+                time_span_option.into(),
+                &mut db,
+            )
+            .await?
+        }
+    };
+    Ok(Json(
+        sessions
+            .iter()
+            .map(|session| training_zones.session_zones(session))
+            .collect(),
+    ))
+}
+
+/// Get the time in heart rate and pace zones aggregated in time buckets.
+pub async fn get_zone_statistics(
+    auth: AuthUserOrAP,
+    Query(StatisticIntervalOption { interval }): Query<StatisticIntervalOption>,
+    Query(MovementIdOption { movement_id }): Query<MovementIdOption>,
+    Query(time_span_option): Query<TimeSpanOption>,
+    mut db: DbConn,
+) -> HandlerResult<Json<Vec<ZoneStatistic>>> {
+    auth.check_scope::<CardioSessionDb>(ScopeAccess::Read)?;
+    auth.check_scope::<TrainingZoneDb>(ScopeAccess::Read)?;
+
+    let training_zones = TrainingZones(TrainingZoneDb::get_active_by_user(*auth, &mut db).await?);
+    let sessions = CardioSessionDb::get_zone_data_by_user_and_timespan(
+        *auth,
+        movement_id,
+        time_span_option.into(),
+        &mut db,
+    )
+    .await?;
+    Ok(Json(training_zones.statistics(&sessions, interval)))
+}
+
+/// Get the track, cadence and heart rate of a cardio session.
+///
+/// If `tolerance` or `max_points` is set, the track is simplified.
+pub async fn get_cardio_session_track(
+    auth: AuthUserOrAP,
+    Path(id): Path<UnverifiedId<CardioSessionId>>,
+    Query(simplify_option): Query<SimplifyOption>,
+    mut db: DbConn,
+) -> HandlerResult<Json<CardioSessionTrack>> {
+    auth.check_scope::<CardioSessionDb>(ScopeAccess::Read)?;
+
+    let simplification = simplify_option.simplification()?;
+    let cardio_session_id = id.verify_user_ap_get(auth, &mut db).await?;
+    let mut track = CardioSessionDb::get_track_by_id(cardio_session_id, &mut db).await?;
+    if let Some(simplification) = simplification {
+        simplification.apply(&mut track.track);
+    }
+    Ok(Json(track))
+}
+
+/// Create a cardio session from an uploaded GPX, TCX or FIT file and return it.
+pub async fn import_cardio_session(
+    auth: AuthUserOrAP,
+    Query(CardioSessionImportOption {
+        movement_id,
+        cardio_type,
+        format,
+    }): Query<CardioSessionImportOption>,
+    Query(MatchRouteOption { match_route }): Query<MatchRouteOption>,
+    mut db: DbConn,
+    file: Bytes,
+) -> HandlerResult<Json<CardioSession>> {
+    auth.check_scope::<CardioSessionDb>(ScopeAccess::Write)?;
+
+    let movement_id = movement_id.verify_user_ap_get(auth, &mut db).await?;
+    let format = format
+        .or_else(|| TrackFileFormat::detect(&file))
+        .ok_or(StatusCode::UNSUPPORTED_MEDIA_TYPE)?;
+    let points = track_file::parse(&file, format)?;
+    let mut cardio_session =
+        track_file::into_cardio_session(&points, *auth, movement_id, cardio_type)?;
+    if match_route {
+        assign_routes(std::slice::from_mut(&mut cardio_session), *auth, &mut db).await?;
+    }
+
+    db.transaction(async |db| {
+        CardioSessionDb::create(&cardio_session, db).await?;
+        segment::detect_for_cardio_sessions(*auth, &[cardio_session.id], db).await
+    })
+    .await?;
+    Ok(Json(cardio_session))
+}
+
+/// Export a cardio session with its track, heart rate and cadence as GPX or TCX file.
+pub async fn export_cardio_session(
+    auth: AuthUserOrAP,
+    Path(id): Path<UnverifiedId<CardioSessionId>>,
+    Query(ExportOption { format }): Query<ExportOption>,
+    mut db: DbConn,
+) -> HandlerResult<impl IntoResponse + use<>> {
+    auth.check_scope::<CardioSessionDb>(ScopeAccess::Read)?;
+
+    let cardio_session_id = id.verify_user_ap_get(auth, &mut db).await?;
+    let cardio_session = CardioSessionDb::get_by_id(cardio_session_id, &mut db).await?;
+    let movement = MovementDb::get_by_id(cardio_session.movement_id, &mut db).await?;
+    let format = format.unwrap_or(TrackFileFormat::Gpx);
+    let file = track_file::export_cardio_session(&cardio_session, &movement.name, format)?;
+
+    let name = format!(
+        "{}_{}",
+        movement.name,
+        cardio_session.datetime.format("%Y-%m-%d_%H-%M")
+    );
+    Ok((export_headers(&name, format), file))
+}
+
 pub async fn update_cardio_sessions(
     auth: AuthUserOrAP,
+    Query(RowResultsOption { row_results }): Query<RowResultsOption>,
+    Query(LastEpochOption { last_epoch }): Query<LastEpochOption>,
+    Query(NormalizeOption { normalize }): Query<NormalizeOption>,
     mut db: DbConn,
     Json(cardio_sessions): Json<UnverifiedSingleOrVec<CardioSession>>,
-) -> HandlerResult<Json<EpochResponse>> {
-    match cardio_sessions {
+) -> HandlerResult<BulkResponse<CardioSessionId, CardioSession>> {
+    let (cardio_sessions, checked) = match cardio_sessions {
         UnverifiedSingleOrVec::Single(cardio_session) => {
-            let cardio_session = cardio_session.verify_user_ap_update(auth, &mut db).await?;
-            CardioSessionDb::update(&cardio_session, &mut db).await?;
+            let mut cardio_session = cardio_session.verify_user_ap_update(auth, &mut db).await?;
+            if normalize {
+                cardio_summary::normalize(&mut cardio_session)?;
+            }
+            (vec![cardio_session], last_epoch.is_some())
         }
         UnverifiedSingleOrVec::Vec(cardio_sessions) => {
-            let cardio_sessions = cardio_sessions.verify_user_ap_update(auth, &mut db).await?;
-            CardioSessionDb::update_multiple(&cardio_sessions, &mut db).await?;
+            let mut cardio_sessions = cardio_sessions.verify_user_ap_update(auth, &mut db).await?;
+            if normalize {
+                cardio_sessions
+                    .iter_mut()
+                    .try_for_each(cardio_summary::normalize)?;
+            }
+            (cardio_sessions, row_results || last_epoch.is_some())
         }
-    }
+    };
+    let rows = db
+        .transaction(async |db| {
+            let rows = if checked {
+                Some(
+                    CardioSessionDb::update_multiple_checked(&cardio_sessions, last_epoch, db)
+                        .await?,
+                )
+            } else {
+                CardioSessionDb::update_multiple(&cardio_sessions, db).await?;
+                None
+            };
+            detect_segment_efforts(*auth, &cardio_sessions, rows.as_deref(), db)
+                .await
+                .map(|()| rows)
+        })
+        .await?;
     let epoch = CardioSessionDb::get_epoch_by_user(*auth, &mut db).await?;
-    Ok(Json(EpochResponse { epoch }))
+    Ok(BulkResponse { epoch, rows })
+}
+
+/// Detect the segment efforts of the created or updated cardio sessions.
+///
+/// If `rows` reports a conflict, none of the cardio sessions have been applied.
+async fn detect_segment_efforts(
+    user_id: UserId,
+    cardio_sessions: &[CardioSession],
+    rows: Option<&[RowResult<CardioSessionId, CardioSession>]>,
+    db: &mut AsyncPgConnection,
+) -> QueryResult<()> {
+    if rows.is_some_and(|rows| !rows.iter().all(RowResult::is_ok)) {
+        return Ok(());
+    }
+    let ids: Vec<_> = cardio_sessions.iter().map(|c| c.id).collect();
+    segment::detect_for_cardio_sessions(user_id, &ids, db).await
 }
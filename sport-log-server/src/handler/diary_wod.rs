@@ -1,18 +1,23 @@
 use axum::{Json, extract::Query};
-use sport_log_types::{Diary, DiaryId, EpochResponse, Wod, WodId};
+use sport_log_types::{Diary, DiaryId, ScopeAccess, Wod, WodId};
 
 use crate::{
     auth::AuthUserOrAP,
     db::*,
-    handler::{HandlerResult, IdOption, UnverifiedSingleOrVec},
+    handler::{
+        BulkResponse, HandlerResult, IdOption, LastEpochOption, RowResultsOption,
+        UnverifiedSingleOrVec,
+    },
     state::DbConn,
 };
 
 pub async fn create_wods(
     auth: AuthUserOrAP,
+    Query(RowResultsOption { row_results }): Query<RowResultsOption>,
     mut db: DbConn,
     Json(wods): Json<UnverifiedSingleOrVec<Wod>>,
-) -> HandlerResult<Json<EpochResponse>> {
+) -> HandlerResult<BulkResponse<WodId, Wod>> {
+    let mut rows = None;
     match wods {
         UnverifiedSingleOrVec::Single(wod) => {
             let wod = wod.verify_user_ap_create(auth)?;
@@ -20,11 +25,15 @@ pub async fn create_wods(
         }
         UnverifiedSingleOrVec::Vec(wods) => {
             let wods = wods.verify_user_ap_create(auth)?;
-            WodDb::create_multiple(&wods, &mut db).await?;
+            if row_results {
+                rows = Some(WodDb::create_multiple_checked(&wods, &mut db).await?);
+            } else {
+                WodDb::create_multiple(&wods, &mut db).await?;
+            }
         }
     }
     let epoch = WodDb::get_epoch_by_user(*auth, &mut db).await?;
-    Ok(Json(EpochResponse { epoch }))
+    Ok(BulkResponse { epoch, rows })
 }
 
 pub async fn get_wods(
@@ -32,6 +41,8 @@ pub async fn get_wods(
     Query(IdOption { id }): Query<IdOption<UnverifiedId<WodId>>>,
     mut db: DbConn,
 ) -> HandlerResult<Json<Vec<Wod>>> {
+    auth.check_scope::<WodDb>(ScopeAccess::Read)?;
+
     match id {
         Some(id) => {
             let wod_id = id.verify_user_ap_get(auth, &mut db).await?;
@@ -45,28 +56,41 @@ pub async fn get_wods(
 
 pub async fn update_wods(
     auth: AuthUserOrAP,
+    Query(RowResultsOption { row_results }): Query<RowResultsOption>,
+    Query(LastEpochOption { last_epoch }): Query<LastEpochOption>,
     mut db: DbConn,
     Json(wods): Json<UnverifiedSingleOrVec<Wod>>,
-) -> HandlerResult<Json<EpochResponse>> {
+) -> HandlerResult<BulkResponse<WodId, Wod>> {
+    let mut rows = None;
     match wods {
         UnverifiedSingleOrVec::Single(wod) => {
             let wod = wod.verify_user_ap_update(auth, &mut db).await?;
-            WodDb::update(&wod, &mut db).await?;
+            if last_epoch.is_some() {
+                rows = Some(WodDb::update_multiple_checked(&[wod], last_epoch, &mut db).await?);
+            } else {
+                WodDb::update(&wod, &mut db).await?;
+            }
         }
         UnverifiedSingleOrVec::Vec(wods) => {
             let wods = wods.verify_user_ap_update(auth, &mut db).await?;
-            WodDb::update_multiple(&wods, &mut db).await?;
+            if row_results || last_epoch.is_some() {
+                rows = Some(WodDb::update_multiple_checked(&wods, last_epoch, &mut db).await?);
+            } else {
+                WodDb::update_multiple(&wods, &mut db).await?;
+            }
         }
     }
     let epoch = WodDb::get_epoch_by_user(*auth, &mut db).await?;
-    Ok(Json(EpochResponse { epoch }))
+    Ok(BulkResponse { epoch, rows })
 }
 
 pub async fn create_diaries(
     auth: AuthUserOrAP,
+    Query(RowResultsOption { row_results }): Query<RowResultsOption>,
     mut db: DbConn,
     Json(diaries): Json<UnverifiedSingleOrVec<Diary>>,
-) -> HandlerResult<Json<EpochResponse>> {
+) -> HandlerResult<BulkResponse<DiaryId, Diary>> {
+    let mut rows = None;
     match diaries {
         UnverifiedSingleOrVec::Single(diary) => {
             let diary = diary.verify_user_ap_create(auth)?;
@@ -74,11 +98,15 @@ pub async fn create_diaries(
         }
         UnverifiedSingleOrVec::Vec(diaries) => {
             let diaries = diaries.verify_user_ap_create(auth)?;
-            DiaryDb::create_multiple(&diaries, &mut db).await?;
+            if row_results {
+                rows = Some(DiaryDb::create_multiple_checked(&diaries, &mut db).await?);
+            } else {
+                DiaryDb::create_multiple(&diaries, &mut db).await?;
+            }
         }
     }
     let epoch = DiaryDb::get_epoch_by_user(*auth, &mut db).await?;
-    Ok(Json(EpochResponse { epoch }))
+    Ok(BulkResponse { epoch, rows })
 }
 
 pub async fn get_diaries(
@@ -86,6 +114,8 @@ pub async fn get_diaries(
     Query(IdOption { id }): Query<IdOption<UnverifiedId<DiaryId>>>,
     mut db: DbConn,
 ) -> HandlerResult<Json<Vec<Diary>>> {
+    auth.check_scope::<DiaryDb>(ScopeAccess::Read)?;
+
     match id {
         Some(id) => {
             let diary_id = id.verify_user_ap_get(auth, &mut db).await?;
@@ -99,19 +129,30 @@ pub async fn get_diaries(
 
 pub async fn update_diaries(
     auth: AuthUserOrAP,
+    Query(RowResultsOption { row_results }): Query<RowResultsOption>,
+    Query(LastEpochOption { last_epoch }): Query<LastEpochOption>,
     mut db: DbConn,
     Json(diaries): Json<UnverifiedSingleOrVec<Diary>>,
-) -> HandlerResult<Json<EpochResponse>> {
+) -> HandlerResult<BulkResponse<DiaryId, Diary>> {
+    let mut rows = None;
     match diaries {
         UnverifiedSingleOrVec::Single(diary) => {
             let diary = diary.verify_user_ap_update(auth, &mut db).await?;
-            DiaryDb::update(&diary, &mut db).await?;
+            if last_epoch.is_some() {
+                rows = Some(DiaryDb::update_multiple_checked(&[diary], last_epoch, &mut db).await?);
+            } else {
+                DiaryDb::update(&diary, &mut db).await?;
+            }
         }
         UnverifiedSingleOrVec::Vec(diaries) => {
             let diaries = diaries.verify_user_ap_update(auth, &mut db).await?;
-            DiaryDb::update_multiple(&diaries, &mut db).await?;
+            if row_results || last_epoch.is_some() {
+                rows = Some(DiaryDb::update_multiple_checked(&diaries, last_epoch, &mut db).await?);
+            } else {
+                DiaryDb::update_multiple(&diaries, &mut db).await?;
+            }
         }
     }
     let epoch = DiaryDb::get_epoch_by_user(*auth, &mut db).await?;
-    Ok(Json(EpochResponse { epoch }))
+    Ok(BulkResponse { epoch, rows })
 }
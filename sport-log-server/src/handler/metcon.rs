@@ -1,21 +1,26 @@
 use axum::{Json, extract::Query};
 use sport_log_types::{
-    EpochResponse, Metcon, MetconId, MetconMovement, MetconMovementId, MetconSession,
-    MetconSessionId,
+    Metcon, MetconId, MetconMovement, MetconMovementId, MetconSession, MetconSessionId,
+    MetconStatistic, ScopeAccess,
 };
 
 use crate::{
     auth::AuthUserOrAP,
     db::*,
-    handler::{HandlerResult, IdOption, TimeSpanOption, UnverifiedSingleOrVec},
+    handler::{
+        BulkResponse, HandlerResult, IdOption, LastEpochOption, MovementIdOption, RowResultsOption,
+        StatisticIntervalOption, TimeSpanOption, UnverifiedSingleOrVec,
+    },
     state::DbConn,
 };
 
 pub async fn create_metcon_sessions(
     auth: AuthUserOrAP,
+    Query(RowResultsOption { row_results }): Query<RowResultsOption>,
     mut db: DbConn,
     Json(metcon_sessions): Json<UnverifiedSingleOrVec<MetconSession>>,
-) -> HandlerResult<Json<EpochResponse>> {
+) -> HandlerResult<BulkResponse<MetconSessionId, MetconSession>> {
+    let mut rows = None;
     match metcon_sessions {
         UnverifiedSingleOrVec::Single(metcon_session) => {
             let metcon_session = metcon_session.verify_user_ap_create(auth)?;
@@ -23,11 +28,17 @@ pub async fn create_metcon_sessions(
         }
         UnverifiedSingleOrVec::Vec(metcon_sessions) => {
             let metcon_sessions = metcon_sessions.verify_user_ap_create(auth)?;
-            MetconSessionDb::create_multiple(&metcon_sessions, &mut db).await?;
+            if row_results {
+                rows = Some(
+                    MetconSessionDb::create_multiple_checked(&metcon_sessions, &mut db).await?,
+                );
+            } else {
+                MetconSessionDb::create_multiple(&metcon_sessions, &mut db).await?;
+            }
         }
     }
     let epoch = MetconSessionDb::get_epoch_by_user(*auth, &mut db).await?;
-    Ok(Json(EpochResponse { epoch }))
+    Ok(BulkResponse { epoch, rows })
 }
 
 pub async fn get_metcon_sessions(
@@ -36,6 +47,8 @@ pub async fn get_metcon_sessions(
     Query(time_span_option): Query<TimeSpanOption>,
     mut db: DbConn,
 ) -> HandlerResult<Json<Vec<MetconSession>>> {
+    auth.check_scope::<MetconSessionDb>(ScopeAccess::Read)?;
+
     match id {
         Some(id) => {
             let metcon_session_id = id.verify_user_ap_get(auth, &mut db).await?;
@@ -53,28 +66,51 @@ pub async fn get_metcon_sessions(
 
 pub async fn update_metcon_sessions(
     auth: AuthUserOrAP,
+    Query(RowResultsOption { row_results }): Query<RowResultsOption>,
+    Query(LastEpochOption { last_epoch }): Query<LastEpochOption>,
     mut db: DbConn,
     Json(metcon_sessions): Json<UnverifiedSingleOrVec<MetconSession>>,
-) -> HandlerResult<Json<EpochResponse>> {
+) -> HandlerResult<BulkResponse<MetconSessionId, MetconSession>> {
+    let mut rows = None;
     match metcon_sessions {
         UnverifiedSingleOrVec::Single(metcon_session) => {
             let metcon_session = metcon_session.verify_user_ap_update(auth, &mut db).await?;
-            MetconSessionDb::update(&metcon_session, &mut db).await?;
+            if last_epoch.is_some() {
+                rows = Some(
+                    MetconSessionDb::update_multiple_checked(
+                        &[metcon_session],
+                        last_epoch,
+                        &mut db,
+                    )
+                    .await?,
+                );
+            } else {
+                MetconSessionDb::update(&metcon_session, &mut db).await?;
+            }
         }
         UnverifiedSingleOrVec::Vec(metcon_sessions) => {
             let metcon_sessions = metcon_sessions.verify_user_ap_update(auth, &mut db).await?;
-            MetconSessionDb::update_multiple(&metcon_sessions, &mut db).await?;
+            if row_results || last_epoch.is_some() {
+                rows = Some(
+                    MetconSessionDb::update_multiple_checked(&metcon_sessions, last_epoch, &mut db)
+                        .await?,
+                );
+            } else {
+                MetconSessionDb::update_multiple(&metcon_sessions, &mut db).await?;
+            }
         }
     }
     let epoch = MetconSessionDb::get_epoch_by_user(*auth, &mut db).await?;
-    Ok(Json(EpochResponse { epoch }))
+    Ok(BulkResponse { epoch, rows })
 }
 
 pub async fn create_metcons(
     auth: AuthUserOrAP,
+    Query(RowResultsOption { row_results }): Query<RowResultsOption>,
     mut db: DbConn,
     Json(metcons): Json<UnverifiedSingleOrVec<Metcon>>,
-) -> HandlerResult<Json<EpochResponse>> {
+) -> HandlerResult<BulkResponse<MetconId, Metcon>> {
+    let mut rows = None;
     match metcons {
         UnverifiedSingleOrVec::Single(metcon) => {
             let metcon = metcon.verify_user_ap_create(auth)?;
@@ -82,11 +118,15 @@ pub async fn create_metcons(
         }
         UnverifiedSingleOrVec::Vec(metcons) => {
             let metcons = metcons.verify_user_ap_create(auth)?;
-            MetconDb::create_multiple(&metcons, &mut db).await?;
+            if row_results {
+                rows = Some(MetconDb::create_multiple_checked(&metcons, &mut db).await?);
+            } else {
+                MetconDb::create_multiple(&metcons, &mut db).await?;
+            }
         }
     }
     let epoch = MetconDb::get_epoch_by_user_optional(*auth, &mut db).await?;
-    Ok(Json(EpochResponse { epoch }))
+    Ok(BulkResponse { epoch, rows })
 }
 
 pub async fn get_metcons(
@@ -94,6 +134,8 @@ pub async fn get_metcons(
     Query(IdOption { id }): Query<IdOption<UnverifiedId<MetconId>>>,
     mut db: DbConn,
 ) -> HandlerResult<Json<Vec<Metcon>>> {
+    auth.check_scope::<MetconDb>(ScopeAccess::Read)?;
+
     match id {
         Some(id) => {
             let metcon_id = id.verify_user_ap_get(auth, &mut db).await?;
@@ -109,28 +151,43 @@ pub async fn get_metcons(
 
 pub async fn update_metcons(
     auth: AuthUserOrAP,
+    Query(RowResultsOption { row_results }): Query<RowResultsOption>,
+    Query(LastEpochOption { last_epoch }): Query<LastEpochOption>,
     mut db: DbConn,
     Json(metcons): Json<UnverifiedSingleOrVec<Metcon>>,
-) -> HandlerResult<Json<EpochResponse>> {
+) -> HandlerResult<BulkResponse<MetconId, Metcon>> {
+    let mut rows = None;
     match metcons {
         UnverifiedSingleOrVec::Single(metcon) => {
             let metcon = metcon.verify_user_ap_update(auth, &mut db).await?;
-            MetconDb::update(&metcon, &mut db).await?;
+            if last_epoch.is_some() {
+                rows =
+                    Some(MetconDb::update_multiple_checked(&[metcon], last_epoch, &mut db).await?);
+            } else {
+                MetconDb::update(&metcon, &mut db).await?;
+            }
         }
         UnverifiedSingleOrVec::Vec(metcons) => {
             let metcons = metcons.verify_user_ap_update(auth, &mut db).await?;
-            MetconDb::update_multiple(&metcons, &mut db).await?;
+            if row_results || last_epoch.is_some() {
+                rows =
+                    Some(MetconDb::update_multiple_checked(&metcons, last_epoch, &mut db).await?);
+            } else {
+                MetconDb::update_multiple(&metcons, &mut db).await?;
+            }
         }
     }
     let epoch = MetconDb::get_epoch_by_user_optional(*auth, &mut db).await?;
-    Ok(Json(EpochResponse { epoch }))
+    Ok(BulkResponse { epoch, rows })
 }
 
 pub async fn create_metcon_movements(
     auth: AuthUserOrAP,
+    Query(RowResultsOption { row_results }): Query<RowResultsOption>,
     mut db: DbConn,
     Json(metcon_movements): Json<UnverifiedSingleOrVec<MetconMovement>>,
-) -> HandlerResult<Json<EpochResponse>> {
+) -> HandlerResult<BulkResponse<MetconMovementId, MetconMovement>> {
+    let mut rows = None;
     match metcon_movements {
         UnverifiedSingleOrVec::Single(metcon_movement) => {
             let metcon_movement = metcon_movement.verify_user_ap_create(auth)?;
@@ -138,11 +195,17 @@ pub async fn create_metcon_movements(
         }
         UnverifiedSingleOrVec::Vec(metcon_movements) => {
             let metcon_movements = metcon_movements.verify_user_ap_create(auth)?;
-            MetconMovementDb::create_multiple(&metcon_movements, &mut db).await?;
+            if row_results {
+                rows = Some(
+                    MetconMovementDb::create_multiple_checked(&metcon_movements, &mut db).await?,
+                );
+            } else {
+                MetconMovementDb::create_multiple(&metcon_movements, &mut db).await?;
+            }
         }
     }
     let epoch = MetconMovementDb::get_epoch_by_user_optional(*auth, &mut db).await?;
-    Ok(Json(EpochResponse { epoch }))
+    Ok(BulkResponse { epoch, rows })
 }
 
 pub async fn get_metcon_movements(
@@ -150,6 +213,8 @@ pub async fn get_metcon_movements(
     Query(IdOption { id }): Query<IdOption<UnverifiedId<MetconMovementId>>>,
     mut db: DbConn,
 ) -> HandlerResult<Json<Vec<MetconMovement>>> {
+    auth.check_scope::<MetconMovementDb>(ScopeAccess::Read)?;
+
     match id {
         Some(id) => {
             let metcon_movement_id = id.verify_user_ap_get(auth, &mut db).await?;
@@ -165,21 +230,70 @@ pub async fn get_metcon_movements(
 
 pub async fn update_metcon_movements(
     auth: AuthUserOrAP,
+    Query(RowResultsOption { row_results }): Query<RowResultsOption>,
+    Query(LastEpochOption { last_epoch }): Query<LastEpochOption>,
     mut db: DbConn,
     Json(metcon_movements): Json<UnverifiedSingleOrVec<MetconMovement>>,
-) -> HandlerResult<Json<EpochResponse>> {
+) -> HandlerResult<BulkResponse<MetconMovementId, MetconMovement>> {
+    let mut rows = None;
     match metcon_movements {
         UnverifiedSingleOrVec::Single(metcon_movement) => {
             let metcon_movement = metcon_movement.verify_user_ap_update(auth, &mut db).await?;
-            MetconMovementDb::update(&metcon_movement, &mut db).await?;
+            if last_epoch.is_some() {
+                rows = Some(
+                    MetconMovementDb::update_multiple_checked(
+                        &[metcon_movement],
+                        last_epoch,
+                        &mut db,
+                    )
+                    .await?,
+                );
+            } else {
+                MetconMovementDb::update(&metcon_movement, &mut db).await?;
+            }
         }
         UnverifiedSingleOrVec::Vec(metcon_movements) => {
             let metcon_movements = metcon_movements
                 .verify_user_ap_update(auth, &mut db)
                 .await?;
-            MetconMovementDb::update_multiple(&metcon_movements, &mut db).await?;
+            if row_results || last_epoch.is_some() {
+                rows = Some(
+                    MetconMovementDb::update_multiple_checked(
+                        &metcon_movements,
+                        last_epoch,
+                        &mut db,
+                    )
+                    .await?,
+                );
+            } else {
+                MetconMovementDb::update_multiple(&metcon_movements, &mut db).await?;
+            }
         }
     }
     let epoch = MetconMovementDb::get_epoch_by_user_optional(*auth, &mut db).await?;
-    Ok(Json(EpochResponse { epoch }))
+    Ok(BulkResponse { epoch, rows })
+}
+
+/// Get the metcon sessions of the user aggregated in time buckets.
+///
+/// If `movement_id` is set, only sessions of metcons containing the movement are considered.
+pub async fn get_metcon_statistics(
+    auth: AuthUserOrAP,
+    Query(StatisticIntervalOption { interval }): Query<StatisticIntervalOption>,
+    Query(MovementIdOption { movement_id }): Query<MovementIdOption>,
+    Query(time_span_option): Query<TimeSpanOption>,
+    mut db: DbConn,
+) -> HandlerResult<Json<Vec<MetconStatistic>>> {
+    auth.check_scope::<MetconSessionDb>(ScopeAccess::Read)?;
+
+    MetconSessionDb::get_statistics_by_user(
+        *auth,
+        interval,
+        movement_id,
+        time_span_option.into(),
+        &mut db,
+    )
+    .await
+    .map(Json)
+    .map_err(Into::into)
 }
@@ -1,6 +1,11 @@
-use axum::http::StatusCode;
+use axum::{
+    Json,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
 use chrono::{DateTime, Utc};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use sport_log_types::{Epoch, EpochResponse, MovementId, StatisticInterval};
 
 use crate::db::{Timespan, Unverified};
 pub use crate::error::*;
@@ -8,22 +13,26 @@ pub use crate::error::*;
 mod account;
 mod action;
 mod app;
+mod archive;
 mod cardio;
 mod diary_wod;
 mod metcon;
 mod movement;
 mod platform;
+mod scope;
 mod strength;
 mod user;
 
 pub use account::*;
 pub use action::*;
 pub use app::*;
+pub use archive::*;
 pub use cardio::*;
 pub use diary_wod::*;
 pub use metcon::*;
 pub use movement::*;
 pub use platform::*;
+pub use scope::*;
 pub use strength::*;
 pub use user::*;
 
@@ -40,6 +49,18 @@ pub struct IdOption<T> {
     pub id: Option<T>,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct MovementIdOption {
+    #[serde(default = "none")]
+    pub movement_id: Option<MovementId>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StatisticIntervalOption {
+    #[serde(default)]
+    pub interval: StatisticInterval,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct TimeSpanOption {
     #[serde(default = "none")]
@@ -48,6 +69,59 @@ pub struct TimeSpanOption {
     pub end: Option<DateTime<Utc>>,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct RowResultsOption {
+    /// If set, the results of bulk create and update requests are reported for every entry.
+    #[serde(default)]
+    pub row_results: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LastEpochOption {
+    /// The epoch of the table the client has last seen.
+    ///
+    /// If set, updates of entries which have been changed since then are rejected and the results
+    /// are reported for every entry.
+    #[serde(default = "none")]
+    pub last_epoch: Option<Epoch>,
+}
+
+/// Response of `POST` and `PUT` endpoints which accept one or multiple entries.
+///
+/// `rows` is only set if the results for every entry have been requested using
+/// [`RowResultsOption`] or [`LastEpochOption`].
+#[derive(Debug)]
+pub struct BulkResponse<I, T> {
+    pub epoch: Epoch,
+    pub rows: Option<Vec<RowResult<I, T>>>,
+}
+
+/// Response of bulk create and update requests with results for every entry.
+///
+/// If any entry conflicts with the existing data, none of the entries have been applied.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RowResultsResponse<I, T> {
+    pub epoch: Epoch,
+    pub rows: Vec<RowResult<I, T>>,
+}
+
+impl<I: Serialize, T: Serialize> IntoResponse for BulkResponse<I, T> {
+    fn into_response(self) -> Response {
+        match self.rows {
+            Some(rows) => {
+                let status = if rows.iter().all(RowResult::is_ok) {
+                    StatusCode::OK
+                } else {
+                    StatusCode::CONFLICT
+                };
+                let epoch = self.epoch;
+                (status, Json(RowResultsResponse { epoch, rows })).into_response()
+            }
+            None => Json(EpochResponse { epoch: self.epoch }).into_response(),
+        }
+    }
+}
+
 impl From<TimeSpanOption> for Timespan {
     fn from(tso: TimeSpanOption) -> Self {
         match tso {
@@ -1,18 +1,23 @@
 use axum::{Json, extract::Query};
-use sport_log_types::{EpochResponse, Movement, MovementId};
+use sport_log_types::{Movement, MovementId, ScopeAccess};
 
 use crate::{
     auth::*,
     db::*,
-    handler::{HandlerResult, IdOption, UnverifiedSingleOrVec},
+    handler::{
+        BulkResponse, HandlerResult, IdOption, LastEpochOption, RowResultsOption,
+        UnverifiedSingleOrVec,
+    },
     state::DbConn,
 };
 
 pub async fn create_movements(
     auth: AuthUserOrAP,
+    Query(RowResultsOption { row_results }): Query<RowResultsOption>,
     mut db: DbConn,
     Json(movements): Json<UnverifiedSingleOrVec<Movement>>,
-) -> HandlerResult<Json<EpochResponse>> {
+) -> HandlerResult<BulkResponse<MovementId, Movement>> {
+    let mut rows = None;
     match movements {
         UnverifiedSingleOrVec::Single(movement) => {
             let movement = movement.verify_user_ap_create(auth)?;
@@ -20,11 +25,15 @@ pub async fn create_movements(
         }
         UnverifiedSingleOrVec::Vec(movements) => {
             let movements = movements.verify_user_ap_create(auth)?;
-            MovementDb::create_multiple(&movements, &mut db).await?;
+            if row_results {
+                rows = Some(MovementDb::create_multiple_checked(&movements, &mut db).await?);
+            } else {
+                MovementDb::create_multiple(&movements, &mut db).await?;
+            }
         }
     }
     let epoch = MovementDb::get_epoch_by_user_optional(*auth, &mut db).await?;
-    Ok(Json(EpochResponse { epoch }))
+    Ok(BulkResponse { epoch, rows })
 }
 
 pub async fn get_movements(
@@ -32,6 +41,8 @@ pub async fn get_movements(
     Query(IdOption { id }): Query<IdOption<UnverifiedId<MovementId>>>,
     mut db: DbConn,
 ) -> HandlerResult<Json<Vec<Movement>>> {
+    auth.check_scope::<MovementDb>(ScopeAccess::Read)?;
+
     match id {
         Some(id) => {
             let movement_id = id.verify_user_ap_get(auth, &mut db).await?;
@@ -47,19 +58,34 @@ pub async fn get_movements(
 
 pub async fn update_movements(
     auth: AuthUserOrAP,
+    Query(RowResultsOption { row_results }): Query<RowResultsOption>,
+    Query(LastEpochOption { last_epoch }): Query<LastEpochOption>,
     mut db: DbConn,
     Json(movements): Json<UnverifiedSingleOrVec<Movement>>,
-) -> HandlerResult<Json<EpochResponse>> {
+) -> HandlerResult<BulkResponse<MovementId, Movement>> {
+    let mut rows = None;
     match movements {
         UnverifiedSingleOrVec::Single(movement) => {
             let movement = movement.verify_user_ap_update(auth, &mut db).await?;
-            MovementDb::update(&movement, &mut db).await?;
+            if last_epoch.is_some() {
+                rows = Some(
+                    MovementDb::update_multiple_checked(&[movement], last_epoch, &mut db).await?,
+                );
+            } else {
+                MovementDb::update(&movement, &mut db).await?;
+            }
         }
         UnverifiedSingleOrVec::Vec(movements) => {
             let movements = movements.verify_user_ap_update(auth, &mut db).await?;
-            MovementDb::update_multiple(&movements, &mut db).await?;
+            if row_results || last_epoch.is_some() {
+                rows = Some(
+                    MovementDb::update_multiple_checked(&movements, last_epoch, &mut db).await?,
+                );
+            } else {
+                MovementDb::update_multiple(&movements, &mut db).await?;
+            }
         }
     }
     let epoch = MovementDb::get_epoch_by_user_optional(*auth, &mut db).await?;
-    Ok(Json(EpochResponse { epoch }))
+    Ok(BulkResponse { epoch, rows })
 }
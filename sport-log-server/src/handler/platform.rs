@@ -10,6 +10,7 @@ use sport_log_types::{
 use crate::{
     auth::{AuthAdmin, AuthUser},
     config::Config,
+    crypto::CredentialKeys,
     db::*,
     handler::{ErrorMessage, HandlerError, HandlerResult, IdOption, UnverifiedSingleOrVec},
     state::DbConn,
@@ -135,21 +136,22 @@ pub async fn adm_update_platforms(
 
 pub async fn create_platform_credentials(
     auth: AuthUser,
+    State(keys): State<&CredentialKeys>,
     mut db: DbConn,
     Json(platform_credentials): Json<UnverifiedSingleOrVec<PlatformCredential>>,
 ) -> HandlerResult<Json<EpochResponse>> {
     match platform_credentials {
         UnverifiedSingleOrVec::Single(platform_credential) => {
-            let platform_credential = platform_credential
+            let mut platform_credential = platform_credential
                 .verify_user_create(auth)
                 .map_err(HandlerError::from)?;
-            PlatformCredentialDb::create(&platform_credential, &mut db).await?;
+            PlatformCredentialDb::create(&mut platform_credential, keys, &mut db).await?;
         }
         UnverifiedSingleOrVec::Vec(platform_credentials) => {
-            let platform_credentials = platform_credentials
+            let mut platform_credentials = platform_credentials
                 .verify_user_create(auth)
                 .map_err(HandlerError::from)?;
-            PlatformCredentialDb::create_multiple(&platform_credentials, &mut db).await?;
+            PlatformCredentialDb::create_multiple(&mut platform_credentials, keys, &mut db).await?;
         }
     }
     let epoch = PlatformCredentialDb::get_epoch_by_user(*auth, &mut db).await?;
@@ -176,23 +178,40 @@ pub async fn get_platform_credentials(
 
 pub async fn update_platform_credentials(
     auth: AuthUser,
+    State(keys): State<&CredentialKeys>,
     mut db: DbConn,
     Json(platform_credentials): Json<UnverifiedSingleOrVec<PlatformCredential>>,
 ) -> HandlerResult<Json<EpochResponse>> {
     match platform_credentials {
         UnverifiedSingleOrVec::Single(platform_credential) => {
-            let platform_credential = platform_credential
+            let mut platform_credential = platform_credential
                 .verify_user_update(auth, &mut db)
                 .await?;
-            PlatformCredentialDb::update(&platform_credential, &mut db).await?;
+            PlatformCredentialDb::update(&mut platform_credential, keys, &mut db).await?;
         }
         UnverifiedSingleOrVec::Vec(platform_credentials) => {
-            let platform_credentials = platform_credentials
+            let mut platform_credentials = platform_credentials
                 .verify_user_update(auth, &mut db)
                 .await?;
-            PlatformCredentialDb::update_multiple(&platform_credentials, &mut db).await?;
+            PlatformCredentialDb::update_multiple(&mut platform_credentials, keys, &mut db).await?;
         }
     }
     let epoch = PlatformCredentialDb::get_epoch_by_user(*auth, &mut db).await?;
     Ok(Json(EpochResponse { epoch }))
 }
+
+/// Re-encrypt all platform credentials with the current key.
+///
+/// This has to be called after the `credential_key` has been replaced.
+/// Afterwards the old key can be removed from `old_credential_keys`.
+/// Passwords stored in plaintext are also encrypted at server startup.
+pub async fn adm_rotate_credential_key(
+    _auth: AuthAdmin,
+    State(keys): State<&CredentialKeys>,
+    mut db: DbConn,
+) -> HandlerResult<Json<usize>> {
+    PlatformCredentialDb::reencrypt_all(keys, &mut db)
+        .await
+        .map(Json)
+        .map_err(Into::into)
+}
@@ -0,0 +1,143 @@
+use axum::{Json, extract::Query, http::StatusCode};
+use sport_log_types::{
+    ActionProviderScope, ActionProviderScopeId, EpochResponse, ScopeGrant, ScopeGrantId,
+};
+
+use crate::{
+    auth::*,
+    db::*,
+    handler::{HandlerResult, IdOption, UnverifiedSingleOrVec},
+    state::DbConn,
+};
+
+pub async fn ap_create_action_provider_scopes(
+    auth: AuthAP,
+    mut db: DbConn,
+    Json(action_provider_scopes): Json<UnverifiedSingleOrVec<ActionProviderScope>>,
+) -> HandlerResult<StatusCode> {
+    match action_provider_scopes {
+        UnverifiedSingleOrVec::Single(action_provider_scope) => {
+            let action_provider_scope = action_provider_scope.verify_ap_create(auth)?;
+            ActionProviderScopeDb::create(&action_provider_scope, &mut db).await?;
+        }
+        UnverifiedSingleOrVec::Vec(action_provider_scopes) => {
+            let action_provider_scopes = action_provider_scopes.verify_ap_create(auth)?;
+            ActionProviderScopeDb::create_multiple(&action_provider_scopes, &mut db).await?;
+        }
+    }
+    Ok(StatusCode::OK)
+}
+
+pub async fn ap_get_action_provider_scopes(
+    auth: AuthAP,
+    Query(IdOption { id }): Query<IdOption<UnverifiedId<ActionProviderScopeId>>>,
+    mut db: DbConn,
+) -> HandlerResult<Json<Vec<ActionProviderScope>>> {
+    match id {
+        Some(id) => {
+            let action_provider_scope_id = id.verify_ap_get(auth, &mut db).await?;
+            ActionProviderScopeDb::get_by_id(action_provider_scope_id, &mut db)
+                .await
+                .map(|s| vec![s])
+        }
+        None => ActionProviderScopeDb::get_by_action_provider(*auth, &mut db).await,
+    }
+    .map(Json)
+    .map_err(Into::into)
+}
+
+pub async fn ap_update_action_provider_scopes(
+    auth: AuthAP,
+    mut db: DbConn,
+    Json(action_provider_scopes): Json<UnverifiedSingleOrVec<ActionProviderScope>>,
+) -> HandlerResult<StatusCode> {
+    match action_provider_scopes {
+        UnverifiedSingleOrVec::Single(action_provider_scope) => {
+            let action_provider_scope = action_provider_scope
+                .verify_ap_update(auth, &mut db)
+                .await?;
+            ActionProviderScopeDb::update(&action_provider_scope, &mut db).await?;
+        }
+        UnverifiedSingleOrVec::Vec(action_provider_scopes) => {
+            let action_provider_scopes = action_provider_scopes
+                .verify_ap_update(auth, &mut db)
+                .await?;
+            ActionProviderScopeDb::update_multiple(&action_provider_scopes, &mut db).await?;
+        }
+    }
+    Ok(StatusCode::OK)
+}
+
+pub async fn get_action_provider_scopes(
+    _auth: AuthUser,
+    Query(IdOption { id }): Query<IdOption<UnverifiedId<ActionProviderScopeId>>>,
+    mut db: DbConn,
+) -> HandlerResult<Json<Vec<ActionProviderScope>>> {
+    match id {
+        Some(id) => {
+            let action_provider_scope_id = id.verify_unchecked_get()?;
+            ActionProviderScopeDb::get_by_id(action_provider_scope_id, &mut db)
+                .await
+                .map(|s| vec![s])
+        }
+        None => ActionProviderScopeDb::get_all(&mut db).await,
+    }
+    .map(Json)
+    .map_err(Into::into)
+}
+
+pub async fn create_scope_grants(
+    auth: AuthUser,
+    mut db: DbConn,
+    Json(scope_grants): Json<UnverifiedSingleOrVec<ScopeGrant>>,
+) -> HandlerResult<Json<EpochResponse>> {
+    match scope_grants {
+        UnverifiedSingleOrVec::Single(scope_grant) => {
+            let scope_grant = scope_grant.verify_user_create(auth)?;
+            ScopeGrantDb::create(&scope_grant, &mut db).await?;
+        }
+        UnverifiedSingleOrVec::Vec(scope_grants) => {
+            let scope_grants = scope_grants.verify_user_create(auth)?;
+            ScopeGrantDb::create_multiple(&scope_grants, &mut db).await?;
+        }
+    }
+    let epoch = ScopeGrantDb::get_epoch_by_user(*auth, &mut db).await?;
+    Ok(Json(EpochResponse { epoch }))
+}
+
+pub async fn get_scope_grants(
+    auth: AuthUser,
+    Query(IdOption { id }): Query<IdOption<UnverifiedId<ScopeGrantId>>>,
+    mut db: DbConn,
+) -> HandlerResult<Json<Vec<ScopeGrant>>> {
+    match id {
+        Some(id) => {
+            let scope_grant_id = id.verify_user_get(auth, &mut db).await?;
+            ScopeGrantDb::get_by_id(scope_grant_id, &mut db)
+                .await
+                .map(|s| vec![s])
+        }
+        None => ScopeGrantDb::get_by_user(*auth, &mut db).await,
+    }
+    .map(Json)
+    .map_err(Into::into)
+}
+
+pub async fn update_scope_grants(
+    auth: AuthUser,
+    mut db: DbConn,
+    Json(scope_grants): Json<UnverifiedSingleOrVec<ScopeGrant>>,
+) -> HandlerResult<Json<EpochResponse>> {
+    match scope_grants {
+        UnverifiedSingleOrVec::Single(scope_grant) => {
+            let scope_grant = scope_grant.verify_user_update(auth, &mut db).await?;
+            ScopeGrantDb::update(&scope_grant, &mut db).await?;
+        }
+        UnverifiedSingleOrVec::Vec(scope_grants) => {
+            let scope_grants = scope_grants.verify_user_update(auth, &mut db).await?;
+            ScopeGrantDb::update_multiple(&scope_grants, &mut db).await?;
+        }
+    }
+    let epoch = ScopeGrantDb::get_epoch_by_user(*auth, &mut db).await?;
+    Ok(Json(EpochResponse { epoch }))
+}
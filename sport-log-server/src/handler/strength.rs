@@ -1,20 +1,26 @@
 use axum::{Json, extract::Query};
 use sport_log_types::{
-    Eorm, EpochResponse, StrengthSession, StrengthSessionId, StrengthSet, StrengthSetId,
+    Eorm, ScopeAccess, StrengthRecords, StrengthSession, StrengthSessionId, StrengthSet,
+    StrengthSetId, StrengthStatistic,
 };
 
 use crate::{
     auth::AuthUserOrAP,
     db::*,
-    handler::{HandlerResult, IdOption, TimeSpanOption, UnverifiedSingleOrVec},
+    handler::{
+        BulkResponse, HandlerResult, IdOption, LastEpochOption, MovementIdOption, RowResultsOption,
+        StatisticIntervalOption, TimeSpanOption, UnverifiedSingleOrVec,
+    },
     state::DbConn,
 };
 
 pub async fn create_strength_sessions(
     auth: AuthUserOrAP,
+    Query(RowResultsOption { row_results }): Query<RowResultsOption>,
     mut db: DbConn,
     Json(strength_sessions): Json<UnverifiedSingleOrVec<StrengthSession>>,
-) -> HandlerResult<Json<EpochResponse>> {
+) -> HandlerResult<BulkResponse<StrengthSessionId, StrengthSession>> {
+    let mut rows = None;
     match strength_sessions {
         UnverifiedSingleOrVec::Single(strength_session) => {
             let strength_session = strength_session.verify_user_ap_create(auth)?;
@@ -22,11 +28,17 @@ pub async fn create_strength_sessions(
         }
         UnverifiedSingleOrVec::Vec(strength_sessions) => {
             let strength_sessions = strength_sessions.verify_user_ap_create(auth)?;
-            StrengthSessionDb::create_multiple(&strength_sessions, &mut db).await?;
+            if row_results {
+                rows = Some(
+                    StrengthSessionDb::create_multiple_checked(&strength_sessions, &mut db).await?,
+                );
+            } else {
+                StrengthSessionDb::create_multiple(&strength_sessions, &mut db).await?;
+            }
         }
     }
     let epoch = StrengthSessionDb::get_epoch_by_user(*auth, &mut db).await?;
-    Ok(Json(EpochResponse { epoch }))
+    Ok(BulkResponse { epoch, rows })
 }
 
 pub async fn get_strength_sessions(
@@ -35,6 +47,8 @@ pub async fn get_strength_sessions(
     Query(time_span_option): Query<TimeSpanOption>,
     mut db: DbConn,
 ) -> HandlerResult<Json<Vec<StrengthSession>>> {
+    auth.check_scope::<StrengthSessionDb>(ScopeAccess::Read)?;
+
     match id {
         Some(id) => {
             let strength_session_id = id.verify_user_ap_get(auth, &mut db).await?;
@@ -53,32 +67,59 @@ pub async fn get_strength_sessions(
 
 pub async fn update_strength_sessions(
     auth: AuthUserOrAP,
+    Query(RowResultsOption { row_results }): Query<RowResultsOption>,
+    Query(LastEpochOption { last_epoch }): Query<LastEpochOption>,
     mut db: DbConn,
     Json(strength_sessions): Json<UnverifiedSingleOrVec<StrengthSession>>,
-) -> HandlerResult<Json<EpochResponse>> {
+) -> HandlerResult<BulkResponse<StrengthSessionId, StrengthSession>> {
+    let mut rows = None;
     match strength_sessions {
         UnverifiedSingleOrVec::Single(strength_session) => {
             let strength_session = strength_session
                 .verify_user_ap_update(auth, &mut db)
                 .await?;
-            StrengthSessionDb::update(&strength_session, &mut db).await?;
+            if last_epoch.is_some() {
+                rows = Some(
+                    StrengthSessionDb::update_multiple_checked(
+                        &[strength_session],
+                        last_epoch,
+                        &mut db,
+                    )
+                    .await?,
+                );
+            } else {
+                StrengthSessionDb::update(&strength_session, &mut db).await?;
+            }
         }
         UnverifiedSingleOrVec::Vec(strength_sessions) => {
             let strength_sessions = strength_sessions
                 .verify_user_ap_update(auth, &mut db)
                 .await?;
-            StrengthSessionDb::update_multiple(&strength_sessions, &mut db).await?;
+            if row_results || last_epoch.is_some() {
+                rows = Some(
+                    StrengthSessionDb::update_multiple_checked(
+                        &strength_sessions,
+                        last_epoch,
+                        &mut db,
+                    )
+                    .await?,
+                );
+            } else {
+                StrengthSessionDb::update_multiple(&strength_sessions, &mut db).await?;
+            }
         }
     }
     let epoch = StrengthSessionDb::get_epoch_by_user(*auth, &mut db).await?;
-    Ok(Json(EpochResponse { epoch }))
+    Ok(BulkResponse { epoch, rows })
 }
 
 pub async fn create_strength_sets(
     auth: AuthUserOrAP,
+    Query(RowResultsOption { row_results }): Query<RowResultsOption>,
     mut db: DbConn,
     Json(strength_sets): Json<UnverifiedSingleOrVec<StrengthSet>>,
-) -> HandlerResult<Json<EpochResponse>> {
+) -> HandlerResult<BulkResponse<StrengthSetId, StrengthSet>> {
+    let mut rows = None;
     match strength_sets {
         UnverifiedSingleOrVec::Single(strength_set) => {
             let strength_set = strength_set.verify_user_ap_create(auth)?;
@@ -86,11 +127,15 @@ pub async fn create_strength_sets(
         }
         UnverifiedSingleOrVec::Vec(strength_sets) => {
             let strength_sets = strength_sets.verify_user_ap_create(auth)?;
-            StrengthSetDb::create_multiple(&strength_sets, &mut db).await?;
+            if row_results {
+                rows = Some(StrengthSetDb::create_multiple_checked(&strength_sets, &mut db).await?);
+            } else {
+                StrengthSetDb::create_multiple(&strength_sets, &mut db).await?;
+            }
         }
     }
     let epoch = StrengthSetDb::get_epoch_by_user(*auth, &mut db).await?;
-    Ok(Json(EpochResponse { epoch }))
+    Ok(BulkResponse { epoch, rows })
 }
 
 pub async fn get_strength_sets(
@@ -98,6 +143,8 @@ pub async fn get_strength_sets(
     Query(IdOption { id }): Query<IdOption<UnverifiedId<StrengthSetId>>>,
     mut db: DbConn,
 ) -> HandlerResult<Json<Vec<StrengthSet>>> {
+    auth.check_scope::<StrengthSetDb>(ScopeAccess::Read)?;
+
     match id {
         Some(id) => {
             let strength_set_id = id.verify_user_ap_get(auth, &mut db).await?;
@@ -113,21 +160,74 @@ pub async fn get_strength_sets(
 
 pub async fn update_strength_sets(
     auth: AuthUserOrAP,
+    Query(RowResultsOption { row_results }): Query<RowResultsOption>,
+    Query(LastEpochOption { last_epoch }): Query<LastEpochOption>,
     mut db: DbConn,
     Json(strength_sets): Json<UnverifiedSingleOrVec<StrengthSet>>,
-) -> HandlerResult<Json<EpochResponse>> {
+) -> HandlerResult<BulkResponse<StrengthSetId, StrengthSet>> {
+    let mut rows = None;
     match strength_sets {
         UnverifiedSingleOrVec::Single(strength_set) => {
             let strength_set = strength_set.verify_user_ap_update(auth, &mut db).await?;
-            StrengthSetDb::update(&strength_set, &mut db).await?;
+            if last_epoch.is_some() {
+                rows = Some(
+                    StrengthSetDb::update_multiple_checked(&[strength_set], last_epoch, &mut db)
+                        .await?,
+                );
+            } else {
+                StrengthSetDb::update(&strength_set, &mut db).await?;
+            }
         }
         UnverifiedSingleOrVec::Vec(strength_sets) => {
             let strength_sets = strength_sets.verify_user_ap_update(auth, &mut db).await?;
-            StrengthSetDb::update_multiple(&strength_sets, &mut db).await?;
+            if row_results || last_epoch.is_some() {
+                rows = Some(
+                    StrengthSetDb::update_multiple_checked(&strength_sets, last_epoch, &mut db)
+                        .await?,
+                );
+            } else {
+                StrengthSetDb::update_multiple(&strength_sets, &mut db).await?;
+            }
         }
     }
     let epoch = StrengthSetDb::get_epoch_by_user(*auth, &mut db).await?;
-    Ok(Json(EpochResponse { epoch }))
+    Ok(BulkResponse { epoch, rows })
+}
+
+/// Get the personal records of the user for every movement or only for `movement_id`.
+pub async fn get_strength_records(
+    auth: AuthUserOrAP,
+    Query(MovementIdOption { movement_id }): Query<MovementIdOption>,
+    mut db: DbConn,
+) -> HandlerResult<Json<Vec<StrengthRecords>>> {
+    auth.check_scope::<StrengthSetDb>(ScopeAccess::Read)?;
+
+    StrengthSetDb::get_records_by_user(*auth, movement_id, &mut db)
+        .await
+        .map(Json)
+        .map_err(Into::into)
+}
+
+/// Get the strength sessions of the user aggregated in time buckets.
+pub async fn get_strength_statistics(
+    auth: AuthUserOrAP,
+    Query(StatisticIntervalOption { interval }): Query<StatisticIntervalOption>,
+    Query(MovementIdOption { movement_id }): Query<MovementIdOption>,
+    Query(time_span_option): Query<TimeSpanOption>,
+    mut db: DbConn,
+) -> HandlerResult<Json<Vec<StrengthStatistic>>> {
+    auth.check_scope::<StrengthSessionDb>(ScopeAccess::Read)?;
+
+    StrengthSessionDb::get_statistics_by_user(
+        *auth,
+        interval,
+        movement_id,
+        time_span_option.into(),
+        &mut db,
+    )
+    .await
+    .map(Json)
+    .map_err(Into::into)
 }
 
 pub async fn get_eorms(_auth: AuthUserOrAP, mut db: DbConn) -> HandlerResult<Json<Vec<Eorm>>> {
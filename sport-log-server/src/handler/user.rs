@@ -1,8 +1,12 @@
 use axum::{Json, extract::State, http::StatusCode};
-use sport_log_types::{EpochResponse, User};
+use axum_extra::{
+    TypedHeader,
+    headers::{Authorization, authorization::Basic},
+};
+use sport_log_types::{AuthTokens, EpochResponse, RefreshToken, User};
 
 use crate::{
-    auth::{AuthAdmin, AuthUser},
+    auth::{AuthAdmin, AuthSession, AuthUser},
     config::Config,
     db::*,
     handler::{ErrorMessage, HandlerError, HandlerResult, UnverifiedSingleOrVec, check_password},
@@ -75,3 +79,36 @@ pub async fn delete_user(auth: AuthUser, mut db: DbConn) -> HandlerResult<Status
     UserDb::delete(*auth, &mut db).await?;
     Ok(StatusCode::OK)
 }
+
+pub async fn login(
+    TypedHeader(auth): TypedHeader<Authorization<Basic>>,
+    mut db: DbConn,
+) -> HandlerResult<Json<AuthTokens>> {
+    let user_id = UserDb::auth(auth.username(), auth.password(), &mut db)
+        .await
+        .map_err(|_| HandlerError::from(StatusCode::UNAUTHORIZED))?;
+    AuthSessionDb::create(user_id, &mut db)
+        .await
+        .map(Json)
+        .map_err(Into::into)
+}
+
+pub async fn refresh(
+    mut db: DbConn,
+    Json(refresh_token): Json<RefreshToken>,
+) -> HandlerResult<Json<AuthTokens>> {
+    AuthSessionDb::refresh(&refresh_token.refresh_token, &mut db)
+        .await
+        .map(Json)
+        .map_err(|_| HandlerError::from(StatusCode::UNAUTHORIZED))
+}
+
+pub async fn logout(auth: AuthSession, mut db: DbConn) -> HandlerResult<StatusCode> {
+    AuthSessionDb::delete(auth.session_id(), &mut db).await?;
+    Ok(StatusCode::OK)
+}
+
+pub async fn logout_all(auth: AuthUser, mut db: DbConn) -> HandlerResult<StatusCode> {
+    AuthSessionDb::delete_by_user(*auth, &mut db).await?;
+    Ok(StatusCode::OK)
+}
@@ -0,0 +1,332 @@
+//! Heatmap tiles of the tracks of the cardio sessions of a user.
+//!
+//! The tiles use the XYZ scheme of web maps and are rendered as PNG images.
+//! The color of a pixel depends on the number of tracks passing through it.
+//! Rendered tiles are cached together with the epoch of the cardio sessions of the user, so they
+//! are rendered again as soon as a cardio session of the user changes.
+
+use std::{
+    collections::HashMap,
+    f64::consts::PI,
+    hash::{DefaultHasher, Hash, Hasher},
+    io::Write,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use axum::{
+    body::Bytes,
+    http::{HeaderMap, StatusCode, header::IF_NONE_MATCH},
+};
+use chrono::{DateTime, Utc};
+use flate2::{Compression, Crc, write::ZlibEncoder};
+use serde::Deserialize;
+use sport_log_types::{CardioType, Epoch, MovementId, Position, UserId};
+
+use crate::{
+    db::Timespan,
+    error::{ErrorMessage, HandlerError},
+    handler::TimeSpanOption,
+};
+
+/// The width and height of a tile in pixels.
+const TILE_SIZE: usize = 256;
+
+/// The maximal zoom level of a tile.
+const MAX_ZOOM: u32 = 20;
+
+/// The maximal latitude of the web mercator projection.
+const MAX_LATITUDE: f64 = 85.051_128_78;
+
+/// The number of tracks through a pixel at which its color is saturated.
+const SATURATION: f64 = 25.;
+
+/// The number of tiles that are cached.
+const CACHE_CAPACITY: usize = 4096;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize)]
+pub struct HeatmapOption {
+    #[serde(default)]
+    pub movement_id: Option<MovementId>,
+    #[serde(default)]
+    pub cardio_type: Option<CardioType>,
+    #[serde(default)]
+    pub start: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub end: Option<DateTime<Utc>>,
+}
+
+impl HeatmapOption {
+    pub fn timespan(&self) -> Timespan {
+        TimeSpanOption {
+            start: self.start,
+            end: self.end,
+        }
+        .into()
+    }
+}
+
+/// A tile at zoom level `z` with the column `x` and the row `y`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Tile {
+    z: u32,
+    x: u32,
+    y: u32,
+}
+
+impl Tile {
+    pub fn new(z: u32, x: u32, y: u32) -> Result<Self, HandlerError> {
+        if z > MAX_ZOOM || x >= 1 << z || y >= 1 << z {
+            return Err(HandlerError::from((
+                StatusCode::BAD_REQUEST,
+                ErrorMessage::Other {
+                    error: format!("The tile {z}/{x}/{y} does not exist."),
+                },
+            )));
+        }
+        Ok(Self { z, x, y })
+    }
+
+    fn scale(self) -> f64 {
+        f64::from(1u32 << self.z)
+    }
+
+    /// The bounding box of the tile as minimal longitude, minimal latitude, maximal longitude and
+    /// maximal latitude.
+    pub fn bounds(self) -> (f64, f64, f64, f64) {
+        let longitude = |x: u32| f64::from(x) / self.scale() * 360. - 180.;
+        let latitude = |y: u32| {
+            (PI * (1. - 2. * f64::from(y) / self.scale()))
+                .sinh()
+                .atan()
+                .to_degrees()
+        };
+        (
+            longitude(self.x),
+            latitude(self.y + 1),
+            longitude(self.x + 1),
+            latitude(self.y),
+        )
+    }
+
+    /// The position in pixels relative to the top left corner of the tile.
+    fn pixel(self, position: &Position) -> (f64, f64) {
+        let size = self.scale() * TILE_SIZE as f64;
+        let latitude = position
+            .latitude
+            .clamp(-MAX_LATITUDE, MAX_LATITUDE)
+            .to_radians();
+        let x = (position.longitude + 180.) / 360. * size;
+        let y = (1. - latitude.tan().asinh() / PI) / 2. * size;
+        (
+            x - f64::from(self.x) * TILE_SIZE as f64,
+            y - f64::from(self.y) * TILE_SIZE as f64,
+        )
+    }
+}
+
+/// Clip the line from `a` to `b` to the area of a tile.
+fn clip((x1, y1): (f64, f64), (x2, y2): (f64, f64)) -> Option<((f64, f64), (f64, f64))> {
+    let (dx, dy) = (x2 - x1, y2 - y1);
+    let max = TILE_SIZE as f64;
+    let (mut from, mut to) = (0f64, 1f64);
+    for (p, q) in [(-dx, x1), (dx, max - x1), (-dy, y1), (dy, max - y1)] {
+        if p == 0. {
+            if q < 0. {
+                return None;
+            }
+        } else if p < 0. {
+            from = from.max(q / p);
+        } else {
+            to = to.min(q / p);
+        }
+    }
+    (from <= to).then_some((
+        (x1 + from * dx, y1 + from * dy),
+        (x1 + to * dx, y1 + to * dy),
+    ))
+}
+
+/// The number of tracks passing through every pixel of a tile.
+struct Counts {
+    counts: Vec<u32>,
+    /// The index of the last track that passed through every pixel plus one.
+    visited: Vec<usize>,
+}
+
+impl Counts {
+    fn new() -> Self {
+        Self {
+            counts: vec![0; TILE_SIZE * TILE_SIZE],
+            visited: vec![0; TILE_SIZE * TILE_SIZE],
+        }
+    }
+
+    fn visit(&mut self, track: usize, (x, y): (f64, f64)) {
+        if !(0. ..TILE_SIZE as f64).contains(&x) || !(0. ..TILE_SIZE as f64).contains(&y) {
+            return;
+        }
+        let index = y as usize * TILE_SIZE + x as usize;
+        if self.visited[index] != track + 1 {
+            self.visited[index] = track + 1;
+            self.counts[index] += 1;
+        }
+    }
+
+    fn draw(&mut self, track: usize, a: (f64, f64), b: (f64, f64)) {
+        let Some(((x1, y1), (x2, y2))) = clip(a, b) else {
+            return;
+        };
+        let steps = (x2 - x1).abs().max((y2 - y1).abs()).ceil().max(1.);
+        for step in 0..=steps as usize {
+            let fraction = step as f64 / steps;
+            self.visit(
+                track,
+                (x1 + fraction * (x2 - x1), y1 + fraction * (y2 - y1)),
+            );
+        }
+    }
+
+    /// The colors of the pixels as RGBA.
+    fn colors(&self) -> Vec<u8> {
+        let mut colors = Vec::with_capacity(self.counts.len() * 4);
+        for &count in &self.counts {
+            if count == 0 {
+                colors.extend([0; 4]);
+                continue;
+            }
+            let heat = ((1. + f64::from(count)).ln() / (1. + SATURATION).ln()).min(1.);
+            colors.extend([
+                255,
+                (64. + 191. * heat) as u8,
+                (255. * heat.powi(3)) as u8,
+                (128. + 127. * heat) as u8,
+            ]);
+        }
+        colors
+    }
+}
+
+/// Render the tracks to a tile.
+///
+/// Rendering takes a while for many tracks, so it should not run on the async runtime.
+pub fn render(tile: Tile, tracks: &[Vec<Position>]) -> Bytes {
+    let mut counts = Counts::new();
+    for (index, track) in tracks.iter().enumerate() {
+        let pixels: Vec<_> = track.iter().map(|position| tile.pixel(position)).collect();
+        match *pixels.as_slice() {
+            [] => {}
+            [pixel] => counts.visit(index, pixel),
+            _ => {
+                for pair in pixels.windows(2) {
+                    counts.draw(index, pair[0], pair[1]);
+                }
+            }
+        }
+    }
+    encode_png(TILE_SIZE as u32, TILE_SIZE as u32, &counts.colors()).into()
+}
+
+/// Encode RGBA pixels as PNG image.
+fn encode_png(width: u32, height: u32, rgba: &[u8]) -> Vec<u8> {
+    fn chunk(png: &mut Vec<u8>, kind: [u8; 4], data: &[u8]) {
+        let mut crc = Crc::new();
+        crc.update(&kind);
+        crc.update(data);
+        png.extend((data.len() as u32).to_be_bytes());
+        png.extend(kind);
+        png.extend(data);
+        png.extend(crc.sum().to_be_bytes());
+    }
+
+    let mut header = Vec::with_capacity(13);
+    header.extend(width.to_be_bytes());
+    header.extend(height.to_be_bytes());
+    // bit depth 8, color type RGBA, default compression, filter and no interlace
+    header.extend([8, 6, 0, 0, 0]);
+
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    for row in rgba.chunks(width as usize * 4) {
+        // writing to a vector does not fail
+        let _ = encoder.write_all(&[0]);
+        let _ = encoder.write_all(row);
+    }
+    let data = encoder.finish().unwrap_or_default();
+
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    chunk(&mut png, *b"IHDR", &header);
+    chunk(&mut png, *b"IDAT", &data);
+    chunk(&mut png, *b"IEND", &[]);
+    png
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct HeatmapKey {
+    pub user_id: UserId,
+    pub tile: Tile,
+    pub option: HeatmapOption,
+}
+
+impl HeatmapKey {
+    /// The entity tag of the tile rendered for `epoch`.
+    pub fn etag(&self, epoch: Epoch) -> String {
+        let mut hasher = DefaultHasher::new();
+        self.option.hash(&mut hasher);
+        let Tile { z, x, y } = self.tile;
+        format!(
+            r#""{}-{z}-{x}-{y}-{}-{:016x}""#,
+            self.user_id.0,
+            epoch.0,
+            hasher.finish()
+        )
+    }
+}
+
+/// Check whether an `If-None-Match` header matches `etag`.
+///
+/// The header may contain a list of entity tags or `*`.
+/// Weak entity tags are compared by their value.
+pub fn if_none_match(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get_all(IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .any(|tag| tag == "*" || tag.strip_prefix("W/").unwrap_or(tag) == etag)
+}
+
+/// The rendered tiles together with the epoch of the cardio sessions they were rendered for.
+#[derive(Debug, Clone, Default)]
+pub struct HeatmapCache(Arc<Mutex<HashMap<HeatmapKey, (Epoch, Bytes)>>>);
+
+impl HeatmapCache {
+    /// Get a tile if it was rendered for `epoch`.
+    pub fn get(&self, key: &HeatmapKey, epoch: Epoch) -> Option<Bytes> {
+        let mut tiles = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        match tiles.get(key) {
+            Some((cached_epoch, tile)) if *cached_epoch == epoch => Some(tile.clone()),
+            Some(_) => {
+                tiles.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Cache a tile.
+    ///
+    /// If the cache is full, the outdated tiles of the user are removed first and all tiles if
+    /// this is not enough.
+    pub fn insert(&self, key: HeatmapKey, epoch: Epoch, tile: Bytes) {
+        let mut tiles = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        if tiles.len() >= CACHE_CAPACITY {
+            tiles.retain(|cached_key, (cached_epoch, _)| {
+                cached_key.user_id != key.user_id || *cached_epoch == epoch
+            });
+            if tiles.len() >= CACHE_CAPACITY {
+                tiles.clear();
+            }
+        }
+        tiles.insert(key, (epoch, tile));
+    }
+}
@@ -210,6 +210,98 @@ define_derive_deftly! {
     }
 }
 
+define_derive_deftly! {
+    GetPageByUserAndEpoch:
+
+    impl crate::db::GetPageByUserAndEpoch for crate::db::$ttype {
+        async fn get_page_by_user_and_epoch(
+            user_id: sport_log_types::UserId,
+            after_epoch: sport_log_types::Epoch,
+            after_id: i64,
+            limit: i64,
+            db: &mut diesel_async::AsyncPgConnection
+        ) -> diesel::result::QueryResult<Vec<(sport_log_types::Epoch, i64, Self::Type)>> {
+            use crate::db::{Db, DbWithUserId, ModifiableDb};
+            use diesel_async::RunQueryDsl;
+            use diesel::prelude::*;
+
+            Self::table()
+                .filter(Self::user_id_column().eq(user_id))
+                .filter(
+                    Self::epoch_column().gt(after_epoch).or(Self::epoch_column()
+                        .eq(after_epoch)
+                        .and(Self::id_column().gt(after_id)))
+                )
+                .order_by((Self::epoch_column(), Self::id_column()))
+                .limit(limit)
+                .select((Self::epoch_column(), Self::id_column(), Self::Type::as_select()))
+                .get_results(db)
+                .await
+        }
+    }
+}
+
+define_derive_deftly! {
+    GetPageByUserAndEpochOptional:
+
+    impl crate::db::GetPageByUserAndEpoch for crate::db::$ttype {
+        async fn get_page_by_user_and_epoch(
+            user_id: sport_log_types::UserId,
+            after_epoch: sport_log_types::Epoch,
+            after_id: i64,
+            limit: i64,
+            db: &mut diesel_async::AsyncPgConnection
+        ) -> diesel::result::QueryResult<Vec<(sport_log_types::Epoch, i64, Self::Type)>> {
+            use crate::db::{Db, DbWithUserId, ModifiableDb};
+            use diesel_async::RunQueryDsl;
+            use diesel::prelude::*;
+
+            Self::table()
+                .filter(Self::user_id_column().eq(user_id).or(Self::user_id_column().is_null()))
+                .filter(
+                    Self::epoch_column().gt(after_epoch).or(Self::epoch_column()
+                        .eq(after_epoch)
+                        .and(Self::id_column().gt(after_id)))
+                )
+                .order_by((Self::epoch_column(), Self::id_column()))
+                .limit(limit)
+                .select((Self::epoch_column(), Self::id_column(), Self::Type::as_select()))
+                .get_results(db)
+                .await
+        }
+    }
+}
+
+define_derive_deftly! {
+    GetPageByEpoch:
+
+    impl crate::db::GetPageByUserAndEpoch for crate::db::$ttype {
+        async fn get_page_by_user_and_epoch(
+            _user_id: sport_log_types::UserId,
+            after_epoch: sport_log_types::Epoch,
+            after_id: i64,
+            limit: i64,
+            db: &mut diesel_async::AsyncPgConnection
+        ) -> diesel::result::QueryResult<Vec<(sport_log_types::Epoch, i64, Self::Type)>> {
+            use crate::db::{Db, ModifiableDb};
+            use diesel_async::RunQueryDsl;
+            use diesel::prelude::*;
+
+            Self::table()
+                .filter(
+                    Self::epoch_column().gt(after_epoch).or(Self::epoch_column()
+                        .eq(after_epoch)
+                        .and(Self::id_column().gt(after_id)))
+                )
+                .order_by((Self::epoch_column(), Self::id_column()))
+                .limit(limit)
+                .select((Self::epoch_column(), Self::id_column(), Self::Type::as_select()))
+                .get_results(db)
+                .await
+        }
+    }
+}
+
 define_derive_deftly! {
     GetAll:
 
@@ -270,6 +362,130 @@ define_derive_deftly! {
             }).await
         }
     }
+
+    impl crate::db::UpdateIfUnchanged for crate::db::$ttype {
+        async fn update_if_unchanged(
+            value: &Self::Type,
+            epoch: sport_log_types::Epoch,
+            db: &mut diesel_async::AsyncPgConnection
+        ) -> diesel::result::QueryResult<Option<sport_log_types::Epoch>> {
+            use crate::db::{Db, ModifiableDb};
+            use diesel_async::RunQueryDsl;
+            use diesel::prelude::*;
+
+            let rows = diesel::update(
+                    Self::table()
+                        .find(value.id)
+                        .filter(Self::epoch_column().le(epoch))
+                )
+                .set(value)
+                .execute(db)
+                .await?;
+            if rows > 0 {
+                return Ok(None);
+            }
+            // the entry either does not exist or has been changed since `epoch`
+            Self::table()
+                .find(value.id)
+                .select(Self::epoch_column())
+                .get_result(db)
+                .await
+                .map(Some)
+        }
+    }
+}
+
+define_derive_deftly! {
+    CheckedMultiple:
+
+    impl crate::db::CheckedMultiple for crate::db::$ttype {
+        fn id(value: &Self::Type) -> Self::Id {
+            value.id
+        }
+
+        async fn get_conflicting(
+            value: &Self::Type,
+            error: &diesel::result::Error,
+            db: &mut diesel_async::AsyncPgConnection
+        ) -> diesel::result::QueryResult<Option<Self::Type>> {
+            use crate::db::{Db, DbWithUserId, GetByUniqueKey, UniqueKey};
+            use diesel_async::RunQueryDsl;
+            use diesel::prelude::*;
+
+            match UniqueKey::from_error(error) {
+                Some(UniqueKey::PrimaryKey) => Self::table()
+                    .find(value.id)
+                    .filter(Self::user_id_column().eq(value.user_id))
+                    .select(Self::Type::as_select())
+                    .get_result(db)
+                    .await
+                    .optional(),
+                Some(UniqueKey::Index) => Self::get_by_unique_key(value, db).await,
+                None => Ok(None),
+            }
+        }
+    }
+}
+
+define_derive_deftly! {
+    Archive:
+
+    impl crate::db::Archive for crate::db::$ttype {
+        async fn get_archived_by_user(
+            user_id: sport_log_types::UserId,
+            db: &mut diesel_async::AsyncPgConnection
+        ) -> diesel::result::QueryResult<Vec<sport_log_types::Archived<Self::Type>>> {
+            use crate::db::{ArchivableDb, Db, DbWithUserId};
+            use diesel_async::RunQueryDsl;
+            use diesel::prelude::*;
+
+            Self::table()
+                .filter(Self::user_id_column().eq(user_id))
+                .filter(Self::archived_at_column().is_not_null())
+                .order_by(Self::archived_at_column().desc())
+                .select((Self::archived_at_column().assume_not_null(), Self::Type::as_select()))
+                .get_results(db)
+                .await
+                .map(|entries| {
+                    entries
+                        .into_iter()
+                        .map(|(archived_at, entry)| sport_log_types::Archived { archived_at, entry })
+                        .collect()
+                })
+        }
+
+        async fn restore(
+            id: Self::Id,
+            user_id: sport_log_types::UserId,
+            db: &mut diesel_async::AsyncPgConnection
+        ) -> diesel::result::QueryResult<Self::Type> {
+            use crate::db::{ArchivableDb, Db, DbWithUserId, restore_record};
+            use diesel_async::RunQueryDsl;
+            use diesel::prelude::*;
+
+            let archived: bool = diesel::select(diesel::dsl::exists(
+                Self::table()
+                    .filter(Self::id_column().eq(id))
+                    .filter(Self::user_id_column().eq(user_id))
+                    .filter(Self::archived_at_column().is_not_null()),
+            ))
+            .get_result(db)
+            .await?;
+            if !archived {
+                return Err(diesel::result::Error::NotFound);
+            }
+
+            diesel::select(restore_record(Self::TABLE_NAME, id))
+                .execute(db)
+                .await?;
+
+            Self::table()
+                .find(id)
+                .select(Self::Type::as_select())
+                .get_result(db)
+                .await
+        }
+    }
 }
 
 define_derive_deftly! {
@@ -520,16 +736,18 @@ define_derive_deftly! {
             self,
             auth: crate::auth::AuthUserOrAP,
             db: &mut diesel_async::AsyncPgConnection,
-        ) -> Result<Self::Id, axum::http::StatusCode> {
+        ) -> Result<Self::Id, crate::error::HandlerError> {
             use crate::db::CheckUserId;
 
+            auth.check_scope::<crate::db::$ttype>(sport_log_types::ScopeAccess::Read)?;
+
             if crate::db::$ttype::check_user_id(self.0, *auth, db)
                 .await
                 .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?
             {
                 Ok(self.0)
             } else {
-                Err(axum::http::StatusCode::FORBIDDEN)
+                Err(axum::http::StatusCode::FORBIDDEN.into())
             }
         }
     }
@@ -545,16 +763,18 @@ define_derive_deftly! {
             self,
             auth: crate::auth::AuthUserOrAP,
             db: &mut diesel_async::AsyncPgConnection,
-        ) -> Result<Self::Id, axum::http::StatusCode> {
+        ) -> Result<Self::Id, crate::error::HandlerError> {
             use crate::db::CheckOptionalUserId;
 
+            auth.check_scope::<crate::db::$ttype>(sport_log_types::ScopeAccess::Read)?;
+
             if crate::db::$ttype::check_optional_user_id(self.0, *auth, db)
                 .await
                 .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?
             {
                 Ok(self.0)
             } else {
-                Err(axum::http::StatusCode::FORBIDDEN)
+                Err(axum::http::StatusCode::FORBIDDEN.into())
             }
         }
     }
@@ -713,9 +933,11 @@ define_derive_deftly! {
             self,
             auth: crate::auth::AuthUserOrAP,
             db: &mut diesel_async::AsyncPgConnection,
-        ) -> Result<Self::Type, axum::http::StatusCode> {
+        ) -> Result<Self::Type, crate::error::HandlerError> {
             use crate::db::CheckUserId;
 
+            auth.check_scope::<crate::db::$ttype>(sport_log_types::ScopeAccess::Write)?;
+
             let value = self.0;
             if value.user_id == *auth
                 && crate::db::$ttype::check_user_id(value.id, *auth, db)
@@ -724,7 +946,7 @@ define_derive_deftly! {
             {
                 Ok(value)
             } else {
-                Err(axum::http::StatusCode::FORBIDDEN)
+                Err(axum::http::StatusCode::FORBIDDEN.into())
             }
         }
     }
@@ -736,9 +958,11 @@ define_derive_deftly! {
             self,
             auth: crate::auth::AuthUserOrAP,
             db: &mut diesel_async::AsyncPgConnection,
-        ) -> Result<Vec<Self::Type>, axum::http::StatusCode> {
+        ) -> Result<Vec<Self::Type>, crate::error::HandlerError> {
             use crate::db::CheckUserId;
 
+            auth.check_scope::<crate::db::$ttype>(sport_log_types::ScopeAccess::Write)?;
+
             let values = self.0;
             let ids: Vec<_> = values.iter().map(|value| value.id).collect();
             if values.iter().all(|value| value.user_id == *auth)
@@ -748,7 +972,7 @@ define_derive_deftly! {
             {
                 Ok(values)
             } else {
-                Err(axum::http::StatusCode::FORBIDDEN)
+                Err(axum::http::StatusCode::FORBIDDEN.into())
             }
         }
     }
@@ -764,9 +988,11 @@ define_derive_deftly! {
             self,
             auth: crate::auth::AuthUserOrAP,
             db: &mut diesel_async::AsyncPgConnection,
-        ) -> Result<Self::Type, axum::http::StatusCode> {
+        ) -> Result<Self::Type, crate::error::HandlerError> {
             use crate::db::CheckUserId;
 
+            auth.check_scope::<crate::db::$ttype>(sport_log_types::ScopeAccess::Write)?;
+
             let value = self.0;
             if value.user_id == Some(*auth)
                 && crate::db::$ttype::check_user_id(value.id, *auth, db)
@@ -775,7 +1001,7 @@ define_derive_deftly! {
             {
                 Ok(value)
             } else {
-                Err(axum::http::StatusCode::FORBIDDEN)
+                Err(axum::http::StatusCode::FORBIDDEN.into())
             }
         }
     }
@@ -787,9 +1013,11 @@ define_derive_deftly! {
             self,
             auth: crate::auth::AuthUserOrAP,
             db: &mut diesel_async::AsyncPgConnection,
-        ) -> Result<Vec<Self::Type>, axum::http::StatusCode> {
+        ) -> Result<Vec<Self::Type>, crate::error::HandlerError> {
             use crate::db::CheckUserId;
 
+            auth.check_scope::<crate::db::$ttype>(sport_log_types::ScopeAccess::Write)?;
+
             let values = self.0;
             let ids: Vec<_> = values.iter().map(|value| value.id).collect();
             if values.iter().all(|value| value.user_id == Some(*auth))
@@ -799,7 +1027,7 @@ define_derive_deftly! {
             {
                 Ok(values)
             } else {
-                Err(axum::http::StatusCode::FORBIDDEN)
+                Err(axum::http::StatusCode::FORBIDDEN.into())
             }
         }
     }
@@ -850,12 +1078,14 @@ define_derive_deftly! {
         fn verify_user_ap_create(
             self,
             auth: crate::auth::AuthUserOrAP,
-        ) -> Result<Self::Type, axum::http::StatusCode> {
+        ) -> Result<Self::Type, crate::error::HandlerError> {
+            auth.check_scope::<crate::db::$ttype>(sport_log_types::ScopeAccess::Write)?;
+
             let value = self.0;
             if value.user_id == *auth {
                 Ok(value)
             } else {
-                Err(axum::http::StatusCode::FORBIDDEN)
+                Err(axum::http::StatusCode::FORBIDDEN.into())
             }
         }
     }
@@ -866,12 +1096,14 @@ define_derive_deftly! {
         fn verify_user_ap_create(
             self,
             auth: crate::auth::AuthUserOrAP,
-        ) -> Result<Vec<Self::Type>, axum::http::StatusCode> {
+        ) -> Result<Vec<Self::Type>, crate::error::HandlerError> {
+            auth.check_scope::<crate::db::$ttype>(sport_log_types::ScopeAccess::Write)?;
+
             let values = self.0;
             if values.iter().all(|value| value.user_id == *auth) {
                 Ok(values)
             } else {
-                Err(axum::http::StatusCode::FORBIDDEN)
+                Err(axum::http::StatusCode::FORBIDDEN.into())
             }
         }
     }
@@ -886,12 +1118,14 @@ define_derive_deftly! {
         fn verify_user_ap_create(
             self,
             auth: crate::auth::AuthUserOrAP,
-        ) -> Result<Self::Type, axum::http::StatusCode> {
+        ) -> Result<Self::Type, crate::error::HandlerError> {
+            auth.check_scope::<crate::db::$ttype>(sport_log_types::ScopeAccess::Write)?;
+
             let value = self.0;
             if value.user_id == Some(*auth) {
                 Ok(value)
             } else {
-                Err(axum::http::StatusCode::FORBIDDEN)
+                Err(axum::http::StatusCode::FORBIDDEN.into())
             }
         }
     }
@@ -902,12 +1136,14 @@ define_derive_deftly! {
         fn verify_user_ap_create(
             self,
             auth: crate::auth::AuthUserOrAP,
-        ) -> Result<Vec<Self::Type>, axum::http::StatusCode> {
+        ) -> Result<Vec<Self::Type>, crate::error::HandlerError> {
+            auth.check_scope::<crate::db::$ttype>(sport_log_types::ScopeAccess::Write)?;
+
             let values = self.0;
             if values.iter().all(|value| value.user_id == Some(*auth)) {
                 Ok(values)
             } else {
-                Err(axum::http::StatusCode::FORBIDDEN)
+                Err(axum::http::StatusCode::FORBIDDEN.into())
             }
         }
     }
@@ -29,20 +29,36 @@ use tracing_subscriber::EnvFilter;
 
 use crate::{
     config::Config,
+    crypto::CredentialKeys,
+    db::PlatformCredentialDb,
+    heatmap::HeatmapCache,
+    notify::EpochChanges,
     state::{AppState, DbPool},
 };
 
 #[macro_use]
 mod macros;
 mod auth;
+mod cardio_summary;
 mod config;
+mod crypto;
 mod db;
 mod error;
 mod handler;
+mod heatmap;
+mod notify;
+mod route_match;
 mod router;
+mod segment;
+mod simplify;
+mod spatial;
+mod split;
 mod state;
 #[cfg(test)]
 mod tests;
+mod track_file;
+mod training_load;
+mod training_zone;
 
 const CONFIG_FILE: &str = "sport-log-server.toml";
 
@@ -89,6 +105,21 @@ async fn get_db_pool(config: &Config) -> Result<DbPool, String> {
     Ok(pool)
 }
 
+/// Encrypt the passwords of platform credentials stored before credential encryption was added.
+async fn encrypt_credentials(db_pool: &DbPool, keys: &CredentialKeys) -> Result<(), String> {
+    let mut db = db_pool
+        .get()
+        .await
+        .map_err(|err| format!("failed to get database connection from pool: {err}"))?;
+    let encrypted = PlatformCredentialDb::encrypt_plaintext(keys, &mut db)
+        .await
+        .map_err(|err| format!("failed to encrypt platform credentials: {err}"))?;
+    if encrypted > 0 {
+        info!("encrypted {encrypted} platform credentials stored in plaintext");
+    }
+    Ok(())
+}
+
 async fn run_server(router: Router, config: &Config) -> Result<(), String> {
     let address = if cfg!(debug_assertions) {
         &config.debug_address
@@ -118,6 +149,14 @@ async fn main() -> ExitCode {
         }
     };
 
+    let credential_keys = match CredentialKeys::new(config) {
+        Ok(credential_keys) => Box::leak(Box::new(credential_keys)),
+        Err(error) => {
+            error!("{error}");
+            return ExitCode::FAILURE;
+        }
+    };
+
     let db_pool = match get_db_pool(config).await {
         Ok(db_pool) => db_pool,
         Err(error) => {
@@ -126,7 +165,21 @@ async fn main() -> ExitCode {
         }
     };
 
-    let state = AppState { db_pool, config };
+    if let Err(error) = encrypt_credentials(&db_pool, credential_keys).await {
+        error!("{error}");
+        return ExitCode::FAILURE;
+    }
+
+    let epoch_changes = EpochChanges::default();
+    tokio::spawn(epoch_changes.clone().listen(&config.database_url));
+
+    let state = AppState {
+        db_pool,
+        config,
+        credential_keys,
+        epoch_changes,
+        heatmap_cache: HeatmapCache::default(),
+    };
 
     let router = router::get_router(state);
 
@@ -0,0 +1,156 @@
+//! Notifications about new epochs.
+//!
+//! The epoch triggers of the database publish every new epoch on the channel [`EPOCH_CHANNEL`].
+//! A single listening connection forwards them to all subscribers of [`EpochChanges`].
+
+use std::{pin::pin, str::FromStr, time::Duration};
+
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use futures_util::StreamExt;
+use sport_log_types::{Epoch, EpochMapDelta, UserId};
+use tokio::sync::broadcast::{
+    self, Receiver, Sender,
+    error::{RecvError, TryRecvError},
+};
+use tracing::{info, warn};
+
+const EPOCH_CHANNEL: &str = "epoch";
+
+/// Number of changes that are buffered for slow subscribers before changes are lost.
+const CHANNEL_CAPACITY: usize = 4096;
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// A new epoch of an entry in `table`.
+///
+/// `user_id` is `None` if the entry does not belong to a user and is therefore relevant for all
+/// users.
+#[derive(Debug, Clone)]
+pub struct EpochChange {
+    pub table: String,
+    pub user_id: Option<UserId>,
+    pub epoch: Epoch,
+}
+
+impl EpochChange {
+    fn is_for(&self, user_id: UserId) -> bool {
+        self.user_id.is_none_or(|id| id == user_id)
+    }
+}
+
+impl FromStr for EpochChange {
+    type Err = String;
+
+    /// Parse the payload `{table},{user_id},{epoch}` of a notification.
+    fn from_str(payload: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid epoch notification {payload:?}");
+        let mut parts = payload.split(',');
+        let (Some(table), Some(user_id), Some(epoch), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(invalid());
+        };
+        let user_id = if user_id.is_empty() {
+            None
+        } else {
+            Some(UserId(user_id.parse().map_err(|_| invalid())?))
+        };
+        let epoch = Epoch(epoch.parse().map_err(|_| invalid())?);
+
+        Ok(Self {
+            table: table.to_owned(),
+            user_id,
+            epoch,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EpochChanges(Sender<EpochChange>);
+
+impl Default for EpochChanges {
+    fn default() -> Self {
+        Self(broadcast::channel(CHANNEL_CAPACITY).0)
+    }
+}
+
+impl EpochChanges {
+    pub fn subscribe(&self, user_id: UserId) -> EpochSubscription {
+        EpochSubscription {
+            receiver: self.0.subscribe(),
+            user_id,
+        }
+    }
+
+    pub fn send(&self, change: EpochChange) {
+        // there might be no subscribers
+        let _ = self.0.send(change);
+    }
+
+    /// Forward all epoch notifications of the database to the subscribers.
+    ///
+    /// If the connection to the database is lost, it is reestablished after [`RECONNECT_DELAY`].
+    pub async fn listen(self, database_url: &str) {
+        loop {
+            if let Err(error) = self.forward(database_url).await {
+                warn!("failed to listen for epoch notifications: {error}");
+            }
+            tokio::time::sleep(RECONNECT_DELAY).await;
+        }
+    }
+
+    async fn forward(&self, database_url: &str) -> Result<(), String> {
+        let mut db = AsyncPgConnection::establish(database_url)
+            .await
+            .map_err(|err| err.to_string())?;
+        diesel::sql_query(format!("listen {EPOCH_CHANNEL}"))
+            .execute(&mut db)
+            .await
+            .map_err(|err| err.to_string())?;
+
+        info!("listening for epoch notifications");
+
+        let mut notifications = pin!(db.notifications_stream());
+        while let Some(notification) = notifications.next().await {
+            let notification = notification.map_err(|err| err.to_string())?;
+            match notification.payload.parse() {
+                Ok(change) => self.send(change),
+                Err(error) => warn!("{error}"),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The epoch changes relevant for a single user.
+pub struct EpochSubscription {
+    receiver: Receiver<EpochChange>,
+    user_id: UserId,
+}
+
+impl EpochSubscription {
+    /// Wait for the next changes of the user.
+    ///
+    /// All changes that are already available are combined into a single [`EpochMapDelta`].
+    /// Returns [`RecvError::Lagged`] if changes have been lost.
+    pub async fn next(&mut self) -> Result<EpochMapDelta, RecvError> {
+        let mut delta = EpochMapDelta::new();
+        while delta.is_empty() {
+            let mut change = Some(self.receiver.recv().await?);
+            while let Some(next) = change {
+                if next.is_for(self.user_id) {
+                    let epoch = delta.entry(next.table).or_default();
+                    *epoch = (*epoch).max(next.epoch);
+                }
+                change = match self.receiver.try_recv() {
+                    Ok(next) => Some(next),
+                    Err(TryRecvError::Empty) => None,
+                    Err(TryRecvError::Lagged(count)) => return Err(RecvError::Lagged(count)),
+                    Err(TryRecvError::Closed) => return Err(RecvError::Closed),
+                };
+            }
+        }
+        Ok(delta)
+    }
+}
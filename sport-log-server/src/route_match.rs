@@ -0,0 +1,226 @@
+//! Matching of cardio sessions to routes and the history of the sessions on a route.
+//!
+//! A session matches a route if the start and end of both tracks are close to each other and the
+//! discrete Fréchet distance between both tracks is small.
+//! Since the positions of a route are usually much sparser than the ones of a recorded session,
+//! both tracks are resampled to positions in equal distances before they are compared.
+
+use diesel::prelude::*;
+use sport_log_types::{
+    CardioSessionId, CardioSessionSummary, Position, RouteHistory, RouteHistoryEntry, RouteId,
+    RouteMatch, schema::route,
+};
+
+use crate::track_file;
+
+/// The number of positions the tracks are resampled to.
+const RESAMPLE_POINTS: usize = 250;
+
+/// The Fréchet distance in meters up to which a session matches a route.
+///
+/// The distance between two resampled positions of the route is added to it.
+const MATCH_DISTANCE: f64 = 100.;
+
+/// The fields of a route that are needed to match cardio sessions.
+#[derive(Queryable, Selectable, Debug)]
+#[diesel(table_name = route)]
+pub struct RouteMatchTrack {
+    pub id: RouteId,
+    pub track: Option<Vec<Position>>,
+}
+
+/// A track resampled to [`RESAMPLE_POINTS`] positions in equal distances.
+#[derive(Debug)]
+pub struct Resampled {
+    positions: Vec<(f64, f64)>,
+    /// The distance in meters between two consecutive positions.
+    spacing: f64,
+}
+
+impl Resampled {
+    /// Returns `None` if the track has no length.
+    pub fn new(track: &[Position]) -> Option<Self> {
+        let mut distances = Vec::with_capacity(track.len());
+        let mut total = 0.;
+        for (i, position) in track.iter().enumerate() {
+            if let Some(previous) = i.checked_sub(1).map(|i| &track[i]) {
+                total += track_file::distance(
+                    previous.latitude,
+                    previous.longitude,
+                    position.latitude,
+                    position.longitude,
+                );
+            }
+            distances.push(total);
+        }
+        if total <= 0. {
+            return None;
+        }
+
+        let spacing = total / (RESAMPLE_POINTS - 1) as f64;
+        let mut positions = Vec::with_capacity(RESAMPLE_POINTS);
+        let mut next = 1;
+        for i in 0..RESAMPLE_POINTS {
+            let target = spacing * i as f64;
+            while next < track.len() - 1 && distances[next] < target {
+                next += 1;
+            }
+            let (from, to) = (&track[next - 1], &track[next]);
+            let length = distances[next] - distances[next - 1];
+            let fraction = if length > 0. {
+                ((target - distances[next - 1]) / length).clamp(0., 1.)
+            } else {
+                1.
+            };
+            positions.push((
+                from.latitude + fraction * (to.latitude - from.latitude),
+                from.longitude + fraction * (to.longitude - from.longitude),
+            ));
+        }
+        Some(Self { positions, spacing })
+    }
+
+    /// The length of the track in meters.
+    pub fn length(&self) -> f64 {
+        self.spacing * (RESAMPLE_POINTS - 1) as f64
+    }
+
+    /// The Fréchet distance between both tracks if it is at most `tolerance` plus the distance
+    /// between two resampled positions of `self`.
+    pub fn matches(&self, other: &Self, tolerance: f64) -> Option<f64> {
+        let tolerance = tolerance + self.spacing;
+        let close = |a: Option<&(f64, f64)>, b: Option<&(f64, f64)>| {
+            a.zip(b).is_some_and(|(a, b)| distance(*a, *b) <= tolerance)
+        };
+        if !close(self.positions.first(), other.positions.first())
+            || !close(self.positions.last(), other.positions.last())
+        {
+            return None;
+        }
+        let distance = frechet_distance(&self.positions, &other.positions);
+        (distance <= tolerance).then_some(distance)
+    }
+}
+
+fn distance((lat1, lon1): (f64, f64), (lat2, lon2): (f64, f64)) -> f64 {
+    track_file::distance(lat1, lon1, lat2, lon2)
+}
+
+/// The discrete Fréchet distance between two non-empty paths in meters.
+fn frechet_distance(a: &[(f64, f64)], b: &[(f64, f64)]) -> f64 {
+    let mut previous: Vec<f64> = vec![];
+    for (i, &p) in a.iter().enumerate() {
+        let mut row: Vec<f64> = Vec::with_capacity(b.len());
+        for (j, &q) in b.iter().enumerate() {
+            let coupling = match (i, j) {
+                (0, 0) => 0.,
+                (0, _) => row[j - 1],
+                (_, 0) => previous[0],
+                _ => previous[j].min(previous[j - 1]).min(row[j - 1]),
+            };
+            row.push(coupling.max(distance(p, q)));
+        }
+        previous = row;
+    }
+    previous.last().copied().unwrap_or_default()
+}
+
+/// The routes of a user which have a track.
+#[derive(Debug)]
+pub struct Routes(Vec<(RouteId, Resampled)>);
+
+impl Routes {
+    pub fn new(routes: Vec<RouteMatchTrack>) -> Self {
+        Self(
+            routes
+                .into_iter()
+                .filter_map(|route| Some((route.id, Resampled::new(route.track.as_deref()?)?)))
+                .collect(),
+        )
+    }
+
+    /// The routes matching the track of a cardio session ordered by their Fréchet distance.
+    pub fn matches(
+        &self,
+        cardio_session_id: CardioSessionId,
+        track: &[Position],
+    ) -> Vec<RouteMatch> {
+        if self.0.is_empty() {
+            return vec![];
+        }
+        let Some(session) = Resampled::new(track) else {
+            return vec![];
+        };
+
+        let mut matches: Vec<_> = self
+            .0
+            .iter()
+            .filter_map(|(route_id, route)| {
+                route
+                    .matches(&session, MATCH_DISTANCE)
+                    .map(|distance| RouteMatch {
+                        cardio_session_id,
+                        route_id: *route_id,
+                        distance,
+                    })
+            })
+            .collect();
+        matches.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+        matches
+    }
+
+    /// The route matching the track of a cardio session best.
+    pub fn best_match(
+        &self,
+        cardio_session_id: CardioSessionId,
+        track: &[Position],
+    ) -> Option<RouteMatch> {
+        self.matches(cardio_session_id, track).into_iter().next()
+    }
+}
+
+/// The history of a route from its sessions ordered by `datetime`.
+pub fn history(route_id: RouteId, sessions: Vec<CardioSessionSummary>) -> RouteHistory {
+    let sessions: Vec<_> = sessions
+        .into_iter()
+        .map(|session| RouteHistoryEntry {
+            cardio_session_id: session.id,
+            datetime: session.datetime,
+            time: session.time,
+        })
+        .collect();
+
+    let best = sessions
+        .iter()
+        .filter(|session| session.time.is_some())
+        .min_by_key(|session| session.time)
+        .cloned();
+
+    let points: Vec<_> = sessions
+        .iter()
+        .filter_map(|session| {
+            let days =
+                (session.datetime - sessions[0].datetime).num_milliseconds() as f64 / 86_400_000.;
+            Some((days, f64::from(session.time?)))
+        })
+        .collect();
+    let count = points.len() as f64;
+    let mean_days = points.iter().map(|(days, _)| days).sum::<f64>() / count;
+    let mean_time = points.iter().map(|(_, time)| time).sum::<f64>() / count;
+    let covariance: f64 = points
+        .iter()
+        .map(|(days, time)| (days - mean_days) * (time - mean_time))
+        .sum();
+    let variance: f64 = points
+        .iter()
+        .map(|(days, _)| (days - mean_days).powi(2))
+        .sum();
+    let trend = (points.len() >= 2 && variance > 0.).then(|| covariance / variance);
+
+    RouteHistory {
+        route_id,
+        sessions,
+        best,
+        trend,
+    }
+}
@@ -18,7 +18,7 @@ use tower_http::{
 };
 use tracing::{Span, debug, trace, warn};
 
-use crate::{error::HandlerError, handler::*, state::AppState};
+use crate::{db::*, error::HandlerError, handler::*, state::AppState};
 
 async fn handler_not_found() -> HandlerError {
     HandlerError::from(StatusCode::NOT_FOUND)
@@ -57,7 +57,13 @@ pub fn get_router(state: AppState) -> Router {
             ADM_DELETABLE_ACTION_EVENT,
             get(adm_get_deletable_action_events),
         ) // scheduler
-        .route(ADM_USER, post(adm_create_users)); // needed if user self registration disabled
+        .route(ADM_USER, post(adm_create_users)) // needed if user self registration disabled
+        .route(ADM_ROTATE_CREDENTIAL_KEY, post(adm_rotate_credential_key))
+        .route(ADM_PURGE_ARCHIVE, post(adm_purge_archive)) // scheduler
+        .route(
+            ADM_NORMALIZE_CARDIO_SESSION,
+            post(adm_normalize_cardio_sessions),
+        );
 
     let ap_router = Router::new()
         .route(AP_PLATFORM, post(ap_create_platform).get(ap_get_platforms))
@@ -71,6 +77,12 @@ pub fn get_router(state: AppState) -> Router {
                 .get(ap_get_actions)
                 .put(ap_update_actions),
         )
+        .route(
+            AP_ACTION_PROVIDER_SCOPE,
+            post(ap_create_action_provider_scopes)
+                .get(ap_get_action_provider_scopes)
+                .put(ap_update_action_provider_scopes),
+        )
         .route(AP_ACTION_EVENT, delete(ap_disable_action_events))
         .route(
             AP_EXECUTABLE_ACTION_EVENT,
@@ -80,7 +92,19 @@ pub fn get_router(state: AppState) -> Router {
     let user_router = Router::new()
         .route(APP_INFO, get(get_app_info))
         .route(APP_DOWNLOAD, get(download_app))
-        .route(ACCOUNT_DATA, get(get_account_data))
+        .route(
+            ACCOUNT_DATA,
+            get(get_account_data)
+                .post(create_account_data)
+                .put(update_account_data),
+        )
+        .route(ACCOUNT_DATA_EVENTS, get(get_account_data_events))
+        .route(ACCOUNT_DATA_PAGE, get(get_account_data_page))
+        .route(ACCOUNT_DATA_SYNC, post(sync_account_data))
+        .route(LOGIN, post(login))
+        .route(REFRESH, post(refresh))
+        .route(LOGOUT, post(logout))
+        .route(LOGOUT_ALL, post(logout_all))
         .route(
             USER,
             post(create_user)
@@ -109,18 +133,40 @@ pub fn get_router(state: AppState) -> Router {
                 .get(get_action_events)
                 .put(update_action_events),
         )
+        .route(ACTION_PROVIDER_SCOPE, get(get_action_provider_scopes))
+        .route(
+            SCOPE_GRANT,
+            post(create_scope_grants)
+                .get(get_scope_grants)
+                .put(update_scope_grants),
+        )
         .route(
             STRENGTH_SESSION,
             post(create_strength_sessions)
                 .get(get_strength_sessions)
                 .put(update_strength_sessions),
         )
+        .route(
+            STRENGTH_SESSION_ARCHIVE,
+            get(get_archived::<StrengthSessionDb>),
+        )
+        .route(
+            STRENGTH_SESSION_RESTORE,
+            post(restore_archived::<StrengthSessionDb>),
+        )
         .route(
             STRENGTH_SET,
             post(create_strength_sets)
                 .get(get_strength_sets)
                 .put(update_strength_sets),
         )
+        .route(STRENGTH_SET_ARCHIVE, get(get_archived::<StrengthSetDb>))
+        .route(
+            STRENGTH_SET_RESTORE,
+            post(restore_archived::<StrengthSetDb>),
+        )
+        .route(STRENGTH_RECORD, get(get_strength_records))
+        .route(STRENGTH_STATISTIC, get(get_strength_statistics))
         .route(EORM, get(get_eorms))
         .route(
             METCON_SESSION,
@@ -128,37 +174,117 @@ pub fn get_router(state: AppState) -> Router {
                 .get(get_metcon_sessions)
                 .put(update_metcon_sessions),
         )
+        .route(METCON_SESSION_ARCHIVE, get(get_archived::<MetconSessionDb>))
+        .route(
+            METCON_SESSION_RESTORE,
+            post(restore_archived::<MetconSessionDb>),
+        )
         .route(
             METCON,
             post(create_metcons).get(get_metcons).put(update_metcons),
         )
+        .route(METCON_ARCHIVE, get(get_archived::<MetconDb>))
+        .route(METCON_RESTORE, post(restore_archived::<MetconDb>))
         .route(
             METCON_MOVEMENT,
             post(create_metcon_movements)
                 .get(get_metcon_movements)
                 .put(update_metcon_movements),
         )
+        .route(
+            METCON_MOVEMENT_ARCHIVE,
+            get(get_archived::<MetconMovementDb>),
+        )
+        .route(
+            METCON_MOVEMENT_RESTORE,
+            post(restore_archived::<MetconMovementDb>),
+        )
+        .route(METCON_STATISTIC, get(get_metcon_statistics))
         .route(
             CARDIO_SESSION,
             post(create_cardio_sessions)
                 .get(get_cardio_sessions)
                 .put(update_cardio_sessions),
         )
+        .route(CARDIO_SESSION_ARCHIVE, get(get_archived::<CardioSessionDb>))
+        .route(
+            CARDIO_SESSION_RESTORE,
+            post(restore_archived::<CardioSessionDb>),
+        )
+        .route(CARDIO_SESSION_EXPORT, get(export_cardio_session))
+        .route(CARDIO_SESSION_IMPORT, post(import_cardio_session))
+        .route(CARDIO_SESSION_SUMMARY, get(get_cardio_session_summaries))
+        .route(
+            CARDIO_SESSION_SPATIAL,
+            get(get_spatial_cardio_session_summaries),
+        )
+        .route(CARDIO_SESSION_OVERVIEW, get(get_cardio_session_overviews))
+        .route(CARDIO_SESSION_TRACK, get(get_cardio_session_track))
+        .route(CARDIO_SESSION_SPLIT, get(get_cardio_session_splits))
+        .route(
+            CARDIO_SESSION_ROUTE_MATCH,
+            get(get_cardio_session_route_matches),
+        )
+        .route(
+            CARDIO_SESSION_VALIDATION,
+            get(get_cardio_session_validations),
+        )
+        .route(CARDIO_STATISTIC, get(get_cardio_statistics))
+        .route(CARDIO_BEST_EFFORT, get(get_cardio_best_efforts))
+        .route(TRAINING_IMPULSE, get(get_training_impulses))
+        .route(TRAINING_LOAD, get(get_training_load))
+        .route(
+            TRAINING_ZONE,
+            post(create_training_zones)
+                .get(get_training_zones)
+                .put(update_training_zones),
+        )
+        .route(TRAINING_ZONE_ARCHIVE, get(get_archived::<TrainingZoneDb>))
+        .route(
+            TRAINING_ZONE_RESTORE,
+            post(restore_archived::<TrainingZoneDb>),
+        )
+        .route(CARDIO_SESSION_ZONE, get(get_cardio_session_zones))
+        .route(ZONE_STATISTIC, get(get_zone_statistics))
+        .route(HEATMAP, get(get_heatmap_tile))
         .route(
             ROUTE,
             post(create_routes).get(get_routes).put(update_routes),
         )
+        .route(ROUTE_ARCHIVE, get(get_archived::<RouteDb>))
+        .route(ROUTE_RESTORE, post(restore_archived::<RouteDb>))
+        .route(ROUTE_EXPORT, get(export_route))
+        .route(ROUTE_TRACK, get(get_route_track))
+        .route(ROUTE_SUMMARY, get(get_route_summaries))
+        .route(ROUTE_SPATIAL, get(get_spatial_route_summaries))
+        .route(ROUTE_OVERVIEW, get(get_route_overviews))
+        .route(ROUTE_HISTORY, get(get_route_history))
+        .route(ROUTE_MATCH, post(match_routes))
+        .route(
+            SEGMENT,
+            post(create_segments).get(get_segments).put(update_segments),
+        )
+        .route(SEGMENT_ARCHIVE, get(get_archived::<SegmentDb>))
+        .route(SEGMENT_RESTORE, post(restore_archived::<SegmentDb>))
+        .route(SEGMENT_EFFORT, get(get_segment_efforts))
+        .route(SEGMENT_BEST_EFFORT, get(get_segment_best_efforts))
         .route(
             DIARY,
             post(create_diaries).get(get_diaries).put(update_diaries),
         )
+        .route(DIARY_ARCHIVE, get(get_archived::<DiaryDb>))
+        .route(DIARY_RESTORE, post(restore_archived::<DiaryDb>))
         .route(WOD, post(create_wods).get(get_wods).put(update_wods))
+        .route(WOD_ARCHIVE, get(get_archived::<WodDb>))
+        .route(WOD_RESTORE, post(restore_archived::<WodDb>))
         .route(
             MOVEMENT,
             post(create_movements)
                 .get(get_movements)
                 .put(update_movements),
-        );
+        )
+        .route(MOVEMENT_ARCHIVE, get(get_archived::<MovementDb>))
+        .route(MOVEMENT_RESTORE, post(restore_archived::<MovementDb>));
 
     let trace_layer = ServiceBuilder::new()
         .layer(SetSensitiveRequestHeadersLayer::new(iter::once(
@@ -0,0 +1,205 @@
+//! Detection of the traversals of segments in the tracks of cardio sessions.
+//!
+//! A traversal starts at the position of the track closest to the start of the segment and ends at
+//! the following position closest to its end.
+//! It is only counted as an effort if the part of the track in between matches the track of the
+//! segment.
+//! The efforts are stored and detected again whenever a cardio session or a segment is created or
+//! updated.
+
+use chrono::{DateTime, Duration, Utc};
+use diesel::prelude::*;
+use diesel_async::AsyncPgConnection;
+use sport_log_types::{
+    CardioSessionId, Position, Segment, SegmentEffort, SegmentId, UserId, schema::cardio_session,
+};
+
+use crate::{
+    db::{CardioSessionDb, SegmentDb, SegmentEffortDb},
+    route_match::Resampled,
+    spatial::SpatialFilter,
+    split, track_file,
+};
+
+/// The distance in meters within which a position passes the start or the end of a segment.
+const ENDPOINT_RADIUS: f64 = 30.;
+
+/// The Fréchet distance in meters up to which a part of a track traverses a segment.
+const MATCH_DISTANCE: f64 = 30.;
+
+/// The factor by which the distance of a traversal may exceed the length of the segment.
+const MAX_LENGTH_FACTOR: f64 = 1.5;
+
+/// The fields of a cardio session that are needed to detect its segment efforts.
+#[derive(Queryable, Selectable, Debug)]
+#[diesel(table_name = cardio_session)]
+pub struct CardioSessionSegmentData {
+    pub id: CardioSessionId,
+    pub datetime: DateTime<Utc>,
+    pub track: Option<Vec<Position>>,
+    pub heart_rate: Option<Vec<i32>>,
+}
+
+fn distance(a: &Position, b: &Position) -> f64 {
+    track_file::distance(a.latitude, a.longitude, b.latitude, b.longitude)
+}
+
+/// The index of the position closest to `target` within the run of positions starting at `start`
+/// that are all within [`ENDPOINT_RADIUS`] of `target` and the index of the last position of the
+/// run.
+fn closest_in_run(track: &[Position], start: usize, target: &Position) -> (usize, usize) {
+    let mut closest = start;
+    let mut end = start;
+    while end + 1 < track.len() && distance(&track[end + 1], target) <= ENDPOINT_RADIUS {
+        end += 1;
+        if distance(&track[end], target) < distance(&track[closest], target) {
+            closest = end;
+        }
+    }
+    (closest, end)
+}
+
+/// The efforts of a segment in the track of a cardio session.
+fn efforts(
+    segment: &Segment,
+    resampled: &Resampled,
+    session: &CardioSessionSegmentData,
+) -> Vec<SegmentEffort> {
+    let (Some(track), Some(start), Some(end)) = (
+        session.track.as_deref(),
+        segment.track.first(),
+        segment.track.last(),
+    ) else {
+        return vec![];
+    };
+
+    let mut efforts = vec![];
+    let mut i = 0;
+    while i < track.len() {
+        if distance(&track[i], start) > ENDPOINT_RADIUS {
+            i += 1;
+            continue;
+        }
+        let (first, run_end) = closest_in_run(track, i, start);
+        i = run_end + 1;
+
+        let max_distance =
+            track[first].distance + resampled.length() * MAX_LENGTH_FACTOR + ENDPOINT_RADIUS;
+        let Some(run_start) = (i..track.len())
+            .take_while(|&j| track[j].distance <= max_distance)
+            .find(|&j| distance(&track[j], end) <= ENDPOINT_RADIUS)
+        else {
+            continue;
+        };
+        let (last, _) = closest_in_run(track, run_start, end);
+
+        let time = track[last].time - track[first].time;
+        let traversed = Resampled::new(&track[first..=last]);
+        if time <= 0
+            || traversed
+                .and_then(|traversed| resampled.matches(&traversed, MATCH_DISTANCE))
+                .is_none()
+        {
+            continue;
+        }
+        efforts.push(SegmentEffort {
+            user_id: segment.user_id,
+            segment_id: segment.id,
+            cardio_session_id: session.id,
+            start: track[first].time,
+            time,
+            avg_heart_rate: split::avg_rate(
+                session.heart_rate.as_deref(),
+                f64::from(track[first].time),
+                f64::from(track[last].time),
+            ),
+            datetime: session.datetime + Duration::milliseconds(i64::from(track[first].time)),
+        });
+        i = last + 1;
+    }
+    efforts
+}
+
+/// Detect the efforts of `segments` in `sessions`.
+fn detect(segments: &[Segment], sessions: &[CardioSessionSegmentData]) -> Vec<SegmentEffort> {
+    let mut detected = vec![];
+    for segment in segments {
+        let Some(resampled) = Resampled::new(&segment.track) else {
+            continue;
+        };
+        for session in sessions {
+            detected.extend(efforts(segment, &resampled, session));
+        }
+    }
+    detected
+}
+
+/// The bounding boxes of the areas around the start and the end of a segment a track has to pass
+/// through.
+fn endpoint_bounds(segment: &Segment) -> Vec<(f64, f64, f64, f64)> {
+    [segment.track.first(), segment.track.last()]
+        .into_iter()
+        .flatten()
+        .map(|position| {
+            SpatialFilter::Distance {
+                latitude: position.latitude,
+                longitude: position.longitude,
+                radius: ENDPOINT_RADIUS,
+            }
+            .bounds()
+        })
+        .collect()
+}
+
+/// Detect the efforts of all segments of a user in the cardio sessions `cardio_session_ids`.
+///
+/// Deleted cardio sessions are ignored so that their efforts are kept for a restore.
+pub async fn detect_for_cardio_sessions(
+    user_id: UserId,
+    cardio_session_ids: &[CardioSessionId],
+    db: &mut AsyncPgConnection,
+) -> QueryResult<()> {
+    if cardio_session_ids.is_empty() {
+        return Ok(());
+    }
+    let segments = SegmentDb::get_active_by_user(user_id, None, db).await?;
+    if segments.is_empty() {
+        return Ok(());
+    }
+    let sessions =
+        CardioSessionDb::get_segment_data_by_ids(user_id, cardio_session_ids, db).await?;
+    if sessions.is_empty() {
+        return Ok(());
+    }
+    let segment_ids: Vec<_> = segments.iter().map(|segment| segment.id).collect();
+    let session_ids: Vec<_> = sessions.iter().map(|session| session.id).collect();
+    let detected = detect(&segments, &sessions);
+    SegmentEffortDb::replace(&segment_ids, Some(&session_ids), &detected, db).await
+}
+
+/// Detect the efforts of the segments `segment_ids` in all cardio sessions of a user.
+///
+/// Only the cardio sessions whose bounding box contains the areas around the start and the end of
+/// a segment are considered.
+/// Deleted segments are ignored so that their efforts are kept for a restore.
+pub async fn detect_for_segments(
+    user_id: UserId,
+    segment_ids: &[SegmentId],
+    db: &mut AsyncPgConnection,
+) -> QueryResult<()> {
+    if segment_ids.is_empty() {
+        return Ok(());
+    }
+    let segments = SegmentDb::get_active_by_user(user_id, Some(segment_ids), db).await?;
+    for segment in &segments {
+        let sessions = CardioSessionDb::get_segment_data_by_user_and_bounds(
+            user_id,
+            &endpoint_bounds(segment),
+            db,
+        )
+        .await?;
+        let detected = detect(std::slice::from_ref(segment), &sessions);
+        SegmentEffortDb::replace(&[segment.id], None, &detected, db).await?;
+    }
+    Ok(())
+}
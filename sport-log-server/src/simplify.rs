@@ -0,0 +1,251 @@
+//! Simplification of tracks for clients that do not need every recorded position.
+//!
+//! Tracks are simplified with the Douglas–Peucker algorithm.
+//! The segments are refined in the order of their largest deviation, so that the number of
+//! positions can be capped as well.
+//! Only positions of the original track are retained, so their `distance` and `time` stay valid.
+//!
+//! Overviews of the tracks are stored together with the epoch of their cardio session or route and
+//! are computed again once it changed.
+
+use std::{
+    collections::{BinaryHeap, HashMap},
+    hash::Hash,
+};
+
+use axum::http::StatusCode;
+use diesel::{
+    QueryResult,
+    result::{DatabaseErrorKind, Error as DieselError},
+};
+use diesel_async::AsyncPgConnection;
+use serde::Deserialize;
+use sport_log_types::{
+    CardioSessionId, CardioSessionOverview, Epoch, Position, RouteId, RouteOverview, UserId,
+};
+
+use crate::{
+    db::{CardioSessionOverviewDb, RouteOverviewDb, Timespan},
+    error::{ErrorMessage, HandlerError},
+    track_file::EARTH_RADIUS,
+};
+
+/// The tolerance in meters of the stored overviews.
+const OVERVIEW_TOLERANCE: f64 = 10.;
+
+/// The maximal number of positions of the stored overviews.
+const OVERVIEW_MAX_POINTS: usize = 100;
+
+/// The id and epoch of an entry with a track and the epoch and track of its stored overview.
+pub type StoredOverview<I> = (I, Epoch, Option<Epoch>, Option<Vec<Position>>);
+
+#[derive(Debug, Deserialize)]
+pub struct SimplifyOption {
+    /// The maximal distance in meters between a removed position and the simplified track.
+    #[serde(default)]
+    pub tolerance: Option<f64>,
+    /// The maximal number of positions of the simplified track.
+    #[serde(default)]
+    pub max_points: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Simplification {
+    tolerance: f64,
+    max_points: usize,
+}
+
+impl SimplifyOption {
+    /// Returns `None` if the tracks should not be simplified.
+    pub fn simplification(&self) -> Result<Option<Simplification>, HandlerError> {
+        let error = |error: &str| {
+            HandlerError::from((
+                StatusCode::BAD_REQUEST,
+                ErrorMessage::Other {
+                    error: error.to_owned(),
+                },
+            ))
+        };
+        if self
+            .tolerance
+            .is_some_and(|tolerance| !tolerance.is_finite() || tolerance < 0.)
+        {
+            return Err(error("The tolerance must not be negative."));
+        }
+        if self.max_points.is_some_and(|max_points| max_points < 2) {
+            return Err(error("The track must retain at least 2 positions."));
+        }
+        if self.tolerance.is_none() && self.max_points.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(Simplification {
+            tolerance: self.tolerance.unwrap_or_default(),
+            max_points: self.max_points.unwrap_or(usize::MAX),
+        }))
+    }
+}
+
+impl Simplification {
+    const OVERVIEW: Self = Self {
+        tolerance: OVERVIEW_TOLERANCE,
+        max_points: OVERVIEW_MAX_POINTS,
+    };
+
+    pub fn apply(self, track: &mut Option<Vec<Position>>) {
+        if let Some(track) = track {
+            *track = self.simplify(track);
+        }
+    }
+
+    pub fn simplify(self, track: &[Position]) -> Vec<Position> {
+        let Some(last) = track.len().checked_sub(1).filter(|&last| last >= 2) else {
+            return track.to_vec();
+        };
+
+        let mut retained = vec![false; track.len()];
+        retained[0] = true;
+        retained[last] = true;
+        let mut count = 2;
+        // the deviations are not negative, so their bits are ordered like their values
+        let mut candidates = BinaryHeap::new();
+        candidates.extend(farthest(track, 0, last));
+        while count < self.max_points
+            && let Some((deviation, start, index, end)) = candidates.pop()
+        {
+            if f64::from_bits(deviation) <= self.tolerance {
+                break;
+            }
+            retained[index] = true;
+            count += 1;
+            candidates.extend(farthest(track, start, index));
+            candidates.extend(farthest(track, index, end));
+        }
+
+        track
+            .iter()
+            .zip(retained)
+            .filter(|(_, retained)| *retained)
+            .map(|(position, _)| position.clone())
+            .collect()
+    }
+}
+
+/// The distance in meters between `position` and the line from `start` to `end`.
+///
+/// The positions are projected onto a plane at `start`, which is precise enough for the short
+/// distances between consecutive positions.
+fn deviation(position: &Position, start: &Position, end: &Position) -> f64 {
+    let project = |position: &Position| {
+        (
+            (position.longitude - start.longitude).to_radians()
+                * start.latitude.to_radians().cos()
+                * EARTH_RADIUS,
+            (position.latitude - start.latitude).to_radians() * EARTH_RADIUS,
+        )
+    };
+    let (x, y) = project(position);
+    let (dx, dy) = project(end);
+    let length = dx * dx + dy * dy;
+    let fraction = if length > 0. {
+        ((x * dx + y * dy) / length).clamp(0., 1.)
+    } else {
+        0.
+    };
+    (x - fraction * dx).hypot(y - fraction * dy)
+}
+
+/// The bits of the largest deviation between `start` and `end`, `start`, the index of the
+/// position with this deviation and `end`.
+fn farthest(track: &[Position], start: usize, end: usize) -> Option<(u64, usize, usize, usize)> {
+    (start + 1..end)
+        .map(|index| (deviation(&track[index], &track[start], &track[end]), index))
+        .max_by(|(a, _), (b, _)| a.total_cmp(b))
+        .map(|(deviation, index)| (deviation.to_bits(), start, index, end))
+}
+
+/// The overviews of the entries in `stored`.
+///
+/// The overviews of the entries whose epoch differs from the one of the stored overview are
+/// computed from `tracks` which has to contain the tracks of all of them and added to `computed`.
+fn refresh<I: Copy + Eq + Hash>(
+    stored: Vec<StoredOverview<I>>,
+    mut tracks: HashMap<I, Vec<Position>>,
+    computed: &mut Vec<(I, Epoch, Vec<Position>)>,
+) -> Vec<(I, Vec<Position>)> {
+    let mut overviews = Vec::with_capacity(stored.len());
+    for (id, epoch, overview_epoch, overview) in stored {
+        match overview {
+            Some(overview) if overview_epoch == Some(epoch) => overviews.push((id, overview)),
+            _ => {
+                let Some(track) = tracks.remove(&id) else {
+                    continue;
+                };
+                let overview = Simplification::OVERVIEW.simplify(&track);
+                computed.push((id, epoch, overview.clone()));
+                overviews.push((id, overview));
+            }
+        }
+    }
+    overviews
+}
+
+fn outdated<I: Copy>(stored: &[StoredOverview<I>]) -> Vec<I> {
+    stored
+        .iter()
+        .filter(|(_, epoch, overview_epoch, _)| *overview_epoch != Some(*epoch))
+        .map(|(id, ..)| *id)
+        .collect()
+}
+
+/// Get the overviews of the cardio sessions of a user with a track ordered by datetime.
+///
+/// Missing and outdated overviews are computed and stored.
+pub async fn cardio_session_overviews(
+    user_id: UserId,
+    cardio_session_id: Option<CardioSessionId>,
+    timespan: Timespan,
+    db: &mut AsyncPgConnection,
+) -> QueryResult<Vec<CardioSessionOverview>> {
+    let stored =
+        CardioSessionOverviewDb::get_by_user(user_id, cardio_session_id, timespan, db).await?;
+    let tracks = CardioSessionOverviewDb::get_tracks(&outdated(&stored), db).await?;
+    let mut computed = vec![];
+    let overviews = refresh(stored, tracks.into_iter().collect(), &mut computed);
+    ignore_deleted(CardioSessionOverviewDb::upsert(&computed, db).await)?;
+    Ok(overviews
+        .into_iter()
+        .map(|(cardio_session_id, track)| CardioSessionOverview {
+            cardio_session_id,
+            track,
+        })
+        .collect())
+}
+
+/// Ignore foreign key violations of overviews of entries that were deleted concurrently.
+///
+/// The overviews are computed again on the next request.
+fn ignore_deleted(result: QueryResult<()>) -> QueryResult<()> {
+    match result {
+        Err(DieselError::DatabaseError(DatabaseErrorKind::ForeignKeyViolation, _)) => Ok(()),
+        result => result,
+    }
+}
+
+/// Get the overviews of the routes of a user with a track ordered by name.
+///
+/// Missing and outdated overviews are computed and stored.
+pub async fn route_overviews(
+    user_id: UserId,
+    route_id: Option<RouteId>,
+    db: &mut AsyncPgConnection,
+) -> QueryResult<Vec<RouteOverview>> {
+    let stored = RouteOverviewDb::get_by_user(user_id, route_id, db).await?;
+    let tracks = RouteOverviewDb::get_tracks(&outdated(&stored), db).await?;
+    let mut computed = vec![];
+    let overviews = refresh(stored, tracks.into_iter().collect(), &mut computed);
+    ignore_deleted(RouteOverviewDb::upsert(&computed, db).await)?;
+    Ok(overviews
+        .into_iter()
+        .map(|(route_id, track)| RouteOverview { route_id, track })
+        .collect())
+}
@@ -0,0 +1,293 @@
+//! Spatial queries on the tracks of cardio sessions and routes.
+//!
+//! If PostGIS is available, the tracks are stored as geometries and the queries are answered by
+//! the database.
+//! Otherwise the bounding boxes of the tracks are used to find candidates whose tracks are then
+//! checked line by line.
+
+use axum::http::StatusCode;
+use diesel::prelude::*;
+use serde::Deserialize;
+use sport_log_types::{
+    CardioSessionId, Position, RouteId,
+    schema::{cardio_session, route},
+};
+
+use crate::{
+    error::{ErrorMessage, HandlerError},
+    track_file::{self, EARTH_RADIUS},
+};
+
+/// The number of meters per degree of latitude.
+const METERS_PER_DEGREE: f64 = 111_320.;
+
+/// The maximal radius in meters of a distance query.
+const MAX_RADIUS: f64 = 100_000.;
+
+#[derive(Debug, Deserialize)]
+pub struct SpatialOption {
+    #[serde(default)]
+    pub min_latitude: Option<f64>,
+    #[serde(default)]
+    pub min_longitude: Option<f64>,
+    #[serde(default)]
+    pub max_latitude: Option<f64>,
+    #[serde(default)]
+    pub max_longitude: Option<f64>,
+    #[serde(default)]
+    pub latitude: Option<f64>,
+    #[serde(default)]
+    pub longitude: Option<f64>,
+    /// The distance from `latitude` and `longitude` in meters.
+    #[serde(default)]
+    pub radius: Option<f64>,
+}
+
+/// The area a track has to pass through.
+#[derive(Debug, Clone, Copy)]
+pub enum SpatialFilter {
+    BoundingBox {
+        min_latitude: f64,
+        min_longitude: f64,
+        max_latitude: f64,
+        max_longitude: f64,
+    },
+    Distance {
+        latitude: f64,
+        longitude: f64,
+        radius: f64,
+    },
+}
+
+impl TryFrom<SpatialOption> for SpatialFilter {
+    type Error = HandlerError;
+
+    fn try_from(option: SpatialOption) -> Result<Self, Self::Error> {
+        let error = |error: &str| {
+            HandlerError::from((
+                StatusCode::BAD_REQUEST,
+                ErrorMessage::Other {
+                    error: error.to_owned(),
+                },
+            ))
+        };
+        let latitude = |latitude: f64| (-90. ..=90.).contains(&latitude);
+        let longitude = |longitude: f64| (-180. ..=180.).contains(&longitude);
+
+        let filter = match option {
+            SpatialOption {
+                min_latitude: Some(min_latitude),
+                min_longitude: Some(min_longitude),
+                max_latitude: Some(max_latitude),
+                max_longitude: Some(max_longitude),
+                latitude: None,
+                longitude: None,
+                radius: None,
+            } => Self::BoundingBox {
+                min_latitude,
+                min_longitude,
+                max_latitude,
+                max_longitude,
+            },
+            SpatialOption {
+                min_latitude: None,
+                min_longitude: None,
+                max_latitude: None,
+                max_longitude: None,
+                latitude: Some(latitude),
+                longitude: Some(longitude),
+                radius: Some(radius),
+            } => Self::Distance {
+                latitude,
+                longitude,
+                radius,
+            },
+            _ => {
+                return Err(error(
+                    "Either min_latitude, min_longitude, max_latitude and max_longitude or \
+                    latitude, longitude and radius must be set.",
+                ));
+            }
+        };
+
+        match filter {
+            Self::BoundingBox {
+                min_latitude,
+                min_longitude,
+                max_latitude,
+                max_longitude,
+            } => {
+                if !(latitude(min_latitude)
+                    && latitude(max_latitude)
+                    && longitude(min_longitude)
+                    && longitude(max_longitude)
+                    && min_latitude <= max_latitude
+                    && min_longitude <= max_longitude)
+                {
+                    return Err(error("The bounding box is invalid."));
+                }
+            }
+            Self::Distance {
+                latitude: lat,
+                longitude: lon,
+                radius,
+            } => {
+                if !(latitude(lat) && longitude(lon)) {
+                    return Err(error("The position is invalid."));
+                }
+                if !(radius > 0. && radius <= MAX_RADIUS) {
+                    return Err(error(&format!(
+                        "The radius must be greater than 0 and at most {MAX_RADIUS} m."
+                    )));
+                }
+            }
+        }
+        Ok(filter)
+    }
+}
+
+impl SpatialFilter {
+    /// The bounding box of the filter as minimal longitude, minimal latitude, maximal longitude
+    /// and maximal latitude.
+    pub fn bounds(self) -> (f64, f64, f64, f64) {
+        match self {
+            Self::BoundingBox {
+                min_latitude,
+                min_longitude,
+                max_latitude,
+                max_longitude,
+            } => (min_longitude, min_latitude, max_longitude, max_latitude),
+            Self::Distance {
+                latitude,
+                longitude,
+                radius,
+            } => {
+                let latitude_delta = radius / METERS_PER_DEGREE;
+                let longitude_delta = (radius
+                    / (METERS_PER_DEGREE * latitude.to_radians().cos().max(f64::EPSILON)))
+                .min(180.);
+                (
+                    longitude - longitude_delta,
+                    (latitude - latitude_delta).max(-90.),
+                    longitude + longitude_delta,
+                    (latitude + latitude_delta).min(90.),
+                )
+            }
+        }
+    }
+
+    /// Check whether the track passes through the area.
+    ///
+    /// Like `ST_Intersects` and `ST_DWithin` the lines between consecutive positions are
+    /// considered as well.
+    pub fn matches(self, track: &[Position]) -> bool {
+        match track {
+            [] => false,
+            [position] => self.contains(position),
+            _ => track
+                .windows(2)
+                .any(|pair| self.intersects(&pair[0], &pair[1])),
+        }
+    }
+
+    /// Check whether the position lies in the area.
+    fn contains(self, position: &Position) -> bool {
+        match self {
+            Self::BoundingBox {
+                min_latitude,
+                min_longitude,
+                max_latitude,
+                max_longitude,
+            } => {
+                (min_latitude..=max_latitude).contains(&position.latitude)
+                    && (min_longitude..=max_longitude).contains(&position.longitude)
+            }
+            Self::Distance {
+                latitude,
+                longitude,
+                radius,
+            } => {
+                track_file::distance(latitude, longitude, position.latitude, position.longitude)
+                    <= radius
+            }
+        }
+    }
+
+    /// Check whether the line from `a` to `b` passes through the area.
+    fn intersects(self, a: &Position, b: &Position) -> bool {
+        if self.contains(a) || self.contains(b) {
+            return true;
+        }
+        match self {
+            Self::BoundingBox {
+                min_latitude,
+                min_longitude,
+                max_latitude,
+                max_longitude,
+            } => {
+                // Liang–Barsky clipping of the line to the bounding box
+                let (dx, dy) = (b.longitude - a.longitude, b.latitude - a.latitude);
+                let (mut from, mut to) = (0f64, 1f64);
+                for (p, q) in [
+                    (-dx, a.longitude - min_longitude),
+                    (dx, max_longitude - a.longitude),
+                    (-dy, a.latitude - min_latitude),
+                    (dy, max_latitude - a.latitude),
+                ] {
+                    if p == 0. {
+                        if q < 0. {
+                            return false;
+                        }
+                    } else if p < 0. {
+                        from = from.max(q / p);
+                    } else {
+                        to = to.min(q / p);
+                    }
+                }
+                from <= to
+            }
+            Self::Distance {
+                latitude,
+                longitude,
+                radius,
+            } => {
+                // the positions are projected onto a plane at the center, which is precise enough
+                // for the short distances between consecutive positions
+                let project = |position: &Position| {
+                    (
+                        (position.longitude - longitude).to_radians()
+                            * latitude.to_radians().cos()
+                            * EARTH_RADIUS,
+                        (position.latitude - latitude).to_radians() * EARTH_RADIUS,
+                    )
+                };
+                let (ax, ay) = project(a);
+                let (bx, by) = project(b);
+                let (dx, dy) = (bx - ax, by - ay);
+                let length = dx * dx + dy * dy;
+                let fraction = if length > 0. {
+                    (-(ax * dx + ay * dy) / length).clamp(0., 1.)
+                } else {
+                    0.
+                };
+                (ax + fraction * dx).hypot(ay + fraction * dy) <= radius
+            }
+        }
+    }
+}
+
+/// The id and track of a cardio session whose bounding box matches a [`SpatialFilter`].
+#[derive(Queryable, Selectable, Debug)]
+#[diesel(table_name = cardio_session)]
+pub struct CardioSessionCandidate {
+    pub id: CardioSessionId,
+    pub track: Option<Vec<Position>>,
+}
+
+/// The id and track of a route whose bounding box matches a [`SpatialFilter`].
+#[derive(Queryable, Selectable, Debug)]
+#[diesel(table_name = route)]
+pub struct RouteCandidate {
+    pub id: RouteId,
+    pub track: Option<Vec<Position>>,
+}
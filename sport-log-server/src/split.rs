@@ -0,0 +1,203 @@
+//! Splits and best efforts of cardio sessions interpolated from their track.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use sport_log_types::{
+    CardioBestEffort, CardioSessionId, CardioSessionTrack, CardioSplit, MovementId, Position,
+    schema::cardio_session,
+};
+
+/// The distances in meters of the best efforts (1 km, 5 km, 10 km and half marathon).
+pub const BEST_EFFORT_DISTANCES: [f64; 4] = [1000., 5000., 10_000., 21_097.5];
+
+/// The maximal number of splits of a track.
+const MAX_SPLITS: f64 = 10_000.;
+
+/// The fields of a cardio session that are needed to compute its best efforts.
+#[derive(Queryable, Selectable, Debug)]
+#[diesel(table_name = cardio_session)]
+pub struct CardioSessionPositions {
+    pub id: CardioSessionId,
+    pub movement_id: MovementId,
+    pub datetime: DateTime<Utc>,
+    pub track: Option<Vec<Position>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    distance: f64,
+    time: f64,
+    elevation: f64,
+}
+
+impl From<&Position> for Sample {
+    fn from(position: &Position) -> Self {
+        Self {
+            distance: position.distance,
+            time: f64::from(position.time),
+            elevation: position.elevation,
+        }
+    }
+}
+
+impl Sample {
+    /// The sample at `distance` between `self` and `next`.
+    ///
+    /// `distance` must be in the range of the distances of both samples.
+    fn interpolate(self, next: Self, distance: f64) -> Self {
+        let length = next.distance - self.distance;
+        if length <= 0. {
+            return next;
+        }
+        let fraction = (distance - self.distance) / length;
+        Self {
+            distance,
+            time: self.time + fraction * (next.time - self.time),
+            elevation: self.elevation + fraction * (next.elevation - self.elevation),
+        }
+    }
+}
+
+/// The average rate in events per minute of the events between `start` and `end`.
+pub fn avg_rate(events: Option<&[i32]>, start: f64, end: f64) -> Option<i32> {
+    let events = events?;
+    if end <= start {
+        return None;
+    }
+    let count = events.partition_point(|&event| f64::from(event) < end)
+        - events.partition_point(|&event| f64::from(event) < start);
+    Some((count as f64 * 60_000. / (end - start)).round() as i32)
+}
+
+/// Split the track of a cardio session into splits of `split_distance` meters.
+///
+/// The ascent and descent of a split are the sums of all elevation differences within it.
+/// Returns no splits if the session has no track.
+/// The remainder of tracks that are longer than [`MAX_SPLITS`] splits is returned as the last split.
+pub fn splits(track: &CardioSessionTrack, split_distance: f64) -> Vec<CardioSplit> {
+    let Some([first, positions @ ..]) = track.track.as_deref() else {
+        return vec![];
+    };
+
+    let mut splits = vec![];
+    let mut push_split = |start: Sample, end: Sample, ascent: f64, descent: f64| {
+        let distance = end.distance - start.distance;
+        let time = end.time - start.time;
+        splits.push(CardioSplit {
+            end_distance: end.distance,
+            distance,
+            time: time.round() as i32,
+            // milliseconds per meter are seconds per kilometer
+            pace: time / distance,
+            ascent,
+            descent,
+            avg_heart_rate: avg_rate(track.heart_rate.as_deref(), start.time, end.time),
+            avg_cadence: avg_rate(track.cadence.as_deref(), start.time, end.time),
+        });
+    };
+
+    let mut start = Sample::from(first);
+    let mut last = start;
+    let (mut ascent, mut descent) = (0., 0.);
+    let climb = |from: Sample, to: Sample, ascent: &mut f64, descent: &mut f64| {
+        let difference = to.elevation - from.elevation;
+        if difference > 0. {
+            *ascent += difference;
+        } else {
+            *descent -= difference;
+        }
+    };
+    let origin = start.distance;
+    let mut count = 1.;
+    for position in positions {
+        let sample = Sample::from(position);
+        // the boundaries are computed from the count, so that they always advance
+        let sample_count = ((sample.distance - origin) / split_distance)
+            .floor()
+            .clamp(0., MAX_SPLITS);
+        while count <= sample_count {
+            let end = last.interpolate(sample, origin + count * split_distance);
+            climb(last, end, &mut ascent, &mut descent);
+            push_split(start, end, ascent, descent);
+            (start, last) = (end, end);
+            (ascent, descent) = (0., 0.);
+            count += 1.;
+        }
+        climb(last, sample, &mut ascent, &mut descent);
+        last = sample;
+    }
+    if last.distance > start.distance {
+        push_split(start, last, ascent, descent);
+    }
+
+    splits
+}
+
+/// The fastest effort over `distance` meters as the start time and duration in milliseconds.
+///
+/// Only efforts starting at a position of the track are considered.
+fn best_effort(track: &[Position], distance: f64) -> Option<(i32, i32)> {
+    let mut best: Option<(f64, f64)> = None;
+    let mut end = 0;
+    for (i, position) in track.iter().enumerate() {
+        let target = position.distance + distance;
+        end = end.max(i + 1);
+        while end < track.len() && track[end].distance < target {
+            end += 1;
+        }
+        let Some(next) = track.get(end) else {
+            break;
+        };
+        let start = Sample::from(position);
+        let time = Sample::from(&track[end - 1])
+            .interpolate(Sample::from(next), target)
+            .time
+            - start.time;
+        if best.is_none_or(|(_, best_time)| time < best_time) {
+            best = Some((start.time, time));
+        }
+    }
+    best.map(|(start, time)| (start.round() as i32, time.round() as i32))
+}
+
+/// The best efforts over [`BEST_EFFORT_DISTANCES`] of every movement.
+///
+/// Distances which have not been covered by any session of a movement are omitted.
+#[derive(Debug, Default)]
+pub struct BestEfforts(BTreeMap<(MovementId, usize), CardioBestEffort>);
+
+impl BestEfforts {
+    /// Add the efforts of a session which are faster than the best efforts so far.
+    pub fn add(&mut self, session: &CardioSessionPositions) {
+        let Some(track) = session.track.as_deref() else {
+            return;
+        };
+        for (index, distance) in BEST_EFFORT_DISTANCES.into_iter().enumerate() {
+            let Some((start, time)) = best_effort(track, distance) else {
+                continue;
+            };
+            let best_effort = CardioBestEffort {
+                movement_id: session.movement_id,
+                distance,
+                cardio_session_id: session.id,
+                datetime: session.datetime,
+                start,
+                time,
+            };
+            self.0
+                .entry((session.movement_id, index))
+                .and_modify(|best| {
+                    if time < best.time {
+                        *best = best_effort.clone();
+                    }
+                })
+                .or_insert(best_effort);
+        }
+    }
+
+    pub fn into_vec(self) -> Vec<CardioBestEffort> {
+        self.0.into_values().collect()
+    }
+}
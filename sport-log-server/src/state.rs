@@ -7,12 +7,15 @@ use diesel_async::{
     pooled_connection::deadpool::{Object, Pool},
 };
 
-use crate::Config;
+use crate::{Config, crypto::CredentialKeys, heatmap::HeatmapCache, notify::EpochChanges};
 
 #[derive(Clone)]
 pub struct AppState {
     pub db_pool: DbPool,
     pub config: &'static Config,
+    pub credential_keys: &'static CredentialKeys,
+    pub epoch_changes: EpochChanges,
+    pub heatmap_cache: HeatmapCache,
 }
 
 pub type DbPool = Pool<AsyncPgConnection>;
@@ -24,6 +27,24 @@ impl FromRef<AppState> for &'static Config {
     }
 }
 
+impl FromRef<AppState> for &'static CredentialKeys {
+    fn from_ref(state: &AppState) -> Self {
+        state.credential_keys
+    }
+}
+
+impl FromRef<AppState> for EpochChanges {
+    fn from_ref(state: &AppState) -> Self {
+        state.epoch_changes.clone()
+    }
+}
+
+impl FromRef<AppState> for HeatmapCache {
+    fn from_ref(state: &AppState) -> Self {
+        state.heatmap_cache.clone()
+    }
+}
+
 impl FromRequestParts<AppState> for DbConn {
     type Rejection = StatusCode;
 
@@ -1,58 +1,114 @@
-use std::{io::Write, sync::LazyLock};
+use std::{
+    io::{Read, Write},
+    sync::LazyLock,
+};
 
 use axum::{
     Router,
     body::{self, Body},
     http::{
-        HeaderName, HeaderValue, Request, StatusCode,
-        header::{ACCEPT_ENCODING, AUTHORIZATION, CONTENT_TYPE},
+        HeaderName, HeaderValue, Method, Request, StatusCode,
+        header::{ACCEPT_ENCODING, AUTHORIZATION, CONTENT_TYPE, ETAG, IF_NONE_MATCH},
     },
     response::Response,
 };
 use base64::{Engine, engine::general_purpose::STANDARD};
-use chrono::{Duration, Utc};
+use chrono::{DateTime, Duration, Utc};
+use diesel::{ExpressionMethods, QueryDsl};
 use diesel_async::{
-    AsyncConnection, AsyncPgConnection,
+    AnsiTransactionManager, AsyncConnection, AsyncPgConnection, RunQueryDsl, TransactionManager,
     pooled_connection::{
         AsyncDieselConnectionManager,
         deadpool::{Hook, Pool},
     },
 };
-use flate2::write::GzDecoder;
+use flate2::{read::ZlibDecoder, write::GzDecoder};
+use futures_util::StreamExt;
 use hyper::header::CONTENT_ENCODING;
 use mime::APPLICATION_JSON;
 use rand::RngExt;
 use serde::de::DeserializeOwned;
 use sport_log_types::{
-    ADMIN_USERNAME, AccountData, Action, ActionEvent, ActionEventId, ActionId, ActionProvider,
-    ActionProviderId, Diary, DiaryId, Epoch, EpochMap, EpochResponse, ID_HEADER, Platform,
-    PlatformId, User, UserId,
+    ADMIN_USERNAME, AccountData, AccountDataChanges, AccountDataChangesResponse, AccountDataCursor,
+    AccountDataPage, AccountDataSync, Action, ActionEvent, ActionEventId, ActionId, ActionProvider,
+    ActionProviderId, ActionProviderScope, ActionProviderScopeId, Archived, AuthTokens,
+    CardioBestEffort, CardioSession, CardioSessionId, CardioSessionOverview, CardioSessionSummary,
+    CardioSessionTrack, CardioSessionValidation, CardioSessionZones, CardioSplit, CardioStatistic,
+    CardioSummaryField, CardioType, Diary, DiaryId, DistanceUnit, Epoch, EpochMap, EpochResponse,
+    ExecutableActionEvent, ID_HEADER, Metcon, MetconId, MetconMovement, MetconMovementId,
+    MetconSession, MetconSessionId, MetconStatistic, MetconType, Movement, MovementDimension,
+    MovementId, Platform, PlatformCredential, PlatformCredentialId, PlatformId, Position,
+    PurgedArchive, RefreshToken, Route, RouteHistory, RouteId, RouteMatch, RouteOverview,
+    RouteSummary, RouteTrack, ScopeAccess, ScopeEntity, ScopeGrant, ScopeGrantId, Segment,
+    SegmentEffort, SegmentId, StrengthRecords, StrengthSession, StrengthSessionId, StrengthSet,
+    StrengthSetId, StrengthStatistic, TrainingImpulse, TrainingLoad, TrainingZone, TrainingZoneId,
+    TrainingZoneType, User, UserId, Wod, WodId, ZoneStatistic,
+    schema::{
+        cardio_session_overview, diary, platform_credential, segment_effort, strength_set, user,
+    },
     uri::{
-        ACCOUNT_DATA, ADM_PLATFORM, AP_ACTION_PROVIDER, AP_PLATFORM, DIARY, USER, route_max_version,
+        ACCOUNT_DATA, ACCOUNT_DATA_EVENTS, ACCOUNT_DATA_PAGE, ACCOUNT_DATA_SYNC,
+        ADM_NORMALIZE_CARDIO_SESSION, ADM_PLATFORM, ADM_PURGE_ARCHIVE, AP_ACTION_PROVIDER,
+        AP_ACTION_PROVIDER_SCOPE, AP_EXECUTABLE_ACTION_EVENT, AP_PLATFORM, CARDIO_BEST_EFFORT,
+        CARDIO_SESSION, CARDIO_SESSION_EXPORT, CARDIO_SESSION_IMPORT, CARDIO_SESSION_OVERVIEW,
+        CARDIO_SESSION_ROUTE_MATCH, CARDIO_SESSION_SPATIAL, CARDIO_SESSION_SPLIT,
+        CARDIO_SESSION_SUMMARY, CARDIO_SESSION_TRACK, CARDIO_SESSION_VALIDATION,
+        CARDIO_SESSION_ZONE, CARDIO_STATISTIC, DIARY, DIARY_ARCHIVE, DIARY_RESTORE, LOGIN, LOGOUT,
+        LOGOUT_ALL, METCON_STATISTIC, PLATFORM_CREDENTIAL, REFRESH, ROUTE, ROUTE_EXPORT,
+        ROUTE_HISTORY, ROUTE_MATCH, ROUTE_OVERVIEW, ROUTE_SPATIAL, ROUTE_TRACK, SEGMENT,
+        SEGMENT_BEST_EFFORT, SEGMENT_EFFORT, STRENGTH_RECORD, STRENGTH_SESSION_ARCHIVE,
+        STRENGTH_SESSION_RESTORE, STRENGTH_SET_ARCHIVE, STRENGTH_STATISTIC, TRAINING_IMPULSE,
+        TRAINING_LOAD, TRAINING_ZONE, USER, ZONE_STATISTIC, route_max_version,
     },
 };
 use tower::Service;
 
 use crate::{
-    config::Config,
+    config::{ArchiveRetention, Config},
+    crypto::CredentialKeys,
     db::*,
-    get_config, router,
+    get_config,
+    handler::{ErrorMessage, RowResultsResponse},
+    heatmap::HeatmapCache,
+    notify::EpochChanges,
+    router, split,
     state::{AppState, DbPool},
+    track_file::{self, TrackFileFormat},
 };
 
 const ADMIN_PASSWORD_PLAINTEXT: &str = "admin-passwd";
 
+const GPX: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<gpx version="1.1" creator="test" xmlns="http://www.topografix.com/GPX/1/1"
+  xmlns:gpxtpx="http://www.garmin.com/xmlschemas/TrackPointExtension/v1">
+  <trk><trkseg>
+    <trkpt lat="47.0" lon="11.0"><ele>600</ele><time>2024-05-01T10:00:00Z</time>
+      <extensions><gpxtpx:TrackPointExtension><gpxtpx:hr>120</gpxtpx:hr></gpxtpx:TrackPointExtension></extensions>
+    </trkpt>
+    <trkpt lat="47.001" lon="11.0"><ele>620</ele><time>2024-05-01T10:01:00Z</time>
+      <extensions><gpxtpx:TrackPointExtension><gpxtpx:hr>120</gpxtpx:hr></gpxtpx:TrackPointExtension></extensions>
+    </trkpt>
+    <trkpt lat="47.002" lon="11.0"><ele>605</ele><time>2024-05-01T10:02:00Z</time>
+      <extensions><gpxtpx:TrackPointExtension><gpxtpx:hr>120</gpxtpx:hr></gpxtpx:TrackPointExtension></extensions>
+    </trkpt>
+  </trkseg></trk>
+</gpx>"#;
+
 static TEST_USER: LazyLock<User> = LazyLock::new(|| User {
     id: UserId(123_456_789),
     username: String::from("test-user-username-123456789"),
     password: String::from("test-user-Password-123456789"),
     email: String::from("test-user-email-123456789"),
+    max_heart_rate: None,
+    resting_heart_rate: None,
 });
 static TEST_USER2: LazyLock<User> = LazyLock::new(|| User {
     id: UserId(213_456_789),
     username: String::from("test-user2-username-213456789"),
     password: String::from("test-user2-Password-213456789"),
     email: String::from("test-user2-email-213456789"),
+    max_heart_rate: None,
+    resting_heart_rate: None,
 });
 static TEST_PLATFORM: LazyLock<Platform> = LazyLock::new(|| Platform {
     id: PlatformId(123_456_789),
@@ -77,6 +133,13 @@ static TEST_ACTION: LazyLock<Action> = LazyLock::new(|| Action {
     delete_after: 1,
     deleted: false,
 });
+static TEST_AP_SCOPE: LazyLock<ActionProviderScope> = LazyLock::new(|| ActionProviderScope {
+    id: ActionProviderScopeId(123_456_789),
+    action_provider_id: TEST_AP.id,
+    entity: ScopeEntity::Diary,
+    access: ScopeAccess::Read,
+    deleted: false,
+});
 static TEST_DIARY: LazyLock<Diary> = LazyLock::new(|| Diary {
     id: DiaryId(123_456_789),
     user_id: TEST_USER.id,
@@ -119,9 +182,14 @@ async fn init() -> (Router, DbPool, &'static Config) {
 
     let db_pool = get_test_db_pool(config);
 
+    let credential_keys = Box::leak(Box::new(CredentialKeys::new(config).unwrap()));
+
     let state = AppState {
         db_pool: db_pool.clone(),
         config,
+        credential_keys,
+        epoch_changes: EpochChanges::default(),
+        heatmap_cache: HeatmapCache::default(),
     };
 
     let router = router::get_router(state);
@@ -139,6 +207,9 @@ async fn init() -> (Router, DbPool, &'static Config) {
         .await
         .unwrap();
     ActionDb::create(&TEST_ACTION, &mut db).await.unwrap();
+    ActionProviderScopeDb::create(&TEST_AP_SCOPE, &mut db)
+        .await
+        .unwrap();
 
     (router, db_pool, config)
 }
@@ -157,6 +228,10 @@ fn auth_header(username: &str, password: &str) -> (HeaderName, String) {
     )
 }
 
+fn bearer_header(token: &str) -> (HeaderName, String) {
+    (AUTHORIZATION, format!("Bearer {token}"))
+}
+
 fn auth_as_headers(username: &str, id: i64, password: &str) -> [(HeaderName, String); 2] {
     [auth_header(username, password), (ID_HEADER, id.to_string())]
 }
@@ -198,6 +273,123 @@ async fn account_data_request(
     (status, account_data)
 }
 
+async fn account_data_changes_request(
+    router: &mut Router,
+    method: Method,
+    changes: &AccountDataChanges,
+) -> (StatusCode, AccountDataChangesResponse) {
+    let auth_header = auth_header(&TEST_USER.username, &TEST_USER.password);
+    let response = request(
+        router,
+        Request::builder()
+            .method(method)
+            .uri(route_max_version("", ACCOUNT_DATA, None))
+            .header(auth_header.0, auth_header.1)
+            .header(CONTENT_TYPE, APPLICATION_JSON.as_ref())
+            .body(serde_json::to_string(changes).unwrap().into())
+            .unwrap(),
+    )
+    .await;
+
+    let status = response.status();
+    let response = parse_body(response).await;
+    (status, response)
+}
+
+async fn create_cardio_movement(db: &mut AsyncPgConnection) -> Movement {
+    let movement = Movement {
+        id: MovementId(rnd()),
+        user_id: Some(TEST_USER.id),
+        name: format!("movement{}", rnd()),
+        description: None,
+        movement_dimension: MovementDimension::Distance,
+        cardio: true,
+        deleted: false,
+    };
+    MovementDb::create(&movement, db).await.unwrap();
+    movement
+}
+
+fn cardio_session(movement_id: MovementId) -> CardioSession {
+    CardioSession {
+        id: CardioSessionId(rnd()),
+        user_id: TEST_USER.id,
+        movement_id,
+        cardio_type: CardioType::Training,
+        datetime: Utc::now(),
+        distance: None,
+        ascent: None,
+        descent: None,
+        time: None,
+        calories: None,
+        track: None,
+        avg_cadence: None,
+        cadence: None,
+        avg_heart_rate: None,
+        heart_rate: None,
+        route_id: None,
+        comments: None,
+        deleted: false,
+    }
+}
+
+async fn import_request(
+    router: &mut Router,
+    movement_id: MovementId,
+    format: Option<&str>,
+    file: Vec<u8>,
+) -> Response {
+    let header = auth_header(&TEST_USER.username, &TEST_USER.password);
+    let movement_id = movement_id.0.to_string();
+    let mut query = vec![
+        ("movement_id", movement_id.as_str()),
+        ("cardio_type", "Training"),
+    ];
+    query.extend(format.map(|format| ("format", format)));
+    request(
+        router,
+        Request::post(route_max_version("", CARDIO_SESSION_IMPORT, Some(&query)))
+            .header(header.0, header.1)
+            .body(file.into())
+            .unwrap(),
+    )
+    .await
+}
+
+async fn export_request(router: &mut Router, route: &str, id: i64, format: &str) -> Response {
+    let header = auth_header(&TEST_USER.username, &TEST_USER.password);
+    let route =
+        route_max_version("", route, Some(&[("format", format)])).replace("{id}", &id.to_string());
+    request(
+        router,
+        Request::get(route)
+            .header(header.0, header.1)
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await
+}
+
+async fn archive_request(
+    router: &mut Router,
+    method: Method,
+    route: &str,
+    id: i64,
+    user: &User,
+) -> Response {
+    let header = auth_header(&user.username, &user.password);
+    request(
+        router,
+        Request::builder()
+            .method(method)
+            .uri(route_max_version("", route, None).replace("{id}", &id.to_string()))
+            .header(header.0, header.1)
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await
+}
+
 async fn parse_body<T: DeserializeOwned>(response: Response) -> T {
     let bytes = body::to_bytes(response.into_body(), usize::MAX)
         .await
@@ -218,6 +410,36 @@ async fn parse_gzip_body<T: DeserializeOwned>(response: Response) -> T {
     serde_json::from_str(data).unwrap()
 }
 
+async fn login(router: &mut Router) -> AuthTokens {
+    let header = auth_header(&TEST_USER.username, &TEST_USER.password);
+    let response = request(
+        router,
+        Request::post(route_max_version("", LOGIN, None))
+            .header(header.0, header.1)
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_json(&response);
+    parse_body(response).await
+}
+
+/// Use a get request with an access token and return the status.
+async fn bearer_auth(router: &mut Router, route: &str, access_token: &str) -> StatusCode {
+    let header = bearer_header(access_token);
+    request(
+        router,
+        Request::get(route)
+            .header(header.0, header.1)
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await
+    .status()
+}
+
 /// Use a get request to make sure that the authentication succeeds.
 async fn auth(router: &mut Router, route: &str, username: &str, password: &str) {
     let header = auth_header(username, password);
@@ -491,6 +713,100 @@ async fn user_auth_without_credentials() {
     auth_without_credentials(&mut router, &route_max_version("", USER, None)).await;
 }
 
+#[tokio::test]
+async fn user_token_auth() {
+    let (mut router, _, _) = init().await;
+    let tokens = login(&mut router).await;
+
+    for route in [USER, DIARY] {
+        let status = bearer_auth(
+            &mut router,
+            &route_max_version("", route, None),
+            &tokens.access_token,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    let status = bearer_auth(
+        &mut router,
+        &route_max_version("", USER, None),
+        "wrong token",
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn user_token_refresh() {
+    let (mut router, _, _) = init().await;
+    let tokens = login(&mut router).await;
+
+    let refresh = |refresh_token: String| {
+        Request::post(route_max_version("", REFRESH, None))
+            .header(CONTENT_TYPE, APPLICATION_JSON.as_ref())
+            .body(
+                serde_json::to_string(&RefreshToken { refresh_token })
+                    .unwrap()
+                    .into(),
+            )
+            .unwrap()
+    };
+
+    let response = request(&mut router, refresh(tokens.refresh_token.clone())).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let new_tokens: AuthTokens = parse_body(response).await;
+
+    let route = route_max_version("", USER, None);
+    let status = bearer_auth(&mut router, &route, &tokens.access_token).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let status = bearer_auth(&mut router, &route, &new_tokens.access_token).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // refresh tokens can only be used once
+    let response = request(&mut router, refresh(tokens.refresh_token)).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn user_token_logout() {
+    let (mut router, _, _) = init().await;
+    let tokens = login(&mut router).await;
+    let other_tokens = login(&mut router).await;
+
+    let header = bearer_header(&tokens.access_token);
+    let response = request(
+        &mut router,
+        Request::post(route_max_version("", LOGOUT, None))
+            .header(header.0, header.1)
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let route = route_max_version("", USER, None);
+    let status = bearer_auth(&mut router, &route, &tokens.access_token).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let status = bearer_auth(&mut router, &route, &other_tokens.access_token).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // revoke all sessions using basic auth
+    let header = auth_header(&TEST_USER.username, &TEST_USER.password);
+    let response = request(
+        &mut router,
+        Request::post(route_max_version("", LOGOUT_ALL, None))
+            .header(header.0, header.1)
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let status = bearer_auth(&mut router, &route, &other_tokens.access_token).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
 #[tokio::test]
 async fn admin_as_user_auth() {
     let (mut router, _, _) = init().await;
@@ -574,6 +890,17 @@ async fn ap_as_user_ap_auth() {
         .await
         .unwrap();
 
+    // grant the scope for diaries
+    let scope_grant = ScopeGrant {
+        id: ScopeGrantId(rnd()),
+        user_id: TEST_USER.id,
+        action_provider_scope_id: TEST_AP_SCOPE.id,
+        deleted: false,
+    };
+    ScopeGrantDb::create(&scope_grant, &mut db_pool.get().await.unwrap())
+        .await
+        .unwrap();
+
     auth_as(
         &mut router,
         &route_max_version("", DIARY, None),
@@ -584,6 +911,124 @@ async fn ap_as_user_ap_auth() {
     .await;
 }
 
+#[tokio::test]
+async fn ap_as_user_ap_auth_missing_scope() {
+    let (mut router, db_pool, _) = init().await;
+
+    // create ActionEvent to ensure access permission for user
+    let action_event = ActionEvent {
+        id: ActionEventId(rnd()),
+        user_id: TEST_USER.id,
+        action_id: TEST_ACTION.id,
+        datetime: Utc::now() + Duration::try_days(1).unwrap(),
+        arguments: None,
+        enabled: true,
+        deleted: false,
+    };
+    ActionEventDb::create(&action_event, &mut db_pool.get().await.unwrap())
+        .await
+        .unwrap();
+
+    let [basic_header, user_id_header] =
+        auth_as_headers(&TEST_AP.name, TEST_USER.id.0, &TEST_AP.password);
+
+    // the scope has not been granted
+    let response = request(
+        &mut router,
+        Request::get(route_max_version("", DIARY, None))
+            .header(basic_header.0.clone(), basic_header.1.clone())
+            .header(user_id_header.0.clone(), user_id_header.1.clone())
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let error: serde_json::Value = parse_body(response).await;
+    assert_eq!(
+        error["message"]["missing_scope"],
+        serde_json::json!({ "entity": "Diary", "access": "Read" })
+    );
+
+    // only the read scope is granted
+    let scope_grant = ScopeGrant {
+        id: ScopeGrantId(rnd()),
+        user_id: TEST_USER.id,
+        action_provider_scope_id: TEST_AP_SCOPE.id,
+        deleted: false,
+    };
+    ScopeGrantDb::create(&scope_grant, &mut db_pool.get().await.unwrap())
+        .await
+        .unwrap();
+
+    let diary = Diary {
+        id: DiaryId(rnd()),
+        user_id: TEST_USER.id,
+        ..TEST_DIARY.clone()
+    };
+    let response = request(
+        &mut router,
+        Request::post(route_max_version("", DIARY, None))
+            .header(basic_header.0, basic_header.1)
+            .header(user_id_header.0, user_id_header.1)
+            .header(CONTENT_TYPE, APPLICATION_JSON.as_ref())
+            .body(serde_json::to_string(&diary).unwrap().into())
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let error: serde_json::Value = parse_body(response).await;
+    assert_eq!(
+        error["message"]["missing_scope"],
+        serde_json::json!({ "entity": "Diary", "access": "Write" })
+    );
+}
+
+#[tokio::test]
+async fn ap_update_scope() {
+    let (mut router, _, _) = init().await;
+
+    let header = auth_header(&TEST_AP.name, &TEST_AP.password);
+    let mut update_scope = async |action_provider_scope: &ActionProviderScope| {
+        request(
+            &mut router,
+            Request::put(route_max_version("", AP_ACTION_PROVIDER_SCOPE, None))
+                .header(header.0.clone(), header.1.clone())
+                .header(CONTENT_TYPE, APPLICATION_JSON.as_ref())
+                .body(serde_json::to_string(action_provider_scope).unwrap().into())
+                .unwrap(),
+        )
+        .await
+        .status()
+    };
+
+    // the access of a granted scope can not be widened
+    let action_provider_scope = ActionProviderScope {
+        access: ScopeAccess::Write,
+        ..TEST_AP_SCOPE.clone()
+    };
+    assert_eq!(
+        update_scope(&action_provider_scope).await,
+        StatusCode::FORBIDDEN
+    );
+
+    // and the entity can not be changed
+    let action_provider_scope = ActionProviderScope {
+        entity: ScopeEntity::CardioSession,
+        ..TEST_AP_SCOPE.clone()
+    };
+    assert_eq!(
+        update_scope(&action_provider_scope).await,
+        StatusCode::FORBIDDEN
+    );
+
+    // but the scope can be deleted
+    let action_provider_scope = ActionProviderScope {
+        deleted: true,
+        ..TEST_AP_SCOPE.clone()
+    };
+    assert_eq!(update_scope(&action_provider_scope).await, StatusCode::OK);
+}
+
 #[tokio::test]
 async fn ap_as_user_ap_auth_no_event() {
     let (mut router, db_pool, _) = init().await;
@@ -988,48 +1433,2891 @@ async fn get_account_data() {
     assert_eq!(account_data.diaries[0].id, TEST_DIARY.id);
 }
 
+async fn account_data_page_request(
+    router: &mut Router,
+    epoch_map: Option<EpochMap>,
+    cursor: Option<AccountDataCursor>,
+) -> (StatusCode, AccountDataPage) {
+    let auth_header = auth_header(&TEST_USER.username, &TEST_USER.password);
+    let cursor = cursor.map(|cursor| cursor.to_string());
+    let mut query = vec![("page_size", "2")];
+    if let Some(cursor) = &cursor {
+        query.push(("cursor", cursor));
+    }
+    let response = request(
+        router,
+        Request::get(route_max_version("", ACCOUNT_DATA_PAGE, Some(&query)))
+            .header(auth_header.0, auth_header.1)
+            .header(CONTENT_TYPE, APPLICATION_JSON.as_ref())
+            .body(serde_json::to_string(&epoch_map).unwrap().into())
+            .unwrap(),
+    )
+    .await;
+
+    let status = response.status();
+    let page = parse_body(response).await;
+    (status, page)
+}
+
 #[tokio::test]
-async fn epoch_from_create_and_update() {
-    let (mut router, _, _) = init().await;
+async fn account_data_pages() {
+    let (mut router, db_pool, _) = init().await;
 
-    // get account data - check empty & extract diary epoch
-    let (status, account_data) = account_data_request(&mut router, None).await;
-    let epoch = account_data.epoch_map.diary;
+    let mut db = db_pool.get().await.unwrap();
+    for i in 0..3 {
+        let diary = Diary {
+            id: DiaryId(rnd()),
+            date: TEST_DIARY.date - Duration::days(i),
+            ..TEST_DIARY.clone()
+        };
+        DiaryDb::create(&diary, &mut db).await.unwrap();
+    }
+    let wod = Wod {
+        id: WodId(rnd()),
+        user_id: TEST_USER.id,
+        date: TEST_DIARY.date,
+        description: None,
+        deleted: false,
+    };
+    WodDb::create(&wod, &mut db).await.unwrap();
+    drop(db);
+
+    let (_, account_data) = account_data_request(&mut router, None).await;
+
+    // get all pages - check that all entries are returned exactly once
+    let mut diaries = vec![];
+    let mut wods = vec![];
+    let mut platforms = vec![];
+    let mut cursor = None;
+    let mut epoch_map = None;
+    for page_number in 0.. {
+        let (status, page) = account_data_page_request(&mut router, None, cursor).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let data = page.account_data;
+        assert_eq!(data.user.is_some(), page_number == 0);
+        assert!(data.diaries.len() + data.wods.len() + data.platforms.len() <= 2);
+        epoch_map.get_or_insert(data.epoch_map);
+        diaries.extend(data.diaries.into_iter().map(|diary| diary.id));
+        wods.extend(data.wods.into_iter().map(|wod| wod.id));
+        platforms.extend(data.platforms.into_iter().map(|platform| platform.id));
+
+        cursor = page.next;
+        if cursor.is_none() {
+            break;
+        }
+    }
+    assert_eq!(diaries.len(), 3);
+    assert_eq!(wods, vec![wod.id]);
+    assert_eq!(platforms.len(), account_data.platforms.len());
+
+    // get pages since the epoch map of the first page - check no new data
+    let (status, page) = account_data_page_request(&mut router, epoch_map, None).await;
 
     assert_eq!(status, StatusCode::OK);
-    assert!(account_data.diaries.is_empty());
+    assert!(page.account_data.user.is_none());
+    assert!(page.account_data.diaries.is_empty());
+    assert!(page.account_data.wods.is_empty());
+    assert!(page.next.is_none());
+}
 
-    // create new diary - check epoch increased by one
+#[tokio::test]
+async fn cardio_session_summary_and_track() {
+    let (mut router, db_pool, _) = init().await;
+
+    let mut db = db_pool.get().await.unwrap();
+    let movement = create_cardio_movement(&mut db).await;
+    let position = Position {
+        longitude: 11.0,
+        latitude: 47.0,
+        elevation: 600.0,
+        distance: 0.0,
+        time: 0,
+    };
+    let cardio_session = CardioSession {
+        distance: Some(1000),
+        time: Some(300_000),
+        track: Some(vec![position.clone(), position]),
+        avg_heart_rate: Some(140),
+        heart_rate: Some(vec![130, 150]),
+        ..cardio_session(movement.id)
+    };
+    CardioSessionDb::create(&cardio_session, &mut db)
+        .await
+        .unwrap();
+    drop(db);
+
+    // get summaries - check summary of cardio session
     let header = auth_header(&TEST_USER.username, &TEST_USER.password);
     let response = request(
         &mut router,
-        Request::post(route_max_version("", DIARY, None))
+        Request::get(route_max_version("", CARDIO_SESSION_SUMMARY, None))
             .header(header.0, header.1)
-            .header(CONTENT_TYPE, APPLICATION_JSON.as_ref())
-            .body(serde_json::to_string(&TEST_DIARY as &Diary).unwrap().into())
+            .body(Body::empty())
             .unwrap(),
     )
     .await;
 
     assert_eq!(response.status(), StatusCode::OK);
-    let epoch_response: EpochResponse = parse_body(response).await;
-    assert_eq!(epoch_response.epoch, Epoch(epoch.0 + 1));
+    let summaries: Vec<CardioSessionSummary> = parse_body(response).await;
+    assert_eq!(summaries.len(), 1);
+    assert_eq!(summaries[0].id, cardio_session.id);
+    assert_eq!(summaries[0].avg_heart_rate, Some(140));
 
-    // update diary - check epoch increased again by one
+    // get track - check series
     let header = auth_header(&TEST_USER.username, &TEST_USER.password);
+    let route = route_max_version("", CARDIO_SESSION_TRACK, None)
+        .replace("{id}", &cardio_session.id.0.to_string());
     let response = request(
         &mut router,
-        Request::put(route_max_version("", DIARY, None))
+        Request::get(route)
             .header(header.0, header.1)
-            .header(CONTENT_TYPE, APPLICATION_JSON.as_ref())
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let track: CardioSessionTrack = parse_body(response).await;
+    assert_eq!(track.id, cardio_session.id);
+    assert_eq!(track.track.unwrap().len(), 2);
+    assert_eq!(track.heart_rate, Some(vec![130, 150]));
+    assert!(track.cadence.is_none());
+
+    // get account data without tracks - check series omitted
+    let header = auth_header(&TEST_USER.username, &TEST_USER.password);
+    let response = request(
+        &mut router,
+        Request::get(route_max_version(
+            "",
+            ACCOUNT_DATA,
+            Some(&[("omit_tracks", "true")]),
+        ))
+        .header(header.0, header.1)
+        .header(CONTENT_TYPE, APPLICATION_JSON.as_ref())
+        .body(serde_json::to_string(&None::<EpochMap>).unwrap().into())
+        .unwrap(),
+    )
+    .await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let account_data: AccountData = parse_body(response).await;
+    assert!(account_data.cardio_sessions.is_empty());
+    assert_eq!(account_data.cardio_session_summaries.len(), 1);
+    assert_eq!(
+        account_data.cardio_session_summaries[0].id,
+        cardio_session.id
+    );
+    assert_eq!(
+        account_data.cardio_session_summaries[0].distance,
+        Some(1000)
+    );
+}
+
+#[tokio::test]
+async fn cardio_session_import_gpx() {
+    let (mut router, db_pool, _) = init().await;
+
+    let mut db = db_pool.get().await.unwrap();
+    let movement = create_cardio_movement(&mut db).await;
+    drop(db);
+
+    let response = import_request(&mut router, movement.id, None, GPX.into()).await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let cardio_session: CardioSession = parse_body(response).await;
+    assert_eq!(cardio_session.movement_id, movement.id);
+    assert_eq!(cardio_session.cardio_type, CardioType::Training);
+    assert_eq!(
+        cardio_session.datetime.to_rfc3339(),
+        "2024-05-01T10:00:00+00:00"
+    );
+    assert_eq!(cardio_session.time, Some(120_000));
+    assert_eq!(cardio_session.distance, Some(222));
+    assert_eq!(cardio_session.ascent, Some(20));
+    assert_eq!(cardio_session.descent, Some(15));
+    assert_eq!(cardio_session.track.as_ref().unwrap().len(), 3);
+    assert_eq!(cardio_session.heart_rate.as_ref().unwrap().len(), 240);
+    assert_eq!(cardio_session.avg_heart_rate, Some(120));
+    assert!(cardio_session.cadence.is_none());
+
+    let mut db = db_pool.get().await.unwrap();
+    let stored = CardioSessionDb::get_by_id(cardio_session.id, &mut db)
+        .await
+        .unwrap();
+    assert_eq!(stored.user_id, TEST_USER.id);
+}
+
+#[tokio::test]
+async fn cardio_session_export() {
+    let (mut router, db_pool, _) = init().await;
+
+    let mut db = db_pool.get().await.unwrap();
+    let movement = create_cardio_movement(&mut db).await;
+    drop(db);
+
+    let response = import_request(&mut router, movement.id, None, GPX.into()).await;
+    let cardio_session: CardioSession = parse_body(response).await;
+
+    for format in [TrackFileFormat::Gpx, TrackFileFormat::Tcx] {
+        let response = export_request(
+            &mut router,
+            CARDIO_SESSION_EXPORT,
+            cardio_session.id.0,
+            format.extension(),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers().get(CONTENT_TYPE).unwrap(),
+            format.content_type()
+        );
+        let body = body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let points = track_file::parse(&body, format).unwrap();
+        assert_eq!(points.len(), 3);
+        assert_eq!(
+            points[2].time.unwrap().to_rfc3339(),
+            "2024-05-01T10:02:00+00:00"
+        );
+        assert!((points[1].latitude.unwrap() - 47.001).abs() < 1e-9);
+        assert!(points.iter().all(|point| point.heart_rate == Some(120.)));
+    }
+
+    let response = export_request(
+        &mut router,
+        CARDIO_SESSION_EXPORT,
+        cardio_session.id.0,
+        "fit",
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn route_export() {
+    let (mut router, db_pool, _) = init().await;
+
+    let position = |latitude| Position {
+        longitude: 11.0,
+        latitude,
+        elevation: 600.0,
+        distance: 0.0,
+        time: 0,
+    };
+    let route = Route {
+        id: RouteId(rnd()),
+        user_id: TEST_USER.id,
+        name: format!("route <{}>", rnd()),
+        distance: Some(111),
+        ascent: None,
+        descent: None,
+        track: Some(vec![position(47.0), position(47.001)]),
+        marked_positions: Some(vec![position(47.0005)]),
+        deleted: false,
+    };
+    let mut db = db_pool.get().await.unwrap();
+    RouteDb::create(&route, &mut db).await.unwrap();
+    drop(db);
+
+    let response = export_request(&mut router, ROUTE_EXPORT, route.id.0, "gpx").await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let points = track_file::parse(&body, TrackFileFormat::Gpx).unwrap();
+    assert_eq!(points.len(), 2);
+    let gpx = String::from_utf8(body.to_vec()).unwrap();
+    assert!(gpx.contains(r#"<wpt lat="47.0005" lon="11">"#));
+    assert!(gpx.contains("&lt;"));
+
+    let response = export_request(&mut router, ROUTE_EXPORT, route.id.0, "tcx").await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    // get track - check track and marked positions
+    let header = auth_header(&TEST_USER.username, &TEST_USER.password);
+    let response = request(
+        &mut router,
+        Request::get(
+            route_max_version("", ROUTE_TRACK, None).replace("{id}", &route.id.0.to_string()),
+        )
+        .header(header.0, header.1)
+        .body(Body::empty())
+        .unwrap(),
+    )
+    .await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let track: RouteTrack = parse_body(response).await;
+    assert_eq!(track.id, route.id);
+    assert_eq!(track.track.unwrap().len(), 2);
+    assert_eq!(track.marked_positions.unwrap()[0].latitude, 47.0005);
+}
+
+#[tokio::test]
+async fn cardio_session_import_fit() {
+    let (mut router, db_pool, _) = init().await;
+
+    let mut db = db_pool.get().await.unwrap();
+    let movement = create_cardio_movement(&mut db).await;
+    drop(db);
+
+    // definition of local message 0 as record with timestamp, position_lat, position_long,
+    // distance, heart_rate and cadence
+    let mut records = vec![0x40, 0, 0, 20, 0, 6];
+    records.extend([
+        253, 4, 0x86, 0, 4, 0x85, 1, 4, 0x85, 5, 4, 0x86, 3, 1, 0x02, 4, 1, 0x02,
+    ]);
+    let timestamp = 1_083_492_000_u32; // 2024-05-01T10:00:00Z
+    let semicircles = |degrees: f64| ((degrees / 180.) * 2_147_483_648.) as i32;
+    for (offset, latitude, distance, heart_rate) in [(0, 47.0, 0, 150), (60, 47.001, 11_100, 150)] {
+        records.push(0);
+        records.extend((timestamp + offset).to_le_bytes());
+        records.extend(semicircles(latitude).to_le_bytes());
+        records.extend(semicircles(11.0).to_le_bytes());
+        records.extend(u32::to_le_bytes(distance));
+        records.extend([heart_rate, 0xFF]);
+    }
+    let mut fit = vec![12, 0x20, 0, 0];
+    fit.extend((records.len() as u32).to_le_bytes());
+    fit.extend(b".FIT");
+    fit.extend(records);
+    fit.extend(crate::track_file::fit::crc(&fit).to_le_bytes());
+
+    let response = import_request(&mut router, movement.id, Some("fit"), fit).await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let cardio_session: CardioSession = parse_body(response).await;
+    assert_eq!(
+        cardio_session.datetime.to_rfc3339(),
+        "2024-05-01T10:00:00+00:00"
+    );
+    assert_eq!(cardio_session.time, Some(60_000));
+    assert_eq!(cardio_session.distance, Some(111));
+    let track = cardio_session.track.unwrap();
+    assert_eq!(track.len(), 2);
+    assert!((track[1].latitude - 47.001).abs() < 1e-6);
+    assert!((track[1].longitude - 11.0).abs() < 1e-6);
+    assert_eq!(cardio_session.avg_heart_rate, Some(150));
+    assert!(cardio_session.cadence.is_none());
+}
+
+#[tokio::test]
+async fn cardio_session_import_invalid_file() {
+    let (mut router, db_pool, _) = init().await;
+
+    let mut db = db_pool.get().await.unwrap();
+    let movement = create_cardio_movement(&mut db).await;
+    drop(db);
+
+    let response = import_request(&mut router, movement.id, None, b"not a track".to_vec()).await;
+    assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+    let response = import_request(&mut router, movement.id, Some("gpx"), b"<gpx>".to_vec()).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let gpx = |points: &[(&str, &str, &str)]| {
+        let points: String = points
+            .iter()
+            .map(|(lat, time, hr)| {
+                format!(
+                    r#"<trkpt lat="{lat}" lon="11.0"><time>{time}</time>
+                    <extensions><gpxtpx:TrackPointExtension><gpxtpx:hr>{hr}</gpxtpx:hr></gpxtpx:TrackPointExtension></extensions>
+                    </trkpt>"#
+                )
+            })
+            .collect();
+        format!(
+            r#"<gpx xmlns:gpxtpx="http://www.garmin.com/xmlschemas/TrackPointExtension/v1"><trk><trkseg>{points}</trkseg></trk></gpx>"#
+        )
+        .into_bytes()
+    };
+    let start = "2024-05-01T10:00:00Z";
+    let end = "2024-05-01T11:00:00Z";
+    for points in [
+        [("47.0", start, "inf"), ("47.001", end, "120")],
+        [("47.0", start, "1e12"), ("47.001", end, "120")],
+        [("47.0", start, "120"), ("47.001", end, "NaN")],
+        [("91.0", start, "120"), ("47.001", end, "120")],
+        [
+            ("47.0", start, "120"),
+            ("47.001", "2024-06-01T10:00:00Z", "120"),
+        ],
+    ] {
+        let response = import_request(&mut router, movement.id, None, gpx(&points)).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    // a single point has neither time nor distance
+    let response = import_request(
+        &mut router,
+        movement.id,
+        None,
+        gpx(&[("47.0", start, "120")]),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let cardio_session: CardioSession = parse_body(response).await;
+    assert_eq!(cardio_session.track.as_ref().unwrap().len(), 1);
+    assert_eq!(cardio_session.time, None);
+    assert_eq!(cardio_session.distance, None);
+    assert_eq!(cardio_session.avg_heart_rate, None);
+}
+
+#[tokio::test]
+async fn account_data_events() {
+    let (_, db_pool, config) = init().await;
+
+    let epoch_changes = EpochChanges::default();
+    let mut router = router::get_router(AppState {
+        db_pool,
+        config,
+        credential_keys: Box::leak(Box::new(CredentialKeys::new(config).unwrap())),
+        epoch_changes: epoch_changes.clone(),
+        heatmap_cache: HeatmapCache::default(),
+    });
+
+    let header = auth_header(&TEST_USER.username, &TEST_USER.password);
+    let response = request(
+        &mut router,
+        Request::get(route_max_version("", ACCOUNT_DATA_EVENTS, None))
+            .header(header.0, header.1)
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers().get(CONTENT_TYPE).unwrap(),
+        "text/event-stream"
+    );
+
+    // changes of other users are filtered, changes of tables without user are sent to all users
+    for payload in [
+        format!("diary,{},5", TEST_USER2.id.0),
+        "platform,,3".to_owned(),
+        format!("diary,{},6", TEST_USER.id.0),
+        format!("diary,{},7", TEST_USER.id.0),
+    ] {
+        epoch_changes.send(payload.parse().unwrap());
+    }
+
+    let mut body = response.into_body().into_data_stream();
+    let event = tokio::time::timeout(std::time::Duration::from_secs(5), body.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        event,
+        "event: epoch\ndata: {\"diary\":\"7\",\"platform\":\"3\"}\n\n"
+    );
+}
+
+async fn create_diary_with_epoch(user_id: UserId, day: i64, db: &mut AsyncPgConnection) -> Epoch {
+    let diary = Diary {
+        id: DiaryId(rnd()),
+        user_id,
+        date: Utc::now().date_naive() - Duration::days(day),
+        bodyweight: None,
+        comments: None,
+        deleted: false,
+    };
+    DiaryDb::create(&diary, db).await.unwrap();
+    diary::table
+        .find(diary.id)
+        .select(diary::epoch)
+        .get_result(db)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn concurrent_epochs() {
+    const TASKS: i64 = 8;
+    const DIARIES: i64 = 20;
+
+    // Concurrent transactions can not use the test transaction of the db pool.
+    // Therefore all data is committed for a random user which is deleted again at the end, even if
+    // an assertion fails. The diaries of the user are deleted with it.
+    let config: &'static Config = Box::leak(Box::new(get_config().await.unwrap()));
+    let mut db = AsyncPgConnection::establish(&config.database_url)
+        .await
+        .unwrap();
+
+    let mut user = User {
+        id: UserId(rnd()),
+        username: format!("epoch-user-{}", rnd()),
+        password: "epoch-user-password".to_owned(),
+        email: format!("epoch-user-email-{}", rnd()),
+        max_heart_rate: None,
+        resting_heart_rate: None,
+    };
+    UserDb::create(&mut user, &mut db).await.unwrap();
+    let user_id = user.id;
+
+    let result = tokio::spawn(async move {
+        let mut db = AsyncPgConnection::establish(&config.database_url)
+            .await
+            .unwrap();
+
+        // concurrent writers never get the same epoch
+        let tasks: Vec<_> = (0..TASKS)
+            .map(|task| {
+                tokio::spawn(async move {
+                    let mut db = AsyncPgConnection::establish(&config.database_url)
+                        .await
+                        .unwrap();
+                    let mut epochs = vec![];
+                    for i in 0..DIARIES {
+                        epochs.push(
+                            create_diary_with_epoch(user_id, task * DIARIES + i, &mut db).await,
+                        );
+                    }
+                    epochs
+                })
+            })
+            .collect();
+        let mut epochs = vec![];
+        for task in tasks {
+            epochs.extend(task.await.unwrap());
+        }
+        epochs.sort();
+        assert_eq!(epochs, (1..=TASKS * DIARIES).map(Epoch).collect::<Vec<_>>());
+
+        // a writer waits for an uncommitted transaction with a lower epoch
+        AnsiTransactionManager::begin_transaction(&mut db)
+            .await
+            .unwrap();
+        let first_epoch = create_diary_with_epoch(user_id, TASKS * DIARIES, &mut db).await;
+        let second = tokio::spawn(async move {
+            let mut db = AsyncPgConnection::establish(&config.database_url)
+                .await
+                .unwrap();
+            create_diary_with_epoch(user_id, TASKS * DIARIES + 1, &mut db).await
+        });
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+        assert!(!second.is_finished());
+        AnsiTransactionManager::commit_transaction(&mut db)
+            .await
+            .unwrap();
+        let second_epoch = second.await.unwrap();
+        assert_eq!(first_epoch, Epoch(TASKS * DIARIES + 1));
+        assert_eq!(second_epoch, Epoch(TASKS * DIARIES + 2));
+    })
+    .await;
+
+    UserDb::delete(user_id, &mut db).await.unwrap();
+    result.unwrap();
+}
+
+#[tokio::test]
+async fn archive_restore() {
+    let (mut router, db_pool, _) = init().await;
+
+    let mut db = db_pool.get().await.unwrap();
+    let movement = create_cardio_movement(&mut db).await;
+    let mut strength_session = StrengthSession {
+        id: StrengthSessionId(rnd()),
+        user_id: TEST_USER.id,
+        datetime: Utc::now(),
+        movement_id: movement.id,
+        interval: None,
+        comments: None,
+        deleted: false,
+    };
+    StrengthSessionDb::create(&strength_session, &mut db)
+        .await
+        .unwrap();
+    let mut strength_sets: Vec<_> = (0..3)
+        .map(|set_number| StrengthSet {
+            id: StrengthSetId(rnd()),
+            user_id: TEST_USER.id,
+            strength_session_id: strength_session.id,
+            set_number,
+            count: 5,
+            weight: Some(100.),
+            deleted: false,
+        })
+        .collect();
+    StrengthSetDb::create_multiple(&strength_sets, &mut db)
+        .await
+        .unwrap();
+    let deleted_epoch = StrengthSetDb::get_epoch_by_user(TEST_USER.id, &mut db)
+        .await
+        .unwrap();
+    // deleted directly in the same transaction as the strength session
+    let mut deleted_strength_set = strength_sets.pop().unwrap();
+    deleted_strength_set.deleted = true;
+    StrengthSetDb::update(&deleted_strength_set, &mut db)
+        .await
+        .unwrap();
+    strength_session.deleted = true;
+    StrengthSessionDb::update(&strength_session, &mut db)
+        .await
+        .unwrap();
+    drop(db);
+
+    let response = archive_request(
+        &mut router,
+        Method::GET,
+        STRENGTH_SET_ARCHIVE,
+        0,
+        &TEST_USER,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let archived: Vec<Archived<StrengthSet>> = parse_body(response).await;
+    for strength_set in &strength_sets {
+        assert!(
+            archived
+                .iter()
+                .any(|archived| archived.entry.id == strength_set.id && archived.entry.deleted)
+        );
+    }
+
+    // other users can neither see nor restore the entries
+    let response = archive_request(
+        &mut router,
+        Method::GET,
+        STRENGTH_SESSION_ARCHIVE,
+        0,
+        &TEST_USER2,
+    )
+    .await;
+    let archived: Vec<Archived<StrengthSession>> = parse_body(response).await;
+    assert!(
+        archived
+            .iter()
+            .all(|archived| archived.entry.id != strength_session.id)
+    );
+    let response = archive_request(
+        &mut router,
+        Method::POST,
+        STRENGTH_SESSION_RESTORE,
+        strength_session.id.0,
+        &TEST_USER2,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    // the strength sets are restored together with the strength session
+    let response = archive_request(
+        &mut router,
+        Method::POST,
+        STRENGTH_SESSION_RESTORE,
+        strength_session.id.0,
+        &TEST_USER,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let restored: StrengthSession = parse_body(response).await;
+    assert!(!restored.deleted);
+
+    let mut db = db_pool.get().await.unwrap();
+    for strength_set in &strength_sets {
+        let (epoch, deleted): (Epoch, bool) = strength_set::table
+            .find(strength_set.id)
+            .select((strength_set::epoch, strength_set::deleted))
+            .get_result(&mut db)
+            .await
+            .unwrap();
+        assert!(!deleted);
+        assert!(epoch > deleted_epoch);
+    }
+    let deleted: bool = strength_set::table
+        .find(deleted_strength_set.id)
+        .select(strength_set::deleted)
+        .get_result(&mut db)
+        .await
+        .unwrap();
+    assert!(deleted);
+    drop(db);
+
+    let response = archive_request(
+        &mut router,
+        Method::POST,
+        STRENGTH_SESSION_RESTORE,
+        strength_session.id.0,
+        &TEST_USER,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn archive_restore_unique_violation() {
+    let (mut router, db_pool, _) = init().await;
+
+    let mut db = db_pool.get().await.unwrap();
+    let date = TEST_DIARY.date - Duration::days(1000);
+    let mut diary = Diary {
+        id: DiaryId(rnd()),
+        date,
+        ..TEST_DIARY.clone()
+    };
+    DiaryDb::create(&diary, &mut db).await.unwrap();
+    diary.deleted = true;
+    DiaryDb::update(&diary, &mut db).await.unwrap();
+    let new_diary = Diary {
+        id: DiaryId(rnd()),
+        date,
+        ..TEST_DIARY.clone()
+    };
+    DiaryDb::create(&new_diary, &mut db).await.unwrap();
+    drop(db);
+
+    let response = archive_request(&mut router, Method::GET, DIARY_ARCHIVE, 0, &TEST_USER).await;
+    let archived: Vec<Archived<Diary>> = parse_body(response).await;
+    assert!(
+        archived
+            .iter()
+            .any(|archived| archived.entry.id == diary.id)
+    );
+
+    let response = archive_request(
+        &mut router,
+        Method::POST,
+        DIARY_RESTORE,
+        diary.id.0,
+        &TEST_USER,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+async fn archive_purge() {
+    let (_, db_pool, config) = init().await;
+
+    let config = Box::leak(Box::new(Config {
+        archive_retention: ArchiveRetention {
+            default: Some(30),
+            ..ArchiveRetention::default()
+        },
+        ..config.clone()
+    }));
+    let mut router = router::get_router(AppState {
+        db_pool: db_pool.clone(),
+        config,
+        credential_keys: Box::leak(Box::new(CredentialKeys::new(config).unwrap())),
+        epoch_changes: EpochChanges::default(),
+        heatmap_cache: HeatmapCache::default(),
+    });
+
+    let mut db = db_pool.get().await.unwrap();
+    let mut old_diary = Diary {
+        id: DiaryId(rnd()),
+        date: Utc::now().date_naive() - Duration::days(100),
+        ..TEST_DIARY.clone()
+    };
+    let mut recent_diary = Diary {
+        id: DiaryId(rnd()),
+        date: Utc::now().date_naive() - Duration::days(1),
+        ..TEST_DIARY.clone()
+    };
+    DiaryDb::create_multiple(&[old_diary.clone(), recent_diary.clone()], &mut db)
+        .await
+        .unwrap();
+    let epoch_before_delete = DiaryDb::get_epoch_by_user(TEST_USER.id, &mut db)
+        .await
+        .unwrap();
+    old_diary.deleted = true;
+    recent_diary.deleted = true;
+    DiaryDb::update_multiple(&[old_diary.clone(), recent_diary.clone()], &mut db)
+        .await
+        .unwrap();
+    diesel::sql_query(
+        "update only diary_archive set archived_at = now() - interval '60 days' where id = $1",
+    )
+    .bind::<diesel::sql_types::BigInt, _>(old_diary.id)
+    .execute(&mut db)
+    .await
+    .unwrap();
+    // an effort of a segment and a cardio session that do not exist anymore
+    let orphaned_effort = SegmentEffort {
+        user_id: TEST_USER.id,
+        segment_id: SegmentId(rnd()),
+        cardio_session_id: CardioSessionId(rnd()),
+        start: 0,
+        time: 1000,
+        avg_heart_rate: None,
+        datetime: Utc::now(),
+    };
+    SegmentEffortDb::replace(
+        &[orphaned_effort.segment_id],
+        Some(&[orphaned_effort.cardio_session_id]),
+        std::slice::from_ref(&orphaned_effort),
+        &mut db,
+    )
+    .await
+    .unwrap();
+    drop(db);
+
+    let header = auth_header(ADMIN_USERNAME, ADMIN_PASSWORD_PLAINTEXT);
+    let response = request(
+        &mut router,
+        Request::post(route_max_version("", ADM_PURGE_ARCHIVE, None))
+            .header(header.0, header.1)
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let purged: Vec<PurgedArchive> = parse_body(response).await;
+    assert!(
+        purged
+            .iter()
+            .any(|purged| purged.table == "diary" && purged.count >= 1)
+    );
+    let mut db = db_pool.get().await.unwrap();
+    let efforts: i64 = segment_effort::table
+        .filter(segment_effort::segment_id.eq(orphaned_effort.segment_id))
+        .count()
+        .get_result(&mut db)
+        .await
+        .unwrap();
+    assert_eq!(efforts, 0);
+    drop(db);
+
+    // only the entry that is older than the retention is purged
+    let response = archive_request(&mut router, Method::GET, DIARY_ARCHIVE, 0, &TEST_USER).await;
+    let archived: Vec<Archived<Diary>> = parse_body(response).await;
+    assert!(
+        archived
+            .iter()
+            .all(|archived| archived.entry.id != old_diary.id)
+    );
+    assert!(
+        archived
+            .iter()
+            .any(|archived| archived.entry.id == recent_diary.id)
+    );
+
+    // clients that are up to date or have never synchronized can sync as usual
+    let (status, account_data) = account_data_request(&mut router, None).await;
+    assert_eq!(status, StatusCode::OK);
+    let mut epoch_map = account_data.epoch_map;
+    let (status, _) = account_data_request(&mut router, Some(epoch_map.clone())).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = account_data_request(&mut router, Some(EpochMap::default())).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // clients that have not seen the deletion of the purged entry have to do an init sync
+    epoch_map.diary = epoch_before_delete;
+    let header = auth_header(&TEST_USER.username, &TEST_USER.password);
+    let response = request(
+        &mut router,
+        Request::get(route_max_version("", ACCOUNT_DATA, None))
+            .header(header.0, header.1)
+            .header(CONTENT_TYPE, APPLICATION_JSON.as_ref())
+            .body(serde_json::to_string(&Some(epoch_map)).unwrap().into())
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::GONE);
+    let body: serde_json::Value = parse_body(response).await;
+    assert!(matches!(
+        serde_json::from_value(body["message"].clone()),
+        Ok(ErrorMessage::InitSyncRequired { table }) if table == "diary"
+    ));
+}
+
+#[test]
+fn archived_tables() {
+    // every table of the epoch map except the user has an archive
+    let epoch_map = serde_json::to_value(EpochMap::default()).unwrap();
+    let mut tables: Vec<_> = epoch_map
+        .as_object()
+        .unwrap()
+        .keys()
+        .map(String::as_str)
+        .filter(|table| *table != "user")
+        .collect();
+    tables.sort_unstable();
+    let mut archived_tables = ARCHIVED_TABLES;
+    archived_tables.sort_unstable();
+    assert_eq!(tables, archived_tables);
+    for table in ARCHIVED_TABLES {
+        assert!(EpochMap::default().get(table).is_some());
+    }
+}
+
+#[tokio::test]
+async fn strength_records() {
+    let (mut router, db_pool, _) = init().await;
+
+    let mut db = db_pool.get().await.unwrap();
+    let movement = Movement {
+        id: MovementId(rnd()),
+        user_id: Some(TEST_USER.id),
+        name: format!("movement{}", rnd()),
+        description: None,
+        movement_dimension: MovementDimension::Reps,
+        cardio: false,
+        deleted: false,
+    };
+    MovementDb::create(&movement, &mut db).await.unwrap();
+    let strength_sessions: Vec<_> = (0..3)
+        .map(|days_ago| StrengthSession {
+            id: StrengthSessionId(rnd()),
+            user_id: TEST_USER.id,
+            datetime: Utc::now() - Duration::days(days_ago),
+            movement_id: movement.id,
+            interval: None,
+            comments: None,
+            deleted: false,
+        })
+        .collect();
+    StrengthSessionDb::create_multiple(&strength_sessions, &mut db)
+        .await
+        .unwrap();
+    let strength_set =
+        |strength_session: &StrengthSession, set_number, count, weight| StrengthSet {
+            id: StrengthSetId(rnd()),
+            user_id: TEST_USER.id,
+            strength_session_id: strength_session.id,
+            set_number,
+            count,
+            weight,
+            deleted: false,
+        };
+    // the records of the deleted session are ignored
+    let deleted = strength_set(&strength_sessions[0], 0, 5, Some(200.));
+    let older_5 = strength_set(&strength_sessions[2], 0, 5, Some(100.));
+    let older_3 = strength_set(&strength_sessions[2], 1, 3, Some(110.));
+    let newer_5 = strength_set(&strength_sessions[1], 0, 5, Some(105.));
+    let newer_10 = strength_set(&strength_sessions[1], 1, 10, None);
+    let strength_sets = [
+        deleted,
+        older_5.clone(),
+        older_3.clone(),
+        newer_5.clone(),
+        newer_10.clone(),
+    ];
+    StrengthSetDb::create_multiple(&strength_sets, &mut db)
+        .await
+        .unwrap();
+    StrengthSessionDb::update(
+        &StrengthSession {
+            deleted: true,
+            ..strength_sessions[0].clone()
+        },
+        &mut db,
+    )
+    .await
+    .unwrap();
+    drop(db);
+
+    let header = auth_header(&TEST_USER.username, &TEST_USER.password);
+    let response = request(
+        &mut router,
+        Request::get(format!(
+            "{}?movement_id={}",
+            route_max_version("", STRENGTH_RECORD, None),
+            movement.id.0
+        ))
+        .header(header.0, header.1)
+        .body(Body::empty())
+        .unwrap(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let records: Vec<StrengthRecords> = parse_body(response).await;
+    assert_eq!(records.len(), 1);
+    let records = &records[0];
+    assert_eq!(records.movement_id, movement.id);
+
+    let max_weight: Vec<_> = records
+        .max_weight
+        .iter()
+        .map(|record| (record.count, record.weight, record.strength_set_id))
+        .collect();
+    assert_eq!(
+        max_weight,
+        [
+            (3, Some(110.), Some(older_3.id)),
+            (5, Some(105.), Some(newer_5.id))
+        ]
+    );
+
+    // 105 / 0.89 > 110 / 0.94
+    let max_eorm = records.max_eorm.as_ref().unwrap();
+    assert_eq!(max_eorm.strength_set_id, Some(newer_5.id));
+    assert!((max_eorm.weight.unwrap() - 105. / 0.89).abs() < 0.01);
+
+    // 5 * 100 + 3 * 110 > 5 * 105
+    let max_volume = records.max_volume.as_ref().unwrap();
+    assert_eq!(max_volume.strength_session_id, strength_sessions[2].id);
+    assert_eq!(max_volume.strength_set_id, None);
+    assert_eq!(max_volume.count, 8);
+    assert_eq!(max_volume.weight, Some(830.));
+
+    let max_count = records.max_count.as_ref().unwrap();
+    assert_eq!(max_count.strength_set_id, Some(newer_10.id));
+    assert_eq!(max_count.count, 10);
+}
+
+#[tokio::test]
+async fn statistics() {
+    let (mut router, db_pool, _) = init().await;
+
+    let mut db = db_pool.get().await.unwrap();
+    let movement = create_cardio_movement(&mut db).await;
+    let cardio_session = |datetime: &str, distance, time| CardioSession {
+        datetime: datetime.parse().unwrap(),
+        distance: Some(distance),
+        time,
+        ..cardio_session(movement.id)
+    };
+    let cardio_sessions = [
+        // Monday and Wednesday of the same week
+        cardio_session("2024-05-06T10:00:00Z", 5000, Some(1_500_000)),
+        cardio_session("2024-05-08T23:00:00Z", 3000, None),
+        cardio_session("2024-05-13T00:00:00Z", 10000, Some(3_000_000)),
+        // after the end of the timespan
+        cardio_session("2024-05-20T10:00:00Z", 10000, Some(3_000_000)),
+    ];
+    CardioSessionDb::create_multiple(&cardio_sessions, &mut db)
+        .await
+        .unwrap();
+    let strength_session = StrengthSession {
+        id: StrengthSessionId(rnd()),
+        user_id: TEST_USER.id,
+        datetime: "2024-05-06T10:00:00Z".parse().unwrap(),
+        movement_id: movement.id,
+        interval: None,
+        comments: None,
+        deleted: false,
+    };
+    StrengthSessionDb::create(&strength_session, &mut db)
+        .await
+        .unwrap();
+    let strength_sets: Vec<_> = [(5, Some(100.)), (3, None)]
+        .into_iter()
+        .enumerate()
+        .map(|(set_number, (count, weight))| StrengthSet {
+            id: StrengthSetId(rnd()),
+            user_id: TEST_USER.id,
+            strength_session_id: strength_session.id,
+            set_number: set_number as i32,
+            count,
+            weight,
+            deleted: false,
+        })
+        .collect();
+    StrengthSetDb::create_multiple(&strength_sets, &mut db)
+        .await
+        .unwrap();
+    let metcon = |name| Metcon {
+        id: MetconId(rnd()),
+        user_id: Some(TEST_USER.id),
+        name: format!("{name}{}", rnd()),
+        metcon_type: MetconType::ForTime,
+        rounds: Some(3),
+        timecap: None,
+        description: None,
+        deleted: false,
+    };
+    let metcons = [metcon("with movement"), metcon("without movement")];
+    MetconDb::create_multiple(&metcons, &mut db).await.unwrap();
+    let metcon_movement = MetconMovement {
+        id: MetconMovementId(rnd()),
+        user_id: Some(TEST_USER.id),
+        metcon_id: metcons[0].id,
+        movement_id: movement.id,
+        distance_unit: Some(DistanceUnit::Meter),
+        movement_number: 0,
+        count: 400,
+        male_weight: None,
+        female_weight: None,
+        deleted: false,
+    };
+    MetconMovementDb::create(&metcon_movement, &mut db)
+        .await
+        .unwrap();
+    let metcon_session = |metcon_id, datetime: &str| MetconSession {
+        id: MetconSessionId(rnd()),
+        user_id: TEST_USER.id,
+        metcon_id,
+        datetime: datetime.parse().unwrap(),
+        time: Some(600_000),
+        rounds: None,
+        reps: None,
+        rx: true,
+        comments: None,
+        deleted: false,
+    };
+    let metcon_sessions = [
+        metcon_session(metcons[0].id, "2024-05-06T10:00:00Z"),
+        metcon_session(metcons[0].id, "2024-05-09T10:00:00Z"),
+        metcon_session(metcons[0].id, "2024-05-14T10:00:00Z"),
+        metcon_session(metcons[1].id, "2024-05-06T10:00:00Z"),
+        // after the end of the timespan
+        metcon_session(metcons[0].id, "2024-05-20T10:00:00Z"),
+    ];
+    MetconSessionDb::create_multiple(&metcon_sessions, &mut db)
+        .await
+        .unwrap();
+    drop(db);
+
+    let header = auth_header(&TEST_USER.username, &TEST_USER.password);
+    let response = request(
+        &mut router,
+        Request::get(format!(
+            "{}?interval=week&movement_id={}&start=2024-05-01T00:00:00Z&end=2024-05-15T00:00:00Z",
+            route_max_version("", CARDIO_STATISTIC, None),
+            movement.id.0
+        ))
+        .header(header.0.clone(), header.1.clone())
+        .body(Body::empty())
+        .unwrap(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let statistics: Vec<CardioStatistic> = parse_body(response).await;
+    let statistics: Vec<_> = statistics
+        .iter()
+        .map(|statistic| {
+            (
+                statistic.bucket.to_rfc3339(),
+                statistic.session_count,
+                statistic.distance,
+                statistic.time,
+            )
+        })
+        .collect();
+    assert_eq!(
+        statistics,
+        [
+            ("2024-05-06T00:00:00+00:00".to_owned(), 2, 8000, 1_500_000),
+            ("2024-05-13T00:00:00+00:00".to_owned(), 1, 10000, 3_000_000),
+        ]
+    );
+
+    let response = request(
+        &mut router,
+        Request::get(format!(
+            "{}?interval=month&movement_id={}",
+            route_max_version("", STRENGTH_STATISTIC, None),
+            movement.id.0
+        ))
+        .header(header.0.clone(), header.1.clone())
+        .body(Body::empty())
+        .unwrap(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let statistics: Vec<StrengthStatistic> = parse_body(response).await;
+    assert_eq!(statistics.len(), 1);
+    assert_eq!(
+        statistics[0].bucket.to_rfc3339(),
+        "2024-05-01T00:00:00+00:00"
+    );
+    assert_eq!(statistics[0].session_count, 1);
+    assert_eq!(statistics[0].count, 8);
+    assert!((statistics[0].volume - 500.).abs() < f64::EPSILON);
+
+    // only metcons containing the movement
+    let response = request(
+        &mut router,
+        Request::get(format!(
+            "{}?interval=week&movement_id={}&start=2024-05-01T00:00:00Z&end=2024-05-15T00:00:00Z",
+            route_max_version("", METCON_STATISTIC, None),
+            movement.id.0
+        ))
+        .header(header.0, header.1)
+        .body(Body::empty())
+        .unwrap(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let statistics: Vec<MetconStatistic> = parse_body(response).await;
+    let statistics: Vec<_> = statistics
+        .iter()
+        .map(|statistic| {
+            (
+                statistic.metcon_id,
+                statistic.bucket.to_rfc3339(),
+                statistic.session_count,
+            )
+        })
+        .collect();
+    assert_eq!(
+        statistics,
+        [
+            (metcons[0].id, "2024-05-06T00:00:00+00:00".to_owned(), 2),
+            (metcons[0].id, "2024-05-13T00:00:00+00:00".to_owned(), 1),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn training_load() {
+    let (mut router, db_pool, _) = init().await;
+
+    let mut db = db_pool.get().await.unwrap();
+    let movement = create_cardio_movement(&mut db).await;
+    let cardio_session = |datetime: &str, time, avg_heart_rate, heart_rate| CardioSession {
+        datetime: datetime.parse().unwrap(),
+        time: Some(time),
+        avg_heart_rate: Some(avg_heart_rate),
+        heart_rate,
+        ..cardio_session(movement.id)
+    };
+    // one hour and ten minutes (a beat every 500 ms) at 120 bpm
+    let cardio_sessions = [
+        cardio_session("2024-05-10T12:00:00Z", 3_600_000, 120, None),
+        cardio_session(
+            "2024-05-10T13:00:00Z",
+            600_000,
+            120,
+            Some((0..=1200).map(|beat| beat * 500).collect()),
+        ),
+    ];
+    CardioSessionDb::create_multiple(&cardio_sessions, &mut db)
+        .await
+        .unwrap();
+    drop(db);
+
+    let header = auth_header(&TEST_USER.username, &TEST_USER.password);
+    let training_load_request = Request::get(format!(
+        "{}?start=2024-05-10T00:00:00Z&end=2024-05-11T00:00:00Z",
+        route_max_version("", TRAINING_LOAD, None),
+    ))
+    .header(header.0.clone(), header.1.clone())
+    .body(Body::empty())
+    .unwrap();
+
+    // the heart rate profile is required
+    let response = request(&mut router, training_load_request).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let mut db = db_pool.get().await.unwrap();
+    diesel::update(user::table.find(TEST_USER.id))
+        .set((
+            user::max_heart_rate.eq(Some(190)),
+            user::resting_heart_rate.eq(Some(50)),
+        ))
+        .execute(&mut db)
+        .await
+        .unwrap();
+    drop(db);
+
+    // heart rate reserve of 0.5
+    let trimp_per_minute = 0.5 * 0.64 * 0.96f64.exp();
+
+    let response = request(
+        &mut router,
+        Request::get(route_max_version("", TRAINING_IMPULSE, None))
+            .header(header.0.clone(), header.1.clone())
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let impulses: Vec<TrainingImpulse> = parse_body(response).await;
+    assert_eq!(impulses.len(), 2);
+    assert_eq!(impulses[0].cardio_session_id, cardio_sessions[0].id);
+    assert!((impulses[0].trimp - 60. * trimp_per_minute).abs() < 0.01);
+    assert_eq!(impulses[1].cardio_session_id, cardio_sessions[1].id);
+    assert!((impulses[1].trimp - 10. * trimp_per_minute).abs() < 0.01);
+
+    let response = request(
+        &mut router,
+        Request::get(format!(
+            "{}?start=2024-05-10T00:00:00Z&end=2024-05-11T00:00:00Z",
+            route_max_version("", TRAINING_LOAD, None),
+        ))
+        .header(header.0.clone(), header.1.clone())
+        .body(Body::empty())
+        .unwrap(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let loads: Vec<TrainingLoad> = parse_body(response).await;
+    assert_eq!(loads.len(), 2);
+    let load = 70. * trimp_per_minute;
+    assert!((loads[0].load - load).abs() < 0.01);
+    assert!(loads[1].load.abs() < f64::EPSILON);
+    for day in &loads {
+        assert!((day.acute - load / 7.).abs() < 0.01);
+        assert!((day.chronic - load / 42.).abs() < 0.01);
+        assert!((day.ratio.unwrap() - 6.).abs() < 0.01);
+        assert!((day.form - (load / 42. - load / 7.)).abs() < 0.01);
+    }
+
+    // an update without the heart rate profile keeps it
+    let response = request(
+        &mut router,
+        Request::put(route_max_version("", USER, None))
+            .header(header.0.clone(), header.1.clone())
+            .header(CONTENT_TYPE, APPLICATION_JSON.as_ref())
+            .body(serde_json::to_string(&*TEST_USER).unwrap().into())
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = request(
+        &mut router,
+        Request::get(route_max_version("", USER, None))
+            .header(header.0.clone(), header.1.clone())
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+    let user: User = parse_body(response).await;
+    assert_eq!(user.max_heart_rate, Some(190));
+    assert_eq!(user.resting_heart_rate, Some(50));
+
+    let response = request(
+        &mut router,
+        Request::get(format!(
+            "{}?start=2000-01-01T00:00:00Z&end=2024-05-11T00:00:00Z",
+            route_max_version("", TRAINING_LOAD, None),
+        ))
+        .header(header.0, header.1)
+        .body(Body::empty())
+        .unwrap(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn training_zones() {
+    let (mut router, db_pool, _) = init().await;
+
+    let mut db = db_pool.get().await.unwrap();
+    let movement = create_cardio_movement(&mut db).await;
+    let other_movement = create_cardio_movement(&mut db).await;
+    // ten minutes (a beat every 500 ms) at 120 bpm
+    let cardio_session = |movement_id, datetime: &str, track| CardioSession {
+        datetime: datetime.parse().unwrap(),
+        time: Some(600_000),
+        track,
+        heart_rate: Some((0..=1200).map(|beat| beat * 500).collect()),
+        ..cardio_session(movement_id)
+    };
+    // 100 seconds at 400 s/km and 50 seconds at 250 s/km
+    let track = (0..=15)
+        .map(|i| Position {
+            longitude: 0.,
+            latitude: 0.,
+            elevation: 0.,
+            distance: f64::from(i.min(10) * 25 + (i - 10).max(0) * 40),
+            time: i * 10_000,
+        })
+        .collect();
+    let cardio_sessions = [
+        cardio_session(movement.id, "2024-05-10T12:00:00Z", Some(track)),
+        cardio_session(other_movement.id, "2024-05-12T12:00:00Z", None),
+        cardio_session(other_movement.id, "2024-05-13T12:00:00Z", None),
+    ];
+    CardioSessionDb::create_multiple(&cardio_sessions, &mut db)
+        .await
+        .unwrap();
+    drop(db);
+
+    let training_zone = |zone_type, movement_id, thresholds| TrainingZone {
+        id: TrainingZoneId(rnd()),
+        user_id: TEST_USER.id,
+        zone_type,
+        movement_id,
+        thresholds,
+        deleted: false,
+    };
+    let training_zones = vec![
+        training_zone(TrainingZoneType::HeartRate, None, vec![100, 150]),
+        training_zone(TrainingZoneType::HeartRate, Some(movement.id), vec![130]),
+        training_zone(TrainingZoneType::Pace, None, vec![360, 300]),
+    ];
+    let header = auth_header(&TEST_USER.username, &TEST_USER.password);
+    let response = request(
+        &mut router,
+        Request::post(route_max_version("", TRAINING_ZONE, None))
+            .header(header.0.clone(), header.1.clone())
+            .header(CONTENT_TYPE, APPLICATION_JSON.as_ref())
+            .body(serde_json::to_string(&training_zones).unwrap().into())
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let (_, account_data) = account_data_request(&mut router, None).await;
+    assert_eq!(account_data.training_zones.len(), 3);
+
+    // the zone definition of the movement takes precedence
+    let response = request(
+        &mut router,
+        Request::get(format!(
+            "{}?id={}",
+            route_max_version("", CARDIO_SESSION_ZONE, None),
+            cardio_sessions[0].id.0
+        ))
+        .header(header.0.clone(), header.1.clone())
+        .body(Body::empty())
+        .unwrap(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let zones: Vec<CardioSessionZones> = parse_body(response).await;
+    assert_eq!(zones.len(), 1);
+    let heart_rate = zones[0].heart_rate.as_ref().unwrap();
+    assert_eq!(heart_rate.training_zone_id, training_zones[1].id);
+    assert_eq!(heart_rate.time, [600_000, 0]);
+    let pace = zones[0].pace.as_ref().unwrap();
+    assert_eq!(pace.training_zone_id, training_zones[2].id);
+    assert_eq!(pace.time, [100_000, 0, 50_000]);
+
+    let response = request(
+        &mut router,
+        Request::get(format!(
+            "{}?movement_id={}",
+            route_max_version("", ZONE_STATISTIC, None),
+            other_movement.id.0
+        ))
+        .header(header.0, header.1)
+        .body(Body::empty())
+        .unwrap(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let statistics: Vec<ZoneStatistic> = parse_body(response).await;
+    assert_eq!(statistics.len(), 2);
+    for (statistic, bucket) in statistics
+        .iter()
+        .zip(["2024-05-06T00:00:00Z", "2024-05-13T00:00:00Z"])
+    {
+        assert_eq!(statistic.training_zone_id, training_zones[0].id);
+        assert_eq!(statistic.bucket, bucket.parse::<DateTime<Utc>>().unwrap());
+        assert_eq!(statistic.session_count, 1);
+        assert_eq!(statistic.time, [0, 600_000, 0]);
+    }
+}
+
+#[tokio::test]
+async fn splits_and_best_efforts() {
+    let (mut router, db_pool, _) = init().await;
+
+    let mut db = db_pool.get().await.unwrap();
+    let movement = create_cardio_movement(&mut db).await;
+    // a position every 10 seconds, climbing one meter every position
+    let track = |meters_per_position: i32, positions: i32| {
+        (0..=positions)
+            .map(|i| Position {
+                longitude: 0.,
+                latitude: 0.,
+                elevation: f64::from(i),
+                distance: f64::from(i * meters_per_position),
+                time: i * 10_000,
+            })
+            .collect()
+    };
+    // 120 bpm
+    let cardio_session = |datetime: &str, track| CardioSession {
+        datetime: datetime.parse().unwrap(),
+        track: Some(track),
+        heart_rate: Some((0..1000).map(|beat| beat * 500).collect()),
+        ..cardio_session(movement.id)
+    };
+    // 2500 m at 200 s/km and 1500 m at 100 s/km
+    let cardio_sessions = [
+        cardio_session("2024-05-10T12:00:00Z", track(50, 50)),
+        cardio_session("2024-05-11T12:00:00Z", track(100, 15)),
+    ];
+    CardioSessionDb::create_multiple(&cardio_sessions, &mut db)
+        .await
+        .unwrap();
+    drop(db);
+
+    let header = auth_header(&TEST_USER.username, &TEST_USER.password);
+    let split_uri = CARDIO_SESSION_SPLIT.replace("{id}", &cardio_sessions[0].id.0.to_string());
+    let split_request = |query: &str| {
+        Request::get(format!(
+            "{}?{query}",
+            route_max_version("", &split_uri, None)
+        ))
+        .header(header.0.clone(), header.1.clone())
+        .body(Body::empty())
+        .unwrap()
+    };
+
+    let response = request(&mut router, split_request("")).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let splits: Vec<CardioSplit> = parse_body(response).await;
+    assert_eq!(splits.len(), 3);
+    for (split, (end_distance, time)) in
+        splits
+            .iter()
+            .zip([(1000., 200_000), (2000., 200_000), (2500., 100_000)])
+    {
+        assert!((split.end_distance - end_distance).abs() < 0.01);
+        assert_eq!(split.time, time);
+        assert!((split.pace - 200.).abs() < 0.01);
+        assert!((split.ascent - split.distance / 50.).abs() < 0.01);
+        assert!(split.descent.abs() < 0.01);
+        assert_eq!(split.avg_cadence, None);
+    }
+    assert_eq!(splits[0].avg_heart_rate, Some(120));
+
+    let response = request(&mut router, split_request("unit=Mile")).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let splits: Vec<CardioSplit> = parse_body(response).await;
+    assert_eq!(splits.len(), 2);
+    assert!((splits[0].distance - 1609.344).abs() < 0.01);
+    assert_eq!(splits[0].time, 321_869);
+
+    let response = request(&mut router, split_request("distance=1&unit=Meter")).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    // the number of splits of absurdly long tracks is capped
+    let splits = split::splits(
+        &CardioSessionTrack {
+            id: cardio_sessions[0].id,
+            track: Some(
+                [0., 1e17]
+                    .into_iter()
+                    .map(|distance| Position {
+                        longitude: 0.,
+                        latitude: 0.,
+                        elevation: 0.,
+                        distance,
+                        time: 0,
+                    })
+                    .collect(),
+            ),
+            cadence: None,
+            heart_rate: None,
+        },
+        1000.,
+    );
+    assert_eq!(splits.len(), 10_001);
+
+    let response = request(
+        &mut router,
+        Request::get(route_max_version("", CARDIO_BEST_EFFORT, None))
+            .header(header.0.clone(), header.1.clone())
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let best_efforts: Vec<CardioBestEffort> = parse_body(response).await;
+    assert_eq!(best_efforts.len(), 1);
+    assert_eq!(best_efforts[0].movement_id, movement.id);
+    assert!((best_efforts[0].distance - 1000.).abs() < f64::EPSILON);
+    assert_eq!(best_efforts[0].cardio_session_id, cardio_sessions[1].id);
+    assert_eq!(best_efforts[0].start, 0);
+    assert_eq!(best_efforts[0].time, 100_000);
+}
+
+#[tokio::test]
+async fn cardio_session_normalization() {
+    let (mut router, db_pool, _) = init().await;
+
+    let mut db = db_pool.get().await.unwrap();
+    let movement = create_cardio_movement(&mut db).await;
+    // 110 m in 100 seconds, climbing 30 m
+    let track = |teleport: bool| {
+        (0..=10)
+            .map(|i| Position {
+                longitude: 0.,
+                latitude: f64::from(i) * 0.0001 + if teleport && i > 5 { 1. } else { 0. },
+                elevation: f64::from(i * 3),
+                distance: f64::from(i * 11),
+                time: i * 10_000,
+            })
+            .collect()
+    };
+    let cardio_session = |track| CardioSession {
+        distance: Some(5000),
+        time: Some(100_000),
+        track: Some(track),
+        ..cardio_session(movement.id)
+    };
+    let stored = cardio_session(track(false));
+    let invalid = cardio_session(track(true));
+    CardioSessionDb::create_multiple(&[stored.clone(), invalid.clone()], &mut db)
+        .await
+        .unwrap();
+    drop(db);
+
+    let header = auth_header(&TEST_USER.username, &TEST_USER.password);
+    let create_request = |cardio_session: &CardioSession| {
+        Request::post(format!(
+            "{}?normalize=true",
+            route_max_version("", CARDIO_SESSION, None)
+        ))
+        .header(header.0.clone(), header.1.clone())
+        .header(CONTENT_TYPE, APPLICATION_JSON.as_ref())
+        .body(serde_json::to_string(cardio_session).unwrap().into())
+        .unwrap()
+    };
+
+    let normalized = cardio_session(track(false));
+    let response = request(&mut router, create_request(&normalized)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let mut db = db_pool.get().await.unwrap();
+    let normalized = CardioSessionDb::get_by_id(normalized.id, &mut db)
+        .await
+        .unwrap();
+    drop(db);
+    assert_eq!(normalized.distance, Some(110));
+    assert_eq!(normalized.time, Some(100_000));
+    assert!(normalized.ascent.unwrap() > 0);
+    assert_eq!(normalized.descent, Some(0));
+
+    // physically impossible tracks are rejected
+    let response = request(&mut router, create_request(&cardio_session(track(true)))).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = request(
+        &mut router,
+        Request::get(format!(
+            "{}?id={}",
+            route_max_version("", CARDIO_SESSION_VALIDATION, None),
+            stored.id.0
+        ))
+        .header(header.0.clone(), header.1.clone())
+        .body(Body::empty())
+        .unwrap(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let validations: Vec<CardioSessionValidation> = parse_body(response).await;
+    assert_eq!(validations.len(), 1);
+    assert!(validations[0].invalid_track.is_none());
+    let fields: Vec<_> = validations[0]
+        .discrepancies
+        .iter()
+        .map(|discrepancy| (discrepancy.field, discrepancy.stored, discrepancy.computed))
+        .collect();
+    assert_eq!(fields.len(), 3);
+    assert_eq!(fields[0], (CardioSummaryField::Distance, Some(5000), 110));
+    assert_eq!(fields[1].0, CardioSummaryField::Ascent);
+    assert_eq!(fields[2], (CardioSummaryField::Descent, None, 0));
+
+    let header = auth_header(ADMIN_USERNAME, ADMIN_PASSWORD_PLAINTEXT);
+    let normalize_request = |dry_run: bool| {
+        Request::post(format!(
+            "{}?dry_run={dry_run}",
+            route_max_version("", ADM_NORMALIZE_CARDIO_SESSION, None)
+        ))
+        .header(header.0.clone(), header.1.clone())
+        .body(Body::empty())
+        .unwrap()
+    };
+    for dry_run in [true, false] {
+        let response = request(&mut router, normalize_request(dry_run)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let validations: Vec<CardioSessionValidation> = parse_body(response).await;
+        assert!(validations.iter().any(|validation| {
+            validation.cardio_session_id == stored.id && validation.discrepancies.len() == 3
+        }));
+        assert!(validations.iter().any(|validation| {
+            validation.cardio_session_id == invalid.id && validation.invalid_track.is_some()
+        }));
+        assert!(
+            validations
+                .iter()
+                .all(|validation| validation.cardio_session_id != normalized.id)
+        );
+
+        let mut db = db_pool.get().await.unwrap();
+        let distance = CardioSessionDb::get_by_id(stored.id, &mut db)
+            .await
+            .unwrap()
+            .distance;
+        assert_eq!(distance, Some(if dry_run { 5000 } else { 110 }));
+        let distance = CardioSessionDb::get_by_id(invalid.id, &mut db)
+            .await
+            .unwrap()
+            .distance;
+        assert_eq!(distance, Some(5000));
+    }
+}
+
+#[tokio::test]
+async fn route_matching_and_history() {
+    let (mut router, db_pool, _) = init().await;
+
+    let mut db = db_pool.get().await.unwrap();
+    let movement = create_cardio_movement(&mut db).await;
+    // 5 km to the north or east
+    let track = |north: bool, positions: i32, offset: f64| {
+        let step = 0.045 / f64::from(positions);
+        (0..=positions)
+            .map(|i| Position {
+                longitude: 11. + if north { offset } else { f64::from(i) * step },
+                latitude: 47. + if north { f64::from(i) * step } else { offset },
+                elevation: 600.,
+                distance: f64::from(i) * 5000. / f64::from(positions),
+                time: i * 15_000,
+            })
+            .collect()
+    };
+    let route = |north: bool| Route {
+        id: RouteId(rnd()),
+        user_id: TEST_USER.id,
+        name: format!("route <{}>", rnd()),
+        distance: Some(5000),
+        ascent: None,
+        descent: None,
+        track: Some(track(north, 10, 0.)),
+        marked_positions: None,
+        deleted: false,
+    };
+    let (north, east) = (route(true), route(false));
+    RouteDb::create_multiple(&[north.clone(), east.clone()], &mut db)
+        .await
+        .unwrap();
+    let cardio_session = |datetime: &str, time: i32, track| CardioSession {
+        datetime: datetime.parse().unwrap(),
+        distance: Some(5000),
+        time: Some(time),
+        track: Some(track),
+        ..cardio_session(movement.id)
+    };
+    // about 15 m next to the northern route
+    let unmatched = cardio_session("2024-05-20T12:00:00Z", 1_400_000, track(true, 200, 0.0002));
+    // about 1.5 km next to the northern route
+    let off_route = cardio_session("2024-05-21T12:00:00Z", 1_300_000, track(true, 200, 0.02));
+    CardioSessionDb::create_multiple(&[unmatched.clone(), off_route.clone()], &mut db)
+        .await
+        .unwrap();
+    drop(db);
+
+    let header = auth_header(&TEST_USER.username, &TEST_USER.password);
+    let matched = cardio_session("2024-05-10T12:00:00Z", 1_500_000, track(true, 200, 0.0002));
+    let response = request(
+        &mut router,
+        Request::post(format!(
+            "{}?match_route=true",
+            route_max_version("", CARDIO_SESSION, None)
+        ))
+        .header(header.0.clone(), header.1.clone())
+        .header(CONTENT_TYPE, APPLICATION_JSON.as_ref())
+        .body(serde_json::to_string(&matched).unwrap().into())
+        .unwrap(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let mut db = db_pool.get().await.unwrap();
+    let route_id = CardioSessionDb::get_by_id(matched.id, &mut db)
+        .await
+        .unwrap()
+        .route_id;
+    drop(db);
+    assert_eq!(route_id, Some(north.id));
+
+    let get_request = |uri: &str| {
+        Request::get(route_max_version("", uri, None))
+            .header(header.0.clone(), header.1.clone())
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    let match_uri =
+        |id: CardioSessionId| CARDIO_SESSION_ROUTE_MATCH.replace("{id}", &id.0.to_string());
+    let response = request(&mut router, get_request(&match_uri(unmatched.id))).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let matches: Vec<RouteMatch> = parse_body(response).await;
+    assert_eq!(matches.len(), 1);
+    assert_eq!(matches[0].route_id, north.id);
+    assert!(matches[0].distance < 50.);
+
+    let response = request(&mut router, get_request(&match_uri(off_route.id))).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let matches: Vec<RouteMatch> = parse_body(response).await;
+    assert!(matches.is_empty());
+
+    for dry_run in [true, false] {
+        let response = request(
+            &mut router,
+            Request::post(format!(
+                "{}?dry_run={dry_run}",
+                route_max_version("", ROUTE_MATCH, None)
+            ))
+            .header(header.0.clone(), header.1.clone())
+            .body(Body::empty())
+            .unwrap(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let matches: Vec<RouteMatch> = parse_body(response).await;
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].cardio_session_id, unmatched.id);
+        assert_eq!(matches[0].route_id, north.id);
+
+        let mut db = db_pool.get().await.unwrap();
+        let route_id = CardioSessionDb::get_by_id(unmatched.id, &mut db)
+            .await
+            .unwrap()
+            .route_id;
+        drop(db);
+        assert_eq!(route_id, (!dry_run).then_some(north.id));
+    }
+
+    let history_uri = ROUTE_HISTORY.replace("{id}", &north.id.0.to_string());
+    let response = request(&mut router, get_request(&history_uri)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let history: RouteHistory = parse_body(response).await;
+    assert_eq!(history.route_id, north.id);
+    let sessions: Vec<_> = history
+        .sessions
+        .iter()
+        .map(|session| session.cardio_session_id)
+        .collect();
+    assert_eq!(sessions, [matched.id, unmatched.id]);
+    assert_eq!(history.best.unwrap().cardio_session_id, unmatched.id);
+    // 100 s faster in 10 days
+    assert!((history.trend.unwrap() + 10_000.).abs() < 0.01);
+}
+
+#[tokio::test]
+async fn segment_efforts() {
+    let (mut router, db_pool, _) = init().await;
+
+    let mut db = db_pool.get().await.unwrap();
+    let movement = create_cardio_movement(&mut db).await;
+    // 5 km to the north in 200 positions of 25 m
+    let track = |offset: f64, seconds: i32| {
+        (0..=200)
+            .map(|i| Position {
+                longitude: 11. + offset,
+                latitude: 47. + f64::from(i) * 0.000_225,
+                elevation: 600.,
+                distance: f64::from(i) * 25.,
+                time: i * seconds * 1000,
+            })
+            .collect()
+    };
+    let cardio_session = |datetime: &str, track, heart_rate| CardioSession {
+        datetime: datetime.parse().unwrap(),
+        track: Some(track),
+        heart_rate,
+        ..cardio_session(movement.id)
+    };
+    // about 15 m next to the segment
+    let slow = cardio_session("2024-05-10T12:00:00Z", track(0.0002, 15), None);
+    CardioSessionDb::create(&slow, &mut db).await.unwrap();
+    drop(db);
+
+    let header = auth_header(&TEST_USER.username, &TEST_USER.password);
+    let json_request = |method: &str, uri: &str, body: String| {
+        Request::builder()
+            .method(method)
+            .uri(route_max_version("", uri, None))
+            .header(header.0.clone(), header.1.clone())
+            .header(CONTENT_TYPE, APPLICATION_JSON.as_ref())
+            .body(body.into())
+            .unwrap()
+    };
+    let get_efforts = |uri: &str, segment_id: SegmentId| {
+        Request::get(format!(
+            "{}?segment_id={}",
+            route_max_version("", uri, None),
+            segment_id.0
+        ))
+        .header(header.0.clone(), header.1.clone())
+        .body(Body::empty())
+        .unwrap()
+    };
+
+    // the kilometer from position 40 to position 80
+    let segment = Segment {
+        id: SegmentId(rnd()),
+        user_id: TEST_USER.id,
+        name: format!("segment <{}>", rnd()),
+        track: vec![
+            Position {
+                longitude: 11.,
+                latitude: 47.009,
+                elevation: 600.,
+                distance: 0.,
+                time: 0,
+            },
+            Position {
+                longitude: 11.,
+                latitude: 47.018,
+                elevation: 600.,
+                distance: 1000.,
+                time: 0,
+            },
+        ],
+        deleted: false,
+    };
+    let response = request(
+        &mut router,
+        json_request("POST", SEGMENT, serde_json::to_string(&segment).unwrap()),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // efforts are detected in existing sessions when a segment is created
+    let response = request(&mut router, get_efforts(SEGMENT_EFFORT, segment.id)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let efforts: Vec<SegmentEffort> = parse_body(response).await;
+    assert_eq!(efforts.len(), 1);
+    assert_eq!(efforts[0].cardio_session_id, slow.id);
+    assert_eq!(efforts[0].start, 600_000);
+    assert_eq!(efforts[0].time, 600_000);
+    assert_eq!(efforts[0].avg_heart_rate, None);
+    assert_eq!(
+        efforts[0].datetime,
+        "2024-05-10T12:10:00Z".parse::<DateTime<Utc>>().unwrap()
+    );
+
+    // and in sessions when they are created
+    // 120 bpm
+    let fast = cardio_session(
+        "2024-05-20T12:00:00Z",
+        track(-0.0002, 12),
+        Some((0..5000).map(|beat| beat * 500).collect()),
+    );
+    let response = request(
+        &mut router,
+        json_request(
+            "POST",
+            CARDIO_SESSION,
+            serde_json::to_string(&fast).unwrap(),
+        ),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = request(&mut router, get_efforts(SEGMENT_EFFORT, segment.id)).await;
+    let efforts: Vec<SegmentEffort> = parse_body(response).await;
+    let sessions: Vec<_> = efforts.iter().map(|e| e.cardio_session_id).collect();
+    assert_eq!(sessions, [slow.id, fast.id]);
+
+    let response = request(&mut router, get_efforts(SEGMENT_BEST_EFFORT, segment.id)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let best_efforts: Vec<SegmentEffort> = parse_body(response).await;
+    assert_eq!(best_efforts.len(), 1);
+    assert_eq!(best_efforts[0].cardio_session_id, fast.id);
+    assert_eq!(best_efforts[0].time, 480_000);
+    assert_eq!(best_efforts[0].avg_heart_rate, Some(120));
+
+    // and in sessions when they are updated
+    let mut off_segment = fast.clone();
+    off_segment.track = Some(track(0.02, 12));
+    let response = request(
+        &mut router,
+        json_request(
+            "PUT",
+            CARDIO_SESSION,
+            serde_json::to_string(&off_segment).unwrap(),
+        ),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = request(&mut router, get_efforts(SEGMENT_BEST_EFFORT, segment.id)).await;
+    let best_efforts: Vec<SegmentEffort> = parse_body(response).await;
+    assert_eq!(best_efforts.len(), 1);
+    assert_eq!(best_efforts[0].cardio_session_id, slow.id);
+
+    // but not when the update is rejected
+    let response = request(
+        &mut router,
+        Request::put(route_max_version(
+            "",
+            CARDIO_SESSION,
+            Some(&[("last_epoch", "0")]),
+        ))
+        .header(header.0.clone(), header.1.clone())
+        .header(CONTENT_TYPE, APPLICATION_JSON.as_ref())
+        .body(serde_json::to_string(&fast).unwrap().into())
+        .unwrap(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    let response = request(&mut router, get_efforts(SEGMENT_BEST_EFFORT, segment.id)).await;
+    let best_efforts: Vec<SegmentEffort> = parse_body(response).await;
+    assert_eq!(best_efforts.len(), 1);
+    assert_eq!(best_efforts[0].cardio_session_id, slow.id);
+
+    // efforts of deleted sessions are hidden
+    let mut deleted = slow.clone();
+    deleted.deleted = true;
+    let response = request(
+        &mut router,
+        json_request(
+            "PUT",
+            CARDIO_SESSION,
+            serde_json::to_string(&deleted).unwrap(),
+        ),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = request(&mut router, get_efforts(SEGMENT_EFFORT, segment.id)).await;
+    let efforts: Vec<SegmentEffort> = parse_body(response).await;
+    assert!(efforts.is_empty());
+}
+
+#[tokio::test]
+async fn spatial_queries() {
+    // Without PostGIS in the test database only the fallback to the bounding boxes is covered.
+    let (mut router, db_pool, _) = init().await;
+
+    let mut db = db_pool.get().await.unwrap();
+    let movement = create_cardio_movement(&mut db).await;
+    // 1 km to the north
+    let track = |latitude: f64, longitude: f64| {
+        (0..=10)
+            .map(|i| Position {
+                longitude,
+                latitude: latitude + f64::from(i) * 0.0009,
+                elevation: 600.,
+                distance: f64::from(i) * 100.,
+                time: i * 30_000,
+            })
+            .collect::<Vec<_>>()
+    };
+    let cardio_session = |track| CardioSession {
+        track,
+        ..cardio_session(movement.id)
+    };
+    let near = cardio_session(Some(track(47., 11.)));
+    let far = cardio_session(Some(track(48., 16.)));
+    let without_track = cardio_session(None);
+    CardioSessionDb::create_multiple(&[near.clone(), far.clone(), without_track], &mut db)
+        .await
+        .unwrap();
+    let route = Route {
+        id: RouteId(rnd()),
+        user_id: TEST_USER.id,
+        name: format!("route <{}>", rnd()),
+        distance: Some(1000),
+        ascent: None,
+        descent: None,
+        track: Some(track(48., 16.)),
+        marked_positions: None,
+        deleted: false,
+    };
+    RouteDb::create(&route, &mut db).await.unwrap();
+    drop(db);
+
+    let header = auth_header(&TEST_USER.username, &TEST_USER.password);
+    let spatial_request = |uri: &str, query: &str| {
+        Request::get(format!("{}?{query}", route_max_version("", uri, None)))
+            .header(header.0.clone(), header.1.clone())
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    let response = request(
+        &mut router,
+        spatial_request(
+            CARDIO_SESSION_SPATIAL,
+            "min_latitude=46.9&min_longitude=10.9&max_latitude=47.1&max_longitude=11.1",
+        ),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let sessions: Vec<CardioSessionSummary> = parse_body(response).await;
+    let ids: Vec<_> = sessions.iter().map(|session| session.id).collect();
+    assert_eq!(ids, [near.id]);
+
+    // the track passes between two positions
+    let response = request(
+        &mut router,
+        spatial_request(
+            CARDIO_SESSION_SPATIAL,
+            "min_latitude=47.0003&min_longitude=10.9999&max_latitude=47.0006&max_longitude=11.0001",
+        ),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let sessions: Vec<CardioSessionSummary> = parse_body(response).await;
+    let ids: Vec<_> = sessions.iter().map(|session| session.id).collect();
+    assert_eq!(ids, [near.id]);
+
+    // the closest positions are about 55 m and the line between them about 23 m away
+    let response = request(
+        &mut router,
+        spatial_request(
+            CARDIO_SESSION_SPATIAL,
+            "latitude=47.00045&longitude=11.0003&radius=30",
+        ),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let sessions: Vec<CardioSessionSummary> = parse_body(response).await;
+    let ids: Vec<_> = sessions.iter().map(|session| session.id).collect();
+    assert_eq!(ids, [near.id]);
+
+    // the end of the track is about 100 m away
+    for (radius, count) in [(50, 0), (150, 1)] {
+        let response = request(
+            &mut router,
+            spatial_request(
+                CARDIO_SESSION_SPATIAL,
+                &format!("latitude=47.01&longitude=11&radius={radius}"),
+            ),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let sessions: Vec<CardioSessionSummary> = parse_body(response).await;
+        assert_eq!(sessions.len(), count);
+    }
+
+    let response = request(
+        &mut router,
+        spatial_request(ROUTE_SPATIAL, "latitude=48.005&longitude=16.001&radius=500"),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let routes: Vec<RouteSummary> = parse_body(response).await;
+    let ids: Vec<_> = routes.iter().map(|route| route.id).collect();
+    assert_eq!(ids, [route.id]);
+
+    for query in [
+        "latitude=47&longitude=11",
+        "latitude=47&longitude=11&radius=100&min_latitude=46",
+        "min_latitude=47.1&min_longitude=10.9&max_latitude=46.9&max_longitude=11.1",
+        "latitude=91&longitude=11&radius=100",
+    ] {
+        let response = request(&mut router, spatial_request(CARDIO_SESSION_SPATIAL, query)).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
+
+#[tokio::test]
+async fn heatmap_tiles() {
+    /// The alpha channel of the pixels of a PNG image.
+    fn alpha(png: &[u8]) -> Vec<u8> {
+        let mut data = vec![];
+        let mut offset = 8;
+        while offset < png.len() {
+            let length = u32::from_be_bytes(png[offset..offset + 4].try_into().unwrap()) as usize;
+            if &png[offset + 4..offset + 8] == b"IDAT" {
+                data.extend_from_slice(&png[offset + 8..offset + 8 + length]);
+            }
+            offset += length + 12;
+        }
+        let mut pixels = vec![];
+        ZlibDecoder::new(data.as_slice())
+            .read_to_end(&mut pixels)
+            .unwrap();
+        // every row starts with a filter byte
+        pixels
+            .chunks(256 * 4 + 1)
+            .flat_map(|row| row[1..].chunks(4).map(|pixel| pixel[3]))
+            .collect()
+    }
+
+    let (mut router, db_pool, _) = init().await;
+
+    let mut db = db_pool.get().await.unwrap();
+    let movement = create_cardio_movement(&mut db).await;
+    // 1 km to the north within the tile 12/2173/1440
+    let track = (0..=10)
+        .map(|i| Position {
+            longitude: 11.,
+            latitude: 47. + f64::from(i) * 0.0009,
+            elevation: 600.,
+            distance: f64::from(i) * 100.,
+            time: i * 30_000,
+        })
+        .collect();
+    let mut cardio_session = CardioSession {
+        track: Some(track),
+        ..cardio_session(movement.id)
+    };
+    CardioSessionDb::create(&cardio_session, &mut db)
+        .await
+        .unwrap();
+    drop(db);
+
+    let header = auth_header(&TEST_USER.username, &TEST_USER.password);
+    let tile_request = |tile: &str, if_none_match: Option<&HeaderValue>| {
+        let mut request = Request::get(route_max_version("", &format!("/heatmap/{tile}"), None))
+            .header(header.0.clone(), header.1.clone());
+        if let Some(etag) = if_none_match {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        request.body(Body::empty()).unwrap()
+    };
+
+    let response = request(&mut router, tile_request("12/2173/1440", None)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[CONTENT_TYPE], "image/png");
+    let etag = response.headers()[ETAG].clone();
+    let png = body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    assert!(png.starts_with(b"\x89PNG\r\n\x1a\n"));
+    let pixels = alpha(&png);
+    assert_eq!(pixels.len(), 256 * 256);
+    assert!(pixels.iter().any(|&alpha| alpha > 0));
+
+    // the track is not in the tile or filtered out
+    for tile in ["12/0/0", "12/2173/1440?cardio_type=ActiveRecovery"] {
+        let response = request(&mut router, tile_request(tile, None)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let png = body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert!(alpha(&png).iter().all(|&alpha| alpha == 0));
+    }
+
+    let response = request(&mut router, tile_request("12/2173/1440", Some(&etag))).await;
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    let etag_list = format!(r#""other", W/{}"#, etag.to_str().unwrap());
+    let response = request(
+        &mut router,
+        tile_request(
+            "12/2173/1440",
+            Some(&HeaderValue::from_str(&etag_list).unwrap()),
+        ),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    let response = request(
+        &mut router,
+        tile_request("12/2173/1440", Some(&HeaderValue::from_static("*"))),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+    // other tiles and options have other entity tags
+    for tile in ["12/0/0", "12/2173/1440?cardio_type=ActiveRecovery"] {
+        let response = request(&mut router, tile_request(tile, Some(&etag))).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_ne!(response.headers()[ETAG], etag);
+    }
+
+    // the cached tile is invalidated by a change of the cardio sessions
+    let mut db = db_pool.get().await.unwrap();
+    cardio_session.deleted = true;
+    CardioSessionDb::update(&cardio_session, &mut db)
+        .await
+        .unwrap();
+    drop(db);
+
+    let response = request(&mut router, tile_request("12/2173/1440", Some(&etag))).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_ne!(response.headers()[ETAG], etag);
+    let png = body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    assert!(alpha(&png).iter().all(|&alpha| alpha == 0));
+
+    for tile in ["12/4096/0", "21/0/0"] {
+        let response = request(&mut router, tile_request(tile, None)).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
+
+#[tokio::test]
+async fn track_simplification() {
+    let (mut router, db_pool, _) = init().await;
+
+    // 2 km to the north with a detour of about 50 m to the east in the middle
+    let track = |detour: bool| {
+        (0..=200)
+            .map(|i| Position {
+                longitude: if detour && i == 100 { 11.0007 } else { 11. },
+                latitude: 47. + f64::from(i) * 0.0001,
+                elevation: 600.,
+                distance: f64::from(i) * 10.,
+                time: i * 1000,
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let mut db = db_pool.get().await.unwrap();
+    let movement = create_cardio_movement(&mut db).await;
+    let mut cardio_session = CardioSession {
+        distance: Some(2000),
+        time: Some(200_000),
+        track: Some(track(true)),
+        ..cardio_session(movement.id)
+    };
+    CardioSessionDb::create(&cardio_session, &mut db)
+        .await
+        .unwrap();
+    let route = Route {
+        id: RouteId(rnd()),
+        user_id: TEST_USER.id,
+        name: format!("route <{}>", rnd()),
+        distance: Some(2000),
+        ascent: None,
+        descent: None,
+        track: Some(track(true)),
+        marked_positions: None,
+        deleted: false,
+    };
+    RouteDb::create(&route, &mut db).await.unwrap();
+    drop(db);
+
+    let header = auth_header(&TEST_USER.username, &TEST_USER.password);
+    let get_request = |uri: &str, query: &str| {
+        Request::get(format!("{}?{query}", route_max_version("", uri, None)))
+            .header(header.0.clone(), header.1.clone())
+            .body(Body::empty())
+            .unwrap()
+    };
+    let session_query = |query: &str| format!("id={}&{query}", cardio_session.id.0);
+    let track_uri = CARDIO_SESSION_TRACK.replace("{id}", &cardio_session.id.0.to_string());
+
+    // only the detour and the positions next to it deviate from a straight line
+    for (query, times) in [
+        ("tolerance=5", vec![0, 99, 100, 101, 200]),
+        ("max_points=3", vec![0, 100, 200]),
+        ("tolerance=100", vec![0, 200]),
+    ] {
+        let response = request(&mut router, get_request(&track_uri, query)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let session_track: CardioSessionTrack = parse_body(response).await;
+        let track = session_track.track.unwrap();
+        let expected: Vec<_> = times.iter().map(|time| time * 1000).collect();
+        assert_eq!(
+            track
+                .iter()
+                .map(|position| position.time)
+                .collect::<Vec<_>>(),
+            expected
+        );
+        assert!(
+            track
+                .iter()
+                .all(|position| position.distance == f64::from(position.time / 100))
+        );
+    }
+
+    let response = request(&mut router, get_request(&track_uri, "")).await;
+    let session_track: CardioSessionTrack = parse_body(response).await;
+    assert_eq!(session_track.track.unwrap().len(), 201);
+
+    for query in ["tolerance=-1", "max_points=1"] {
+        let response = request(&mut router, get_request(&track_uri, query)).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    // cardio sessions and routes are always returned with their full tracks
+    let response = request(
+        &mut router,
+        get_request(CARDIO_SESSION, &session_query("max_points=3")),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let sessions: Vec<CardioSession> = parse_body(response).await;
+    assert_eq!(sessions[0].track.as_ref().unwrap().len(), 201);
+
+    let response = request(
+        &mut router,
+        get_request(ROUTE, &format!("id={}&max_points=3", route.id.0)),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let routes: Vec<Route> = parse_body(response).await;
+    assert_eq!(routes[0].track.as_ref().unwrap().len(), 201);
+
+    let response = request(
+        &mut router,
+        get_request(ROUTE_OVERVIEW, &format!("id={}", route.id.0)),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let overviews: Vec<RouteOverview> = parse_body(response).await;
+    assert_eq!(overviews.len(), 1);
+    assert_eq!(overviews[0].route_id, route.id);
+    assert_eq!(overviews[0].track.len(), 5);
+
+    let overview_request = || get_request(CARDIO_SESSION_OVERVIEW, &session_query(""));
+    let response = request(&mut router, overview_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let overviews: Vec<CardioSessionOverview> = parse_body(response).await;
+    assert_eq!(overviews.len(), 1);
+    assert_eq!(overviews[0].cardio_session_id, cardio_session.id);
+    assert_eq!(overviews[0].track.len(), 5);
+
+    // the stored overview is replaced once the cardio session changes
+    let mut db = db_pool.get().await.unwrap();
+    let stored: i64 = cardio_session_overview::table
+        .filter(cardio_session_overview::columns::cardio_session_id.eq(cardio_session.id))
+        .count()
+        .get_result(&mut db)
+        .await
+        .unwrap();
+    assert_eq!(stored, 1);
+    cardio_session.track = Some(track(false));
+    CardioSessionDb::update(&cardio_session, &mut db)
+        .await
+        .unwrap();
+    drop(db);
+
+    let response = request(&mut router, overview_request()).await;
+    let overviews: Vec<CardioSessionOverview> = parse_body(response).await;
+    assert_eq!(overviews[0].track.len(), 2);
+}
+
+#[tokio::test]
+async fn epoch_from_create_and_update() {
+    let (mut router, _, _) = init().await;
+
+    // get account data - check empty & extract diary epoch
+    let (status, account_data) = account_data_request(&mut router, None).await;
+    let epoch = account_data.epoch_map.diary;
+
+    assert_eq!(status, StatusCode::OK);
+    assert!(account_data.diaries.is_empty());
+
+    // create new diary - check epoch increased by one
+    let header = auth_header(&TEST_USER.username, &TEST_USER.password);
+    let response = request(
+        &mut router,
+        Request::post(route_max_version("", DIARY, None))
+            .header(header.0, header.1)
+            .header(CONTENT_TYPE, APPLICATION_JSON.as_ref())
+            .body(serde_json::to_string(&TEST_DIARY as &Diary).unwrap().into())
+            .unwrap(),
+    )
+    .await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let epoch_response: EpochResponse = parse_body(response).await;
+    assert_eq!(epoch_response.epoch, Epoch(epoch.0 + 1));
+
+    // update diary - check epoch increased again by one
+    let header = auth_header(&TEST_USER.username, &TEST_USER.password);
+    let response = request(
+        &mut router,
+        Request::put(route_max_version("", DIARY, None))
+            .header(header.0, header.1)
+            .header(CONTENT_TYPE, APPLICATION_JSON.as_ref())
+            .body(serde_json::to_string(&TEST_DIARY as &Diary).unwrap().into())
+            .unwrap(),
+    )
+    .await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let epoch_response: EpochResponse = parse_body(response).await;
+    assert_eq!(epoch_response.epoch, Epoch(epoch.0 + 2));
+}
+
+#[tokio::test]
+async fn account_data_create_and_update() {
+    let (mut router, _, _) = init().await;
+
+    let movement = Movement {
+        id: MovementId(rnd()),
+        user_id: Some(TEST_USER.id),
+        name: format!("movement{}", rnd()),
+        description: None,
+        movement_dimension: MovementDimension::Reps,
+        cardio: false,
+        deleted: false,
+    };
+    let strength_session = StrengthSession {
+        id: StrengthSessionId(rnd()),
+        user_id: TEST_USER.id,
+        datetime: Utc::now(),
+        movement_id: movement.id,
+        interval: None,
+        comments: None,
+        deleted: false,
+    };
+    let mut strength_set = StrengthSet {
+        id: StrengthSetId(rnd()),
+        user_id: TEST_USER.id,
+        strength_session_id: strength_session.id,
+        set_number: 0,
+        count: 5,
+        weight: None,
+        deleted: false,
+    };
+
+    // create dependent entries of multiple tables at once
+    let changes = AccountDataChanges {
+        movements: vec![movement],
+        strength_sessions: vec![strength_session],
+        strength_sets: vec![strength_set.clone()],
+        diaries: vec![TEST_DIARY.clone()],
+        ..AccountDataChanges::default()
+    };
+    let (status, response) =
+        account_data_changes_request(&mut router, Method::POST, &changes).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert!(response.conflicts.is_empty());
+
+    let (_, account_data) = account_data_request(&mut router, None).await;
+    assert_eq!(
+        account_data.epoch_map.strength_set,
+        response.epoch_map.strength_set
+    );
+    assert_eq!(account_data.strength_sets.len(), 1);
+    assert_eq!(account_data.strength_sets[0].id, strength_set.id);
+    assert_eq!(account_data.diaries.len(), 1);
+
+    // update entry - check epoch increased
+    strength_set.count = 10;
+    let changes = AccountDataChanges {
+        strength_sets: vec![strength_set.clone()],
+        ..AccountDataChanges::default()
+    };
+    let (status, update_response) =
+        account_data_changes_request(&mut router, Method::PUT, &changes).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert!(update_response.conflicts.is_empty());
+    assert!(update_response.epoch_map.strength_set > response.epoch_map.strength_set);
+
+    let (_, account_data) = account_data_request(&mut router, Some(response.epoch_map)).await;
+    assert_eq!(account_data.strength_sets.len(), 1);
+    assert_eq!(account_data.strength_sets[0].count, 10);
+}
+
+#[tokio::test]
+async fn account_data_create_conflict() {
+    let (mut router, db_pool, _) = init().await;
+
+    DiaryDb::create(&TEST_DIARY, &mut db_pool.get().await.unwrap())
+        .await
+        .unwrap();
+
+    // create wod and diary which conflicts with existing diary
+    let wod = Wod {
+        id: WodId(rnd()),
+        user_id: TEST_USER.id,
+        date: TEST_DIARY.date,
+        description: None,
+        deleted: false,
+    };
+    let diary = Diary {
+        id: DiaryId(rnd()),
+        ..TEST_DIARY.clone()
+    };
+    let changes = AccountDataChanges {
+        wods: vec![wod],
+        diaries: vec![diary],
+        ..AccountDataChanges::default()
+    };
+    let (status, response) =
+        account_data_changes_request(&mut router, Method::POST, &changes).await;
+
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(response.conflicts.len(), 1);
+    assert_eq!(response.conflicts[0].table, "diary");
+    assert_eq!(response.conflicts[0].status, StatusCode::CONFLICT.as_u16());
+
+    // check that non conflicting wod has not been created
+    let (_, account_data) = account_data_request(&mut router, None).await;
+    assert!(account_data.wods.is_empty());
+    assert_eq!(account_data.diaries.len(), 1);
+    assert_eq!(account_data.diaries[0].id, TEST_DIARY.id);
+}
+
+#[tokio::test]
+async fn account_data_sync() {
+    let (mut router, db_pool, _) = init().await;
+
+    DiaryDb::create(&TEST_DIARY, &mut db_pool.get().await.unwrap())
+        .await
+        .unwrap();
+
+    let sync_request = async |router: &mut Router, sync: &AccountDataSync| {
+        let header = auth_header(&TEST_USER.username, &TEST_USER.password);
+        let response = request(
+            router,
+            Request::post(route_max_version("", ACCOUNT_DATA_SYNC, None))
+                .header(header.0, header.1)
+                .header(CONTENT_TYPE, APPLICATION_JSON.as_ref())
+                .body(serde_json::to_string(sync).unwrap().into())
+                .unwrap(),
+        )
+        .await;
+        let status = response.status();
+        let response: AccountDataChangesResponse = parse_body(response).await;
+        (status, response)
+    };
+
+    // an update that fails rolls back the creates as well
+    let wod = Wod {
+        id: WodId(rnd()),
+        user_id: TEST_USER.id,
+        date: TEST_DIARY.date,
+        description: None,
+        deleted: false,
+    };
+    let missing_diary = Diary {
+        id: DiaryId(rnd()),
+        date: TEST_DIARY.date - Duration::days(1),
+        ..TEST_DIARY.clone()
+    };
+    let sync = AccountDataSync {
+        created: AccountDataChanges {
+            wods: vec![wod.clone()],
+            ..AccountDataChanges::default()
+        },
+        updated: AccountDataChanges {
+            diaries: vec![missing_diary],
+            ..AccountDataChanges::default()
+        },
+    };
+    let (status, response) = sync_request(&mut router, &sync).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(response.conflicts.len(), 1);
+    assert_eq!(response.conflicts[0].table, "diary");
+
+    let (_, account_data) = account_data_request(&mut router, None).await;
+    assert!(account_data.wods.is_empty());
+
+    // creates and updates are applied together
+    let diary = Diary {
+        comments: Some("updated".to_owned()),
+        ..TEST_DIARY.clone()
+    };
+    let sync = AccountDataSync {
+        created: AccountDataChanges {
+            wods: vec![wod.clone()],
+            ..AccountDataChanges::default()
+        },
+        updated: AccountDataChanges {
+            diaries: vec![diary],
+            ..AccountDataChanges::default()
+        },
+    };
+    let (status, response) = sync_request(&mut router, &sync).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(response.conflicts.is_empty());
+
+    let (_, account_data) = account_data_request(&mut router, None).await;
+    assert_eq!(account_data.wods.len(), 1);
+    assert_eq!(account_data.wods[0].id, wod.id);
+    assert_eq!(account_data.diaries[0].comments.as_deref(), Some("updated"));
+}
+
+#[tokio::test]
+async fn bulk_create_row_results() {
+    let (mut router, db_pool, _) = init().await;
+
+    DiaryDb::create(&TEST_DIARY, &mut db_pool.get().await.unwrap())
+        .await
+        .unwrap();
+
+    let new_diary = Diary {
+        id: DiaryId(rnd()),
+        date: TEST_DIARY.date - Duration::days(1),
+        ..TEST_DIARY.clone()
+    };
+    let same_id = Diary {
+        date: TEST_DIARY.date - Duration::days(2),
+        ..TEST_DIARY.clone()
+    };
+    let same_date = Diary {
+        id: DiaryId(rnd()),
+        ..TEST_DIARY.clone()
+    };
+
+    // create diaries with conflicts - check results of every row
+    let header = auth_header(&TEST_USER.username, &TEST_USER.password);
+    let response = request(
+        &mut router,
+        Request::post(route_max_version(
+            "",
+            DIARY,
+            Some(&[("row_results", "true")]),
+        ))
+        .header(header.0, header.1)
+        .header(CONTENT_TYPE, APPLICATION_JSON.as_ref())
+        .body(
+            serde_json::to_string(&[&new_diary, &same_id, &same_date])
+                .unwrap()
+                .into(),
+        )
+        .unwrap(),
+    )
+    .await;
+
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    let response: RowResultsResponse<DiaryId, Diary> = parse_body(response).await;
+    assert_eq!(response.rows.len(), 3);
+
+    assert_eq!(response.rows[0].id, new_diary.id);
+    assert_eq!(response.rows[0].status, StatusCode::OK.as_u16());
+    assert!(response.rows[0].existing.is_none());
+
+    assert_eq!(response.rows[1].id, same_id.id);
+    assert_eq!(response.rows[1].status, StatusCode::CONFLICT.as_u16());
+    assert!(matches!(
+        response.rows[1].message,
+        Some(ErrorMessage::PrimaryKeyViolation { .. })
+    ));
+    assert_eq!(
+        response.rows[1].existing.as_ref().unwrap().id,
+        TEST_DIARY.id
+    );
+
+    assert_eq!(response.rows[2].id, same_date.id);
+    assert_eq!(response.rows[2].status, StatusCode::CONFLICT.as_u16());
+    assert!(matches!(
+        response.rows[2].message,
+        Some(ErrorMessage::UniqueViolation { .. })
+    ));
+    assert_eq!(
+        response.rows[2].existing.as_ref().unwrap().id,
+        TEST_DIARY.id
+    );
+
+    // check that non conflicting diary has not been created
+    let (_, account_data) = account_data_request(&mut router, None).await;
+    assert_eq!(account_data.diaries.len(), 1);
+
+    // create diary without conflicts - check diary created
+    let header = auth_header(&TEST_USER.username, &TEST_USER.password);
+    let response = request(
+        &mut router,
+        Request::post(route_max_version(
+            "",
+            DIARY,
+            Some(&[("row_results", "true")]),
+        ))
+        .header(header.0, header.1)
+        .header(CONTENT_TYPE, APPLICATION_JSON.as_ref())
+        .body(serde_json::to_string(&[&new_diary]).unwrap().into())
+        .unwrap(),
+    )
+    .await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let response: RowResultsResponse<DiaryId, Diary> = parse_body(response).await;
+    assert_eq!(response.rows.len(), 1);
+    assert_eq!(response.rows[0].status, StatusCode::OK.as_u16());
+
+    let (_, account_data) = account_data_request(&mut router, None).await;
+    assert_eq!(account_data.diaries.len(), 2);
+}
+
+#[tokio::test]
+async fn bulk_create_row_results_other_user() {
+    let (mut router, db_pool, _) = init().await;
+
+    let other_diary = Diary {
+        id: DiaryId(rnd()),
+        user_id: TEST_USER2.id,
+        comments: Some("private".to_owned()),
+        ..TEST_DIARY.clone()
+    };
+    DiaryDb::create(&other_diary, &mut db_pool.get().await.unwrap())
+        .await
+        .unwrap();
+
+    // create diary with the id of the diary of another user - check that it is not returned
+    let diary = Diary {
+        id: other_diary.id,
+        ..TEST_DIARY.clone()
+    };
+    let header = auth_header(&TEST_USER.username, &TEST_USER.password);
+    let response = request(
+        &mut router,
+        Request::post(route_max_version(
+            "",
+            DIARY,
+            Some(&[("row_results", "true")]),
+        ))
+        .header(header.0, header.1)
+        .header(CONTENT_TYPE, APPLICATION_JSON.as_ref())
+        .body(serde_json::to_string(&[&diary]).unwrap().into())
+        .unwrap(),
+    )
+    .await;
+
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    let response: RowResultsResponse<DiaryId, Diary> = parse_body(response).await;
+    assert_eq!(response.rows.len(), 1);
+    assert_eq!(response.rows[0].id, other_diary.id);
+    assert!(matches!(
+        response.rows[0].message,
+        Some(ErrorMessage::PrimaryKeyViolation { .. })
+    ));
+    assert!(response.rows[0].existing.is_none());
+}
+
+async fn update_diary_since(
+    router: &mut Router,
+    diary: &Diary,
+    last_epoch: Epoch,
+) -> (StatusCode, RowResultsResponse<DiaryId, Diary>) {
+    let header = auth_header(&TEST_USER.username, &TEST_USER.password);
+    let last_epoch = last_epoch.0.to_string();
+    let response = request(
+        router,
+        Request::put(route_max_version(
+            "",
+            DIARY,
+            Some(&[("last_epoch", &last_epoch)]),
+        ))
+        .header(header.0, header.1)
+        .header(CONTENT_TYPE, APPLICATION_JSON.as_ref())
+        .body(serde_json::to_string(diary).unwrap().into())
+        .unwrap(),
+    )
+    .await;
+
+    let status = response.status();
+    let response = parse_body(response).await;
+    (status, response)
+}
+
+#[tokio::test]
+async fn update_last_epoch() {
+    let (mut router, _, _) = init().await;
+
+    // create diary - extract epoch
+    let header = auth_header(&TEST_USER.username, &TEST_USER.password);
+    let response = request(
+        &mut router,
+        Request::post(route_max_version("", DIARY, None))
+            .header(header.0, header.1)
+            .header(CONTENT_TYPE, APPLICATION_JSON.as_ref())
             .body(serde_json::to_string(&TEST_DIARY as &Diary).unwrap().into())
             .unwrap(),
     )
     .await;
-
     assert_eq!(response.status(), StatusCode::OK);
-    let epoch_response: EpochResponse = parse_body(response).await;
-    assert_eq!(epoch_response.epoch, Epoch(epoch.0 + 2));
+    let EpochResponse { epoch } = parse_body(response).await;
+
+    // update unchanged diary - check updated
+    let diary = Diary {
+        comments: Some("first".to_owned()),
+        ..TEST_DIARY.clone()
+    };
+    let (status, response) = update_diary_since(&mut router, &diary, epoch).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(response.rows.len(), 1);
+    assert!(response.rows[0].is_ok());
+    let new_epoch = response.epoch;
+
+    // update diary changed since epoch - check conflict with current diary
+    let diary = Diary {
+        comments: Some("second".to_owned()),
+        ..TEST_DIARY.clone()
+    };
+    let (status, response) = update_diary_since(&mut router, &diary, epoch).await;
+
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(response.rows.len(), 1);
+    assert!(matches!(
+        response.rows[0].message,
+        Some(ErrorMessage::EpochConflict { epoch }) if epoch == new_epoch
+    ));
+    let existing = response.rows[0].existing.as_ref().unwrap();
+    assert_eq!(existing.comments.as_deref(), Some("first"));
+
+    // update diary with current epoch - check updated
+    let (status, response) = update_diary_since(&mut router, &diary, new_epoch).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert!(response.rows[0].is_ok());
 }
 
 #[tokio::test]
@@ -1042,6 +4330,8 @@ async fn user_self_registration() {
         username: format!("user{}", user_id.0),
         password: "Password1".to_owned(),
         email: format!("email{}", user_id.0),
+        max_heart_rate: None,
+        resting_heart_rate: None,
     };
 
     let response = request(
@@ -1126,3 +4416,156 @@ async fn ap_self_registration() {
         assert_eq!(response.status(), StatusCode::FORBIDDEN);
     }
 }
+
+#[tokio::test]
+async fn platform_credential_encryption() {
+    let (mut router, db_pool, config) = init().await;
+
+    let action_event = ActionEvent {
+        id: ActionEventId(rnd()),
+        user_id: TEST_USER.id,
+        action_id: TEST_ACTION.id,
+        datetime: Utc::now() + Duration::try_days(1).unwrap(),
+        arguments: None,
+        enabled: true,
+        deleted: false,
+    };
+    ActionEventDb::create(&action_event, &mut db_pool.get().await.unwrap())
+        .await
+        .unwrap();
+
+    let platform_credential = PlatformCredential {
+        id: PlatformCredentialId(rnd()),
+        user_id: TEST_USER.id,
+        platform_id: TEST_PLATFORM.id,
+        username: "username".to_owned(),
+        password: "platform-password".to_owned(),
+        deleted: false,
+    };
+
+    let header = auth_header(&TEST_USER.username, &TEST_USER.password);
+    let response = request(
+        &mut router,
+        Request::post(route_max_version("", PLATFORM_CREDENTIAL, None))
+            .header(header.0.clone(), header.1.clone())
+            .header(CONTENT_TYPE, APPLICATION_JSON.as_ref())
+            .body(serde_json::to_string(&platform_credential).unwrap().into())
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // the user only gets the encrypted password
+    let response = request(
+        &mut router,
+        Request::get(route_max_version("", PLATFORM_CREDENTIAL, None))
+            .header(header.0.clone(), header.1.clone())
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let platform_credentials: Vec<PlatformCredential> = parse_body(response).await;
+    assert_eq!(platform_credentials.len(), 1);
+    assert_ne!(
+        platform_credentials[0].password,
+        platform_credential.password
+    );
+
+    // sending the encrypted password back does not encrypt it again
+    let response = request(
+        &mut router,
+        Request::put(route_max_version("", PLATFORM_CREDENTIAL, None))
+            .header(header.0, header.1)
+            .header(CONTENT_TYPE, APPLICATION_JSON.as_ref())
+            .body(
+                serde_json::to_string(&platform_credentials[0])
+                    .unwrap()
+                    .into(),
+            )
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // the action provider gets the decrypted password
+    let header = auth_header(&TEST_AP.name, &TEST_AP.password);
+    let response = request(
+        &mut router,
+        Request::get(route_max_version("", AP_EXECUTABLE_ACTION_EVENT, None))
+            .header(header.0, header.1)
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let executable_action_events: Vec<ExecutableActionEvent> = parse_body(response).await;
+    assert_eq!(executable_action_events.len(), 1);
+    assert_eq!(
+        executable_action_events[0].password.as_deref(),
+        Some(platform_credential.password.as_str())
+    );
+
+    // passwords that look like encrypted ones are encrypted as well
+    let platform_credential = PlatformCredential {
+        password: "$enc$v1$0$platform-password".to_owned(),
+        ..platform_credential
+    };
+    let header = auth_header(&TEST_USER.username, &TEST_USER.password);
+    let response = request(
+        &mut router,
+        Request::put(route_max_version("", PLATFORM_CREDENTIAL, None))
+            .header(header.0, header.1)
+            .header(CONTENT_TYPE, APPLICATION_JSON.as_ref())
+            .body(serde_json::to_string(&platform_credential).unwrap().into())
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let header = auth_header(&TEST_AP.name, &TEST_AP.password);
+    let response = request(
+        &mut router,
+        Request::get(route_max_version("", AP_EXECUTABLE_ACTION_EVENT, None))
+            .header(header.0, header.1)
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let executable_action_events: Vec<ExecutableActionEvent> = parse_body(response).await;
+    assert_eq!(
+        executable_action_events[0].password.as_deref(),
+        Some(platform_credential.password.as_str())
+    );
+
+    // passwords stored before credential encryption are encrypted at startup
+    let mut db = db_pool.get().await.unwrap();
+    diesel::update(platform_credential::table.find(platform_credential.id))
+        .set((
+            platform_credential::columns::password.eq("plaintext-password"),
+            platform_credential::columns::password_encrypted.eq(false),
+        ))
+        .execute(&mut db)
+        .await
+        .unwrap();
+    let keys = CredentialKeys::new(config).unwrap();
+    let encrypted = PlatformCredentialDb::encrypt_plaintext(&keys, &mut db)
+        .await
+        .unwrap();
+    assert_eq!(encrypted, 1);
+    let password: String = platform_credential::table
+        .find(platform_credential.id)
+        .select(platform_credential::columns::password)
+        .get_result(&mut db)
+        .await
+        .unwrap();
+    assert_eq!(
+        keys.decrypt(&password, TEST_USER.id).as_deref(),
+        Some("plaintext-password")
+    );
+    let encrypted = PlatformCredentialDb::encrypt_plaintext(&keys, &mut db)
+        .await
+        .unwrap();
+    assert_eq!(encrypted, 0);
+}
@@ -0,0 +1,234 @@
+//! Minimal decoder for FIT files.
+//!
+//! Only `record` messages are decoded. All other messages are skipped using their definitions.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+
+use crate::track_file::{TrackFileError, TrackPoint};
+
+/// Seconds between the unix epoch and the FIT epoch (1989-12-31T00:00:00Z).
+const FIT_EPOCH: i64 = 631_065_600;
+
+const SEMICIRCLES_TO_DEGREES: f64 = 180. / 2_147_483_648.;
+
+const RECORD: u16 = 20;
+
+const TIMESTAMP: u8 = 253;
+const POSITION_LAT: u8 = 0;
+const POSITION_LONG: u8 = 1;
+const ALTITUDE: u8 = 2;
+const HEART_RATE: u8 = 3;
+const CADENCE: u8 = 4;
+const DISTANCE: u8 = 5;
+const ENHANCED_ALTITUDE: u8 = 78;
+
+const CRC_TABLE: [u16; 16] = [
+    0x0000, 0xCC01, 0xD801, 0x1400, 0xF001, 0x3C00, 0x2800, 0xE401, 0xA001, 0x6C00, 0x7800, 0xB401,
+    0x5000, 0x9C01, 0x8801, 0x4400,
+];
+
+pub fn is_fit(data: &[u8]) -> bool {
+    data.len() >= 12 && &data[8..12] == b".FIT"
+}
+
+/// The FIT CRC-16 of `data`.
+pub fn crc(data: &[u8]) -> u16 {
+    data.iter().fold(0, |crc, &byte| {
+        let crc =
+            (crc >> 4) ^ CRC_TABLE[usize::from(crc & 0xF)] ^ CRC_TABLE[usize::from(byte & 0xF)];
+        (crc >> 4) ^ CRC_TABLE[usize::from(crc & 0xF)] ^ CRC_TABLE[usize::from(byte >> 4)]
+    })
+}
+
+struct FieldDefinition {
+    number: u8,
+    size: usize,
+}
+
+struct Definition {
+    big_endian: bool,
+    global_message: u16,
+    fields: Vec<FieldDefinition>,
+    developer_size: usize,
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], TrackFileError> {
+        let bytes = self
+            .data
+            .get(self.position..self.position + len)
+            .ok_or_else(|| TrackFileError("unexpected end of fit file".to_owned()))?;
+        self.position += len;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8, TrackFileError> {
+        self.take(1).map(|bytes| bytes[0])
+    }
+}
+
+pub fn parse(data: &[u8]) -> Result<Vec<TrackPoint>, TrackFileError> {
+    if !is_fit(data) {
+        return Err(TrackFileError("file is not a fit file".to_owned()));
+    }
+    let header_size = usize::from(data[0]);
+    let data_size = u32::from_le_bytes([data[4], data[5], data[6], data[7]]) as usize;
+    let end = header_size + data_size;
+    if header_size < 12 || data.len() < end + 2 {
+        return Err(TrackFileError("unexpected end of fit file".to_owned()));
+    }
+    if crc(&data[..end]) != u16::from_le_bytes([data[end], data[end + 1]]) {
+        return Err(TrackFileError("invalid crc of fit file".to_owned()));
+    }
+
+    let mut reader = Reader {
+        data: &data[..end],
+        position: header_size,
+    };
+    let mut definitions: HashMap<u8, Definition> = HashMap::new();
+    let mut last_timestamp = 0;
+    let mut points = vec![];
+
+    while reader.position < end {
+        let header = reader.byte()?;
+        if header & 0x80 != 0 {
+            // compressed timestamp header
+            let local_message = (header >> 5) & 0x3;
+            let offset = u32::from(header & 0x1F);
+            let mut timestamp = (last_timestamp & !0x1F) + offset;
+            if offset < last_timestamp & 0x1F {
+                timestamp = timestamp.wrapping_add(0x20);
+            }
+            last_timestamp = timestamp;
+            if let Some(point) = read_data(
+                &mut reader,
+                &definitions,
+                local_message,
+                &mut last_timestamp,
+            )? {
+                points.push(point);
+            }
+        } else if header & 0x40 != 0 {
+            let definition = read_definition(&mut reader, header & 0x20 != 0)?;
+            definitions.insert(header & 0xF, definition);
+        } else if let Some(point) =
+            read_data(&mut reader, &definitions, header & 0xF, &mut last_timestamp)?
+        {
+            points.push(point);
+        }
+    }
+
+    Ok(points)
+}
+
+fn read_definition(
+    reader: &mut Reader<'_>,
+    developer_data: bool,
+) -> Result<Definition, TrackFileError> {
+    let header = reader.take(5)?;
+    let big_endian = header[1] == 1;
+    let global_message = if big_endian {
+        u16::from_be_bytes([header[2], header[3]])
+    } else {
+        u16::from_le_bytes([header[2], header[3]])
+    };
+    let fields = (0..header[4])
+        .map(|_| {
+            reader.take(3).map(|field| FieldDefinition {
+                number: field[0],
+                size: usize::from(field[1]),
+            })
+        })
+        .collect::<Result<_, _>>()?;
+    let mut developer_size = 0;
+    if developer_data {
+        for _ in 0..reader.byte()? {
+            developer_size += usize::from(reader.take(3)?[1]);
+        }
+    }
+
+    Ok(Definition {
+        big_endian,
+        global_message,
+        fields,
+        developer_size,
+    })
+}
+
+/// Read a data message and return the track point if it is a `record` message.
+fn read_data(
+    reader: &mut Reader<'_>,
+    definitions: &HashMap<u8, Definition>,
+    local_message: u8,
+    last_timestamp: &mut u32,
+) -> Result<Option<TrackPoint>, TrackFileError> {
+    let definition = definitions
+        .get(&local_message)
+        .ok_or_else(|| TrackFileError(format!("undefined local message {local_message}")))?;
+
+    let mut record = (definition.global_message == RECORD).then(TrackPoint::default);
+    let mut latitude = None;
+    let mut longitude = None;
+    let mut timestamp = None;
+    for field in &definition.fields {
+        let bytes = reader.take(field.size)?;
+        if record.is_none() && field.number != TIMESTAMP {
+            continue;
+        }
+        let Some(value) = unsigned(bytes, definition.big_endian) else {
+            continue;
+        };
+        match (field.number, record.as_mut()) {
+            (TIMESTAMP, _) if field.size == 4 => {
+                *last_timestamp = value;
+                timestamp = Some(value);
+            }
+            (POSITION_LAT, Some(_)) if field.size == 4 => latitude = Some(value.cast_signed()),
+            (POSITION_LONG, Some(_)) if field.size == 4 => longitude = Some(value.cast_signed()),
+            (ALTITUDE | ENHANCED_ALTITUDE, Some(point)) => {
+                point.elevation = Some(f64::from(value) / 5. - 500.);
+            }
+            (HEART_RATE, Some(point)) => point.heart_rate = Some(f64::from(value)),
+            (CADENCE, Some(point)) => point.cadence = Some(f64::from(value)),
+            (DISTANCE, Some(point)) => point.distance = Some(f64::from(value) / 100.),
+            _ => {}
+        }
+    }
+    reader.take(definition.developer_size)?;
+
+    Ok(record.map(|mut point| {
+        point.time = DateTime::<Utc>::from_timestamp(
+            FIT_EPOCH + i64::from(timestamp.unwrap_or(*last_timestamp)),
+            0,
+        );
+        if let (Some(latitude), Some(longitude)) = (latitude, longitude) {
+            point.latitude = Some(f64::from(latitude) * SEMICIRCLES_TO_DEGREES);
+            point.longitude = Some(f64::from(longitude) * SEMICIRCLES_TO_DEGREES);
+        }
+        point
+    }))
+}
+
+/// Read an unsigned integer of 1, 2 or 4 bytes.
+///
+/// Returns `None` for other sizes and for the invalid value (all bits set, or `0x7FFFFFFF` which is
+/// the invalid value of signed 32 bit integers).
+fn unsigned(bytes: &[u8], big_endian: bool) -> Option<u32> {
+    let value = match *bytes {
+        [a] => u32::from(a),
+        [a, b] if big_endian => u32::from(u16::from_be_bytes([a, b])),
+        [a, b] => u32::from(u16::from_le_bytes([a, b])),
+        [a, b, c, d] if big_endian => u32::from_be_bytes([a, b, c, d]),
+        [a, b, c, d] => u32::from_le_bytes([a, b, c, d]),
+        _ => return None,
+    };
+    let invalid = u32::MAX >> (32 - 8 * bytes.len());
+    (value != invalid && value != 0x7FFF_FFFF).then_some(value)
+}
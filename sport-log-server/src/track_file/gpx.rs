@@ -0,0 +1,147 @@
+use roxmltree::{Document, Node};
+use sport_log_types::{CardioSession, Position, Route};
+
+use crate::track_file::{
+    TrackFileError, TrackPoint, absolute_time, escape, format_time, parse_time, parse_value,
+    rate_at,
+};
+
+/// Parse the track points of all tracks of a GPX file.
+///
+/// Heart rate and cadence are read from the Garmin `TrackPointExtension`.
+pub fn parse(text: &str) -> Result<Vec<TrackPoint>, TrackFileError> {
+    let document = Document::parse(text)?;
+    if document.root_element().tag_name().name() != "gpx" {
+        return Err(TrackFileError("file is not a gpx file".to_owned()));
+    }
+
+    document
+        .descendants()
+        .filter(|node| node.has_tag_name("trkpt"))
+        .map(parse_point)
+        .collect()
+}
+
+fn parse_point(node: Node<'_, '_>) -> Result<TrackPoint, TrackFileError> {
+    let coordinate = |name| {
+        node.attribute(name)
+            .and_then(|value| value.trim().parse().ok())
+            .ok_or_else(|| TrackFileError(format!("invalid attribute {name} of trkpt")))
+    };
+    let mut point = TrackPoint {
+        latitude: Some(coordinate("lat")?),
+        longitude: Some(coordinate("lon")?),
+        ..TrackPoint::default()
+    };
+
+    for child in node.descendants().filter(Node::is_element) {
+        match child.tag_name().name() {
+            "time" => point.time = Some(parse_time(child)?),
+            "ele" => point.elevation = Some(parse_value(child, "ele")?),
+            "hr" => point.heart_rate = Some(parse_value(child, "hr")?),
+            "cad" => point.cadence = Some(parse_value(child, "cad")?),
+            _ => {}
+        }
+    }
+
+    Ok(point)
+}
+
+/// Write a cardio session as GPX 1.1 track.
+///
+/// Heart rate and cadence are written using the Garmin `TrackPointExtension`.
+pub fn write_cardio_session(cardio_session: &CardioSession, name: &str) -> String {
+    let end = cardio_session.time.unwrap_or(0);
+    let heart_rate = cardio_session.heart_rate.as_deref().unwrap_or_default();
+    let cadence = cardio_session.cadence.as_deref().unwrap_or_default();
+
+    let points: String = cardio_session
+        .track
+        .iter()
+        .flatten()
+        .map(|position| {
+            let heart_rate = rate_at(heart_rate, position.time, end)
+                .map(|heart_rate| format!("<gpxtpx:hr>{heart_rate}</gpxtpx:hr>"));
+            let cadence = rate_at(cadence, position.time, end)
+                .map(|cadence| format!("<gpxtpx:cad>{}</gpxtpx:cad>", cadence.min(254)));
+            let extensions = if heart_rate.is_some() || cadence.is_some() {
+                format!(
+                    "<extensions><gpxtpx:TrackPointExtension>{}{}</gpxtpx:TrackPointExtension></extensions>",
+                    heart_rate.unwrap_or_default(),
+                    cadence.unwrap_or_default()
+                )
+            } else {
+                String::new()
+            };
+            point(
+                "trkpt",
+                position,
+                &format!(
+                    "<time>{}</time>{extensions}",
+                    absolute_time(cardio_session, position.time)
+                ),
+            )
+        })
+        .collect();
+
+    let description = cardio_session
+        .comments
+        .as_deref()
+        .map(|comments| format!("<desc>{}</desc>", escape(comments)))
+        .unwrap_or_default();
+
+    document(
+        &format!(
+            "<metadata><name>{name}</name><time>{time}</time></metadata>\n",
+            name = escape(name),
+            time = format_time(cardio_session.datetime),
+        ),
+        &format!(
+            "<trk><name>{name}</name>{description}<trkseg>\n{points}</trkseg></trk>\n",
+            name = escape(name),
+        ),
+    )
+}
+
+/// Write a route as GPX 1.1 track with the marked positions as waypoints.
+pub fn write_route(route: &Route) -> String {
+    let waypoints: String = route
+        .marked_positions
+        .iter()
+        .flatten()
+        .map(|position| point("wpt", position, ""))
+        .collect();
+    let points: String = route
+        .track
+        .iter()
+        .flatten()
+        .map(|position| point("trkpt", position, ""))
+        .collect();
+
+    document(
+        &format!(
+            "<metadata><name>{name}</name></metadata>\n{waypoints}",
+            name = escape(&route.name),
+        ),
+        &format!(
+            "<trk><name>{name}</name><trkseg>\n{points}</trkseg></trk>\n",
+            name = escape(&route.name),
+        ),
+    )
+}
+
+fn document(header: &str, track: &str) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<gpx version="1.1" creator="Sport Log" xmlns="http://www.topografix.com/GPX/1/1" xmlns:gpxtpx="http://www.garmin.com/xmlschemas/TrackPointExtension/v1">
+{header}{track}</gpx>
+"#
+    )
+}
+
+fn point(tag: &str, position: &Position, content: &str) -> String {
+    format!(
+        "<{tag} lat=\"{}\" lon=\"{}\"><ele>{}</ele>{content}</{tag}>\n",
+        position.latitude, position.longitude, position.elevation
+    )
+}
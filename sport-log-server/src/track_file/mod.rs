@@ -0,0 +1,409 @@
+//! Import and export of cardio sessions and routes as GPX, TCX and FIT files.
+//!
+//! Imported files are parsed into a list of [`TrackPoint`]s which are then converted into a
+//! [`CardioSession`] using [`into_cardio_session`].
+//! Cardio sessions can be exported as GPX or TCX, routes only as GPX.
+
+use std::{borrow::Cow, fmt};
+
+use axum::http::StatusCode;
+use chrono::{DateTime, SecondsFormat, Utc};
+use rand::RngExt;
+use serde::Deserialize;
+use sport_log_types::{
+    CardioSession, CardioSessionId, CardioType, MovementId, Position, Route, UserId,
+};
+
+use crate::error::{ErrorMessage, HandlerError};
+
+pub mod fit;
+mod gpx;
+mod tcx;
+
+/// Minimal elevation difference in meter that is counted as ascent or descent.
+///
+/// Smaller differences are mostly GPS noise.
+const ELEVATION_DIFFERENCE_THRESHOLD: f64 = 10.;
+
+pub const EARTH_RADIUS: f64 = 6_371_000.;
+
+/// Maximal heart rate or cadence in events per minute accepted on import.
+const MAX_RATE: f64 = 300.;
+
+/// Maximal number of heart beats or steps of an imported recording.
+const MAX_EVENTS: usize = 2_000_000;
+
+/// Half of the time window in milliseconds over which heart rate and cadence are averaged on
+/// export.
+const RATE_WINDOW: i32 = 15_000;
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TrackFileFormat {
+    Gpx,
+    Tcx,
+    Fit,
+}
+
+impl TrackFileFormat {
+    /// Detect the format of a file from its content.
+    pub fn detect(data: &[u8]) -> Option<Self> {
+        if fit::is_fit(data) {
+            return Some(Self::Fit);
+        }
+        let text = std::str::from_utf8(data).ok()?;
+        if text.contains("<gpx") {
+            Some(Self::Gpx)
+        } else if text.contains("<TrainingCenterDatabase") {
+            Some(Self::Tcx)
+        } else {
+            None
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Gpx => "application/gpx+xml",
+            Self::Tcx => "application/vnd.garmin.tcx+xml",
+            Self::Fit => "application/vnd.ant.fit",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Gpx => "gpx",
+            Self::Tcx => "tcx",
+            Self::Fit => "fit",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct TrackFileError(String);
+
+impl fmt::Display for TrackFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<roxmltree::Error> for TrackFileError {
+    fn from(error: roxmltree::Error) -> Self {
+        Self(format!("invalid xml: {error}"))
+    }
+}
+
+impl From<TrackFileError> for HandlerError {
+    fn from(error: TrackFileError) -> Self {
+        (
+            StatusCode::BAD_REQUEST,
+            ErrorMessage::Other {
+                error: error.to_string(),
+            },
+        )
+            .into()
+    }
+}
+
+/// A single sample of a recording.
+///
+/// Values which are not contained in the file are `None`.
+/// `distance` is the distance in meter since the start as recorded by the device.
+/// `heart_rate` is measured in beats per minute and `cadence` in steps or revolutions per minute.
+#[derive(Debug, Clone, Default)]
+pub struct TrackPoint {
+    pub time: Option<DateTime<Utc>>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub elevation: Option<f64>,
+    pub distance: Option<f64>,
+    pub heart_rate: Option<f64>,
+    pub cadence: Option<f64>,
+}
+
+impl TrackPoint {
+    /// Check that all values are finite and within their valid range.
+    fn validate(&self) -> Result<(), TrackFileError> {
+        let valid = |value: Option<f64>, range: std::ops::RangeInclusive<f64>| {
+            value.is_none_or(|value| range.contains(&value))
+        };
+        if !valid(self.latitude, -90. ..=90.) || !valid(self.longitude, -180. ..=180.) {
+            return Err(TrackFileError("invalid coordinates".to_owned()));
+        }
+        if !valid(self.elevation, f64::MIN..=f64::MAX) || !valid(self.distance, 0. ..=f64::MAX) {
+            return Err(TrackFileError("invalid elevation or distance".to_owned()));
+        }
+        if !valid(self.heart_rate, 0. ..=MAX_RATE) || !valid(self.cadence, 0. ..=MAX_RATE) {
+            return Err(TrackFileError(format!(
+                "heart rate and cadence must be between 0 and {MAX_RATE} per minute"
+            )));
+        }
+        Ok(())
+    }
+}
+
+pub fn parse(data: &[u8], format: TrackFileFormat) -> Result<Vec<TrackPoint>, TrackFileError> {
+    let points = match format {
+        TrackFileFormat::Gpx => gpx::parse(xml_text(data)?)?,
+        TrackFileFormat::Tcx => tcx::parse(xml_text(data)?)?,
+        TrackFileFormat::Fit => fit::parse(data)?,
+    };
+    if points.is_empty() {
+        return Err(TrackFileError("file contains no track points".to_owned()));
+    }
+    for point in &points {
+        point.validate()?;
+    }
+    Ok(points)
+}
+
+fn xml_text(data: &[u8]) -> Result<&str, TrackFileError> {
+    std::str::from_utf8(data).map_err(|_| TrackFileError("file is not valid utf-8".to_owned()))
+}
+
+fn parse_value<T: std::str::FromStr>(
+    node: roxmltree::Node<'_, '_>,
+    name: &str,
+) -> Result<T, TrackFileError> {
+    node.text()
+        .map(str::trim)
+        .and_then(|text| text.parse().ok())
+        .ok_or_else(|| TrackFileError(format!("invalid value of element {name}")))
+}
+
+fn parse_time(node: roxmltree::Node<'_, '_>) -> Result<DateTime<Utc>, TrackFileError> {
+    node.text()
+        .and_then(|text| DateTime::parse_from_rfc3339(text.trim()).ok())
+        .map(|time| time.with_timezone(&Utc))
+        .ok_or_else(|| TrackFileError("invalid timestamp".to_owned()))
+}
+
+/// Export a cardio session with the activity name `name`.
+pub fn export_cardio_session(
+    cardio_session: &CardioSession,
+    name: &str,
+    format: TrackFileFormat,
+) -> Result<String, TrackFileError> {
+    match format {
+        TrackFileFormat::Gpx => Ok(gpx::write_cardio_session(cardio_session, name)),
+        TrackFileFormat::Tcx => Ok(tcx::write_cardio_session(cardio_session, name)),
+        TrackFileFormat::Fit => Err(TrackFileError(
+            "cardio sessions can not be exported as fit".to_owned(),
+        )),
+    }
+}
+
+pub fn export_route(route: &Route, format: TrackFileFormat) -> Result<String, TrackFileError> {
+    match format {
+        TrackFileFormat::Gpx => Ok(gpx::write_route(route)),
+        TrackFileFormat::Tcx | TrackFileFormat::Fit => Err(TrackFileError(
+            "routes can only be exported as gpx".to_owned(),
+        )),
+    }
+}
+
+fn escape(text: &str) -> Cow<'_, str> {
+    if text.contains(['&', '<', '>', '"', '\'']) {
+        Cow::Owned(
+            text.replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;")
+                .replace('"', "&quot;")
+                .replace('\'', "&apos;"),
+        )
+    } else {
+        Cow::Borrowed(text)
+    }
+}
+
+fn format_time(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+/// The absolute time of a point `time` milliseconds after the start of the cardio session.
+fn absolute_time(cardio_session: &CardioSession, time: i32) -> String {
+    format_time(cardio_session.datetime + chrono::Duration::milliseconds(i64::from(time)))
+}
+
+/// The rate in events per minute around `time` of `events` given as sorted times in milliseconds.
+///
+/// Returns `None` if there are no events in the time window.
+fn rate_at(events: &[i32], time: i32, end: i32) -> Option<u32> {
+    let start = (time - RATE_WINDOW).max(0);
+    let end = (time + RATE_WINDOW).min(end.max(time));
+    if end <= start {
+        return None;
+    }
+    let count = events.partition_point(|&event| event < end)
+        - events.partition_point(|&event| event < start);
+    let rate = (count as f64 * 60_000. / f64::from(end - start)).round() as u32;
+    (rate > 0).then_some(rate)
+}
+
+/// Convert the track points of a recording into a [`CardioSession`].
+///
+/// `datetime` is the time of the first track point.
+/// `distance`, `ascent`, `descent`, `time`, `avg_heart_rate` and `avg_cadence` are computed from
+/// the track points.
+/// Heart rate and cadence samples are converted into the time of every beat or step.
+/// Values which are not positive are `None`.
+pub fn into_cardio_session(
+    points: &[TrackPoint],
+    user_id: UserId,
+    movement_id: MovementId,
+    cardio_type: CardioType,
+) -> Result<CardioSession, TrackFileError> {
+    let start = points
+        .iter()
+        .find_map(|point| point.time)
+        .ok_or_else(|| TrackFileError("file contains no timestamps".to_owned()))?;
+    let offset = |point: &TrackPoint| {
+        let offset = point
+            .time
+            .map_or(0, |time| (time - start).num_milliseconds().max(0));
+        i32::try_from(offset).map_err(|_| TrackFileError("recording is too long".to_owned()))
+    };
+
+    let mut track: Vec<Position> = vec![];
+    for point in points {
+        let (Some(latitude), Some(longitude)) = (point.latitude, point.longitude) else {
+            continue;
+        };
+        let distance = track.last().map_or(0., |last| {
+            last.distance + distance(last.latitude, last.longitude, latitude, longitude)
+        });
+        track.push(Position {
+            longitude,
+            latitude,
+            elevation: point.elevation.unwrap_or(0.),
+            distance,
+            time: offset(point)?,
+        });
+    }
+
+    let time = points
+        .iter()
+        .map(offset)
+        .try_fold(0, |max, offset| offset.map(|offset| max.max(offset)))?;
+    let distance = points
+        .iter()
+        .rev()
+        .find_map(|point| point.distance)
+        .or_else(|| track.last().map(|position| position.distance))
+        .map(|distance| {
+            i32::try_from(distance.round() as i64)
+                .map_err(|_| TrackFileError("distance is too long".to_owned()))
+        })
+        .transpose()?;
+    let (ascent, descent) = ascent_descent(points.iter().filter_map(|point| point.elevation));
+    let heart_rate = event_times(points, time, |point| point.heart_rate)?;
+    let cadence = event_times(points, time, |point| point.cadence)?;
+    let per_minute = |events: &Option<Vec<i32>>| {
+        events
+            .as_ref()
+            .filter(|_| time > 0)
+            .map(|events| (events.len() as f64 / (f64::from(time) / 60_000.)).round() as i32)
+            .filter(|&rate| rate > 0)
+    };
+
+    Ok(CardioSession {
+        id: CardioSessionId(rand::rng().random()),
+        user_id,
+        movement_id,
+        cardio_type,
+        datetime: start,
+        distance: distance.filter(|&distance| distance > 0),
+        ascent,
+        descent,
+        time: (time > 0).then_some(time),
+        calories: None,
+        avg_cadence: per_minute(&cadence),
+        cadence,
+        avg_heart_rate: per_minute(&heart_rate),
+        heart_rate,
+        track: (!track.is_empty()).then_some(track),
+        route_id: None,
+        comments: None,
+        deleted: false,
+    })
+}
+
+/// Great-circle distance in meter between two coordinates in decimal degrees.
+pub fn distance(latitude1: f64, longitude1: f64, latitude2: f64, longitude2: f64) -> f64 {
+    let (latitude1, latitude2) = (latitude1.to_radians(), latitude2.to_radians());
+    let delta_latitude = latitude2 - latitude1;
+    let delta_longitude = (longitude2 - longitude1).to_radians();
+    let a = (delta_latitude / 2.).sin().powi(2)
+        + latitude1.cos() * latitude2.cos() * (delta_longitude / 2.).sin().powi(2);
+    2. * EARTH_RADIUS * a.sqrt().asin()
+}
+
+/// The ascent and descent in meters of a series of elevations.
+///
+/// Differences of at most [`ELEVATION_DIFFERENCE_THRESHOLD`] are ignored.
+/// Returns `None` if there are no elevations.
+pub fn ascent_descent(elevations: impl IntoIterator<Item = f64>) -> (Option<i32>, Option<i32>) {
+    let mut elevations = elevations.into_iter();
+    let Some(mut last_significant_elevation) = elevations.next() else {
+        return (None, None);
+    };
+    let mut ascent = 0.;
+    let mut descent = 0.;
+    for elevation in elevations {
+        let difference = elevation - last_significant_elevation;
+        if difference.abs() > ELEVATION_DIFFERENCE_THRESHOLD {
+            if difference > 0. {
+                ascent += difference;
+            } else {
+                descent -= difference;
+            }
+            last_significant_elevation = elevation;
+        }
+    }
+    (Some(ascent.round() as i32), Some(descent.round() as i32))
+}
+
+/// Convert rate samples (events per minute) into the time in milliseconds of every event.
+///
+/// Every sample is valid until the next sample or the end of the recording.
+/// The rates have to be validated to be at most [`MAX_RATE`].
+fn event_times(
+    points: &[TrackPoint],
+    end: i32,
+    rate: impl Fn(&TrackPoint) -> Option<f64>,
+) -> Result<Option<Vec<i32>>, TrackFileError> {
+    let Some(start) = points.iter().find_map(|point| point.time) else {
+        return Ok(None);
+    };
+    let samples: Vec<(f64, f64)> = points
+        .iter()
+        .filter_map(|point| {
+            let time = (point.time? - start).num_milliseconds() as f64;
+            Some((time, rate(point)?))
+        })
+        .collect();
+    if samples.is_empty() {
+        return Ok(None);
+    }
+
+    let mut events = vec![];
+    let mut next = samples[0].0;
+    for (i, &(time, rate)) in samples.iter().enumerate() {
+        let until = samples.get(i + 1).map_or(f64::from(end), |sample| sample.0);
+        if rate <= 0. {
+            next = until;
+            continue;
+        }
+        next = next.max(time);
+        while next < until {
+            if events.len() == MAX_EVENTS {
+                return Err(TrackFileError(format!(
+                    "recording contains more than {MAX_EVENTS} heart beats or steps"
+                )));
+            }
+            events.push(next.round() as i32);
+            next += 60_000. / rate;
+        }
+    }
+    Ok(Some(events))
+}
@@ -0,0 +1,136 @@
+use roxmltree::{Document, Node};
+use sport_log_types::{CardioSession, Position};
+
+use crate::track_file::{
+    TrackFileError, TrackPoint, absolute_time, escape, format_time, parse_time, parse_value,
+    rate_at,
+};
+
+/// Interval in milliseconds of the trackpoints written for cardio sessions without track.
+const TRACKPOINT_INTERVAL: i32 = 10_000;
+
+/// Parse the trackpoints of all activities of a TCX file.
+///
+/// The cadence is read from `Cadence` or from the `RunCadence` extension.
+pub fn parse(text: &str) -> Result<Vec<TrackPoint>, TrackFileError> {
+    let document = Document::parse(text)?;
+    if document.root_element().tag_name().name() != "TrainingCenterDatabase" {
+        return Err(TrackFileError("file is not a tcx file".to_owned()));
+    }
+
+    document
+        .descendants()
+        .filter(|node| node.has_tag_name("Trackpoint"))
+        .map(parse_point)
+        .collect()
+}
+
+fn parse_point(node: Node<'_, '_>) -> Result<TrackPoint, TrackFileError> {
+    let mut point = TrackPoint::default();
+
+    for child in node.descendants().filter(Node::is_element) {
+        match child.tag_name().name() {
+            "Time" => point.time = Some(parse_time(child)?),
+            "LatitudeDegrees" => point.latitude = Some(parse_value(child, "LatitudeDegrees")?),
+            "LongitudeDegrees" => point.longitude = Some(parse_value(child, "LongitudeDegrees")?),
+            "AltitudeMeters" => point.elevation = Some(parse_value(child, "AltitudeMeters")?),
+            "DistanceMeters" => point.distance = Some(parse_value(child, "DistanceMeters")?),
+            "HeartRateBpm" => {
+                if let Some(value) = child.children().find(|node| node.has_tag_name("Value")) {
+                    point.heart_rate = Some(parse_value(value, "HeartRateBpm")?);
+                }
+            }
+            name @ ("Cadence" | "RunCadence") => point.cadence = Some(parse_value(child, name)?),
+            _ => {}
+        }
+    }
+
+    Ok(point)
+}
+
+/// Write a cardio session as TCX activity with a single lap.
+///
+/// If the cardio session has no track, trackpoints without position are written every
+/// [`TRACKPOINT_INTERVAL`] so that heart rate and cadence are preserved.
+pub fn write_cardio_session(cardio_session: &CardioSession, name: &str) -> String {
+    let end = cardio_session.time.unwrap_or(0);
+    let heart_rate = cardio_session.heart_rate.as_deref().unwrap_or_default();
+    let cadence = cardio_session.cadence.as_deref().unwrap_or_default();
+
+    let samples: Vec<(i32, Option<&Position>)> = match &cardio_session.track {
+        Some(track) if !track.is_empty() => track
+            .iter()
+            .map(|position| (position.time, Some(position)))
+            .collect(),
+        _ if !heart_rate.is_empty() || !cadence.is_empty() => (0..=end)
+            .step_by(TRACKPOINT_INTERVAL as usize)
+            .map(|time| (time, None))
+            .collect(),
+        _ => vec![],
+    };
+    let trackpoints: String = samples
+        .into_iter()
+        .map(|(time, position)| {
+            let position = position
+                .map(|position| {
+                    format!(
+                        "<Position><LatitudeDegrees>{}</LatitudeDegrees><LongitudeDegrees>{}</LongitudeDegrees></Position>\
+                        <AltitudeMeters>{}</AltitudeMeters><DistanceMeters>{}</DistanceMeters>",
+                        position.latitude, position.longitude, position.elevation, position.distance
+                    )
+                })
+                .unwrap_or_default();
+            let heart_rate = rate_at(heart_rate, time, end)
+                .map(|heart_rate| {
+                    format!(
+                        "<HeartRateBpm><Value>{}</Value></HeartRateBpm>",
+                        heart_rate.min(255)
+                    )
+                })
+                .unwrap_or_default();
+            let cadence = rate_at(cadence, time, end)
+                .map(|cadence| format!("<Cadence>{}</Cadence>", cadence.min(254)))
+                .unwrap_or_default();
+            format!(
+                "<Trackpoint><Time>{}</Time>{position}{heart_rate}{cadence}</Trackpoint>\n",
+                absolute_time(cardio_session, time)
+            )
+        })
+        .collect();
+
+    let start = format_time(cardio_session.datetime);
+    let distance = cardio_session
+        .distance
+        .map(|distance| format!("<DistanceMeters>{distance}</DistanceMeters>"))
+        .unwrap_or_default();
+    let avg_heart_rate = cardio_session
+        .avg_heart_rate
+        .map(|avg_heart_rate| {
+            format!("<AverageHeartRateBpm><Value>{avg_heart_rate}</Value></AverageHeartRateBpm>")
+        })
+        .unwrap_or_default();
+    let avg_cadence = cardio_session
+        .avg_cadence
+        .map(|avg_cadence| format!("<Cadence>{}</Cadence>", avg_cadence.min(254)))
+        .unwrap_or_default();
+    let notes = cardio_session.comments.as_deref().map_or_else(
+        || format!("<Notes>{}</Notes>", escape(name)),
+        |comments| format!("<Notes>{}: {}</Notes>", escape(name), escape(comments)),
+    );
+
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<TrainingCenterDatabase xmlns="http://www.garmin.com/xmlschemas/TrainingCenterDatabase/v2">
+<Activities><Activity Sport="Other"><Id>{start}</Id>
+<Lap StartTime="{start}"><TotalTimeSeconds>{time}</TotalTimeSeconds>{distance}<Calories>{calories}</Calories>{avg_heart_rate}<Intensity>Active</Intensity>{avg_cadence}<TriggerMethod>Manual</TriggerMethod>
+<Track>
+{trackpoints}</Track>
+</Lap>
+{notes}
+</Activity></Activities>
+</TrainingCenterDatabase>
+"#,
+        time = f64::from(end) / 1000.,
+        calories = cardio_session.calories.unwrap_or(0),
+    )
+}
@@ -0,0 +1,136 @@
+//! Training load metrics based on the training impulse (TRIMP) by Banister.
+//!
+//! The training impulse of a [`CardioSession`](sport_log_types::CardioSession) weights its
+//! duration with the heart rate reserve of the user.
+//! The daily training impulses are then averaged over an acute and a chronic window.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Days, NaiveDate, Utc};
+use diesel::prelude::*;
+use sport_log_types::{
+    CardioSessionId, TrainingImpulse, TrainingLoad, User, schema::cardio_session,
+};
+
+/// The number of days the acute load is averaged over.
+pub const ACUTE_DAYS: u64 = 7;
+
+/// The number of days the chronic load is averaged over.
+pub const CHRONIC_DAYS: u64 = 42;
+
+/// The maximal number of days of training load that can be requested at once.
+pub const MAX_DAYS: u64 = 3660;
+
+/// The fields of a cardio session that are needed to compute its training impulse.
+#[derive(Queryable, Selectable, Debug)]
+#[diesel(table_name = cardio_session)]
+pub struct CardioSessionHeartRate {
+    pub id: CardioSessionId,
+    pub datetime: DateTime<Utc>,
+    pub time: Option<i32>,
+    pub avg_heart_rate: Option<i32>,
+    pub heart_rate: Option<Vec<i32>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct HeartRateProfile {
+    max_heart_rate: f64,
+    resting_heart_rate: f64,
+}
+
+impl HeartRateProfile {
+    /// Returns `None` if the user has not set a maximal and resting heart rate.
+    pub fn of(user: &User) -> Option<Self> {
+        Some(Self {
+            max_heart_rate: f64::from(user.max_heart_rate?),
+            resting_heart_rate: f64::from(user.resting_heart_rate?),
+        })
+    }
+
+    /// The training impulse of `minutes` at `heart_rate` beats per minute.
+    ///
+    /// Uses the weighting factors of Banister (0.64 and 1.92).
+    fn impulse(self, minutes: f64, heart_rate: f64) -> f64 {
+        let reserve = ((heart_rate - self.resting_heart_rate)
+            / (self.max_heart_rate - self.resting_heart_rate))
+            .clamp(0., 1.);
+        minutes * reserve * 0.64 * (1.92 * reserve).exp()
+    }
+
+    /// The training impulse of a cardio session.
+    ///
+    /// The heart rate series (the time of every beat) is used if present, otherwise the average
+    /// heart rate over the whole session.
+    /// Returns `None` if the session has no heart rate data.
+    pub fn trimp(self, session: &CardioSessionHeartRate) -> Option<f64> {
+        match (&session.heart_rate, session.avg_heart_rate, session.time) {
+            (Some(beats), _, _) if beats.len() >= 2 => Some(
+                beats
+                    .windows(2)
+                    .map(|beats| f64::from(beats[1] - beats[0]))
+                    .filter(|interval| *interval > 0.)
+                    .map(|interval| self.impulse(interval / 60_000., 60_000. / interval))
+                    .sum(),
+            ),
+            (_, Some(avg_heart_rate), Some(time)) => {
+                Some(self.impulse(f64::from(time) / 60_000., f64::from(avg_heart_rate)))
+            }
+            _ => None,
+        }
+    }
+
+    /// The training impulses of all sessions with heart rate data.
+    pub fn training_impulses(self, sessions: &[CardioSessionHeartRate]) -> Vec<TrainingImpulse> {
+        sessions
+            .iter()
+            .filter_map(|session| {
+                self.trimp(session).map(|trimp| TrainingImpulse {
+                    cardio_session_id: session.id,
+                    datetime: session.datetime,
+                    trimp,
+                })
+            })
+            .collect()
+    }
+}
+
+/// The daily training load from `first` to `last` (inclusive).
+///
+/// `impulses` have to include the sessions of the [`CHRONIC_DAYS`] before `first`.
+pub fn training_loads(
+    impulses: &[TrainingImpulse],
+    first: NaiveDate,
+    last: NaiveDate,
+) -> Vec<TrainingLoad> {
+    let mut daily_loads: BTreeMap<NaiveDate, f64> = BTreeMap::new();
+    for impulse in impulses {
+        *daily_loads
+            .entry(impulse.datetime.date_naive())
+            .or_default() += impulse.trimp;
+    }
+    let average = |date: NaiveDate, days: u64| {
+        let start = date - Days::new(days - 1);
+        daily_loads
+            .range(start..=date)
+            .map(|(_, load)| load)
+            .sum::<f64>()
+            / days as f64
+    };
+
+    first
+        .iter_days()
+        .take_while(|date| *date <= last)
+        .map(|date| {
+            let acute = average(date, ACUTE_DAYS);
+            let chronic = average(date, CHRONIC_DAYS);
+            TrainingLoad {
+                date,
+                load: daily_loads.get(&date).copied().unwrap_or_default(),
+                acute,
+                chronic,
+                ratio: (chronic > 0.).then(|| acute / chronic),
+                form: chronic - acute,
+            }
+        })
+        .collect()
+}
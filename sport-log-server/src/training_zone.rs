@@ -0,0 +1,149 @@
+//! Time in heart rate and pace zones of cardio sessions.
+//!
+//! The zones are defined by the [`TrainingZone`]s of the user.
+//! A zone definition for the movement of a session takes precedence over one without a movement.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use sport_log_types::{
+    CardioSessionId, CardioSessionZones, MovementId, Position, StatisticInterval, TrainingZone,
+    TrainingZoneType, ZoneStatistic, ZoneTime, schema::cardio_session,
+};
+
+/// Intervals between two beats that are longer than this (in milliseconds) are treated as gaps
+/// in the recording.
+const MAX_BEAT_INTERVAL: i32 = 5000;
+
+/// The fields of a cardio session that are needed to compute its time in zones.
+#[derive(Queryable, Selectable, Debug)]
+#[diesel(table_name = cardio_session)]
+pub struct CardioSessionZoneData {
+    pub id: CardioSessionId,
+    pub movement_id: MovementId,
+    pub datetime: DateTime<Utc>,
+    pub track: Option<Vec<Position>>,
+    pub heart_rate: Option<Vec<i32>>,
+}
+
+/// The zone definitions of a user which have not been deleted.
+#[derive(Debug)]
+pub struct TrainingZones(pub Vec<TrainingZone>);
+
+impl TrainingZones {
+    fn zone(&self, zone_type: TrainingZoneType, movement_id: MovementId) -> Option<&TrainingZone> {
+        let zones = || self.0.iter().filter(|zone| zone.zone_type == zone_type);
+        zones()
+            .find(|zone| zone.movement_id == Some(movement_id))
+            .or_else(|| zones().find(|zone| zone.movement_id.is_none()))
+    }
+
+    /// The time in zones of a single cardio session.
+    pub fn session_zones(&self, session: &CardioSessionZoneData) -> CardioSessionZones {
+        let zone_time =
+            |zone_type, time: fn(&[i32], &CardioSessionZoneData) -> Option<Vec<i64>>| {
+                self.zone(zone_type, session.movement_id).and_then(|zone| {
+                    time(&zone.thresholds, session).map(|time| ZoneTime {
+                        training_zone_id: zone.id,
+                        time,
+                    })
+                })
+            };
+        CardioSessionZones {
+            cardio_session_id: session.id,
+            datetime: session.datetime,
+            heart_rate: zone_time(TrainingZoneType::HeartRate, |thresholds, session| {
+                heart_rate_time(thresholds, session.heart_rate.as_deref()?)
+            }),
+            pace: zone_time(TrainingZoneType::Pace, |thresholds, session| {
+                pace_time(thresholds, session.track.as_deref()?)
+            }),
+        }
+    }
+
+    /// The time in zones of the sessions aggregated per zone definition and time bucket.
+    pub fn statistics(
+        &self,
+        sessions: &[CardioSessionZoneData],
+        interval: StatisticInterval,
+    ) -> Vec<ZoneStatistic> {
+        let mut statistics: BTreeMap<(DateTime<Utc>, i64), ZoneStatistic> = BTreeMap::new();
+        for session in sessions {
+            let zones = self.session_zones(session);
+            let bucket = interval.bucket(session.datetime);
+            for (zone_type, zone_time) in [
+                (TrainingZoneType::HeartRate, zones.heart_rate),
+                (TrainingZoneType::Pace, zones.pace),
+            ] {
+                let Some(zone_time) = zone_time else {
+                    continue;
+                };
+                let statistic = statistics
+                    .entry((bucket, zone_time.training_zone_id.0))
+                    .or_insert_with(|| ZoneStatistic {
+                        training_zone_id: zone_time.training_zone_id,
+                        zone_type,
+                        bucket,
+                        session_count: 0,
+                        time: vec![0; zone_time.time.len()],
+                    });
+                statistic.session_count += 1;
+                for (total, time) in statistic.time.iter_mut().zip(zone_time.time) {
+                    *total += time;
+                }
+            }
+        }
+        statistics.into_values().collect()
+    }
+}
+
+/// The time spent in every heart rate zone.
+///
+/// `beats` contains the time of every beat in milliseconds since the start of the session.
+/// Returns `None` if there are less than two beats.
+fn heart_rate_time(thresholds: &[i32], beats: &[i32]) -> Option<Vec<i64>> {
+    if beats.len() < 2 {
+        return None;
+    }
+    let mut time = vec![0; thresholds.len() + 1];
+    for beats in beats.windows(2) {
+        let interval = beats[1] - beats[0];
+        if interval <= 0 || interval > MAX_BEAT_INTERVAL {
+            continue;
+        }
+        let heart_rate = 60_000. / f64::from(interval);
+        let zone = thresholds
+            .iter()
+            .filter(|threshold| heart_rate >= f64::from(**threshold))
+            .count();
+        time[zone] += i64::from(interval);
+    }
+    Some(time)
+}
+
+/// The time spent in every pace zone.
+///
+/// Segments of the track without progress in distance are ignored.
+/// Returns `None` if the track has less than two positions.
+fn pace_time(thresholds: &[i32], track: &[Position]) -> Option<Vec<i64>> {
+    if track.len() < 2 {
+        return None;
+    }
+    let mut time = vec![0; thresholds.len() + 1];
+    for positions in track.windows(2) {
+        let interval = positions[1].time - positions[0].time;
+        let distance = positions[1].distance - positions[0].distance;
+        if interval <= 0 || distance <= 0. {
+            continue;
+        }
+        // milliseconds per meter are seconds per kilometer
+        let pace = f64::from(interval) / distance;
+        let zone = thresholds
+            .iter()
+            .filter(|threshold| pace <= f64::from(**threshold))
+            .count();
+        time[zone] += i64::from(interval);
+    }
+    Some(time)
+}
@@ -21,6 +21,18 @@ pub mod sql_types {
     #[diesel(postgres_type(name = "position"))]
     pub struct Position;
 
+    #[derive(diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "scope_access"))]
+    pub struct ScopeAccess;
+
+    #[derive(diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "scope_entity"))]
+    pub struct ScopeEntity;
+
+    #[derive(diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "training_zone_type"))]
+    pub struct TrainingZoneType;
+
     #[derive(diesel::sql_types::SqlType)]
     #[diesel(postgres_type(name = "weekday"))]
     pub struct Weekday;
@@ -39,6 +51,8 @@ diesel::table! {
         delete_after -> Int4,
         epoch -> Int8,
         deleted -> Bool,
+        archived_at -> Nullable<Timestamptz>,
+        archived_by -> Nullable<Int8>,
     }
 }
 
@@ -54,6 +68,8 @@ diesel::table! {
         enabled -> Bool,
         epoch -> Int8,
         deleted -> Bool,
+        archived_at -> Nullable<Timestamptz>,
+        archived_by -> Nullable<Int8>,
     }
 }
 
@@ -70,6 +86,25 @@ diesel::table! {
         description -> Nullable<Text>,
         epoch -> Int8,
         deleted -> Bool,
+        archived_at -> Nullable<Timestamptz>,
+        archived_by -> Nullable<Int8>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::ScopeEntity;
+    use super::sql_types::ScopeAccess;
+
+    action_provider_scope (id) {
+        id -> Int8,
+        action_provider_id -> Int8,
+        entity -> ScopeEntity,
+        access -> ScopeAccess,
+        epoch -> Int8,
+        deleted -> Bool,
+        archived_at -> Nullable<Timestamptz>,
+        archived_by -> Nullable<Int8>,
     }
 }
 
@@ -87,6 +122,21 @@ diesel::table! {
         enabled -> Bool,
         epoch -> Int8,
         deleted -> Bool,
+        archived_at -> Nullable<Timestamptz>,
+        archived_by -> Nullable<Int8>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+
+    auth_session (id) {
+        id -> Int8,
+        user_id -> Int8,
+        access_token_hash -> Bytea,
+        access_token_expires -> Timestamptz,
+        refresh_token_hash -> Bytea,
+        refresh_token_expires -> Timestamptz,
     }
 }
 
@@ -115,6 +165,19 @@ diesel::table! {
         comments -> Nullable<Text>,
         epoch -> Int8,
         deleted -> Bool,
+        archived_at -> Nullable<Timestamptz>,
+        archived_by -> Nullable<Int8>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::Position;
+
+    cardio_session_overview (cardio_session_id) {
+        cardio_session_id -> Int8,
+        epoch -> Int8,
+        track -> Array<Position>,
     }
 }
 
@@ -129,6 +192,8 @@ diesel::table! {
         comments -> Nullable<Text>,
         epoch -> Int8,
         deleted -> Bool,
+        archived_at -> Nullable<Timestamptz>,
+        archived_by -> Nullable<Int8>,
     }
 }
 
@@ -157,6 +222,8 @@ diesel::table! {
         description -> Nullable<Text>,
         epoch -> Int8,
         deleted -> Bool,
+        archived_at -> Nullable<Timestamptz>,
+        archived_by -> Nullable<Int8>,
     }
 }
 
@@ -176,6 +243,8 @@ diesel::table! {
         female_weight -> Nullable<Float4>,
         epoch -> Int8,
         deleted -> Bool,
+        archived_at -> Nullable<Timestamptz>,
+        archived_by -> Nullable<Int8>,
     }
 }
 
@@ -194,6 +263,8 @@ diesel::table! {
         comments -> Nullable<Text>,
         epoch -> Int8,
         deleted -> Bool,
+        archived_at -> Nullable<Timestamptz>,
+        archived_by -> Nullable<Int8>,
     }
 }
 
@@ -211,6 +282,8 @@ diesel::table! {
         cardio -> Bool,
         epoch -> Int8,
         deleted -> Bool,
+        archived_at -> Nullable<Timestamptz>,
+        archived_by -> Nullable<Int8>,
     }
 }
 
@@ -224,6 +297,8 @@ diesel::table! {
         credential -> Bool,
         epoch -> Int8,
         deleted -> Bool,
+        archived_at -> Nullable<Timestamptz>,
+        archived_by -> Nullable<Int8>,
     }
 }
 
@@ -236,10 +311,12 @@ diesel::table! {
         platform_id -> Int8,
         #[max_length = 80]
         username -> Varchar,
-        #[max_length = 80]
-        password -> Varchar,
+        password -> Text,
         epoch -> Int8,
         deleted -> Bool,
+        password_encrypted -> Bool,
+        archived_at -> Nullable<Timestamptz>,
+        archived_by -> Nullable<Int8>,
     }
 }
 
@@ -259,6 +336,64 @@ diesel::table! {
         marked_positions -> Nullable<Array<Position>>,
         epoch -> Int8,
         deleted -> Bool,
+        archived_at -> Nullable<Timestamptz>,
+        archived_by -> Nullable<Int8>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::Position;
+
+    route_overview (route_id) {
+        route_id -> Int8,
+        epoch -> Int8,
+        track -> Array<Position>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+
+    scope_grant (id) {
+        id -> Int8,
+        user_id -> Int8,
+        action_provider_scope_id -> Int8,
+        epoch -> Int8,
+        deleted -> Bool,
+        archived_at -> Nullable<Timestamptz>,
+        archived_by -> Nullable<Int8>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::Position;
+
+    segment (id) {
+        id -> Int8,
+        user_id -> Int8,
+        #[max_length = 80]
+        name -> Varchar,
+        track -> Array<Position>,
+        epoch -> Int8,
+        deleted -> Bool,
+        archived_at -> Nullable<Timestamptz>,
+        archived_by -> Nullable<Int8>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+
+    segment_effort (segment_id, cardio_session_id, start) {
+        user_id -> Int8,
+        segment_id -> Int8,
+        cardio_session_id -> Int8,
+        start -> Int4,
+        time -> Int4,
+        avg_heart_rate -> Nullable<Int4>,
+        datetime -> Timestamptz,
     }
 }
 
@@ -274,6 +409,8 @@ diesel::table! {
         comments -> Nullable<Text>,
         epoch -> Int8,
         deleted -> Bool,
+        archived_at -> Nullable<Timestamptz>,
+        archived_by -> Nullable<Int8>,
     }
 }
 
@@ -289,6 +426,25 @@ diesel::table! {
         weight -> Nullable<Float4>,
         epoch -> Int8,
         deleted -> Bool,
+        archived_at -> Nullable<Timestamptz>,
+        archived_by -> Nullable<Int8>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::TrainingZoneType;
+
+    training_zone (id) {
+        id -> Int8,
+        user_id -> Int8,
+        zone_type -> TrainingZoneType,
+        movement_id -> Nullable<Int8>,
+        thresholds -> Array<Int4>,
+        epoch -> Int8,
+        deleted -> Bool,
+        archived_at -> Nullable<Timestamptz>,
+        archived_by -> Nullable<Int8>,
     }
 }
 
@@ -304,6 +460,8 @@ diesel::table! {
         #[max_length = 80]
         email -> Varchar,
         epoch -> Int8,
+        max_heart_rate -> Nullable<Int4>,
+        resting_heart_rate -> Nullable<Int4>,
     }
 }
 
@@ -317,6 +475,8 @@ diesel::table! {
         description -> Nullable<Text>,
         epoch -> Int8,
         deleted -> Bool,
+        archived_at -> Nullable<Timestamptz>,
+        archived_by -> Nullable<Int8>,
     }
 }
 
@@ -324,11 +484,14 @@ diesel::joinable!(action -> action_provider (action_provider_id));
 diesel::joinable!(action_event -> action (action_id));
 diesel::joinable!(action_event -> user (user_id));
 diesel::joinable!(action_provider -> platform (platform_id));
+diesel::joinable!(action_provider_scope -> action_provider (action_provider_id));
 diesel::joinable!(action_rule -> action (action_id));
 diesel::joinable!(action_rule -> user (user_id));
+diesel::joinable!(auth_session -> user (user_id));
 diesel::joinable!(cardio_session -> movement (movement_id));
 diesel::joinable!(cardio_session -> route (route_id));
 diesel::joinable!(cardio_session -> user (user_id));
+diesel::joinable!(cardio_session_overview -> cardio_session (cardio_session_id));
 diesel::joinable!(diary -> user (user_id));
 diesel::joinable!(metcon -> user (user_id));
 diesel::joinable!(metcon_movement -> metcon (metcon_id));
@@ -340,18 +503,28 @@ diesel::joinable!(movement -> user (user_id));
 diesel::joinable!(platform_credential -> platform (platform_id));
 diesel::joinable!(platform_credential -> user (user_id));
 diesel::joinable!(route -> user (user_id));
+diesel::joinable!(route_overview -> route (route_id));
+diesel::joinable!(scope_grant -> action_provider_scope (action_provider_scope_id));
+diesel::joinable!(scope_grant -> user (user_id));
+diesel::joinable!(segment -> user (user_id));
+diesel::joinable!(segment_effort -> user (user_id));
 diesel::joinable!(strength_session -> movement (movement_id));
 diesel::joinable!(strength_session -> user (user_id));
 diesel::joinable!(strength_set -> strength_session (strength_session_id));
 diesel::joinable!(strength_set -> user (user_id));
+diesel::joinable!(training_zone -> movement (movement_id));
+diesel::joinable!(training_zone -> user (user_id));
 diesel::joinable!(wod -> user (user_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
     action,
     action_event,
     action_provider,
+    action_provider_scope,
     action_rule,
+    auth_session,
     cardio_session,
+    cardio_session_overview,
     diary,
     eorm,
     metcon,
@@ -361,8 +534,13 @@ diesel::allow_tables_to_appear_in_same_query!(
     platform,
     platform_credential,
     route,
+    route_overview,
+    scope_grant,
+    segment,
+    segment_effort,
     strength_session,
     strength_set,
+    training_zone,
     user,
     wod,
 );
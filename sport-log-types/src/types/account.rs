@@ -1,8 +1,11 @@
+use std::{collections::BTreeMap, fmt, str::FromStr};
+
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
 use crate::*;
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct EpochMap {
     pub user: Epoch,
     pub diary: Epoch,
@@ -15,18 +18,69 @@ pub struct EpochMap {
     pub metcon_movement: Epoch,
     pub cardio_session: Epoch,
     pub route: Epoch,
+    #[serde(default)]
+    pub training_zone: Epoch,
+    #[serde(default)]
+    pub segment: Epoch,
     pub platform: Epoch,
     pub platform_credential: Epoch,
     pub action_provider: Epoch,
     pub action: Epoch,
     pub action_rule: Epoch,
     pub action_event: Epoch,
+    #[serde(default)]
+    pub action_provider_scope: Epoch,
+    #[serde(default)]
+    pub scope_grant: Epoch,
+}
+
+impl EpochMap {
+    /// The epoch of the table with the name `table`.
+    pub fn get(&self, table: &str) -> Option<Epoch> {
+        let epoch = match table {
+            "user" => self.user,
+            "diary" => self.diary,
+            "wod" => self.wod,
+            "movement" => self.movement,
+            "strength_session" => self.strength_session,
+            "strength_set" => self.strength_set,
+            "metcon" => self.metcon,
+            "metcon_session" => self.metcon_session,
+            "metcon_movement" => self.metcon_movement,
+            "cardio_session" => self.cardio_session,
+            "route" => self.route,
+            "training_zone" => self.training_zone,
+            "segment" => self.segment,
+            "platform" => self.platform,
+            "platform_credential" => self.platform_credential,
+            "action_provider" => self.action_provider,
+            "action" => self.action,
+            "action_rule" => self.action_rule,
+            "action_event" => self.action_event,
+            "action_provider_scope" => self.action_provider_scope,
+            "scope_grant" => self.scope_grant,
+            _ => return None,
+        };
+        Some(epoch)
+    }
+}
+
+/// The new epochs of the tables which have changed.
+///
+/// The keys are the names of the fields of [`EpochMap`]. Tables which have not changed are omitted.
+pub type EpochMapDelta = BTreeMap<String, Epoch>;
+
+/// A deleted entry that has been moved to the archive from which it can be restored.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Archived<T> {
+    pub archived_at: DateTime<Utc>,
+    pub entry: T,
 }
 
 /// A representation of all or recently updated data belonging to a user account.
 ///
 /// This struct is used for the `account_data` endpoints.
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct AccountData {
     pub user: Option<User>,
     pub diaries: Vec<Diary>,
@@ -38,12 +92,141 @@ pub struct AccountData {
     pub metcon_sessions: Vec<MetconSession>,
     pub metcon_movements: Vec<MetconMovement>,
     pub cardio_sessions: Vec<CardioSession>,
+    /// The cardio sessions without their series if tracks are omitted.
+    #[serde(default)]
+    pub cardio_session_summaries: Vec<CardioSessionSummary>,
     pub routes: Vec<Route>,
+    /// The routes without their track if tracks are omitted.
+    #[serde(default)]
+    pub route_summaries: Vec<RouteSummary>,
+    pub training_zones: Vec<TrainingZone>,
+    pub segments: Vec<Segment>,
     pub platforms: Vec<Platform>,
     pub platform_credentials: Vec<PlatformCredential>,
     pub action_providers: Vec<ActionProvider>,
     pub actions: Vec<Action>,
     pub action_rules: Vec<ActionRule>,
     pub action_events: Vec<ActionEvent>,
+    pub action_provider_scopes: Vec<ActionProviderScope>,
+    pub scope_grants: Vec<ScopeGrant>,
     pub epoch_map: EpochMap,
 }
+
+/// New or updated entries of multiple tables belonging to a user account.
+///
+/// This struct is used for the `POST` and `PUT` `account_data` endpoints which create or update
+/// all entries at once. Tables that are omitted contain no entries.
+///
+/// The [`User`] itself can only be changed using the `user` endpoints.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct AccountDataChanges {
+    pub diaries: Vec<Diary>,
+    pub wods: Vec<Wod>,
+    pub movements: Vec<Movement>,
+    pub strength_sessions: Vec<StrengthSession>,
+    pub strength_sets: Vec<StrengthSet>,
+    pub metcons: Vec<Metcon>,
+    pub metcon_sessions: Vec<MetconSession>,
+    pub metcon_movements: Vec<MetconMovement>,
+    pub cardio_sessions: Vec<CardioSession>,
+    pub routes: Vec<Route>,
+    pub training_zones: Vec<TrainingZone>,
+    pub segments: Vec<Segment>,
+    pub platform_credentials: Vec<PlatformCredential>,
+    pub action_rules: Vec<ActionRule>,
+    pub action_events: Vec<ActionEvent>,
+    pub scope_grants: Vec<ScopeGrant>,
+}
+
+/// New and updated entries of multiple tables belonging to a user account.
+///
+/// This struct is used for the `account_data_sync` endpoint which creates and updates all entries
+/// at once. The new entries are created before the updated entries are updated.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct AccountDataSync {
+    pub created: AccountDataChanges,
+    pub updated: AccountDataChanges,
+}
+
+/// Response of the endpoints that change the entries of multiple tables at once.
+///
+/// If `conflicts` is not empty, none of the changes have been applied.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AccountDataChangesResponse {
+    pub epoch_map: EpochMap,
+    pub conflicts: Vec<TableConflict>,
+}
+
+/// A conflict with the existing data in a single table of a request that affects multiple tables.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TableConflict {
+    pub table: String,
+    pub status: u16,
+    pub message: Option<ErrorMessage>,
+}
+
+/// A page of all or recently updated data belonging to a user account.
+///
+/// This struct is used for the `account_data_page` endpoint.
+/// The tables are paged in the order of their dependencies, so every page can be stored on its
+/// own.
+///
+/// The [`EpochMap`] of the first page has to be stored once the last page has been received.
+/// Entries of already paged tables that are changed in the meantime are then retrieved during the
+/// next synchronization.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AccountDataPage {
+    pub account_data: AccountData,
+    /// The cursor of the next page or `None` if this is the last page.
+    pub next: Option<AccountDataCursor>,
+}
+
+/// The position after the last entry of an [`AccountDataPage`].
+///
+/// The cursor is serialized as an opaque string.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(try_from = "String", into = "String")]
+pub struct AccountDataCursor {
+    /// The index of the table in the paging order.
+    pub table: u8,
+    pub epoch: Epoch,
+    pub id: i64,
+}
+
+impl fmt::Display for AccountDataCursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.table, self.epoch.0, self.id)
+    }
+}
+
+impl FromStr for AccountDataCursor {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        const ERROR: &str = "invalid account data cursor";
+
+        let mut parts = s.splitn(3, '.');
+        let mut next = || parts.next().ok_or(ERROR);
+        Ok(Self {
+            table: next()?.parse().map_err(|_| ERROR)?,
+            epoch: Epoch(next()?.parse().map_err(|_| ERROR)?),
+            id: next()?.parse().map_err(|_| ERROR)?,
+        })
+    }
+}
+
+impl TryFrom<String> for AccountDataCursor {
+    type Error = &'static str;
+
+    fn try_from(cursor: String) -> Result<Self, Self::Error> {
+        cursor.parse()
+    }
+}
+
+impl From<AccountDataCursor> for String {
+    fn from(cursor: AccountDataCursor) -> Self {
+        cursor.to_string()
+    }
+}
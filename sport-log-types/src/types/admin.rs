@@ -1 +1,10 @@
+use serde::{Deserialize, Serialize};
+
 pub const ADMIN_USERNAME: &str = "admin";
+
+/// The number of archived entries of a table that have been deleted permanently.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PurgedArchive {
+    pub table: String,
+    pub count: i64,
+}
@@ -0,0 +1,19 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Tokens issued on login or refresh.
+///
+/// The `access_token` has to be sent as HTTP bearer auth and is only valid for a short time.
+/// Once it is expired a new pair of tokens can be requested using the `refresh_token`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AuthTokens {
+    pub access_token: String,
+    pub access_token_expires: DateTime<Utc>,
+    pub refresh_token: String,
+    pub refresh_token_expires: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RefreshToken {
+    pub refresh_token: String,
+}
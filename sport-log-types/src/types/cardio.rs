@@ -17,11 +17,11 @@ use serde::{Deserialize, Serialize};
 #[cfg(feature = "db")]
 use crate::{
     Movement, User,
-    schema::{cardio_session, route},
+    schema::{cardio_session, route, segment, segment_effort},
 };
 use crate::{MovementId, UserId, types::IdString};
 
-#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[cfg_attr(
     feature = "db",
     derive(DbEnum),
@@ -133,6 +133,71 @@ pub struct Route {
     pub deleted: bool,
 }
 
+/// A [`Route`] without `track` and `marked_positions`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(
+    feature = "db",
+    derive(Queryable, Selectable),
+    diesel(table_name = route)
+)]
+pub struct RouteSummary {
+    pub id: RouteId,
+    pub user_id: UserId,
+    pub name: String,
+    pub distance: Option<i32>,
+    pub ascent: Option<i32>,
+    pub descent: Option<i32>,
+    pub deleted: bool,
+}
+
+/// The `track` and `marked_positions` of a [`Route`].
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(
+    feature = "db",
+    derive(Queryable, Selectable),
+    diesel(table_name = route)
+)]
+pub struct RouteTrack {
+    pub id: RouteId,
+    pub track: Option<Vec<Position>>,
+    pub marked_positions: Option<Vec<Position>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Deftly)]
+#[derive_deftly(IdString)]
+#[serde(try_from = "IdString", into = "IdString")]
+#[cfg_attr(
+    feature = "db",
+    derive(Hash, FromSqlRow, AsExpression),
+    derive_deftly(IntoPgBigInt, FromPgBigInt),
+    diesel(sql_type = BigInt)
+)]
+pub struct SegmentId(pub i64);
+
+/// A part of a track whose traversals are detected in the tracks of all [`CardioSession`]s.
+///
+/// The first and the last position of `track` are the start and the end of the segment.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(
+    feature = "db",
+    derive(
+        Insertable,
+        Associations,
+        Identifiable,
+        Queryable,
+        Selectable,
+        AsChangeset,
+    ),
+    diesel(table_name = segment, belongs_to(User))
+)]
+pub struct Segment {
+    pub id: SegmentId,
+    pub user_id: UserId,
+    pub name: String,
+    pub track: Vec<Position>,
+    pub deleted: bool,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Deftly)]
 #[derive_deftly(IdString)]
 #[serde(try_from = "IdString", into = "IdString")]
@@ -189,3 +254,138 @@ pub struct CardioSession {
     pub comments: Option<String>,
     pub deleted: bool,
 }
+
+/// A [`CardioSession`] without the `track`, `cadence` and `heart_rate` series.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(
+    feature = "db",
+    derive(Queryable, Selectable),
+    diesel(table_name = cardio_session)
+)]
+pub struct CardioSessionSummary {
+    pub id: CardioSessionId,
+    pub user_id: UserId,
+    pub movement_id: MovementId,
+    pub cardio_type: CardioType,
+    pub datetime: DateTime<Utc>,
+    pub distance: Option<i32>,
+    pub ascent: Option<i32>,
+    pub descent: Option<i32>,
+    pub time: Option<i32>,
+    pub calories: Option<i32>,
+    pub avg_cadence: Option<i32>,
+    pub avg_heart_rate: Option<i32>,
+    pub route_id: Option<RouteId>,
+    pub comments: Option<String>,
+    pub deleted: bool,
+}
+
+/// The `track`, `cadence` and `heart_rate` series of a [`CardioSession`].
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(
+    feature = "db",
+    derive(Queryable, Selectable),
+    diesel(table_name = cardio_session)
+)]
+pub struct CardioSessionTrack {
+    pub id: CardioSessionId,
+    pub track: Option<Vec<Position>>,
+    pub cadence: Option<Vec<i32>>,
+    pub heart_rate: Option<Vec<i32>>,
+}
+
+/// A field of a [`CardioSession`] that can be computed from its `track`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CardioSummaryField {
+    Distance,
+    Ascent,
+    Descent,
+    Time,
+}
+
+/// A field of a [`CardioSession`] which disagrees with the value computed from its `track`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CardioSummaryDiscrepancy {
+    pub field: CardioSummaryField,
+    pub stored: Option<i32>,
+    pub computed: i32,
+}
+
+/// The result of checking a [`CardioSession`] against its `track`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CardioSessionValidation {
+    pub cardio_session_id: CardioSessionId,
+    /// The reason why the track is physically impossible or `None` if it is valid.
+    pub invalid_track: Option<String>,
+    /// The discrepancies between the stored and the computed fields.
+    ///
+    /// Empty if the track is invalid.
+    pub discrepancies: Vec<CardioSummaryDiscrepancy>,
+}
+
+/// A [`Route`] whose `track` matches the `track` of a [`CardioSession`].
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RouteMatch {
+    pub cardio_session_id: CardioSessionId,
+    pub route_id: RouteId,
+    /// The discrete Fréchet distance between both tracks in meters.
+    pub distance: f64,
+}
+
+/// A [`CardioSession`] on a [`Route`].
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RouteHistoryEntry {
+    pub cardio_session_id: CardioSessionId,
+    pub datetime: DateTime<Utc>,
+    pub time: Option<i32>,
+}
+
+/// The cardio sessions on a [`Route`] ordered by `datetime`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RouteHistory {
+    pub route_id: RouteId,
+    pub sessions: Vec<RouteHistoryEntry>,
+    /// The session with the shortest time or `None` if no session has a time.
+    pub best: Option<RouteHistoryEntry>,
+    /// The change of the time in milliseconds per day fitted by linear regression.
+    ///
+    /// Negative values mean that the route is getting faster.
+    /// `None` if there are less than two sessions with a time at different datetimes.
+    pub trend: Option<f64>,
+}
+
+/// The simplified `track` of a [`CardioSession`] for overviews like map thumbnails.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CardioSessionOverview {
+    pub cardio_session_id: CardioSessionId,
+    pub track: Vec<Position>,
+}
+
+/// The simplified `track` of a [`Route`] for overviews like map thumbnails.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RouteOverview {
+    pub route_id: RouteId,
+    pub track: Vec<Position>,
+}
+
+/// A traversal of a [`Segment`] within the `track` of a [`CardioSession`].
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(
+    feature = "db",
+    derive(Insertable, Queryable, Selectable),
+    diesel(table_name = segment_effort)
+)]
+pub struct SegmentEffort {
+    pub user_id: UserId,
+    pub segment_id: SegmentId,
+    pub cardio_session_id: CardioSessionId,
+    /// The time in milliseconds since the start of the session at which the effort starts.
+    pub start: i32,
+    /// The time of the effort in milliseconds.
+    pub time: i32,
+    /// The average heart rate or `None` if the session has no heart rate series.
+    pub avg_heart_rate: Option<i32>,
+    /// The start of the effort.
+    pub datetime: DateTime<Utc>,
+}
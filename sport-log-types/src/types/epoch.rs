@@ -5,7 +5,9 @@ use serde::{Deserialize, Serialize};
 
 use crate::types::IdString;
 
-#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deftly)]
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Deftly,
+)]
 #[derive_deftly(IdString)]
 #[serde(try_from = "IdString", into = "IdString")]
 #[cfg_attr(
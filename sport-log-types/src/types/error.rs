@@ -0,0 +1,35 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{Epoch, ScopeAccess, ScopeEntity};
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorMessage {
+    PrimaryKeyViolation {
+        table: String,
+    },
+    ForeignKeyViolation {
+        table: String,
+        column: String,
+    },
+    UniqueViolation {
+        table: String,
+        columns: Vec<String>,
+    },
+    /// The entry has been changed since the epoch the client has last seen.
+    EpochConflict {
+        epoch: Epoch,
+    },
+    MissingScope {
+        entity: ScopeEntity,
+        access: ScopeAccess,
+    },
+    /// Deleted entries of the table have been purged from the archive since the epoch the client
+    /// has last seen, so the client has to perform an init sync.
+    InitSyncRequired {
+        table: String,
+    },
+    Other {
+        error: String,
+    },
+}
@@ -39,6 +39,19 @@ pub enum DistanceUnit {
     Mile,
 }
 
+impl DistanceUnit {
+    /// The length of the unit in meters.
+    pub fn meters(self) -> f64 {
+        match self {
+            Self::Meter => 1.,
+            Self::Km => 1000.,
+            Self::Yard => 0.9144,
+            Self::Foot => 0.3048,
+            Self::Mile => 1609.344,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deftly)]
 #[derive_deftly(IdString)]
 #[serde(try_from = "IdString", into = "IdString")]
@@ -4,13 +4,18 @@ use serde::{Deserialize, Serialize};
 mod account;
 mod action;
 mod admin;
+mod auth;
 mod cardio;
 mod diary_wod;
 mod epoch;
+mod error;
 mod metcon;
 mod movement;
 mod platform;
+mod scope;
+mod statistic;
 mod strength;
+mod training_zone;
 pub mod uri;
 mod user;
 mod version;
@@ -18,13 +23,18 @@ mod version;
 pub use account::*;
 pub use action::*;
 pub use admin::*;
+pub use auth::*;
 pub use cardio::*;
 pub use diary_wod::*;
 pub use epoch::*;
+pub use error::*;
 pub use metcon::*;
 pub use movement::*;
 pub use platform::*;
+pub use scope::*;
+pub use statistic::*;
 pub use strength::*;
+pub use training_zone::*;
 pub use user::*;
 pub use version::*;
 
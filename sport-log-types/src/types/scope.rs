@@ -0,0 +1,118 @@
+use derive_deftly::Deftly;
+#[cfg(feature = "db")]
+use diesel::{deserialize::FromSqlRow, expression::AsExpression, prelude::*, sql_types::BigInt};
+#[cfg(feature = "db")]
+use diesel_derive_enum::DbEnum;
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "db")]
+use crate::{
+    ActionProvider, User,
+    schema::{action_provider_scope, scope_grant},
+};
+use crate::{ActionProviderId, UserId, types::IdString};
+
+/// The entity types an [`ActionProvider`](crate::ActionProvider) can request access to.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "db",
+    derive(DbEnum),
+    ExistingTypePath = "crate::schema::sql_types::ScopeEntity"
+)]
+pub enum ScopeEntity {
+    Diary,
+    Wod,
+    Movement,
+    StrengthSession,
+    StrengthSet,
+    Metcon,
+    MetconSession,
+    MetconMovement,
+    CardioSession,
+    Route,
+    TrainingZone,
+    Segment,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "db",
+    derive(DbEnum),
+    ExistingTypePath = "crate::schema::sql_types::ScopeAccess"
+)]
+pub enum ScopeAccess {
+    Read,
+    Write,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Deftly)]
+#[derive_deftly(IdString)]
+#[serde(try_from = "IdString", into = "IdString")]
+#[cfg_attr(
+    feature = "db",
+    derive(Hash, FromSqlRow, AsExpression),
+    derive_deftly(IntoPgBigInt, FromPgBigInt),
+    diesel(sql_type = BigInt)
+)]
+pub struct ActionProviderScopeId(pub i64);
+
+/// A scope an [`ActionProvider`](crate::ActionProvider) requires to act on behalf of a user.
+///
+/// The scope has no effect until the user creates a [`ScopeGrant`] for it.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(
+    feature = "db",
+    derive(
+        Insertable,
+        Associations,
+        Identifiable,
+        Queryable,
+        Selectable,
+        AsChangeset,
+    ),
+    diesel(table_name = action_provider_scope, belongs_to(ActionProvider))
+)]
+pub struct ActionProviderScope {
+    pub id: ActionProviderScopeId,
+    pub action_provider_id: ActionProviderId,
+    pub entity: ScopeEntity,
+    pub access: ScopeAccess,
+    pub deleted: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Deftly)]
+#[derive_deftly(IdString)]
+#[serde(try_from = "IdString", into = "IdString")]
+#[cfg_attr(
+    feature = "db",
+    derive(Hash, FromSqlRow, AsExpression),
+    derive_deftly(IntoPgBigInt, FromPgBigInt),
+    diesel(sql_type = BigInt)
+)]
+pub struct ScopeGrantId(pub i64);
+
+/// The permission of a user for an [`ActionProvider`](crate::ActionProvider) to use an
+/// [`ActionProviderScope`].
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(
+    feature = "db",
+    derive(
+        Insertable,
+        Associations,
+        Identifiable,
+        Queryable,
+        Selectable,
+        AsChangeset,
+    ),
+    diesel(
+        table_name = scope_grant,
+        belongs_to(User),
+        belongs_to(ActionProviderScope)
+    )
+)]
+pub struct ScopeGrant {
+    pub id: ScopeGrantId,
+    pub user_id: UserId,
+    pub action_provider_scope_id: ActionProviderScopeId,
+    pub deleted: bool,
+}
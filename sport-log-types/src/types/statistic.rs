@@ -0,0 +1,196 @@
+use chrono::{DateTime, Datelike, Days, NaiveDate, NaiveTime, Utc};
+#[cfg(feature = "db")]
+use diesel::{
+    QueryableByName,
+    sql_types::{BigInt, Float8, Timestamptz},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{CardioSessionId, MetconId, MovementId, TrainingZoneId, TrainingZoneType};
+
+/// The length of the time buckets statistics are aggregated in.
+///
+/// Buckets start at midnight UTC and weeks start on Monday.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum StatisticInterval {
+    Day,
+    #[default]
+    Week,
+    Month,
+    Year,
+}
+
+impl StatisticInterval {
+    /// The name of the interval as used by `date_trunc` in Postgres.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Day => "day",
+            Self::Week => "week",
+            Self::Month => "month",
+            Self::Year => "year",
+        }
+    }
+
+    /// The start of the time bucket that contains `datetime`.
+    pub fn bucket(self, datetime: DateTime<Utc>) -> DateTime<Utc> {
+        let date = datetime.date_naive();
+        let start = match self {
+            Self::Day => date,
+            Self::Week => date - Days::new(date.weekday().num_days_from_monday().into()),
+            Self::Month => date.with_day(1).unwrap_or(date),
+            Self::Year => date.with_ordinal(1).unwrap_or(date),
+        };
+        start.and_time(NaiveTime::MIN).and_utc()
+    }
+}
+
+/// The aggregated strength sessions of a movement in a single time bucket.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "db", derive(QueryableByName))]
+pub struct StrengthStatistic {
+    #[cfg_attr(feature = "db", diesel(sql_type = BigInt))]
+    pub movement_id: MovementId,
+    /// The start of the time bucket.
+    #[cfg_attr(feature = "db", diesel(sql_type = Timestamptz))]
+    pub bucket: DateTime<Utc>,
+    #[cfg_attr(feature = "db", diesel(sql_type = BigInt))]
+    pub session_count: i64,
+    /// The sum of the count of all sets.
+    #[cfg_attr(feature = "db", diesel(sql_type = BigInt))]
+    pub count: i64,
+    /// The sum of count × weight of all sets.
+    #[cfg_attr(feature = "db", diesel(sql_type = Float8))]
+    pub volume: f64,
+}
+
+/// The aggregated cardio sessions of a movement in a single time bucket.
+///
+/// Sessions without a distance, ascent or time do not contribute to the corresponding sum.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "db", derive(QueryableByName))]
+pub struct CardioStatistic {
+    #[cfg_attr(feature = "db", diesel(sql_type = BigInt))]
+    pub movement_id: MovementId,
+    /// The start of the time bucket.
+    #[cfg_attr(feature = "db", diesel(sql_type = Timestamptz))]
+    pub bucket: DateTime<Utc>,
+    #[cfg_attr(feature = "db", diesel(sql_type = BigInt))]
+    pub session_count: i64,
+    /// The total distance in meters.
+    #[cfg_attr(feature = "db", diesel(sql_type = BigInt))]
+    pub distance: i64,
+    /// The total ascent in meters.
+    #[cfg_attr(feature = "db", diesel(sql_type = BigInt))]
+    pub ascent: i64,
+    /// The total time in milliseconds.
+    #[cfg_attr(feature = "db", diesel(sql_type = BigInt))]
+    pub time: i64,
+}
+
+/// The aggregated metcon sessions of a metcon in a single time bucket.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "db", derive(QueryableByName))]
+pub struct MetconStatistic {
+    #[cfg_attr(feature = "db", diesel(sql_type = BigInt))]
+    pub metcon_id: MetconId,
+    /// The start of the time bucket.
+    #[cfg_attr(feature = "db", diesel(sql_type = Timestamptz))]
+    pub bucket: DateTime<Utc>,
+    #[cfg_attr(feature = "db", diesel(sql_type = BigInt))]
+    pub session_count: i64,
+}
+
+/// The training impulse (TRIMP) of a [`CardioSession`](crate::CardioSession).
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TrainingImpulse {
+    pub cardio_session_id: CardioSessionId,
+    pub datetime: DateTime<Utc>,
+    pub trimp: f64,
+}
+
+/// The training load of a single day (UTC).
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TrainingLoad {
+    pub date: NaiveDate,
+    /// The sum of the training impulses of the day.
+    pub load: f64,
+    /// The average daily load of the last 7 days.
+    pub acute: f64,
+    /// The average daily load of the last 42 days.
+    pub chronic: f64,
+    /// The acute to chronic load ratio or `None` if there is no chronic load.
+    pub ratio: Option<f64>,
+    /// The chronic minus the acute load.
+    pub form: f64,
+}
+
+/// The time spent in the zones of a [`TrainingZone`](crate::TrainingZone).
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ZoneTime {
+    pub training_zone_id: TrainingZoneId,
+    /// The time in milliseconds spent in every zone from low to high intensity.
+    pub time: Vec<i64>,
+}
+
+/// The time in heart rate and pace zones of a [`CardioSession`](crate::CardioSession).
+///
+/// The zones are `None` if the user has no matching zone definition or the session has no heart
+/// rate series or track respectively.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CardioSessionZones {
+    pub cardio_session_id: CardioSessionId,
+    pub datetime: DateTime<Utc>,
+    pub heart_rate: Option<ZoneTime>,
+    pub pace: Option<ZoneTime>,
+}
+
+/// The aggregated time in the zones of a [`TrainingZone`](crate::TrainingZone) in a single time
+/// bucket.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ZoneStatistic {
+    pub training_zone_id: TrainingZoneId,
+    pub zone_type: TrainingZoneType,
+    /// The start of the time bucket.
+    pub bucket: DateTime<Utc>,
+    pub session_count: i64,
+    /// The total time in milliseconds spent in every zone from low to high intensity.
+    pub time: Vec<i64>,
+}
+
+/// A split of a [`CardioSession`](crate::CardioSession) interpolated from its track.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CardioSplit {
+    /// The distance in meters since the start of the session at the end of the split.
+    pub end_distance: f64,
+    /// The distance of the split in meters.
+    ///
+    /// All splits have the requested distance except for the last one which may be shorter.
+    pub distance: f64,
+    /// The time in milliseconds.
+    pub time: i32,
+    /// The pace in seconds per kilometer.
+    pub pace: f64,
+    /// The ascent in meters.
+    pub ascent: f64,
+    /// The descent in meters.
+    pub descent: f64,
+    /// The average heart rate or `None` if the session has no heart rate series.
+    pub avg_heart_rate: Option<i32>,
+    /// The average cadence or `None` if the session has no cadence series.
+    pub avg_cadence: Option<i32>,
+}
+
+/// The fastest effort over a distance of all cardio sessions of a movement.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CardioBestEffort {
+    pub movement_id: MovementId,
+    /// The distance of the effort in meters.
+    pub distance: f64,
+    pub cardio_session_id: CardioSessionId,
+    pub datetime: DateTime<Utc>,
+    /// The time in milliseconds since the start of the session at which the effort starts.
+    pub start: i32,
+    /// The time of the effort in milliseconds.
+    pub time: i32,
+}
@@ -104,3 +104,36 @@ pub struct Eorm {
     pub reps: i32,
     pub percentage: f32,
 }
+
+/// A personal record of a user for a [`Movement`](crate::Movement).
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StrengthRecord {
+    /// The count of the set or the total count of the session for [`StrengthRecords::max_volume`].
+    pub count: i32,
+    /// The weight of the set, the estimated one-rep max for [`StrengthRecords::max_eorm`] or the
+    /// total volume (count × weight) of the session for [`StrengthRecords::max_volume`].
+    pub weight: Option<f32>,
+    pub strength_session_id: StrengthSessionId,
+    /// The set that achieved the record or `None` if the record is achieved by the whole session.
+    pub strength_set_id: Option<StrengthSetId>,
+    pub datetime: DateTime<Utc>,
+}
+
+/// The personal records of a user for a [`Movement`](crate::Movement).
+///
+/// If a record has been achieved multiple times, the first one is reported.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StrengthRecords {
+    pub movement_id: MovementId,
+    /// The maximal weight for every count, ordered by count.
+    pub max_weight: Vec<StrengthRecord>,
+    /// The maximal one-rep max estimated using [`Eorm`].
+    ///
+    /// Only sets of movements with [`MovementDimension::Reps`](crate::MovementDimension::Reps) are
+    /// considered.
+    pub max_eorm: Option<StrengthRecord>,
+    /// The session with the maximal volume.
+    pub max_volume: Option<StrengthRecord>,
+    /// The set with the maximal count.
+    pub max_count: Option<StrengthRecord>,
+}
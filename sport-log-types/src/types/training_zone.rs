@@ -0,0 +1,64 @@
+use derive_deftly::Deftly;
+#[cfg(feature = "db")]
+use diesel::{deserialize::FromSqlRow, expression::AsExpression, prelude::*, sql_types::BigInt};
+#[cfg(feature = "db")]
+use diesel_derive_enum::DbEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::{MovementId, UserId, types::IdString};
+#[cfg(feature = "db")]
+use crate::{User, schema::training_zone};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "db",
+    derive(DbEnum),
+    ExistingTypePath = "crate::schema::sql_types::TrainingZoneType"
+)]
+pub enum TrainingZoneType {
+    HeartRate,
+    Pace,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Deftly)]
+#[derive_deftly(IdString)]
+#[serde(try_from = "IdString", into = "IdString")]
+#[cfg_attr(
+    feature = "db",
+    derive(Hash, FromSqlRow, AsExpression),
+    derive_deftly(IntoPgBigInt, FromPgBigInt),
+    diesel(sql_type = BigInt)
+)]
+pub struct TrainingZoneId(pub i64);
+
+/// The zone definition of a user for heart rates or paces.
+///
+/// `thresholds` are the boundaries between the zones from low to high intensity, so there is one
+/// zone more than thresholds.
+/// Heart rate thresholds are measured in beats per minute and are strictly ascending.
+/// Pace thresholds are measured in seconds per kilometer and are strictly descending.
+///
+/// A zone definition with a `movement_id` applies to the cardio sessions of this movement,
+/// one without a `movement_id` to all other cardio sessions.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(
+    feature = "db",
+    derive(
+        Insertable,
+        Associations,
+        Identifiable,
+        Queryable,
+        Selectable,
+        AsChangeset,
+    ),
+    diesel(table_name = training_zone, belongs_to(User))
+)]
+pub struct TrainingZone {
+    pub id: TrainingZoneId,
+    pub user_id: UserId,
+    pub zone_type: TrainingZoneType,
+    #[cfg_attr(feature = "db", diesel(treat_none_as_null = true))]
+    pub movement_id: Option<MovementId>,
+    pub thresholds: Vec<i32>,
+    pub deleted: bool,
+}
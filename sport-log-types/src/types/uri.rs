@@ -26,6 +26,14 @@ pub const APP_INFO: &str = "/app/info";
 pub const APP_DOWNLOAD: &str = "/app/download";
 
 pub const ACCOUNT_DATA: &str = "/account_data";
+pub const ACCOUNT_DATA_EVENTS: &str = "/account_data_events";
+pub const ACCOUNT_DATA_PAGE: &str = "/account_data_page";
+pub const ACCOUNT_DATA_SYNC: &str = "/account_data_sync";
+
+pub const LOGIN: &str = "/login";
+pub const REFRESH: &str = "/refresh";
+pub const LOGOUT: &str = "/logout";
+pub const LOGOUT_ALL: &str = "/logout_all";
 
 pub const USER: &str = "/user";
 
@@ -35,22 +43,78 @@ pub const ACTION_PROVIDER: &str = "/action_provider";
 pub const ACTION: &str = "/action";
 pub const ACTION_RULE: &str = "/action_rule";
 pub const ACTION_EVENT: &str = "/action_event";
+pub const ACTION_PROVIDER_SCOPE: &str = "/action_provider_scope";
+pub const SCOPE_GRANT: &str = "/scope_grant";
 
 pub const STRENGTH_SESSION: &str = "/strength_session";
+pub const STRENGTH_SESSION_ARCHIVE: &str = "/strength_session/archive";
+pub const STRENGTH_SESSION_RESTORE: &str = "/strength_session/{id}/restore";
 pub const STRENGTH_SET: &str = "/strength_set";
+pub const STRENGTH_SET_ARCHIVE: &str = "/strength_set/archive";
+pub const STRENGTH_SET_RESTORE: &str = "/strength_set/{id}/restore";
+pub const STRENGTH_RECORD: &str = "/strength_record";
+pub const STRENGTH_STATISTIC: &str = "/strength_statistic";
 pub const EORM: &str = "/eorm";
 
 pub const METCON_SESSION: &str = "/metcon_session";
+pub const METCON_SESSION_ARCHIVE: &str = "/metcon_session/archive";
+pub const METCON_SESSION_RESTORE: &str = "/metcon_session/{id}/restore";
 pub const METCON: &str = "/metcon";
+pub const METCON_ARCHIVE: &str = "/metcon/archive";
+pub const METCON_RESTORE: &str = "/metcon/{id}/restore";
 pub const METCON_MOVEMENT: &str = "/metcon_movement";
+pub const METCON_MOVEMENT_ARCHIVE: &str = "/metcon_movement/archive";
+pub const METCON_MOVEMENT_RESTORE: &str = "/metcon_movement/{id}/restore";
+pub const METCON_STATISTIC: &str = "/metcon_statistic";
 
 pub const CARDIO_SESSION: &str = "/cardio_session";
+pub const CARDIO_SESSION_ARCHIVE: &str = "/cardio_session/archive";
+pub const CARDIO_SESSION_RESTORE: &str = "/cardio_session/{id}/restore";
+pub const CARDIO_SESSION_EXPORT: &str = "/cardio_session/{id}/export";
+pub const CARDIO_SESSION_IMPORT: &str = "/cardio_session/import";
+pub const CARDIO_SESSION_SUMMARY: &str = "/cardio_session_summary";
+pub const CARDIO_SESSION_SPATIAL: &str = "/cardio_session_spatial";
+pub const CARDIO_SESSION_OVERVIEW: &str = "/cardio_session_overview";
+pub const CARDIO_SESSION_TRACK: &str = "/cardio_session/{id}/track";
+pub const CARDIO_SESSION_VALIDATION: &str = "/cardio_session_validation";
+pub const CARDIO_SESSION_SPLIT: &str = "/cardio_session/{id}/split";
+pub const CARDIO_SESSION_ROUTE_MATCH: &str = "/cardio_session/{id}/route_match";
+pub const CARDIO_STATISTIC: &str = "/cardio_statistic";
+pub const CARDIO_BEST_EFFORT: &str = "/cardio_best_effort";
+pub const TRAINING_IMPULSE: &str = "/training_impulse";
+pub const TRAINING_LOAD: &str = "/training_load";
+pub const TRAINING_ZONE: &str = "/training_zone";
+pub const TRAINING_ZONE_ARCHIVE: &str = "/training_zone/archive";
+pub const TRAINING_ZONE_RESTORE: &str = "/training_zone/{id}/restore";
+pub const CARDIO_SESSION_ZONE: &str = "/cardio_session_zone";
+pub const ZONE_STATISTIC: &str = "/zone_statistic";
+pub const HEATMAP: &str = "/heatmap/{z}/{x}/{y}";
 pub const ROUTE: &str = "/route";
+pub const ROUTE_ARCHIVE: &str = "/route/archive";
+pub const ROUTE_RESTORE: &str = "/route/{id}/restore";
+pub const ROUTE_EXPORT: &str = "/route/{id}/export";
+pub const ROUTE_TRACK: &str = "/route/{id}/track";
+pub const ROUTE_SUMMARY: &str = "/route_summary";
+pub const ROUTE_SPATIAL: &str = "/route_spatial";
+pub const ROUTE_OVERVIEW: &str = "/route_overview";
+pub const ROUTE_HISTORY: &str = "/route/{id}/history";
+pub const ROUTE_MATCH: &str = "/route_match";
+pub const SEGMENT: &str = "/segment";
+pub const SEGMENT_ARCHIVE: &str = "/segment/archive";
+pub const SEGMENT_RESTORE: &str = "/segment/{id}/restore";
+pub const SEGMENT_EFFORT: &str = "/segment_effort";
+pub const SEGMENT_BEST_EFFORT: &str = "/segment_best_effort";
 
 pub const DIARY: &str = "/diary";
+pub const DIARY_ARCHIVE: &str = "/diary/archive";
+pub const DIARY_RESTORE: &str = "/diary/{id}/restore";
 pub const WOD: &str = "/wod";
+pub const WOD_ARCHIVE: &str = "/wod/archive";
+pub const WOD_RESTORE: &str = "/wod/{id}/restore";
 
 pub const MOVEMENT: &str = "/movement";
+pub const MOVEMENT_ARCHIVE: &str = "/movement/archive";
+pub const MOVEMENT_RESTORE: &str = "/movement/{id}/restore";
 
 // admin URIs
 
@@ -63,6 +127,9 @@ pub const ADM_ACTION_PROVIDER: &str = concatcp!(ADM, ACTION_PROVIDER);
 pub const ADM_ACTION_EVENT: &str = concatcp!(ADM, ACTION_EVENT);
 pub const ADM_CREATABLE_ACTION_RULE: &str = concatcp!(ADM, "/creatable_action_rule");
 pub const ADM_DELETABLE_ACTION_EVENT: &str = concatcp!(ADM, "/deletable_action_event");
+pub const ADM_ROTATE_CREDENTIAL_KEY: &str = concatcp!(ADM, "/rotate_credential_key");
+pub const ADM_PURGE_ARCHIVE: &str = concatcp!(ADM, "/purge_archive");
+pub const ADM_NORMALIZE_CARDIO_SESSION: &str = concatcp!(ADM, "/normalize_cardio_session");
 
 // ap URIs
 
@@ -70,6 +137,7 @@ const AP: &str = "/ap";
 
 pub const AP_PLATFORM: &str = concatcp!(AP, PLATFORM);
 pub const AP_ACTION_PROVIDER: &str = concatcp!(AP, ACTION_PROVIDER);
+pub const AP_ACTION_PROVIDER_SCOPE: &str = concatcp!(AP, ACTION_PROVIDER_SCOPE);
 pub const AP_ACTION: &str = concatcp!(AP, ACTION);
 pub const AP_ACTION_EVENT: &str = concatcp!(AP, ACTION_EVENT);
 pub const AP_EXECUTABLE_ACTION_EVENT: &str = concatcp!(AP, "/executable_action_event");
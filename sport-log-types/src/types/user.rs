@@ -35,4 +35,12 @@ pub struct User {
     pub username: String,
     pub password: String,
     pub email: String,
+    /// The maximal heart rate in beats per minute used to compute the training load.
+    ///
+    /// It is left unchanged by updates without it.
+    pub max_heart_rate: Option<i32>,
+    /// The resting heart rate in beats per minute used to compute the training load.
+    ///
+    /// It is left unchanged by updates without it.
+    pub resting_heart_rate: Option<i32>,
 }
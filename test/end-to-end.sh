@@ -92,6 +92,18 @@ echo -e "\n\nGET $BASE_URL/v0.4/ap/action"
 curl -s -f -u $AP_USERNAME:$AP_PASSWORD "$BASE_URL/v0.4/ap/action" \
     -H 'Accept: application/json'
 
+echo -e "\n\nPOST $BASE_URL/v0.4/ap/action_provider_scope"
+curl -s -f -u $AP_USERNAME:$AP_PASSWORD -X POST "$BASE_URL/v0.4/ap/action_provider_scope" \
+    -H 'Content-Type: application/json' \
+    -d @data/action_provider_scope.json
+echo -e "\n\nGET $BASE_URL/v0.4/ap/action_provider_scope"
+curl -s -f -u $AP_USERNAME:$AP_PASSWORD "$BASE_URL/v0.4/ap/action_provider_scope" \
+    -H 'Accept: application/json'
+echo -e "\n\nPUT $BASE_URL/v0.4/ap/action_provider_scope"
+curl -s -f -u $AP_USERNAME:$AP_PASSWORD -X PUT "$BASE_URL/v0.4/ap/action_provider_scope" \
+    -H 'Content-Type: application/json' \
+    -d @data/action_provider_scope.json
+
 echo -e "\n\nDELETE $BASE_URL/v0.4/ap/action_event"
 curl -s -f -u $AP_USERNAME:$AP_PASSWORD -X DELETE "$BASE_URL/v0.4/ap/action_event" \
     -H 'Content-Type: application/json' \
@@ -123,7 +135,7 @@ curl -s -f -u $USERNAME:$PASSWORD -X GET "$BASE_URL/v0.4/account_data" \
     -H 'Content-Type: application/json' \
     -d 'null'
 
-entities=(movement diary strength_session strength_set metcon metcon_movement metcon_session route cardio_session platform_credential action_rule action_event)
+entities=(movement diary strength_session strength_set metcon metcon_movement metcon_session route cardio_session platform_credential action_rule action_event scope_grant)
 for entity in "${entities[@]}"; do
     echo -e "\n\nPOST $BASE_URL/v0.4/$entity"
     curl -s -f -u $USERNAME:$PASSWORD -X POST "$BASE_URL/v0.4/$entity" \
@@ -147,6 +159,9 @@ curl -s -f -u $USERNAME:$PASSWORD "$BASE_URL/v0.4/action_provider" \
 echo -e "\n\nGET $BASE_URL/v0.4/action"
 curl -s -f -u $USERNAME:$PASSWORD "$BASE_URL/v0.4/action" \
     -H 'Accept: application/json' 
+echo -e "\n\nGET $BASE_URL/v0.4/action_provider_scope"
+curl -s -f -u $USERNAME:$PASSWORD "$BASE_URL/v0.4/action_provider_scope" \
+    -H 'Accept: application/json' 
 echo -e "\n\nGET $BASE_URL/v0.4/eorm"
 curl -s -f -u $USERNAME:$PASSWORD "$BASE_URL/v0.4/eorm" \
     -H 'Accept: application/json' 